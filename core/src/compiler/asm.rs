@@ -1,4 +1,127 @@
-struct Instruction {
-    opcode: u8,
-    operand: u16,
-}
\ No newline at end of file
+use crate::diagnostic::Span;
+
+// The instructions of the VM, a stack machine over 64 bit slots. Integers are
+// kept sign or zero extended to 64 bits, `Wrap` brings the result of an
+// operation back to the width of its type. Floats are kept as their IEEE 754
+// bits, an f32 in the low 32 bits of the slot. Addresses are byte offsets
+// into the VM memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Push(u64),
+    Pop,
+    Dup,
+    // Push the address of a local of the current frame, a global or a
+    // constant in the data section
+    LocalAddr(u32),
+    GlobalAddr(u32),
+    DataAddr(u32),
+    // Pop an address and push the value stored there
+    Load { size: u8, signed: bool },
+    // Pop a value then an address and store the value there
+    Store { size: u8 },
+    // Pop a source then a destination address and copy `size` bytes, for
+    // values too big for a slot which are handled by address
+    Copy { size: u32 },
+    Add,
+    Sub,
+    Mul,
+    Div { signed: bool },
+    Mod { signed: bool },
+    Pow,
+    And,
+    Or,
+    Xor,
+    Neg,
+    // Logical not of a bool
+    Not,
+    Eq,
+    Ne,
+    Lt { signed: bool },
+    Le { signed: bool },
+    Gt { signed: bool },
+    Ge { signed: bool },
+    Wrap { bits: u8, signed: bool },
+    // Arithmetic and comparisons of two f32s, or f64s when `double` is set
+    FAdd { double: bool },
+    FSub { double: bool },
+    FMul { double: bool },
+    FDiv { double: bool },
+    FMod { double: bool },
+    FPow { double: bool },
+    FNeg { double: bool },
+    FEq { double: bool },
+    FNe { double: bool },
+    FLt { double: bool },
+    FLe { double: bool },
+    FGt { double: bool },
+    FGe { double: bool },
+    // An integer to the nearest f32 or f64
+    IntToFloat { signed: bool, double: bool },
+    // A float to an integer of `bits`, rounding towards zero and saturating
+    // at the range of the integer, NaN is 0
+    FloatToInt { double: bool, bits: u8, signed: bool },
+    // An f32 to an f64, or an f64 to the nearest f32 when `double` is false
+    FloatToFloat { double: bool },
+    Jump(u32),
+    JumpIfFalse(u32),
+    // Call a function of the program by index, its arguments are on the stack
+    Call(u32),
+    // Pop the index of a function and call it, a method called through the
+    // vtable of a trait object
+    CallIndirect,
+    // Call a function of the VM by index with `args` slots from the stack
+    Native { index: u16, args: u8 },
+    // Push the address of `size` fresh bytes on the heap, which are never
+    // freed
+    Alloc { size: u32 },
+    // Pop a length and stop the program unless the index under it, which is
+    // left on the stack, is less
+    CheckIndex,
+    // Pop a length, an end and a start and stop the program unless
+    // start <= end <= length
+    CheckRange,
+    // Return from the current function with the value on the stack, if it
+    // returns one; anything else the function left on the stack is dropped
+    Return { value: bool },
+    Halt,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub entry: u32,
+    // Where each parameter is stored in the frame and how many bytes it takes
+    pub params: Vec<(u32, u8)>,
+    pub frame_size: u32,
+}
+
+// A compiled program: the code, the span each instruction came from for
+// runtime errors, the data section with every string constant, and the
+// functions with their entry points
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub code: Vec<Op>,
+    pub spans: Vec<Span>,
+    pub data: Vec<u8>,
+    pub globals_size: u32,
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    pub fn emit(&mut self, op: Op, span: Span) -> u32 {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() as u32 - 1
+    }
+    // Point the jump at `at` to the next instruction
+    pub fn patch(&mut self, at: u32) {
+        let target = self.code.len() as u32;
+        match &mut self.code[at as usize] {
+            Op::Jump(t) | Op::JumpIfFalse(t) => *t = target,
+            op => panic!("cannot patch {:?}", op),
+        }
+    }
+    pub fn here(&self) -> u32 {
+        self.code.len() as u32
+    }
+}
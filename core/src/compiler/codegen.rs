@@ -0,0 +1,2319 @@
+use super::asm::{Function, Op, Program};
+use super::native;
+use super::types::{CraneType, Layouts};
+use super::vm::from_float;
+use crate::diagnostic::Span;
+use crate::module::{ModuleGraph, ROOT};
+use crate::parser::{Node, NodeType};
+use crate::query::{self, Cache, Code, Inst};
+use crate::resolver::{DefKind, Resolution};
+use crate::typeck::{EnumDef, Instance, Signature, StructDef, TraitDef, Value, OPTION};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Local(u32),
+    Global(u32),
+    //at `offset` from the address held by the local `pointer`: a variable
+    //on the heap or in the environment of a closure. The address of the
+    //variable itself is there when `deref`, then its field is at `field`
+    Indirect { pointer: u32, offset: u32, deref: bool, field: u32 },
+}
+
+//where a variable lives and how it is loaded. An aggregate, an enum, a
+//struct, an array, a slice, a tuple, a trait object or a closure, is too big
+//for the operand stack and is handled by its address
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    place: Place,
+    size: u32,
+    signed: bool,
+    aggregate: bool,
+}
+
+//a function expression waiting to be compiled, with the slot of every
+//variable it captures in its environment when it is a closure
+struct Lambda {
+    index: u32,
+    node: Node,
+    env: Option<Vec<(usize, Slot)>>,
+}
+
+//the `break`s and `continue`s of a loop being compiled, jumps patched once
+//its end and the point where it goes around again are known, and whether a
+//`break` leaves the value of the loop on the stack
+#[derive(Default)]
+struct Jumps {
+    breaks: Vec<u32>,
+    continues: Vec<u32>,
+    value: bool,
+}
+
+//turns the checked modules into one program for the VM. Function 0 is the
+//entry point: it runs the top level statements of every module, imports
+//first, then calls `main` of the root module when there is one. Top level
+//variables are globals, everything else lives in the frame of its function.
+//A trait object is the address of its value, which is on the heap, and of
+//the vtable of its type: a global with the index of the function of every
+//method of the trait, filled in by function 0. A function value is the index
+//of the function, a closure that and the address of its environment, whose
+//function takes that address before its parameters. A slice is the address
+//of its first element and how many there are, a pointer an address and
+//`null` the address 0, which the VM never hands out. A tuple is laid out like
+//a struct of its values, so a function returning several values returns one
+//aggregate, written to the address its caller passes last
+pub struct Codegen<'a> {
+    resolution: &'a Resolution,
+    signatures: &'a HashMap<usize, Signature>,
+    enums: &'a HashMap<String, EnumDef>,
+    structs: &'a HashMap<String, StructDef>,
+    traits: &'a HashMap<String, TraitDef>,
+    instances: &'a [Instance],
+    layouts: &'a Layouts,
+    pub program: Program,
+    //the function index or native index of every function, by def id, and
+    //of every instantiation of a generic function
+    functions: HashMap<usize, u32>,
+    natives: HashMap<usize, u16>,
+    instantiated: HashMap<(usize, Vec<CraneType>), (u32, &'a Instance)>,
+    //the global of every vtable by type and trait, and the slot of every
+    //trait method
+    vtables: HashMap<(CraneType, String), u32>,
+    slots: HashMap<usize, u32>,
+    variables: HashMap<usize, Slot>,
+    //the type of every variable, to find the fields of a struct
+    types: HashMap<usize, CraneType>,
+    strings: HashMap<String, u32>,
+    frame_size: u32,
+    //compiling top level statements, their variables are globals
+    top_level: bool,
+    //the parameter holding where an aggregate return value goes, in a
+    //function that returns one
+    out: Option<Slot>,
+    //the return type of the function being compiled, for the early return
+    //of `?`
+    ret: Option<CraneType>,
+    //the code of functions that did not change since an earlier build
+    pub cache: Option<&'a mut Cache>,
+    //function expressions and the adapters calling a function as a closure,
+    //compiled after everything else, and the adapter of every signature
+    lambdas: Vec<Lambda>,
+    adapters: HashMap<CraneType, u32>,
+    pending: Vec<(u32, CraneType)>,
+    //the function expressions that can outlive the call they are made in,
+    //and the variables one of them assigns to, which live on the heap
+    escaping: HashSet<Span>,
+    boxed: HashSet<usize>,
+    //check indexes against the length, which release builds leave out
+    pub checks: bool,
+    //the value of every constant and static, a constant is written where
+    //it is used and a static is stored in its global before anything runs
+    pub constants: HashMap<usize, Value>,
+    //the loops around the code being compiled, innermost last
+    loops: Vec<Jumps>,
+}
+
+//the functions of a module and the methods of its impls, the methods of a
+//trait are generic over the type implementing it and those of a generic
+//impl over its type parameters
+fn functions(nodes: &[Node]) -> impl Iterator<Item = &Node> {
+    let methods = nodes.iter().filter(|n| n.is(NodeType::Impl) && !n.is_generic()).flat_map(Node::methods);
+    nodes.iter().filter(|n| n.is(NodeType::Function)).chain(methods)
+}
+
+//the type of indexes and lengths
+const LENGTH: CraneType = CraneType::Long { signed: false };
+
+fn align(n: u32, to: u32) -> u32 {
+    n.div_ceil(to) * to
+}
+
+//whether a call pushes a value, a function of unknown return type is taken
+//to return one
+fn returns_value(ret: Option<&CraneType>) -> bool {
+    ret != Some(&CraneType::Void)
+}
+
+//the function expressions in a function, the variables a `let` binds one
+//to, and every use of a variable, by the function expression it is in and
+//whether it is a call of it
+#[derive(Default)]
+struct Escapes {
+    lambdas: Vec<Span>,
+    bound: HashMap<usize, (Span, Option<Span>)>,
+    uses: Vec<(usize, Option<Span>, bool)>,
+}
+
+fn find_escapes(resolution: &Resolution, node: &Node, inside: Option<Span>, found: &mut Escapes) {
+    if node.is(NodeType::Assignment) {
+        let value = node.last().filter(|value| value.is(NodeType::Lambda));
+        if let (Some(id), Some(value)) = (resolution.declared_by(node), value) {
+            found.bound.insert(id, (value.span, inside));
+        }
+    }
+    if let Some(id) = resolution.id_of(node) {
+        let called = node.is(NodeType::FunctionCall) && node.target.is_none();
+        found.uses.push((id, inside, called));
+    }
+    let inside = match node.is(NodeType::Lambda) {
+        true => {
+            found.lambdas.push(node.span);
+            Some(node.span)
+        }
+        false => inside,
+    };
+    for child in &node.children {
+        find_escapes(resolution, child, inside, found);
+    }
+}
+
+impl<'a> Codegen<'a> {
+    pub fn new(
+        resolution: &'a Resolution,
+        signatures: &'a HashMap<usize, Signature>,
+        enums: &'a HashMap<String, EnumDef>,
+        structs: &'a HashMap<String, StructDef>,
+        traits: &'a HashMap<String, TraitDef>,
+        instances: &'a [Instance],
+        layouts: &'a Layouts,
+    ) -> Self {
+        Self {
+            resolution,
+            signatures,
+            enums,
+            structs,
+            traits,
+            instances,
+            layouts,
+            program: Program::default(),
+            functions: HashMap::new(),
+            natives: HashMap::new(),
+            instantiated: HashMap::new(),
+            vtables: HashMap::new(),
+            slots: HashMap::new(),
+            variables: HashMap::new(),
+            types: HashMap::new(),
+            strings: HashMap::new(),
+            frame_size: 0,
+            top_level: false,
+            out: None,
+            ret: None,
+            cache: None,
+            lambdas: Vec::new(),
+            adapters: HashMap::new(),
+            pending: Vec::new(),
+            escaping: HashSet::new(),
+            boxed: HashSet::new(),
+            checks: true,
+            constants: HashMap::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn generate(&mut self, graph: &ModuleGraph) {
+        self.declare("<start>", Vec::new());
+        //every function gets its index before any call to it is compiled,
+        //generic functions are only compiled for their instantiations
+        for &module in &graph.order {
+            for node in functions(&graph.modules[module].tree.nodes) {
+                let function = !node.is_generic();
+                let Some(id) = self.resolution.declared_by(node).filter(|_| function) else {
+                    continue;
+                };
+                if node.is_native() {
+                    if let Some(index) = native::find(node.value()) {
+                        self.natives.insert(id, index as u16);
+                    }
+                } else {
+                    let index = self.declare(node.value(), Vec::new());
+                    self.functions.insert(id, index);
+                }
+            }
+        }
+        for instance in self.instances {
+            let index = self.declare(&instance.name, Vec::new());
+            self.instantiated.insert((instance.id, instance.args.clone()), (index, instance));
+        }
+        let mut traits: Vec<(&String, &TraitDef)> = self.traits.iter().collect();
+        traits.sort_by_key(|(name, _)| *name);
+        for (name, def) in traits {
+            for (slot, method) in def.methods.iter().enumerate() {
+                self.slots.insert(method.id, slot as u32);
+            }
+            for (ty, _) in &def.vtables {
+                let offset = align(self.program.globals_size, 8);
+                self.program.globals_size = offset + 8 * def.methods.len() as u32;
+                self.vtables.insert((ty.clone(), name.clone()), offset);
+            }
+        }
+        let mut statics = Vec::new();
+        for &module in &graph.order {
+            for node in graph.modules[module].tree.nodes.iter().filter(|n| n.is(NodeType::Static)) {
+                let Some(id) = self.resolution.declared_by(node) else { continue };
+                let slot = self.global(node.ty.as_ref());
+                self.variables.insert(id, slot);
+                if let Some(ty) = &node.ty {
+                    self.types.insert(id, ty.clone());
+                }
+                statics.push((id, slot, node.span));
+            }
+        }
+        let mut inits = Vec::new();
+        for &module in &graph.order {
+            let name = format!("<init {}>", graph.modules[module].name);
+            let index = self.declare(&name, Vec::new());
+            self.begin(index);
+            self.top_level = true;
+            for node in graph.modules[module].tree.nodes.iter().filter(|n| !n.is(NodeType::Function)) {
+                self.escapes(node);
+                self.statement(node);
+            }
+            self.top_level = false;
+            self.end(index, Span::default(), false);
+            inits.push(index);
+        }
+        for &module in &graph.order {
+            for node in functions(&graph.modules[module].tree.nodes) {
+                self.function(node);
+            }
+        }
+        //instantiations are not cached, their code depends on the callers
+        for instance in self.instances {
+            if let Some(node) = &instance.node {
+                let (index, _) = self.instantiated[&(instance.id, instance.args.clone())];
+                self.body(index, node, instance.signature.ret.as_ref(), None);
+            }
+        }
+        //compiling a function expression can add more of both
+        loop {
+            if let Some(lambda) = self.lambdas.pop() {
+                let ret = match &lambda.node.ty {
+                    Some(CraneType::Function { ret, .. } | CraneType::Closure { ret, .. }) => Some((**ret).clone()),
+                    _ => None,
+                };
+                self.body(lambda.index, &lambda.node, ret.as_ref(), lambda.env.as_deref());
+            } else if let Some((index, ty)) = self.pending.pop() {
+                self.adapter(index, &ty);
+            } else {
+                break;
+            }
+        }
+        self.begin(0);
+        self.fill_vtables();
+        for (id, slot, span) in statics {
+            let value = self.constants.get(&id).map_or(0, |value| value.bits());
+            self.address(slot, span);
+            self.program.emit(Op::Push(value), span);
+            self.store(slot, span);
+        }
+        for index in inits {
+            self.program.emit(Op::Call(index), Span::default());
+        }
+        let main = graph.modules[ROOT]
+            .tree
+            .nodes
+            .iter()
+            .find(|n| n.is(NodeType::Function) && n.value() == "main");
+        if let Some(main) = main {
+            let id = self.resolution.declared_by(main);
+            if let Some(&index) = id.and_then(|id| self.functions.get(&id)) {
+                let ret = self.signatures[&id.unwrap()].ret.clone();
+                if self.invoke(ret.as_ref(), index, Span::default()) {
+                    self.program.emit(Op::Pop, Span::default());
+                }
+            }
+        }
+        self.end(0, Span::default(), false);
+    }
+
+    //store the function of every slot in the vtables
+    fn fill_vtables(&mut self) {
+        for (name, def) in self.traits {
+            for (ty, slots) in &def.vtables {
+                let base = self.vtables[&(ty.clone(), name.clone())];
+                for (slot, (id, args)) in slots.iter().enumerate() {
+                    let function = match args {
+                        Some(args) => self.instantiated.get(&(*id, args.clone())).map(|&(index, _)| index),
+                        None => self.functions.get(id).copied(),
+                    };
+                    let Some(function) = function else { continue };
+                    self.program.emit(Op::GlobalAddr(base + 8 * slot as u32), Span::default());
+                    self.program.emit(Op::Push(function as u64), Span::default());
+                    self.program.emit(Op::Store { size: 8 }, Span::default());
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, params: Vec<(u32, u8)>) -> u32 {
+        self.program.functions.push(Function {
+            name: name.to_string(),
+            entry: 0,
+            params,
+            frame_size: 0,
+        });
+        self.program.functions.len() as u32 - 1
+    }
+
+    fn begin(&mut self, index: u32) {
+        self.program.functions[index as usize].entry = self.program.here();
+        self.frame_size = 0;
+    }
+
+    //the implicit return at the end of a function, a function that returns a
+    //value and falls off its end has been reported by the type checker
+    fn end(&mut self, index: u32, span: Span, value: bool) {
+        if value {
+            self.program.emit(Op::Push(0), span);
+        }
+        self.program.emit(Op::Return { value }, span);
+        self.program.functions[index as usize].frame_size = self.frame_size;
+    }
+
+    fn aggregate(&self, ty: Option<&CraneType>) -> bool {
+        matches!(
+            ty,
+            Some(
+                CraneType::Enum { .. }
+                    | CraneType::Struct { .. }
+                    | CraneType::Array { .. }
+                    | CraneType::Slice { .. }
+                    | CraneType::Tuple { .. }
+                    | CraneType::Str
+                    | CraneType::Dyn { .. }
+                    | CraneType::Closure { .. }
+            )
+        )
+    }
+
+    //size, alignment and signedness, values of an unknown type are kept as
+    //64 bit signed integers
+    fn layout(&self, ty: Option<&CraneType>) -> (u32, u32, bool) {
+        match ty {
+            Some(CraneType::Void) | None => (8, 8, true),
+            Some(ty) => (self.layouts.size(ty), self.layouts.align(ty), ty.is_signed()),
+        }
+    }
+
+    //call a function whose arguments are on the stack, a function returning
+    //an aggregate is given a place in the frame of the caller to write it to.
+    //True when a value is pushed
+    fn invoke(&mut self, ret: Option<&CraneType>, index: u32, span: Span) -> bool {
+        self.invoke_with(ret, span, |codegen| {
+            codegen.program.emit(Op::Call(index), span);
+        })
+    }
+
+    //`invoke` for a call made by `call`
+    fn invoke_with(&mut self, ret: Option<&CraneType>, span: Span, call: impl FnOnce(&mut Self)) -> bool {
+        if !self.aggregate(ret) {
+            call(self);
+            return returns_value(ret);
+        }
+        let value = self.local(ret);
+        self.address(value, span);
+        call(self);
+        self.address(value, span);
+        true
+    }
+
+    fn function(&mut self, node: &Node) {
+        let Some(id) = self.resolution.declared_by(node) else { return };
+        let Some(&index) = self.functions.get(&id) else { return };
+        if node.body().is_none() {
+            return;
+        }
+        let key = self.cache.as_ref().and_then(|c| c.keys.get(&node.span).copied());
+        if let Some(code) = key.and_then(|key| self.cache.as_mut().unwrap().code(key, query::extent(node))) {
+            if self.emit_cached(index, code) {
+                return;
+            }
+        }
+        let ret = self.signatures.get(&id).and_then(|s| s.ret.as_ref());
+        self.body(index, node, ret, None);
+        if let Some(key) = key {
+            if let Some(code) = self.relocatable(index) {
+                self.cache.as_mut().unwrap().store_code(key, query::extent(node), &code);
+            }
+        }
+    }
+
+    //a closure is passed the address of its environment first, it is the
+    //first local
+    fn body(&mut self, index: u32, node: &Node, ret: Option<&CraneType>, env: Option<&[(usize, Slot)]>) {
+        let Some(body) = node.body() else { return };
+        if !node.is(NodeType::Lambda) {
+            self.escapes(body);
+        }
+        self.begin(index);
+        //aggregates are passed by address and copied into the frame, but
+        //`self`, which a method works on in place
+        let mut params = Vec::new();
+        let mut copies = Vec::new();
+        if let Some(env) = env {
+            self.pointer();
+            params.push((0, 8));
+            for &(id, slot) in env {
+                self.variables.insert(id, slot);
+            }
+        }
+        for param in node.params() {
+            let aggregate = self.aggregate(param.ty.as_ref());
+            let slot = if aggregate { self.pointer() } else { self.local(param.ty.as_ref()) };
+            if let Place::Local(offset) = slot.place {
+                params.push((offset, slot.size as u8));
+            }
+            let Some(id) = self.resolution.declared_by(param) else { continue };
+            if let Some(ty) = &param.ty {
+                self.types.insert(id, ty.clone());
+            }
+            let in_place = param.value() == "self" && !self.boxed.contains(&id);
+            if let (true, Place::Local(pointer)) = (aggregate && in_place, slot.place) {
+                let place = Place::Indirect { pointer, offset: 0, deref: false, field: 0 };
+                let (size, _, signed) = self.layout(param.ty.as_ref());
+                self.variables.insert(id, Slot { place, size, signed, aggregate });
+            } else if aggregate {
+                let copy = self.local(param.ty.as_ref());
+                copies.push((copy, slot));
+                self.variables.insert(id, copy);
+            } else {
+                self.variables.insert(id, slot);
+            }
+        }
+        self.out = self.aggregate(ret).then(|| self.pointer());
+        self.ret = ret.cloned();
+        if let Some(Slot { place: Place::Local(offset), .. }) = self.out {
+            params.push((offset, 8));
+        }
+        self.program.functions[index as usize].params = params;
+        for (copy, pointer) in copies {
+            self.address(copy, node.span);
+            self.load(pointer, node.span);
+            self.store(copy, node.span);
+        }
+        for param in node.params() {
+            let Some(id) = self.resolution.declared_by(param).filter(|id| self.boxed.contains(id)) else {
+                continue;
+            };
+            let slot = self.variables[&id];
+            let cell = self.cell(param.ty.as_ref(), param.span);
+            self.address(cell, param.span);
+            self.load(slot, param.span);
+            self.store(cell, param.span);
+            self.variables.insert(id, cell);
+        }
+        self.statement(body);
+        let value = returns_value(ret) && self.out.is_none();
+        self.out = None;
+        self.ret = None;
+        self.end(index, node.span, value);
+    }
+
+    //a function expression stays in the frame of the function making it when
+    //a `let` binds it to a variable that function only calls. Any other can
+    //outlive the call and so can what it assigns to
+    fn escapes(&mut self, node: &Node) {
+        let mut found = Escapes::default();
+        find_escapes(self.resolution, node, None, &mut found);
+        for span in found.lambdas {
+            let bound = found.bound.iter().find(|(_, (lambda, _))| *lambda == span);
+            let local = bound.is_some_and(|(&id, &(_, inside))| {
+                let mut uses = found.uses.iter().filter(|(used, _, _)| *used == id);
+                uses.all(|&(_, at, called)| called && at == inside)
+            });
+            if local {
+                continue;
+            }
+            self.escaping.insert(span);
+            for id in self.resolution.captures.get(&span).into_iter().flatten() {
+                if self.resolution.assigned.contains(id) {
+                    self.boxed.insert(*id);
+                }
+            }
+        }
+    }
+
+    //calls the function whose index is the environment of the closure with
+    //the arguments it was given
+    fn adapter(&mut self, index: u32, ty: &CraneType) {
+        let CraneType::Function { params, ret } = ty else { return };
+        let span = Span::default();
+        self.begin(index);
+        let mut slots = vec![self.pointer()];
+        for param in params {
+            let slot = if self.aggregate(Some(param)) { self.pointer() } else { self.local(Some(param)) };
+            slots.push(slot);
+        }
+        if self.aggregate(Some(ret)) {
+            slots.push(self.pointer());
+        }
+        let mut params = Vec::new();
+        for slot in &slots {
+            if let Place::Local(offset) = slot.place {
+                params.push((offset, slot.size as u8));
+            }
+        }
+        self.program.functions[index as usize].params = params;
+        for &slot in &slots[1..] {
+            self.load(slot, span);
+        }
+        self.load(slots[0], span);
+        self.program.emit(Op::CallIndirect, span);
+        self.end(index, span, false);
+    }
+
+    //the code just generated for a function with what its instructions
+    //refer to by name, `None` when something has no name to go by
+    fn relocatable(&self, index: u32) -> Option<Code> {
+        let cache = self.cache.as_ref()?;
+        let function = &self.program.functions[index as usize];
+        let entry = function.entry as usize;
+        let name = |id: usize| cache.index.name(self.resolution.defs[id].span).map(str::to_string);
+        let mut insts = Vec::new();
+        for (op, &span) in self.program.code[entry..].iter().zip(&self.program.spans[entry..]) {
+            let inst = match *op {
+                Op::Call(callee) => {
+                    let (&id, _) = self.functions.iter().find(|(_, &i)| i == callee)?;
+                    Inst::Call(name(id)?)
+                }
+                Op::GlobalAddr(offset) => {
+                    let global = |slot: &Slot| matches!(slot.place, Place::Global(o) if o == offset);
+                    let (&id, _) = self.variables.iter().find(|(_, slot)| global(slot))?;
+                    Inst::Global(name(id)?)
+                }
+                Op::DataAddr(offset) => {
+                    let (value, _) = self.strings.iter().find(|(_, &o)| o == offset)?;
+                    Inst::Data(value.clone())
+                }
+                Op::Jump(target) => Inst::Jump(target - entry as u32),
+                Op::JumpIfFalse(target) => Inst::JumpIfFalse(target - entry as u32),
+                op => Inst::Op(op),
+            };
+            insts.push((inst, span));
+        }
+        Some(Code {
+            params: function.params.clone(),
+            frame_size: function.frame_size,
+            insts,
+        })
+    }
+
+    //emit the code of a function from an earlier build, false when it
+    //refers to something this build does not have
+    fn emit_cached(&mut self, index: u32, code: Code) -> bool {
+        let entry = self.program.here();
+        let mut ops = Vec::new();
+        for (inst, span) in code.insts {
+            let def = |cache: &Cache, name: &str| cache.index.span(name).and_then(|s| self.resolution.declarations.get(&s).copied());
+            let cache = self.cache.as_ref().unwrap();
+            let op = match inst {
+                Inst::Op(op) => op,
+                Inst::Call(name) => match def(cache, &name).and_then(|id| self.functions.get(&id)) {
+                    Some(&callee) => Op::Call(callee),
+                    None => return false,
+                },
+                Inst::Global(name) => match def(cache, &name).and_then(|id| self.variables.get(&id)) {
+                    Some(Slot { place: Place::Global(offset), .. }) => Op::GlobalAddr(*offset),
+                    _ => return false,
+                },
+                Inst::Data(value) => Op::DataAddr(self.string(&value)),
+                Inst::Jump(target) => Op::Jump(entry + target),
+                Inst::JumpIfFalse(target) => Op::JumpIfFalse(entry + target),
+            };
+            ops.push((op, span));
+        }
+        for (op, span) in ops {
+            self.program.emit(op, span);
+        }
+        let function = &mut self.program.functions[index as usize];
+        function.entry = entry;
+        function.params = code.params;
+        function.frame_size = code.frame_size;
+        true
+    }
+
+    fn local(&mut self, ty: Option<&CraneType>) -> Slot {
+        let (size, alignment, signed) = self.layout(ty);
+        let offset = align(self.frame_size, alignment);
+        self.frame_size = offset + size;
+        Slot {
+            place: Place::Local(offset),
+            size,
+            signed,
+            aggregate: self.aggregate(ty),
+        }
+    }
+
+    //a variable on the heap, a local holds its address
+    fn cell(&mut self, ty: Option<&CraneType>, span: Span) -> Slot {
+        let (size, _, signed) = self.layout(ty);
+        let Place::Local(pointer) = self.pointer().place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(pointer), span);
+        self.program.emit(Op::Alloc { size }, span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        Slot {
+            place: Place::Indirect {
+                pointer,
+                offset: 0,
+                deref: false,
+                field: 0,
+            },
+            size,
+            signed,
+            aggregate: self.aggregate(ty),
+        }
+    }
+
+    //a local holding an address
+    fn pointer(&mut self) -> Slot {
+        let pointer = CraneType::Pointer {
+            pointee: Box::new(CraneType::Void),
+        };
+        self.local(Some(&pointer))
+    }
+
+    fn global(&mut self, ty: Option<&CraneType>) -> Slot {
+        let (size, alignment, signed) = self.layout(ty);
+        let offset = align(self.program.globals_size, alignment);
+        self.program.globals_size = offset + size;
+        Slot {
+            place: Place::Global(offset),
+            size,
+            signed,
+            aggregate: self.aggregate(ty),
+        }
+    }
+
+    //the value of a constant, which has no slot
+    fn constant(&self, node: &Node) -> Option<Value> {
+        let id = self.resolution.id_of(node).filter(|&id| self.resolution.defs[id].kind == DefKind::Const)?;
+        self.constants.get(&id).copied()
+    }
+
+    //push the value of a slot, or its address for an aggregate
+    fn load(&mut self, slot: Slot, span: Span) {
+        self.address(slot, span);
+        if !slot.aggregate {
+            self.program.emit(Op::Load { size: slot.size as u8, signed: slot.signed }, span);
+        }
+    }
+
+    //the address of the slot and then the value, or the address of the
+    //aggregate to copy, are on the stack
+    fn store(&mut self, slot: Slot, span: Span) {
+        let op = if slot.aggregate {
+            Op::Copy { size: slot.size }
+        } else {
+            Op::Store { size: slot.size as u8 }
+        };
+        self.program.emit(op, span);
+    }
+
+    fn address(&mut self, slot: Slot, span: Span) {
+        match slot.place {
+            Place::Local(offset) => {
+                self.program.emit(Op::LocalAddr(offset), span);
+            }
+            Place::Global(offset) => {
+                self.program.emit(Op::GlobalAddr(offset), span);
+            }
+            Place::Indirect {
+                pointer,
+                offset,
+                deref,
+                field,
+            } => {
+                self.program.emit(Op::LocalAddr(pointer), span);
+                self.program.emit(Op::Load { size: 8, signed: false }, span);
+                self.offset(offset, span);
+                if deref {
+                    self.program.emit(Op::Load { size: 8, signed: false }, span);
+                }
+                self.offset(field, span);
+            }
+        }
+    }
+
+    fn offset(&mut self, offset: u32, span: Span) {
+        if offset > 0 {
+            self.program.emit(Op::Push(offset as u64), span);
+            self.program.emit(Op::Add, span);
+        }
+    }
+
+    fn statement(&mut self, node: &Node) {
+        let span = node.span;
+        match node.node_type {
+            NodeType::Block => {
+                for child in &node.children {
+                    self.statement(child);
+                }
+            }
+            NodeType::Assignment => {
+                let id = self.resolution.declared_by(node);
+                let slot = if self.top_level {
+                    self.global(node.ty.as_ref())
+                } else if id.is_some_and(|id| self.boxed.contains(&id)) {
+                    self.cell(node.ty.as_ref(), span)
+                } else {
+                    self.local(node.ty.as_ref())
+                };
+                if let Some(id) = id {
+                    self.variables.insert(id, slot);
+                    if let Some(ty) = &node.ty {
+                        self.types.insert(id, ty.clone());
+                    }
+                }
+                self.address(slot, span);
+                self.expression(node.last().unwrap());
+                self.store(slot, span);
+            }
+            NodeType::Destructure => self.destructure(node),
+            NodeType::Reassignment => {
+                let target = &node.children[0];
+                let slot = match target.node_type {
+                    NodeType::Index => self.element(target),
+                    NodeType::Member => self.member(target),
+                    NodeType::UnaryExpression => self.deref(target),
+                    _ => self.place(target),
+                };
+                let Some(slot) = slot else {
+                    return;
+                };
+                self.address(slot, span);
+                if node.value() != "Eq" {
+                    self.program.emit(Op::Dup, span);
+                    if !slot.aggregate {
+                        self.program.emit(Op::Load { size: slot.size as u8, signed: slot.signed }, span);
+                    }
+                    self.expression(&node.children[1]);
+                    self.stride(target.ty.as_ref(), span);
+                    let operator = node.value().trim_end_matches("Eq");
+                    match target.ty {
+                        Some(CraneType::Str) => self.native("str_concat", 2, span),
+                        _ => self.arithmetic(operator, target.ty.as_ref(), span),
+                    }
+                } else {
+                    self.expression(&node.children[1]);
+                }
+                self.store(slot, span);
+            }
+            NodeType::Conditional => {
+                self.expression(&node.children[0]);
+                let otherwise = self.program.emit(Op::JumpIfFalse(0), span);
+                self.statement(&node.children[1]);
+                if let Some(other) = node.children.get(2) {
+                    let end = self.program.emit(Op::Jump(0), span);
+                    self.program.patch(otherwise);
+                    self.statement(other);
+                    self.program.patch(end);
+                } else {
+                    self.program.patch(otherwise);
+                }
+            }
+            NodeType::Loop if node.value() == "for" => self.for_loop(node),
+            NodeType::Loop if node.value() == "loop" => {
+                if self.expression(node) {
+                    self.program.emit(Op::Pop, span);
+                }
+            }
+            NodeType::Loop => self.while_loop(node),
+            NodeType::Break => {
+                let value = self.loops.last().is_some_and(|jumps| jumps.value);
+                if let Some(child) = node.children.first() {
+                    if self.expression(child) && !value {
+                        self.program.emit(Op::Pop, span);
+                    }
+                }
+                let jump = self.program.emit(Op::Jump(0), span);
+                if let Some(jumps) = self.loops.last_mut() {
+                    jumps.breaks.push(jump);
+                }
+            }
+            NodeType::Continue => {
+                let jump = self.program.emit(Op::Jump(0), span);
+                if let Some(jumps) = self.loops.last_mut() {
+                    jumps.continues.push(jump);
+                }
+            }
+            NodeType::Return => {
+                let value = match (node.children.first(), self.out) {
+                    (Some(value), Some(out)) => {
+                        self.load(out, span);
+                        self.expression(value);
+                        let (size, _, _) = self.layout(value.coercion.as_ref().or(value.ty.as_ref()));
+                        self.program.emit(Op::Copy { size }, span);
+                        false
+                    }
+                    (Some(value), None) => self.expression(value),
+                    (None, _) => false,
+                };
+                self.program.emit(Op::Return { value }, span);
+            }
+            NodeType::Function
+            | NodeType::Const
+            | NodeType::Static
+            | NodeType::Enum
+            | NodeType::Struct
+            | NodeType::Trait
+            | NodeType::Impl
+            | NodeType::Attribute
+            | NodeType::Import
+            | NodeType::Keyword
+            | NodeType::Err => {}
+            _ => {
+                if self.expression(node) {
+                    self.program.emit(Op::Pop, span);
+                }
+            }
+        }
+    }
+
+    //push the value of an expression, false when there is no value, which
+    //is only the case for calls to void functions
+    fn expression(&mut self, node: &Node) -> bool {
+        match &node.coercion {
+            Some(CraneType::Dyn { name }) => {
+                self.trait_object(node, name);
+                true
+            }
+            Some(CraneType::Closure { .. }) => {
+                self.adapt(node);
+                true
+            }
+            Some(CraneType::Slice { .. }) => {
+                self.view(node);
+                true
+            }
+            _ => self.value(node),
+        }
+    }
+
+    //copy a value to the heap and push the address of a trait object of it,
+    //built in a temporary of the frame
+    fn trait_object(&mut self, node: &Node, name: &str) {
+        let span = node.span;
+        let object = self.local(node.coercion.as_ref());
+        let Place::Local(base) = object.place else { unreachable!() };
+        let (size, _, _) = self.layout(node.ty.as_ref());
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Alloc { size }, span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        let value = self.field(0, node.ty.as_ref());
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Load { size: 8, signed: false }, span);
+        self.value(node);
+        self.store(value, span);
+        let vtable = node.ty.clone().map(|ty| (ty, name.to_string()));
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        match vtable.and_then(|key| self.vtables.get(&key)) {
+            Some(&offset) => self.program.emit(Op::GlobalAddr(offset), span),
+            None => self.program.emit(Op::Push(0), span),
+        };
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.address(object, span);
+    }
+
+    fn value(&mut self, node: &Node) -> bool {
+        let span = node.span;
+        match node.node_type {
+            //an integer literal can be a float
+            NodeType::Number if float(node.ty.as_ref()).is_some() => {
+                let value = node.value().parse::<f64>().unwrap_or(0.0);
+                self.program.emit(Op::Push(from_float(value, float(node.ty.as_ref()).unwrap())), span);
+            }
+            NodeType::Number => {
+                let value = node.value().parse::<i128>().unwrap_or(0) as u64;
+                self.program.emit(Op::Push(value), span);
+            }
+            NodeType::Float => {
+                let value = node.value().parse::<f64>().unwrap_or(0.0);
+                self.program.emit(Op::Push(from_float(value, float(node.ty.as_ref()) != Some(false))), span);
+            }
+            NodeType::Bool => {
+                self.program.emit(Op::Push((node.value() == "true") as u64), span);
+            }
+            NodeType::Character => {
+                let c = node.value().bytes().next().unwrap_or(0);
+                self.program.emit(Op::Push(c as u64), span);
+            }
+            NodeType::String => self.text(node.value(), span),
+            NodeType::Format => self.format(node),
+            NodeType::Identifier => match (self.constant(node), self.place(node)) {
+                (Some(value), _) => {
+                    self.program.emit(Op::Push(value.bits()), span);
+                }
+                (None, Some(slot)) => self.load(slot, span),
+                (None, None) => {
+                    let function = self.resolution.id_of(node).and_then(|id| self.functions.get(&id));
+                    self.program.emit(Op::Push(function.copied().unwrap_or(0) as u64), span);
+                }
+            },
+            NodeType::Lambda => self.lambda(node),
+            NodeType::FunctionCall | NodeType::MethodCall => return self.call(node),
+            //`Square::new(1)`
+            NodeType::Construct if self.resolution.id_of(node).is_some_and(|id| self.signatures.contains_key(&id)) => {
+                return self.call(node);
+            }
+            NodeType::UnaryExpression if node.value() == "And" => self.address_of(&node.children[0]),
+            NodeType::UnaryExpression if node.value() == "Mul" => match self.deref(node) {
+                Some(slot) => self.load(slot, span),
+                None => {
+                    self.program.emit(Op::Push(0), span);
+                }
+            },
+            NodeType::UnaryExpression => {
+                self.expression(&node.children[0]);
+                match (node.value(), node.ty.as_ref()) {
+                    ("Sub", ty) if float(ty).is_some() => {
+                        self.program.emit(Op::FNeg { double: float(ty).unwrap() }, span);
+                    }
+                    ("Sub", _) => {
+                        self.program.emit(Op::Neg, span);
+                        self.wrap(node.ty.as_ref(), span);
+                    }
+                    ("Not", Some(ty)) if ty.is_integer() => {
+                        self.program.emit(Op::Push(u64::MAX), span);
+                        self.program.emit(Op::Xor, span);
+                        self.wrap(node.ty.as_ref(), span);
+                    }
+                    _ => {
+                        self.program.emit(Op::Not, span);
+                    }
+                }
+            }
+            NodeType::Expression => self.binary(node),
+            NodeType::Construct => self.construct(node),
+            NodeType::StructLiteral => self.struct_literal(node),
+            NodeType::ArrayLiteral => self.array_literal(node),
+            NodeType::Tuple => self.tuple(node),
+            NodeType::Try => self.try_value(node),
+            NodeType::Cast => self.cast(node),
+            NodeType::Index if node.children[1].is(NodeType::Range) => self.slice(node),
+            NodeType::Index => match self.element(node) {
+                Some(slot) => self.load(slot, span),
+                None => {
+                    self.program.emit(Op::Push(0), span);
+                }
+            },
+            NodeType::Member => match self.member(node) {
+                Some(slot) => self.load(slot, span),
+                None => {
+                    self.program.emit(Op::Push(0), span);
+                }
+            },
+            NodeType::Match => return self.match_expression(node),
+            NodeType::Loop => return self.loop_value(node),
+            NodeType::Null => {
+                self.program.emit(Op::Push(0), span);
+            }
+            _ => {
+                self.program.emit(Op::Push(0), span);
+            }
+        }
+        true
+    }
+
+    fn binary(&mut self, node: &Node) {
+        let span = node.span;
+        let (left, right) = (&node.children[0], &node.children[1]);
+        match node.value() {
+            "AndAnd" => {
+                self.expression(left);
+                let short = self.program.emit(Op::JumpIfFalse(0), span);
+                self.expression(right);
+                let end = self.program.emit(Op::Jump(0), span);
+                self.program.patch(short);
+                self.program.emit(Op::Push(0), span);
+                self.program.patch(end);
+            }
+            "OrOr" => {
+                self.expression(left);
+                let other = self.program.emit(Op::JumpIfFalse(0), span);
+                self.program.emit(Op::Push(1), span);
+                let end = self.program.emit(Op::Jump(0), span);
+                self.program.patch(other);
+                self.expression(right);
+                self.program.patch(end);
+            }
+            operator => {
+                self.expression(left);
+                self.expression(right);
+                //an operator a struct or enum implements, `!=` negates `eq`
+                if let Some(target) = node.target {
+                    match self.callee(target, node.type_args.as_ref()) {
+                        Some((index, ret)) => {
+                            self.invoke(ret, index, span);
+                        }
+                        None => {
+                            self.program.emit(Op::Push(0), span);
+                        }
+                    }
+                    if operator == "NotEq" {
+                        self.program.emit(Op::Not, span);
+                    }
+                    return;
+                }
+                //`p + n` moves by `n` values, `p - q` counts the values between
+                if let Some(CraneType::Pointer { pointee }) = &left.ty {
+                    if !matches!(right.ty, Some(CraneType::Pointer { .. })) {
+                        self.stride(left.ty.as_ref(), span);
+                    } else if operator == "Sub" {
+                        self.program.emit(Op::Sub, span);
+                        self.program.emit(Op::Push(self.layouts.size(pointee).max(1) as u64), span);
+                        self.program.emit(Op::Div { signed: true }, span);
+                        return;
+                    }
+                }
+                //strings are joined by the VM, and compared by comparing what
+                //`str_cmp` gives with 0
+                let text = left.ty == Some(CraneType::Str);
+                if text && operator == "Add" {
+                    return self.native("str_concat", 2, span);
+                }
+                if text {
+                    self.native("str_cmp", 2, span);
+                    self.program.emit(Op::Push(0), span);
+                }
+                if let Some(double) = float(left.ty.as_ref()) {
+                    let op = match operator {
+                        "EqEq" => Op::FEq { double },
+                        "NotEq" => Op::FNe { double },
+                        "Less" => Op::FLt { double },
+                        "LessEq" => Op::FLe { double },
+                        "Greater" => Op::FGt { double },
+                        "GreaterEq" => Op::FGe { double },
+                        _ => return self.arithmetic(operator, node.ty.as_ref(), span),
+                    };
+                    self.program.emit(op, span);
+                    return;
+                }
+                //integers of two widths compare as the wider, which is signed
+                //when either is
+                let signed = text || [left, right].iter().any(|side| side.ty.as_ref().is_none_or(CraneType::is_signed));
+                let op = match operator {
+                    "EqEq" => Op::Eq,
+                    "NotEq" => Op::Ne,
+                    "Less" => Op::Lt { signed },
+                    "LessEq" => Op::Le { signed },
+                    "Greater" => Op::Gt { signed },
+                    "GreaterEq" => Op::Ge { signed },
+                    _ => return self.arithmetic(operator, node.ty.as_ref(), span),
+                };
+                self.program.emit(op, span);
+            }
+        }
+    }
+
+    //the operands are on the stack, the result is wrapped to the width of
+    //its type
+    fn arithmetic(&mut self, operator: &str, ty: Option<&CraneType>, span: Span) {
+        if let Some(double) = float(ty) {
+            let op = match operator {
+                "Add" => Op::FAdd { double },
+                "Sub" => Op::FSub { double },
+                "Mul" => Op::FMul { double },
+                "Div" => Op::FDiv { double },
+                "Mod" => Op::FMod { double },
+                _ => Op::FPow { double },
+            };
+            self.program.emit(op, span);
+            return;
+        }
+        let signed = ty.is_none_or(CraneType::is_signed);
+        let op = match operator {
+            "Add" => Op::Add,
+            "Sub" => Op::Sub,
+            "Mul" => Op::Mul,
+            "Div" => Op::Div { signed },
+            "Mod" => Op::Mod { signed },
+            "Pow" => Op::Pow,
+            "And" => Op::And,
+            "Or" => Op::Or,
+            _ => Op::Xor,
+        };
+        self.program.emit(op, span);
+        self.wrap(ty, span);
+    }
+
+    //multiply the number on the stack by the size of what a pointer of
+    //type `ty` points to, so that adding it moves the pointer by that many
+    //values. Nothing is done for other types
+    fn stride(&mut self, ty: Option<&CraneType>, span: Span) {
+        if let Some(CraneType::Pointer { pointee }) = ty {
+            let size = self.layouts.size(pointee).max(1);
+            self.program.emit(Op::Push(size as u64), span);
+            self.program.emit(Op::Mul, span);
+        }
+    }
+
+    //push the address of the place of `&x`
+    fn address_of(&mut self, node: &Node) {
+        let slot = match node.node_type {
+            NodeType::Index => self.element(node),
+            NodeType::Member => self.member(node),
+            NodeType::UnaryExpression => self.deref(node),
+            _ => self.place(node),
+        };
+        match slot {
+            Some(slot) => self.address(slot, node.span),
+            None => {
+                self.program.emit(Op::Push(0), node.span);
+            }
+        }
+    }
+
+    //what the pointer of `*p` points to, the pointer is kept in a local so
+    //it can be read and written
+    fn deref(&mut self, node: &Node) -> Option<Slot> {
+        let span = node.span;
+        let ty = node.ty.as_ref()?;
+        let Place::Local(pointer) = self.pointer().place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(pointer), span);
+        self.expression(&node.children[0]);
+        self.program.emit(Op::Store { size: 8 }, span);
+        let (size, _, signed) = self.layout(Some(ty));
+        Some(Slot {
+            place: Place::Indirect {
+                pointer,
+                offset: 0,
+                deref: false,
+                field: 0,
+            },
+            size,
+            signed,
+            aggregate: self.aggregate(Some(ty)),
+        })
+    }
+
+    fn wrap(&mut self, ty: Option<&CraneType>, span: Span) {
+        if let Some(bits) = ty.and_then(CraneType::bits).filter(|&bits| bits < 64) {
+            let signed = ty.unwrap().is_signed();
+            self.program.emit(Op::Wrap { bits: bits as u8, signed }, span);
+        }
+    }
+
+    //the slot of a variable, or of the field of `p.a.b`
+    fn place(&self, node: &Node) -> Option<Slot> {
+        self.typed_place(node).map(|(slot, _)| slot)
+    }
+
+    //`place` with the type of what is there
+    fn typed_place(&self, node: &Node) -> Option<(Slot, Option<CraneType>)> {
+        let id = self.resolution.id_of(node)?;
+        let mut slot = *self.variables.get(&id)?;
+        if !self.resolution.is_field_path(node) {
+            return Some((slot, self.types.get(&id).cloned()));
+        }
+        let mut ty = self.types.get(&id)?.clone();
+        for field in node.value().split('.').skip(1) {
+            (slot, ty) = self.field_of(slot, &ty, field)?;
+        }
+        Some((slot, Some(ty)))
+    }
+
+    //the slot of a field of the value of type `ty` in `slot`
+    fn field_of(&self, slot: Slot, ty: &CraneType, field: &str) -> Option<(Slot, CraneType)> {
+        let (offset, field_ty) = match ty {
+            CraneType::Struct { name, args } => {
+                let (index, field_ty) = self.structs.get(name)?.field(field, args)?;
+                (self.layouts.get_struct(ty)?.offsets[index], field_ty?)
+            }
+            //`t.0`, a value of a tuple
+            CraneType::Tuple { elements } => {
+                let index: usize = field.parse().ok()?;
+                (self.layouts.tuple(elements).offsets[index], elements.get(index)?.clone())
+            }
+            _ => return None,
+        };
+        let (size, _, signed) = self.layout(Some(&field_ty));
+        let place = match slot.place {
+            Place::Local(base) => Place::Local(base + offset),
+            Place::Global(base) => Place::Global(base + offset),
+            Place::Indirect {
+                pointer,
+                offset: at,
+                deref,
+                field,
+            } => Place::Indirect {
+                pointer,
+                offset: at,
+                deref,
+                field: field + offset,
+            },
+        };
+        let slot = Slot {
+            place,
+            size,
+            signed,
+            aggregate: self.aggregate(Some(&field_ty)),
+        };
+        Some((slot, field_ty))
+    }
+
+    //the value of an expression kept in the frame, an aggregate by its
+    //address so that a field of `c[1].x` is written where it is
+    fn temporary(&mut self, node: &Node) -> Option<(Slot, Option<CraneType>)> {
+        let span = node.span;
+        let ty = node.ty.clone();
+        if !self.aggregate(ty.as_ref()) {
+            let slot = self.local(ty.as_ref());
+            self.address(slot, span);
+            if !self.expression(node) {
+                return None;
+            }
+            self.store(slot, span);
+            return Some((slot, ty));
+        }
+        let Place::Local(pointer) = self.pointer().place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(pointer), span);
+        self.expression(node);
+        self.program.emit(Op::Store { size: 8 }, span);
+        let (size, _, signed) = self.layout(ty.as_ref());
+        let place = Place::Indirect {
+            pointer,
+            offset: 0,
+            deref: false,
+            field: 0,
+        };
+        Some((
+            Slot {
+                place,
+                size,
+                signed,
+                aggregate: true,
+            },
+            ty,
+        ))
+    }
+
+    //the slot of `divmod(a, b).0` or `c[1].x`
+    fn member(&mut self, node: &Node) -> Option<Slot> {
+        let (slot, ty) = self.temporary(&node.children[0])?;
+        self.field_of(slot, &ty?, node.value()).map(|(slot, _)| slot)
+    }
+
+    //the slot of what a method is called on, `p` of `p.area()` or the first
+    //child of `V::new().get()`
+    fn receiver(&mut self, node: &Node) -> Option<(Slot, Option<CraneType>)> {
+        if node.is(NodeType::MethodCall) {
+            return self.temporary(&node.children[0]);
+        }
+        let (path, _) = node.value().rsplit_once('.').unwrap();
+        let receiver = Node::new(NodeType::Identifier, Some(path.to_string()), node.line, node.span);
+        self.typed_place(&receiver)
+    }
+
+    //the function index and return type of a function, or of its
+    //instantiation with `args`
+    fn callee(&self, id: usize, args: Option<&Vec<CraneType>>) -> Option<(u32, Option<&'a CraneType>)> {
+        match args {
+            Some(args) => {
+                let &(index, instance) = self.instantiated.get(&(id, args.clone()))?;
+                Some((index, instance.signature.ret.as_ref()))
+            }
+            None => Some((*self.functions.get(&id)?, self.signatures[&id].ret.as_ref())),
+        }
+    }
+
+    fn call(&mut self, node: &Node) -> bool {
+        let span = node.span;
+        if let Some(target) = node.target {
+            return self.method_call(node, target);
+        }
+        //the only method of arrays and slices
+        if self.resolution.is_field_path(node) || node.is(NodeType::MethodCall) {
+            return self.len(node);
+        }
+        let Some(id) = self.resolution.id_of(node) else {
+            return false;
+        };
+        if let Some(&slot) = self.variables.get(&id) {
+            return self.indirect_call(node, id, slot);
+        }
+        if let Some(&index) = self.natives.get(&id) {
+            let params = &self.signatures[&id].params;
+            let mut slots = 0;
+            for (arg, param) in node.arguments().zip(params) {
+                self.expression(arg);
+                slots += 1;
+                //an untyped parameter also gets the type of the argument
+                if param.is_none() {
+                    self.program.emit(Op::Push(native::tag(arg.ty.as_ref())), span);
+                    slots += 1;
+                }
+            }
+            self.program.emit(Op::Native { index, args: slots }, span);
+        } else {
+            let Some((index, ret)) = self.callee(id, node.type_args.as_ref()) else {
+                return false;
+            };
+            for arg in node.arguments() {
+                self.expression(arg);
+            }
+            return self.invoke(ret, index, span);
+        }
+        returns_value(self.signatures[&id].ret.as_ref())
+    }
+
+    //`p.area()` passes `p` as `self`. A method of a trait object is called
+    //with the address of the value, through the slot of the method in the
+    //vtable
+    fn method_call(&mut self, node: &Node, target: usize) -> bool {
+        let span = node.span;
+        let Some((slot, _)) = self.receiver(node) else {
+            return false;
+        };
+        let skip = node.is(NodeType::MethodCall) as usize;
+        let dynamic = self.slots.get(&target).filter(|_| node.type_args.is_none());
+        if let Some(&index) = dynamic {
+            self.address(slot, span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+            for arg in node.arguments().skip(skip) {
+                self.expression(arg);
+            }
+            let ret = self.signatures[&target].ret.as_ref();
+            return self.invoke_with(ret, span, |codegen| {
+                codegen.address(slot, span);
+                codegen.program.emit(Op::Push(8), span);
+                codegen.program.emit(Op::Add, span);
+                codegen.program.emit(Op::Load { size: 8, signed: false }, span);
+                codegen.program.emit(Op::Push(8 * index as u64), span);
+                codegen.program.emit(Op::Add, span);
+                codegen.program.emit(Op::Load { size: 8, signed: false }, span);
+                codegen.program.emit(Op::CallIndirect, span);
+            });
+        }
+        let Some((index, ret)) = self.callee(target, node.type_args.as_ref()) else {
+            return false;
+        };
+        self.load(slot, span);
+        for arg in node.arguments().skip(skip) {
+            self.expression(arg);
+        }
+        self.invoke(ret, index, span)
+    }
+
+    //`f(1)` where `f` holds a function or a closure, which is passed its
+    //environment first
+    fn indirect_call(&mut self, node: &Node, id: usize, slot: Slot) -> bool {
+        let span = node.span;
+        let (closure, ret) = match self.types.get(&id) {
+            Some(CraneType::Function { ret, .. }) => (false, (**ret).clone()),
+            Some(CraneType::Closure { ret, .. }) => (true, (**ret).clone()),
+            _ => return false,
+        };
+        if closure {
+            self.address(slot, span);
+            self.offset(8, span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+        }
+        for arg in node.arguments() {
+            self.expression(arg);
+        }
+        self.invoke_with(Some(&ret), span, |codegen| {
+            codegen.address(slot, span);
+            codegen.program.emit(Op::Load { size: 8, signed: false }, span);
+            codegen.program.emit(Op::CallIndirect, span);
+        })
+    }
+
+    //a function expression is compiled after the function it is in. A
+    //closure copies the variables it captures into its environment, or
+    //their address for those it assigns to, and builds that in the frame
+    //unless it can outlive the call it is made in
+    fn lambda(&mut self, node: &Node) {
+        let span = node.span;
+        let index = self.declare(&format!("<function at line {}>", node.line), Vec::new());
+        let Some(captures) = self.resolution.captures.get(&span) else {
+            self.lambdas.push(Lambda {
+                index,
+                node: node.clone(),
+                env: None,
+            });
+            self.program.emit(Op::Push(index as u64), span);
+            return;
+        };
+        let mut env = Vec::new();
+        let mut copies = Vec::new();
+        let mut size = 0;
+        for &id in captures {
+            let Some(&slot) = self.variables.get(&id) else { continue };
+            let deref = self.resolution.assigned.contains(&id);
+            let offset = align(size, 8);
+            size = offset + if deref { 8 } else { slot.size };
+            let place = Place::Indirect {
+                pointer: 0,
+                offset,
+                deref,
+                field: 0,
+            };
+            env.push((id, Slot { place, ..slot }));
+            copies.push((slot, offset, deref));
+        }
+        let closure = self.local(node.ty.as_ref());
+        let Place::Local(base) = closure.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Push(index as u64), span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        if self.escaping.contains(&span) {
+            self.program.emit(Op::Alloc { size: size.max(8) }, span);
+        } else {
+            let offset = align(self.frame_size, 8);
+            self.frame_size = offset + size;
+            self.program.emit(Op::LocalAddr(offset), span);
+        }
+        self.program.emit(Op::Store { size: 8 }, span);
+        for (slot, offset, deref) in copies {
+            self.program.emit(Op::LocalAddr(base + 8), span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+            self.offset(offset, span);
+            if deref {
+                self.address(slot, span);
+                self.program.emit(Op::Store { size: 8 }, span);
+            } else {
+                self.load(slot, span);
+                self.store(slot, span);
+            }
+        }
+        self.lambdas.push(Lambda {
+            index,
+            node: node.clone(),
+            env: Some(env),
+        });
+        self.address(closure, span);
+    }
+
+    //a function used as a closure, the closure calls the adapter for its
+    //signature with the index of the function as its environment
+    fn adapt(&mut self, node: &Node) {
+        let span = node.span;
+        let Some(ty @ CraneType::Function { .. }) = &node.ty else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let adapter = match self.adapters.get(ty) {
+            Some(&index) => index,
+            None => {
+                let index = self.declare(&format!("<adapter for {}>", ty), Vec::new());
+                self.adapters.insert(ty.clone(), index);
+                self.pending.push((index, ty.clone()));
+                index
+            }
+        };
+        let closure = self.local(node.coercion.as_ref());
+        let Place::Local(base) = closure.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Push(adapter as u64), span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        self.value(node);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.address(closure, span);
+    }
+
+    //build the array in a temporary of the frame and push its address,
+    //`[v; n]` copies the first element into the others
+    fn array_literal(&mut self, node: &Node) {
+        let span = node.span;
+        let Some(CraneType::Array { element, size }) = &node.ty else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let stride = self.layouts.size(element);
+        let value = self.local(node.ty.as_ref());
+        let Place::Local(base) = value.place else { unreachable!() };
+        let repeat = node.value() == "repeat";
+        let values = if repeat { &node.children[..1] } else { &node.children[..] };
+        for (index, item) in values.iter().enumerate() {
+            //`[v; 0]` has no room for `v`
+            if *size == 0 {
+                if self.expression(item) {
+                    self.program.emit(Op::Pop, span);
+                }
+                continue;
+            }
+            let slot = self.field(base + index as u32 * stride, Some(element));
+            self.address(slot, span);
+            self.expression(item);
+            self.store(slot, span);
+        }
+        if repeat && *size > 1 {
+            let counter = self.local(Some(&LENGTH));
+            self.address(counter, span);
+            self.program.emit(Op::Push(1), span);
+            self.store(counter, span);
+            let top = self.program.here();
+            self.load(counter, span);
+            self.program.emit(Op::Push(*size as u64), span);
+            self.program.emit(Op::Lt { signed: false }, span);
+            let done = self.program.emit(Op::JumpIfFalse(0), span);
+            self.program.emit(Op::LocalAddr(base), span);
+            self.load(counter, span);
+            self.program.emit(Op::Push(stride as u64), span);
+            self.program.emit(Op::Mul, span);
+            self.program.emit(Op::Add, span);
+            self.program.emit(Op::LocalAddr(base), span);
+            self.program.emit(Op::Copy { size: stride }, span);
+            self.increment(counter, &LENGTH, span);
+            self.program.emit(Op::Jump(top), span);
+            self.program.patch(done);
+        }
+        self.address(value, span);
+    }
+
+    //an array used as a slice of all of its elements
+    fn view(&mut self, node: &Node) {
+        let span = node.span;
+        //the characters of a string are gone through as their code points
+        if node.ty == Some(CraneType::Str) {
+            self.value(node);
+            return self.native("str_chars", 1, span);
+        }
+        let Some(CraneType::Array { size, .. }) = node.ty else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let slice = self.local(node.coercion.as_ref());
+        let Place::Local(base) = slice.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(base), span);
+        self.value(node);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        self.program.emit(Op::Push(size as u64), span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.address(slice, span);
+    }
+
+    //keep the address of an array or a slice in a local, and give the
+    //length of an array and the type of the elements
+    fn elements(&mut self, node: &Node) -> Option<(u32, Option<u32>, CraneType)> {
+        let (element, size) = match node.coercion.as_ref().or(node.ty.as_ref()) {
+            Some(CraneType::Array { element, size }) => ((**element).clone(), Some(*size)),
+            Some(CraneType::Slice { element }) => ((**element).clone(), None),
+            Some(CraneType::Str) => (CraneType::Char, None),
+            _ => return None,
+        };
+        let Place::Local(pointer) = self.pointer().place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(pointer), node.span);
+        self.expression(node);
+        self.program.emit(Op::Store { size: 8 }, node.span);
+        Some((pointer, size, element))
+    }
+
+    //push the address of the first element of what `elements` kept
+    fn first(&mut self, pointer: u32, size: Option<u32>, span: Span) {
+        self.program.emit(Op::LocalAddr(pointer), span);
+        self.program.emit(Op::Load { size: 8, signed: false }, span);
+        if size.is_none() {
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+        }
+    }
+
+    //push how many elements there are, a slice holds it after the address
+    fn length(&mut self, pointer: u32, size: Option<u32>, span: Span) {
+        let Some(size) = size else {
+            self.program.emit(Op::LocalAddr(pointer), span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+            self.offset(8, span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+            return;
+        };
+        self.program.emit(Op::Push(size as u64), span);
+    }
+
+    //the element of `a[i]`, its address is worked out once and kept in a
+    //local so it can be read and written
+    fn element(&mut self, node: &Node) -> Option<Slot> {
+        let span = node.span;
+        let (pointer, size, element) = self.elements(&node.children[0])?;
+        let Place::Local(address) = self.pointer().place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(address), span);
+        self.first(pointer, size, span);
+        self.expression(&node.children[1]);
+        if self.checks {
+            self.length(pointer, size, span);
+            self.program.emit(Op::CheckIndex, span);
+        }
+        self.program.emit(Op::Push(self.layouts.size(&element) as u64), span);
+        self.program.emit(Op::Mul, span);
+        self.program.emit(Op::Add, span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        let (size, _, signed) = self.layout(Some(&element));
+        Some(Slot {
+            place: Place::Indirect {
+                pointer: address,
+                offset: 0,
+                deref: false,
+                field: 0,
+            },
+            size,
+            signed,
+            aggregate: self.aggregate(Some(&element)),
+        })
+    }
+
+    //`a[1..3]`, the elements from the start up to the end, built in a
+    //temporary of the frame. A missing start is 0, a missing end the length
+    fn slice(&mut self, node: &Node) {
+        let span = node.span;
+        let Some((pointer, size, element)) = self.elements(&node.children[0]) else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let range = &node.children[1];
+        let (start, end) = match range.value() {
+            "a..b" => (range.children.first(), range.children.get(1)),
+            "a.." => (range.children.first(), None),
+            "..b" => (None, range.children.first()),
+            _ => (None, None),
+        };
+        let (first, last) = (self.local(Some(&LENGTH)), self.local(Some(&LENGTH)));
+        self.address(first, span);
+        match start {
+            Some(start) => {
+                self.expression(start);
+            }
+            None => {
+                self.program.emit(Op::Push(0), span);
+            }
+        }
+        self.store(first, span);
+        self.address(last, span);
+        match end {
+            Some(end) => {
+                self.expression(end);
+            }
+            None => self.length(pointer, size, span),
+        }
+        self.store(last, span);
+        if self.checks {
+            self.load(first, span);
+            self.load(last, span);
+            self.length(pointer, size, span);
+            self.program.emit(Op::CheckRange, span);
+        }
+        //a string is only cut where a character starts, which the VM checks
+        if node.ty == Some(CraneType::Str) {
+            self.program.emit(Op::LocalAddr(pointer), span);
+            self.program.emit(Op::Load { size: 8, signed: false }, span);
+            self.load(first, span);
+            self.load(last, span);
+            return self.native("str_slice", 3, span);
+        }
+        let slice = self.local(node.ty.as_ref());
+        let Place::Local(base) = slice.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(base), span);
+        self.first(pointer, size, span);
+        self.load(first, span);
+        self.program.emit(Op::Push(self.layouts.size(&element) as u64), span);
+        self.program.emit(Op::Mul, span);
+        self.program.emit(Op::Add, span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        self.load(last, span);
+        self.load(first, span);
+        self.program.emit(Op::Sub, span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.address(slice, span);
+    }
+
+    //`values.len()`, an array knows its length, a slice or a string holds it
+    fn len(&mut self, node: &Node) -> bool {
+        let span = node.span;
+        match self.receiver(node) {
+            Some((_, Some(CraneType::Array { size, .. }))) => {
+                self.program.emit(Op::Push(size as u64), span);
+            }
+            Some((slot, Some(CraneType::Slice { .. } | CraneType::Str))) => {
+                self.address(slot, span);
+                self.offset(8, span);
+                self.program.emit(Op::Load { size: 8, signed: false }, span);
+            }
+            _ => {
+                self.program.emit(Op::Push(0), span);
+            }
+        }
+        true
+    }
+
+    //compile the body of a loop, its `continue`s go to the next instruction
+    //after it
+    fn loop_body(&mut self, body: &Node, value: bool) -> Vec<u32> {
+        self.loops.push(Jumps { value, ..Jumps::default() });
+        self.statement(body);
+        let jumps = self.loops.pop().unwrap();
+        for jump in jumps.continues {
+            self.program.patch(jump);
+        }
+        jumps.breaks
+    }
+
+    //`while (condition) { ... }`, or `for (init; condition; step) { ... }`
+    //which runs its init first and its step after the body
+    fn while_loop(&mut self, node: &Node) {
+        let span = node.span;
+        let (condition, body, step) = match &node.children[..] {
+            [condition, body] => (condition, body, None),
+            [init, condition, step, body] => {
+                self.statement(init);
+                (condition, body, Some(step))
+            }
+            _ => return,
+        };
+        let top = self.program.here();
+        self.expression(condition);
+        let done = self.program.emit(Op::JumpIfFalse(0), span);
+        let breaks = self.loop_body(body, false);
+        if let Some(step) = step {
+            self.statement(step);
+        }
+        self.program.emit(Op::Jump(top), span);
+        self.program.patch(done);
+        for jump in breaks {
+            self.program.patch(jump);
+        }
+    }
+
+    //`loop { ... }`, which is left by `break` with the value of the loop, if
+    //it has one
+    fn loop_value(&mut self, node: &Node) -> bool {
+        let span = node.span;
+        let value = node.ty.as_ref().is_some_and(|ty| *ty != CraneType::Void);
+        let top = self.program.here();
+        let breaks = self.loop_body(&node.children[0], value);
+        self.program.emit(Op::Jump(top), span);
+        for jump in breaks {
+            self.program.patch(jump);
+        }
+        value
+    }
+
+    //where the variable of a `for` loop is stored. A variable a closure
+    //assigns to gets a new cell every time around
+    fn loop_variable(&mut self, binding: &Node, ty: &CraneType, span: Span) -> Slot {
+        let id = self.resolution.declared_by(binding);
+        let slot = if id.is_some_and(|id| self.boxed.contains(&id)) {
+            self.cell(Some(ty), span)
+        } else if self.top_level {
+            self.global(Some(ty))
+        } else {
+            self.local(Some(ty))
+        };
+        if let Some(id) = id {
+            self.variables.insert(id, slot);
+            self.types.insert(id, ty.clone());
+        }
+        slot
+    }
+
+    //`for i in a..b`, the ends are worked out once before the loop. A range
+    //without an end goes on until a `break`
+    fn range_loop(&mut self, node: &Node) {
+        let span = node.span;
+        let range = &node.children[1];
+        let Some(ty) = range.ty.clone() else { return };
+        let counter = self.local(Some(&ty));
+        self.address(counter, span);
+        self.expression(&range.children[0]);
+        self.store(counter, span);
+        let end = range.children.get(1).map(|end| {
+            let slot = self.local(Some(&ty));
+            self.address(slot, span);
+            self.expression(end);
+            self.store(slot, span);
+            slot
+        });
+        let top = self.program.here();
+        let done = end.map(|end| {
+            self.load(counter, span);
+            self.load(end, span);
+            self.program.emit(Op::Lt { signed: ty.is_signed() }, span);
+            self.program.emit(Op::JumpIfFalse(0), span)
+        });
+        let slot = self.loop_variable(&node.children[0], &ty, span);
+        self.address(slot, span);
+        self.load(counter, span);
+        self.store(slot, span);
+        let breaks = self.loop_body(&node.children[2], false);
+        self.increment(counter, &ty, span);
+        self.program.emit(Op::Jump(top), span);
+        for jump in done.into_iter().chain(breaks) {
+            self.program.patch(jump);
+        }
+    }
+
+    //`for x in values`, every element is copied into `x` in turn
+    fn for_loop(&mut self, node: &Node) {
+        let span = node.span;
+        if node.children[1].is(NodeType::Range) {
+            return self.range_loop(node);
+        }
+        if let Some(target) = node.target {
+            return self.iterator_loop(node, target);
+        }
+        let Some((pointer, size, element)) = self.elements(&node.children[1]) else {
+            return;
+        };
+        let stride = self.layouts.size(&element);
+        let (counter, length) = (self.local(Some(&LENGTH)), self.local(Some(&LENGTH)));
+        self.address(counter, span);
+        self.program.emit(Op::Push(0), span);
+        self.store(counter, span);
+        self.address(length, span);
+        self.length(pointer, size, span);
+        self.store(length, span);
+        let top = self.program.here();
+        self.load(counter, span);
+        self.load(length, span);
+        self.program.emit(Op::Lt { signed: false }, span);
+        let done = self.program.emit(Op::JumpIfFalse(0), span);
+        let slot = self.loop_variable(&node.children[0], &element, span);
+        self.address(slot, span);
+        self.first(pointer, size, span);
+        self.load(counter, span);
+        self.program.emit(Op::Push(stride as u64), span);
+        self.program.emit(Op::Mul, span);
+        self.program.emit(Op::Add, span);
+        if !slot.aggregate {
+            self.program.emit(Op::Load { size: slot.size as u8, signed: slot.signed }, span);
+        }
+        self.store(slot, span);
+        let breaks = self.loop_body(&node.children[2], false);
+        self.increment(counter, &LENGTH, span);
+        self.program.emit(Op::Jump(top), span);
+        for jump in [done].into_iter().chain(breaks) {
+            self.program.patch(jump);
+        }
+    }
+
+    //`for x in values` over an iterator, `next` is called on a copy of it
+    //until it gives `None`
+    fn iterator_loop(&mut self, node: &Node, target: usize) {
+        let span = node.span;
+        let Some((index, Some(ty @ CraneType::Enum { name, args }))) = self.callee(target, node.type_args.as_ref()) else {
+            return;
+        };
+        let def = &self.enums[name];
+        let some = def.variant("Some").unwrap();
+        let layout = self.layouts.get(ty).unwrap();
+        let values = &node.children[1];
+        let iterator = self.local(values.ty.as_ref());
+        self.address(iterator, span);
+        self.expression(values);
+        self.store(iterator, span);
+        let next = self.local(Some(ty));
+        let Place::Local(base) = next.place else { unreachable!() };
+        let top = self.program.here();
+        self.load(iterator, span);
+        self.address(next, span);
+        self.program.emit(Op::Call(index), span);
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Load { size: layout.tag_size as u8, signed: false }, span);
+        self.program.emit(Op::Push(def.variants[some].discriminant), span);
+        self.program.emit(Op::Eq, span);
+        let done = self.program.emit(Op::JumpIfFalse(0), span);
+        let slot = self.loop_variable(&node.children[0], &args[0], span);
+        self.address(slot, span);
+        self.load(self.field(base + layout.offsets[some][0], Some(&args[0])), span);
+        self.store(slot, span);
+        let breaks = self.loop_body(&node.children[2], false);
+        self.program.emit(Op::Jump(top), span);
+        for jump in [done].into_iter().chain(breaks) {
+            self.program.patch(jump);
+        }
+    }
+
+    fn increment(&mut self, counter: Slot, ty: &CraneType, span: Span) {
+        self.address(counter, span);
+        self.load(counter, span);
+        self.program.emit(Op::Push(1), span);
+        self.program.emit(Op::Add, span);
+        self.wrap(Some(ty), span);
+        self.store(counter, span);
+    }
+
+    //the enum, variant index and the value or pattern of every field in
+    //order of a construction or a variant pattern
+    fn variant<'n>(&self, node: &'n Node) -> Option<(&'a EnumDef, usize, Vec<Option<&'n Node>>)> {
+        let Some(CraneType::Enum { name, .. }) = &node.ty else { return None };
+        let def = self.enums.get(name)?;
+        let index = def.variant(node.value().rsplit("::").next().unwrap())?;
+        let variant = &def.variants[index];
+        let mut fields = vec![None; variant.fields.len()];
+        for (position, child) in node.children.iter().enumerate() {
+            match &variant.names {
+                Some(names) if child.is(NodeType::Field) => {
+                    let field = names.iter().position(|n| n == child.value())?;
+                    fields[field] = Some(&child.children[0]);
+                }
+                Some(_) => {}
+                None => *fields.get_mut(position)? = Some(child),
+            }
+        }
+        Some((def, index, fields))
+    }
+
+    //build the variant in a temporary of the frame and push its address
+    fn construct(&mut self, node: &Node) {
+        let span = node.span;
+        let Some((def, index, values)) = self.variant(node) else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let Some(ty @ CraneType::Enum { args, .. }) = &node.ty else { unreachable!() };
+        let layout = self.layouts.get(ty).unwrap();
+        let types = def.fields(index, args);
+        let value = self.local(node.ty.as_ref());
+        let Place::Local(base) = value.place else { unreachable!() };
+        self.address(value, span);
+        self.program.emit(Op::Push(def.variants[index].discriminant), span);
+        self.program.emit(Op::Store { size: layout.tag_size as u8 }, span);
+        for (field, value) in values.into_iter().enumerate() {
+            let Some(value) = value else { continue };
+            let offset = base + layout.offsets[index][field];
+            let slot = self.field(offset, types[field].as_ref());
+            self.address(slot, span);
+            self.expression(value);
+            self.store(slot, span);
+        }
+        self.address(value, span);
+    }
+
+    //`value as u8`, the value is brought to the width of the type. An enum
+    //is converted from the discriminant in its tag, floats by the VM
+    fn cast(&mut self, node: &Node) {
+        let span = node.span;
+        let value = &node.children[0];
+        self.expression(value);
+        if let Some(ty @ CraneType::Enum { .. }) = &value.ty {
+            let tag_size = self.layouts.get(ty).map_or(1, |layout| layout.tag_size);
+            self.program.emit(Op::Load { size: tag_size as u8, signed: false }, span);
+        }
+        let (from, to) = (value.ty.as_ref(), node.ty.as_ref());
+        match (float(from), float(to)) {
+            (Some(from), Some(double)) if from != double => {
+                self.program.emit(Op::FloatToFloat { double }, span);
+            }
+            (Some(_), Some(_)) => {}
+            (Some(double), None) => {
+                let bits = to.and_then(CraneType::bits).unwrap_or(64) as u8;
+                let signed = to.is_none_or(CraneType::is_signed);
+                self.program.emit(Op::FloatToInt { double, bits, signed }, span);
+            }
+            (None, Some(double)) => {
+                let signed = from.is_none_or(CraneType::is_signed);
+                self.program.emit(Op::IntToFloat { signed, double }, span);
+            }
+            (None, None) => self.wrap(to, span),
+        }
+    }
+
+    //`value?`, the value is put in the frame and its tag tested. A `None` or
+    //`Err` builds the one of the return type and returns it
+    fn try_value(&mut self, node: &Node) {
+        let span = node.span;
+        let value = &node.children[0];
+        let (Some(ty @ CraneType::Enum { name, args }), Some(ret @ CraneType::Enum { .. }), Some(out)) =
+            (&value.ty, self.ret.clone(), self.out)
+        else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let def = &self.enums[name];
+        let (ok, fail) = if name == OPTION { ("Some", "None") } else { ("Ok", "Err") };
+        let (ok, fail) = (def.variant(ok).unwrap(), def.variant(fail).unwrap());
+        let layout = self.layouts.get(ty).unwrap();
+        let copy = self.local(value.ty.as_ref());
+        let Place::Local(base) = copy.place else { unreachable!() };
+        self.address(copy, span);
+        self.expression(value);
+        self.store(copy, span);
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::Load { size: layout.tag_size as u8, signed: false }, span);
+        self.program.emit(Op::Push(def.variants[ok].discriminant), span);
+        self.program.emit(Op::Eq, span);
+        let failed = self.program.emit(Op::JumpIfFalse(0), span);
+        let types = def.fields(ok, args);
+        let slot = self.field(base + layout.offsets[ok][0], types[0].as_ref());
+        self.load(slot, span);
+        let done = self.program.emit(Op::Jump(0), span);
+        self.program.patch(failed);
+        let ret_layout = self.layouts.get(&ret).unwrap();
+        let returned = self.local(Some(&ret));
+        let Place::Local(at) = returned.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(at), span);
+        self.program.emit(Op::Push(def.variants[fail].discriminant), span);
+        self.program.emit(Op::Store { size: ret_layout.tag_size as u8 }, span);
+        if let Some(Some(error)) = def.fields(fail, args).first() {
+            let to = self.field(at + ret_layout.offsets[fail][0], Some(error));
+            self.address(to, span);
+            self.load(self.field(base + layout.offsets[fail][0], Some(error)), span);
+            self.store(to, span);
+        }
+        self.load(out, span);
+        self.address(returned, span);
+        self.program.emit(Op::Copy { size: returned.size }, span);
+        self.program.emit(Op::Return { value: false }, span);
+        self.program.patch(done);
+    }
+
+    //build the struct in a temporary of the frame and push its address
+    fn struct_literal(&mut self, node: &Node) {
+        let span = node.span;
+        let ty = node.ty.as_ref();
+        let found = match ty {
+            Some(ty @ CraneType::Struct { name, args }) => self.layouts.get_struct(ty).zip(self.structs.get(name)).map(|l| (l, args)),
+            _ => None,
+        };
+        let Some(((layout, def), args)) = found else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let value = self.local(ty);
+        let Place::Local(base) = value.place else { unreachable!() };
+        for field in &node.children {
+            let Some((index, field_ty)) = def.field(field.value(), args) else { continue };
+            let slot = self.field(base + layout.offsets[index], field_ty.as_ref());
+            self.address(slot, span);
+            self.expression(&field.children[0]);
+            self.store(slot, span);
+        }
+        self.address(value, span);
+    }
+
+    //`(1, true)`, built in the frame like a struct
+    fn tuple(&mut self, node: &Node) {
+        let span = node.span;
+        let Some(CraneType::Tuple { elements }) = &node.ty else {
+            self.program.emit(Op::Push(0), span);
+            return;
+        };
+        let layout = self.layouts.tuple(elements);
+        let value = self.local(node.ty.as_ref());
+        let Place::Local(base) = value.place else { unreachable!() };
+        for ((element, ty), offset) in node.children.iter().zip(elements).zip(layout.offsets) {
+            let slot = self.field(base + offset, Some(ty));
+            self.address(slot, span);
+            self.expression(element);
+            self.store(slot, span);
+        }
+        self.address(value, span);
+    }
+
+    //`let (a, (b, _)) = value`, the value is put in the frame and every name
+    //gets a copy of its part
+    fn destructure(&mut self, node: &Node) {
+        let span = node.span;
+        let value = self.local(node.ty.as_ref());
+        let Place::Local(base) = value.place else { unreachable!() };
+        self.address(value, span);
+        self.expression(node.last().unwrap());
+        self.store(value, span);
+        self.bind_tuple(&node.children[0], base, span);
+    }
+
+    fn bind_tuple(&mut self, pattern: &Node, offset: u32, span: Span) {
+        match (&pattern.node_type, &pattern.ty) {
+            (NodeType::Tuple, Some(CraneType::Tuple { elements })) => {
+                let layout = self.layouts.tuple(elements);
+                for (child, at) in pattern.children.iter().zip(layout.offsets) {
+                    self.bind_tuple(child, offset + at, span);
+                }
+            }
+            (NodeType::Binding, ty) => {
+                let id = self.resolution.declared_by(pattern);
+                let slot = if id.is_some_and(|id| self.boxed.contains(&id)) {
+                    self.cell(ty.as_ref(), span)
+                } else if self.top_level {
+                    self.global(ty.as_ref())
+                } else {
+                    self.local(ty.as_ref())
+                };
+                if let Some(id) = id {
+                    self.variables.insert(id, slot);
+                    if let Some(ty) = ty {
+                        self.types.insert(id, ty.clone());
+                    }
+                }
+                self.address(slot, span);
+                let part = self.field(offset, ty.as_ref());
+                self.load(part, span);
+                self.store(slot, span);
+            }
+            _ => {}
+        }
+    }
+
+    //a field of a value in the frame
+    fn field(&self, offset: u32, ty: Option<&CraneType>) -> Slot {
+        let (size, _, signed) = self.layout(ty);
+        Slot {
+            place: Place::Local(offset),
+            size,
+            signed,
+            aggregate: self.aggregate(ty),
+        }
+    }
+
+    //the value is copied into the frame, each arm tests it in turn and jumps
+    //to the next one when its pattern or guard fails. No arm failing them
+    //all has been checked by the type checker
+    fn match_expression(&mut self, node: &Node) -> bool {
+        let span = node.span;
+        let scrutinee = &node.children[0];
+        let value = self.local(scrutinee.ty.as_ref());
+        let Place::Local(offset) = value.place else { unreachable!() };
+        self.address(value, span);
+        self.expression(scrutinee);
+        self.store(value, span);
+        let produces = node.ty.as_ref() != Some(&CraneType::Void);
+        let mut ends = Vec::new();
+        for arm in &node.children[1..] {
+            let mut fails = Vec::new();
+            self.pattern(&arm.children[0], offset, &mut fails);
+            if let Some(guard) = arm.child(NodeType::Guard) {
+                self.expression(&guard.children[0]);
+                fails.push(self.program.emit(Op::JumpIfFalse(0), guard.span));
+            }
+            let body = arm.last().unwrap();
+            if body.is(NodeType::Block) {
+                self.statement(body);
+            } else {
+                let pushed = self.expression(body);
+                if pushed && !produces {
+                    self.program.emit(Op::Pop, body.span);
+                } else if !pushed && produces {
+                    self.program.emit(Op::Push(0), body.span);
+                }
+            }
+            ends.push(self.program.emit(Op::Jump(0), arm.span));
+            for fail in fails {
+                self.program.patch(fail);
+            }
+        }
+        for end in ends {
+            self.program.patch(end);
+        }
+        produces
+    }
+
+    //test the value at `offset` in the frame against a pattern, adding the
+    //jumps taken when it does not match, and bind the names it has
+    fn pattern(&mut self, node: &Node, offset: u32, fails: &mut Vec<u32>) {
+        let span = node.span;
+        match node.node_type {
+            NodeType::Binding => {
+                if let Some(id) = self.resolution.declared_by(node) {
+                    let mut slot = self.field(offset, node.ty.as_ref());
+                    if self.boxed.contains(&id) {
+                        let cell = self.cell(node.ty.as_ref(), span);
+                        self.address(cell, span);
+                        self.load(slot, span);
+                        self.store(cell, span);
+                        slot = cell;
+                    }
+                    self.variables.insert(id, slot);
+                    if let Some(ty) = &node.ty {
+                        self.types.insert(id, ty.clone());
+                    }
+                }
+            }
+            NodeType::Number | NodeType::UnaryExpression | NodeType::Character | NodeType::Bool => {
+                let slot = self.field(offset, node.ty.as_ref());
+                self.load(slot, span);
+                self.expression(node);
+                self.program.emit(Op::Eq, span);
+                fails.push(self.program.emit(Op::JumpIfFalse(0), span));
+            }
+            NodeType::Construct => {
+                let Some((def, index, patterns)) = self.variant(node) else { return };
+                let Some(ty @ CraneType::Enum { .. }) = &node.ty else { return };
+                let layout = self.layouts.get(ty).unwrap();
+                self.program.emit(Op::LocalAddr(offset), span);
+                self.program.emit(Op::Load { size: layout.tag_size as u8, signed: false }, span);
+                self.program.emit(Op::Push(def.variants[index].discriminant), span);
+                self.program.emit(Op::Eq, span);
+                fails.push(self.program.emit(Op::JumpIfFalse(0), span));
+                for (field, pattern) in patterns.into_iter().enumerate() {
+                    if let Some(pattern) = pattern {
+                        self.pattern(pattern, offset + layout.offsets[index][field], fails);
+                    }
+                }
+            }
+            //`P { x: 0, y }` always matches a `P`, its fields may not
+            NodeType::StructLiteral => {
+                let Some(ty @ CraneType::Struct { name, args }) = &node.ty else { return };
+                let (Some(def), Some(layout)) = (self.structs.get(name), self.layouts.get_struct(ty)) else { return };
+                for field in node.children.iter().filter(|c| c.is(NodeType::Field)) {
+                    if let Some((index, _)) = def.field(field.value(), args) {
+                        self.pattern(&field.children[0], offset + layout.offsets[index], fails);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //a string constant, the address of its bytes in the data section and
+    //their length are put together in a temporary of the frame
+    fn text(&mut self, value: &str, span: Span) {
+        let offset = self.string(value);
+        let text = self.local(Some(&CraneType::Str));
+        let Place::Local(base) = text.place else { unreachable!() };
+        self.program.emit(Op::LocalAddr(base), span);
+        self.program.emit(Op::DataAddr(offset), span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.program.emit(Op::LocalAddr(base + 8), span);
+        self.program.emit(Op::Push(value.len() as u64), span);
+        self.program.emit(Op::Store { size: 8 }, span);
+        self.address(text, span);
+    }
+
+    //"x = {x}", the value of every hole is turned into text, and each part
+    //is joined to the text before it
+    fn format(&mut self, node: &Node) {
+        let span = node.span;
+        if node.children.is_empty() {
+            return self.text("", span);
+        }
+        for (position, part) in node.children.iter().enumerate() {
+            self.expression(part);
+            if part.ty != Some(CraneType::Str) {
+                self.program.emit(Op::Push(native::tag(part.ty.as_ref())), span);
+                self.native("str_show", 2, span);
+            }
+            if position > 0 {
+                self.native("str_concat", 2, span);
+            }
+        }
+    }
+
+    //call a native function the generated code relies on
+    fn native(&mut self, name: &str, args: u8, span: Span) {
+        let index = native::find(name).unwrap() as u16;
+        self.program.emit(Op::Native { index, args }, span);
+    }
+
+    //the offset of the bytes of a string constant in the data section, the
+    //same text is only there once
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some(&offset) = self.strings.get(value) {
+            return offset;
+        }
+        let offset = self.program.data.len() as u32;
+        self.program.data.extend_from_slice(value.as_bytes());
+        self.strings.insert(value.to_string(), offset);
+        offset
+    }
+}
+
+//whether a float type is an f64, None for any other type
+fn float(ty: Option<&CraneType>) -> Option<bool> {
+    match ty {
+        Some(CraneType::Float) => Some(false),
+        Some(CraneType::Double) => Some(true),
+        _ => None,
+    }
+}
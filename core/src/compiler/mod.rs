@@ -1,3 +1,7 @@
+#![allow(dead_code)]
 mod prebuild;
 pub mod types;
-pub mod asm;
\ No newline at end of file
+pub mod asm;
+pub mod codegen;
+pub mod native;
+pub mod vm;
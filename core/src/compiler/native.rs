@@ -0,0 +1,351 @@
+use super::types::CraneType;
+use super::vm::{Object, Vm};
+use std::collections::HashMap;
+use std::fs;
+
+// A function implemented by the VM. The standard library declares each one
+// with `@native` and the signature it is called with; a parameter without a
+// type takes any value and is passed as two slots, the value and its tag
+pub struct Native {
+    pub name: &'static str,
+    pub run: fn(&mut Vm, &[u64]) -> Result<Option<u64>, String>,
+}
+
+pub fn find(name: &str) -> Option<usize> {
+    NATIVES.iter().position(|n| n.name == name)
+}
+
+// Tags say how to show a value passed to an untyped parameter
+const TAG_INT: u64 = 0;
+const TAG_UINT: u64 = 1;
+const TAG_CHAR: u64 = 2;
+const TAG_BOOL: u64 = 3;
+const TAG_STR: u64 = 4;
+const TAG_POINTER: u64 = 5;
+const TAG_F32: u64 = 6;
+const TAG_F64: u64 = 7;
+
+pub fn tag(ty: Option<&CraneType>) -> u64 {
+    match ty {
+        Some(CraneType::Char) => TAG_CHAR,
+        Some(CraneType::Bool) => TAG_BOOL,
+        Some(CraneType::Str) => TAG_STR,
+        Some(CraneType::Pointer { .. }) => TAG_POINTER,
+        Some(CraneType::Float) => TAG_F32,
+        Some(CraneType::Double) => TAG_F64,
+        Some(ty) if ty.is_integer() && !ty.is_signed() => TAG_UINT,
+        _ => TAG_INT,
+    }
+}
+
+fn show(vm: &Vm, value: u64, tag: u64) -> Result<String, String> {
+    Ok(match tag {
+        TAG_UINT => value.to_string(),
+        TAG_CHAR => (value as u8 as char).to_string(),
+        TAG_BOOL => (value != 0).to_string(),
+        TAG_STR => vm.read_str(value)?,
+        TAG_POINTER => format!("0x{:x}", value),
+        //with a fraction even when it is whole, `2.0` and not `2`
+        TAG_F32 => format!("{:?}", f32::from_bits(value as u32)),
+        TAG_F64 => format!("{:?}", f64::from_bits(value)),
+        _ => (value as i64).to_string(),
+    })
+}
+
+fn write(vm: &mut Vm, text: &str, error: bool) -> Result<Option<u64>, String> {
+    if error {
+        eprint!("{}", text);
+    } else {
+        vm.out.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(None)
+}
+
+fn string(vm: &Vm, address: u64) -> Result<String, String> {
+    vm.read_str(address)
+}
+
+fn array<'a>(vm: &'a mut Vm, handle: u64) -> Result<&'a mut Vec<i64>, String> {
+    match vm.object(handle)? {
+        Object::Array(items) => Ok(items),
+        _ => Err(format!("handle {} is not an array", handle)),
+    }
+}
+
+fn map<'a>(vm: &'a mut Vm, handle: u64) -> Result<&'a mut HashMap<i64, i64>, String> {
+    match vm.object(handle)? {
+        Object::Map(entries) => Ok(entries),
+        _ => Err(format!("handle {} is not a map", handle)),
+    }
+}
+
+fn index(len: usize, index: u64) -> Result<usize, String> {
+    if index as usize >= len {
+        return Err(format!("index out of bounds: the length is {} but the index is {}", len, index));
+    }
+    Ok(index as usize)
+}
+
+pub static NATIVES: &[Native] = &[
+    // prelude
+    Native {
+        name: "print",
+        run: |vm, a| {
+            let text = show(vm, a[0], a[1])?;
+            write(vm, &text, false)
+        },
+    },
+    Native {
+        name: "println",
+        run: |vm, a| {
+            let text = show(vm, a[0], a[1])? + "\n";
+            write(vm, &text, false)
+        },
+    },
+    Native {
+        name: "panic",
+        run: |vm, a| Err(string(vm, a[0])?),
+    },
+    // std.string
+    Native {
+        name: "str_len",
+        run: |vm, a| Ok(Some(vm.str_bytes(a[0])?.len() as u64)),
+    },
+    Native {
+        name: "str_eq",
+        run: |vm, a| Ok(Some((string(vm, a[0])? == string(vm, a[1])?) as u64)),
+    },
+    Native {
+        name: "str_concat",
+        run: |vm, a| {
+            let joined = string(vm, a[0])? + &string(vm, a[1])?;
+            Ok(Some(vm.alloc_str(&joined)))
+        },
+    },
+    // a view of the bytes of the string, nothing is copied
+    Native {
+        name: "str_slice",
+        run: |vm, a| {
+            let bytes = vm.str_bytes(a[0])?;
+            let (start, end) = (a[1] as usize, a[2] as usize);
+            if start > end || end > bytes.len() {
+                return Err(format!("cannot slice {}..{} of a string of length {}", start, end, bytes.len()));
+            }
+            //a character boundary is where a byte does not continue a character
+            let boundary = |i: usize| i == bytes.len() || bytes[i] & 0xc0 != 0x80;
+            if let Some(i) = [start, end].into_iter().find(|&i| !boundary(i)) {
+                return Err(format!("byte index {} is not on a character boundary", i));
+            }
+            let first = vm.load(a[0], 8, false)? + start as u64;
+            let view = vm.alloc(16);
+            Ok(Some(vm.view(view, first, (end - start) as u64)))
+        },
+    },
+    Native {
+        name: "str_find",
+        run: |vm, a| {
+            let found = string(vm, a[0])?.find(&string(vm, a[1])?);
+            Ok(Some(found.map_or(-1, |i| i as i64) as u64))
+        },
+    },
+    Native {
+        name: "str_char_at",
+        run: |vm, a| {
+            let s = string(vm, a[0])?;
+            let i = index(s.len(), a[1])?;
+            Ok(Some(s.as_bytes()[i] as u64))
+        },
+    },
+    Native {
+        name: "str_from_int",
+        run: |vm, a| {
+            let text = (a[0] as i64).to_string();
+            Ok(Some(vm.alloc_str(&text)))
+        },
+    },
+    Native {
+        name: "str_to_int",
+        run: |vm, a| {
+            let s = string(vm, a[0])?;
+            match s.trim().parse::<i64>() {
+                Ok(n) => Ok(Some(n as u64)),
+                Err(_) => Err(format!("`{}` is not a number", s)),
+            }
+        },
+    },
+    Native {
+        name: "str_upper",
+        run: |vm, a| {
+            let upper = string(vm, a[0])?.to_uppercase();
+            Ok(Some(vm.alloc_str(&upper)))
+        },
+    },
+    Native {
+        name: "str_lower",
+        run: |vm, a| {
+            let lower = string(vm, a[0])?.to_lowercase();
+            Ok(Some(vm.alloc_str(&lower)))
+        },
+    },
+    // the code generated for strings calls these
+    Native {
+        name: "str_cmp",
+        run: |vm, a| {
+            let ordering = vm.str_bytes(a[0])?.cmp(vm.str_bytes(a[1])?);
+            Ok(Some(ordering as i64 as u64))
+        },
+    },
+    Native {
+        name: "str_show",
+        run: |vm, a| {
+            let text = show(vm, a[0], a[1])?;
+            Ok(Some(vm.alloc_str(&text)))
+        },
+    },
+    // the code points of the characters, as a slice of u32
+    Native {
+        name: "str_chars",
+        run: |vm, a| {
+            let chars: Vec<u32> = string(vm, a[0])?.chars().map(u32::from).collect();
+            let slice = vm.alloc(16 + 4 * chars.len());
+            for (i, &c) in chars.iter().enumerate() {
+                vm.store(slice + 16 + 4 * i as u64, 4, c as u64)?;
+            }
+            Ok(Some(vm.view(slice, slice + 16, chars.len() as u64)))
+        },
+    },
+    // std.array, arrays of i64 behind a handle
+    Native {
+        name: "array_new",
+        run: |vm, _| Ok(Some(vm.new_object(Object::Array(Vec::new())))),
+    },
+    Native {
+        name: "array_push",
+        run: |vm, a| {
+            array(vm, a[0])?.push(a[1] as i64);
+            Ok(None)
+        },
+    },
+    Native {
+        name: "array_pop",
+        run: |vm, a| match array(vm, a[0])?.pop() {
+            Some(value) => Ok(Some(value as u64)),
+            None => Err("pop from an empty array".to_string()),
+        },
+    },
+    Native {
+        name: "array_get",
+        run: |vm, a| {
+            let items = array(vm, a[0])?;
+            let i = index(items.len(), a[1])?;
+            Ok(Some(items[i] as u64))
+        },
+    },
+    Native {
+        name: "array_set",
+        run: |vm, a| {
+            let items = array(vm, a[0])?;
+            let i = index(items.len(), a[1])?;
+            items[i] = a[2] as i64;
+            Ok(None)
+        },
+    },
+    Native {
+        name: "array_len",
+        run: |vm, a| Ok(Some(array(vm, a[0])?.len() as u64)),
+    },
+    // std.map, maps from i64 to i64 behind a handle
+    Native {
+        name: "map_new",
+        run: |vm, _| Ok(Some(vm.new_object(Object::Map(HashMap::new())))),
+    },
+    Native {
+        name: "map_insert",
+        run: |vm, a| {
+            map(vm, a[0])?.insert(a[1] as i64, a[2] as i64);
+            Ok(None)
+        },
+    },
+    Native {
+        name: "map_get",
+        run: |vm, a| match map(vm, a[0])?.get(&(a[1] as i64)) {
+            Some(&value) => Ok(Some(value as u64)),
+            None => Err(format!("key {} is not in the map", a[1] as i64)),
+        },
+    },
+    Native {
+        name: "map_contains",
+        run: |vm, a| Ok(Some(map(vm, a[0])?.contains_key(&(a[1] as i64)) as u64)),
+    },
+    Native {
+        name: "map_remove",
+        run: |vm, a| Ok(Some(map(vm, a[0])?.remove(&(a[1] as i64)).is_some() as u64)),
+    },
+    Native {
+        name: "map_len",
+        run: |vm, a| Ok(Some(map(vm, a[0])?.len() as u64)),
+    },
+    // the keys in ascending order, as a new std.array handle
+    Native {
+        name: "map_keys",
+        run: |vm, a| {
+            let mut keys: Vec<i64> = map(vm, a[0])?.keys().copied().collect();
+            keys.sort_unstable();
+            Ok(Some(vm.new_object(Object::Array(keys))))
+        },
+    },
+    // std.math
+    Native {
+        name: "sqrt",
+        run: |_, a| Ok(Some(f64::from_bits(a[0]).sqrt().to_bits())),
+    },
+    Native {
+        name: "floor",
+        run: |_, a| Ok(Some(f64::from_bits(a[0]).floor().to_bits())),
+    },
+    Native {
+        name: "ceil",
+        run: |_, a| Ok(Some(f64::from_bits(a[0]).ceil().to_bits())),
+    },
+    // std.io
+    Native {
+        name: "eprint",
+        run: |vm, a| {
+            let text = show(vm, a[0], a[1])?;
+            write(vm, &text, true)
+        },
+    },
+    Native {
+        name: "eprintln",
+        run: |vm, a| {
+            let text = show(vm, a[0], a[1])? + "\n";
+            write(vm, &text, true)
+        },
+    },
+    Native {
+        name: "read_line",
+        run: |vm, _| {
+            let mut line = String::new();
+            vm.input.read_line(&mut line).map_err(|e| e.to_string())?;
+            let line = line.trim_end_matches(['\n', '\r']).to_string();
+            Ok(Some(vm.alloc_str(&line)))
+        },
+    },
+    Native {
+        name: "read_file",
+        run: |vm, a| {
+            let path = string(vm, a[0])?;
+            match fs::read_to_string(&path) {
+                Ok(contents) => Ok(Some(vm.alloc_str(&contents))),
+                Err(e) => Err(format!("could not read {}: {}", path, e)),
+            }
+        },
+    },
+    Native {
+        name: "write_file",
+        run: |vm, a| {
+            let (path, contents) = (string(vm, a[0])?, string(vm, a[1])?);
+            Ok(Some(fs::write(path, contents).is_ok() as u64))
+        },
+    },
+];
@@ -1,16 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+
 // Define the enum for basic types
-#[derive(Debug, Clone)]
-enum CraneType {
+// Char is 8 bits, U16 16 bits, Short 32 bits and Long 64 bits wide, Float
+// and Double are IEEE 754 binary32 and binary64
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CraneType {
     //primitive types
     Char,
     U16,
     Short { signed: bool },
     Long { signed: bool },
+    Float,
+    Double,
     Void,
     Bool,
+    //a string, the address of its UTF-8 bytes and how many bytes there are
+    Str,
     //derived types
     Pointer { pointee: Box<CraneType> },
     Array { element: Box<CraneType>, size: u32 },
+    //a view of elements stored one after the other, `[u16]`: the address of
+    //the first and how many there are
+    Slice { element: Box<CraneType> },
+    //values of the given types one after the other, `(u16, bool)`, laid out
+    //like the fields of a struct
+    Tuple { elements: Vec<CraneType> },
+    //an enum by its module and name with its type arguments, `shapes.Shape`
+    //or `prelude.Option<i32>`, the layout of every instantiation is in `Layouts`
+    Enum { name: String, args: Vec<CraneType> },
+    //a struct by its module and name with its type arguments, `pairs.Pair<i32>`,
+    //the layout of every instantiation is in `Layouts`
+    Struct { name: String, args: Vec<CraneType> },
+    //a type parameter of the generic function being checked, only found in
+    //signatures and in the generic form of a body
+    Param { name: String },
+    //a value of any type implementing a trait, `dyn shapes.Shape`: the
+    //address of the value and of the vtable of its type for the trait
+    Dyn { name: String },
+    //a function by its index, `def(i32) -> bool`, a named function or a
+    //function expression that captures nothing
+    Function { params: Vec<CraneType>, ret: Box<CraneType> },
+    //a function with the variables it captured, `dyn def(i32) -> bool`: the
+    //index of the function and the address of its environment
+    Closure { params: Vec<CraneType>, ret: Box<CraneType> },
+}
+
+impl CraneType {
+    // Resolve the name of a primitive type as written in source code
+    pub fn from_name(name: &str) -> Option<CraneType> {
+        match name {
+            "char" | "u8" => Some(CraneType::Char),
+            "u16" => Some(CraneType::U16),
+            "short" | "i32" => Some(CraneType::Short { signed: true }),
+            "ushort" | "u32" => Some(CraneType::Short { signed: false }),
+            "long" | "i64" => Some(CraneType::Long { signed: true }),
+            "ulong" | "u64" => Some(CraneType::Long { signed: false }),
+            "float" | "f32" => Some(CraneType::Float),
+            "double" | "f64" => Some(CraneType::Double),
+            "void" => Some(CraneType::Void),
+            "bool" => Some(CraneType::Bool),
+            "str" => Some(CraneType::Str),
+            _ => None,
+        }
+    }
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            CraneType::Char | CraneType::U16 | CraneType::Short { .. } | CraneType::Long { .. }
+        )
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, CraneType::Float | CraneType::Double)
+    }
+    // The types arithmetic and ordering work on
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            CraneType::Short { signed: true } | CraneType::Long { signed: true }
+        )
+    }
+    // Width in bits of the integer types
+    pub fn bits(&self) -> Option<u32> {
+        match self {
+            CraneType::Char => Some(8),
+            CraneType::U16 => Some(16),
+            CraneType::Short { .. } => Some(32),
+            CraneType::Long { .. } => Some(64),
+            _ => None,
+        }
+    }
+    // Whether a type parameter is left in the type
+    pub fn is_generic(&self) -> bool {
+        match self {
+            CraneType::Param { .. } => true,
+            CraneType::Pointer { pointee } => pointee.is_generic(),
+            CraneType::Array { element, .. } | CraneType::Slice { element } => element.is_generic(),
+            CraneType::Enum { args: elements, .. } | CraneType::Struct { args: elements, .. } | CraneType::Tuple { elements } => {
+                elements.iter().any(CraneType::is_generic)
+            }
+            CraneType::Function { params, ret } | CraneType::Closure { params, ret } => {
+                params.iter().any(CraneType::is_generic) || ret.is_generic()
+            }
+            _ => false,
+        }
+    }
+    // How many types this one is made of, counting itself
+    pub fn weight(&self) -> usize {
+        1 + match self {
+            CraneType::Pointer { pointee: element } | CraneType::Array { element, .. } | CraneType::Slice { element } => element.weight(),
+            CraneType::Enum { args: elements, .. } | CraneType::Struct { args: elements, .. } | CraneType::Tuple { elements } => {
+                elements.iter().map(CraneType::weight).sum()
+            }
+            CraneType::Function { params, ret } | CraneType::Closure { params, ret } => {
+                params.iter().map(CraneType::weight).sum::<usize>() + ret.weight()
+            }
+            _ => 0,
+        }
+    }
+    // Replace the type parameters with the types they stand for
+    pub fn substitute(&self, types: &HashMap<String, CraneType>) -> CraneType {
+        match self {
+            CraneType::Param { name } => types.get(name).cloned().unwrap_or_else(|| self.clone()),
+            CraneType::Pointer { pointee } => CraneType::Pointer {
+                pointee: Box::new(pointee.substitute(types)),
+            },
+            CraneType::Array { element, size } => CraneType::Array {
+                element: Box::new(element.substitute(types)),
+                size: *size,
+            },
+            CraneType::Slice { element } => CraneType::Slice {
+                element: Box::new(element.substitute(types)),
+            },
+            CraneType::Struct { name, args } => CraneType::Struct {
+                name: name.clone(),
+                args: args.iter().map(|arg| arg.substitute(types)).collect(),
+            },
+            CraneType::Enum { name, args } => CraneType::Enum {
+                name: name.clone(),
+                args: args.iter().map(|arg| arg.substitute(types)).collect(),
+            },
+            CraneType::Tuple { elements } => CraneType::Tuple {
+                elements: elements.iter().map(|element| element.substitute(types)).collect(),
+            },
+            CraneType::Function { params, ret } => CraneType::Function {
+                params: params.iter().map(|param| param.substitute(types)).collect(),
+                ret: Box::new(ret.substitute(types)),
+            },
+            CraneType::Closure { params, ret } => CraneType::Closure {
+                params: params.iter().map(|param| param.substitute(types)).collect(),
+                ret: Box::new(ret.substitute(types)),
+            },
+            ty => ty.clone(),
+        }
+    }
+    // Size in bytes of a value of this type in VM memory, `u32::MAX` for a
+    // type too large to be stored, which the type checker reports
+    pub fn size(&self) -> u32 {
+        match self {
+            CraneType::Char | CraneType::Bool => 1,
+            CraneType::U16 => 2,
+            CraneType::Short { .. } | CraneType::Float => 4,
+            CraneType::Long { .. } | CraneType::Double | CraneType::Pointer { .. } | CraneType::Function { .. } => 8,
+            CraneType::Dyn { .. } | CraneType::Closure { .. } | CraneType::Slice { .. } | CraneType::Str => 16,
+            CraneType::Void => 0,
+            CraneType::Array { element, size } => element.size().saturating_mul(*size),
+            CraneType::Tuple { elements } => {
+                let elements: Vec<(u32, u32)> = elements.iter().map(|e| (e.size(), e.align())).collect();
+                StructLayout::new(&elements).map_or(u32::MAX, |layout| layout.size)
+            }
+            //enums and structs are sized by `Layouts`
+            CraneType::Enum { .. } | CraneType::Struct { .. } | CraneType::Param { .. } => 0,
+        }
+    }
+    // Values are stored at addresses that are a multiple of this
+    pub fn align(&self) -> u32 {
+        match self {
+            CraneType::Array { element, .. } => element.align(),
+            CraneType::Tuple { elements } => elements.iter().map(CraneType::align).max().unwrap_or(1),
+            CraneType::Dyn { .. } | CraneType::Closure { .. } | CraneType::Slice { .. } | CraneType::Str => 8,
+            CraneType::Void | CraneType::Enum { .. } | CraneType::Struct { .. } | CraneType::Param { .. } => 1,
+            ty => ty.size(),
+        }
+    }
+    // Whether every value of this integer type is a value of `to`, so it can
+    // be used where `to` is expected without a cast. Signedness only widens
+    // from unsigned to a wider signed type. Floats are not widened, an f32
+    // has other bits than the same f64
+    pub fn widens_to(&self, to: &CraneType) -> bool {
+        match (self.range(), to.range()) {
+            (Some((min, max)), Some((to_min, to_max))) => to_min <= min && max <= to_max,
+            _ => false,
+        }
+    }
+    // The type both integer types widen to, which is one of them
+    pub fn join(&self, other: &CraneType) -> Option<CraneType> {
+        if self.widens_to(other) {
+            Some(other.clone())
+        } else if other.widens_to(self) {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+    // Whether `as` converts a value of this type to `to`: integers to each
+    // other, truncating or extending, integers and floats to each other,
+    // rounding to the nearest or towards zero and saturating, bool to an
+    // integer, and pointers to each other and to and from `u64`
+    pub fn casts_to(&self, to: &CraneType) -> bool {
+        let address = CraneType::Long { signed: false };
+        match (self, to) {
+            (from, to) if from.is_numeric() && to.is_numeric() => true,
+            (CraneType::Bool, to) => to.is_integer() || *to == CraneType::Bool,
+            (CraneType::Pointer { .. }, CraneType::Pointer { .. }) => true,
+            (CraneType::Pointer { .. }, to) => *to == address,
+            (from, CraneType::Pointer { .. }) => *from == address,
+            (from, to) => from == to,
+        }
+    }
+    // Smallest and largest value of the integer types
+    pub fn range(&self) -> Option<(i128, i128)> {
+        let bits = self.bits()?;
+        if self.is_signed() {
+            Some((-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1))
+        } else {
+            Some((0, (1i128 << bits) - 1))
+        }
+    }
+}
+
+impl fmt::Display for CraneType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CraneType::Char => write!(f, "char"),
+            CraneType::U16 => write!(f, "u16"),
+            CraneType::Short { signed: true } => write!(f, "i32"),
+            CraneType::Short { signed: false } => write!(f, "u32"),
+            CraneType::Long { signed: true } => write!(f, "i64"),
+            CraneType::Long { signed: false } => write!(f, "u64"),
+            CraneType::Float => write!(f, "f32"),
+            CraneType::Double => write!(f, "f64"),
+            CraneType::Void => write!(f, "void"),
+            CraneType::Bool => write!(f, "bool"),
+            CraneType::Str => write!(f, "str"),
+            CraneType::Pointer { pointee } => write!(f, "*{}", pointee),
+            CraneType::Array { element, size } => write!(f, "[{}; {}]", element, size),
+            CraneType::Slice { element } => write!(f, "[{}]", element),
+            CraneType::Tuple { elements } => {
+                let elements: Vec<String> = elements.iter().map(CraneType::to_string).collect();
+                //`(u16,)` is a tuple of one, `(u16)` just a `u16`
+                match elements.len() {
+                    1 => write!(f, "({},)", elements[0]),
+                    _ => write!(f, "({})", elements.join(", ")),
+                }
+            }
+            CraneType::Enum { name, args } | CraneType::Struct { name, args } => {
+                write!(f, "{}", name.rsplit('.').next().unwrap())?;
+                if !args.is_empty() {
+                    let args: Vec<String> = args.iter().map(CraneType::to_string).collect();
+                    write!(f, "<{}>", args.join(", "))?;
+                }
+                Ok(())
+            }
+            CraneType::Param { name } => write!(f, "{}", name),
+            CraneType::Dyn { name } => write!(f, "dyn {}", name.rsplit('.').next().unwrap()),
+            CraneType::Function { params, ret } => write_function(f, params, ret),
+            CraneType::Closure { params, ret } => {
+                write!(f, "dyn ")?;
+                write_function(f, params, ret)
+            }
+        }
+    }
+}
+
+// `def(i32, bool) -> u8`, a function returning nothing has no arrow
+fn write_function(f: &mut fmt::Formatter, params: &[CraneType], ret: &CraneType) -> fmt::Result {
+    let params: Vec<String> = params.iter().map(CraneType::to_string).collect();
+    write!(f, "def({})", params.join(", "))?;
+    if *ret != CraneType::Void {
+        write!(f, " -> {}", ret)?;
+    }
+    Ok(())
+}
+
+// How an enum is stored: a tag holding the discriminant of the variant, then the
+// fields of that variant. Every variant starts its fields at the same offset,
+// after the tag and aligned for the most aligned field of any variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumLayout {
+    pub tag_size: u32,
+    pub size: u32,
+    pub align: u32,
+    // The offset of every field, by variant
+    pub offsets: Vec<Vec<u32>>,
+}
+
+impl EnumLayout {
+    // `variants` has the size and alignment of every field of every variant,
+    // the tag is wide enough for the largest discriminant. `None` if it does
+    // not fit in a u32
+    pub fn new(variants: &[Vec<(u32, u32)>], largest: u64) -> Option<EnumLayout> {
+        let tag_size = match largest {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            _ => 4,
+        };
+        let payload_align = variants.iter().flatten().map(|&(_, align)| align).max().unwrap_or(1);
+        let start = round_up(tag_size, payload_align)?;
+        let mut end = tag_size;
+        let mut offsets = Vec::new();
+        for fields in variants {
+            let mut offset = start;
+            let mut variant = Vec::new();
+            for &(size, align) in fields {
+                offset = round_up(offset, align)?;
+                variant.push(offset);
+                offset = offset.checked_add(size)?;
+            }
+            end = end.max(offset);
+            offsets.push(variant);
+        }
+        let align = tag_size.max(payload_align);
+        Some(EnumLayout {
+            tag_size,
+            size: round_up(end, align)?,
+            align,
+            offsets,
+        })
+    }
+}
+
+// How a struct is stored: its fields in the order they are declared, each
+// aligned for its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub size: u32,
+    pub align: u32,
+    pub offsets: Vec<u32>,
+}
+
+impl StructLayout {
+    // `fields` has the size and alignment of every field, `None` if it does
+    // not fit in a u32
+    pub fn new(fields: &[(u32, u32)]) -> Option<StructLayout> {
+        let mut offset: u32 = 0;
+        let mut offsets = Vec::new();
+        for &(size, align) in fields {
+            offset = round_up(offset, align)?;
+            offsets.push(offset);
+            offset = offset.checked_add(size)?;
+        }
+        let align = fields.iter().map(|&(_, align)| align).max().unwrap_or(1);
+        Some(StructLayout {
+            size: round_up(offset, align)?,
+            align,
+            offsets,
+        })
+    }
+}
+
+// The layout of every instantiation of an enum or a struct of the program,
+// and with them the size of any type
+#[derive(Debug, Clone, Default)]
+pub struct Layouts {
+    enums: HashMap<CraneType, EnumLayout>,
+    structs: HashMap<CraneType, StructLayout>,
+}
+
+impl Layouts {
+    pub fn insert(&mut self, ty: &CraneType, layout: EnumLayout) {
+        self.enums.insert(ty.clone(), layout);
+    }
+    pub fn get(&self, ty: &CraneType) -> Option<&EnumLayout> {
+        self.enums.get(ty)
+    }
+    pub fn insert_struct(&mut self, ty: &CraneType, layout: StructLayout) {
+        self.structs.insert(ty.clone(), layout);
+    }
+    pub fn get_struct(&self, ty: &CraneType) -> Option<&StructLayout> {
+        self.structs.get(ty)
+    }
+    // A tuple is laid out like a struct with fields of its element types,
+    // the type checker has made sure it fits
+    pub fn tuple(&self, elements: &[CraneType]) -> StructLayout {
+        let elements: Vec<(u32, u32)> = elements.iter().map(|e| (self.size(e), self.align(e))).collect();
+        StructLayout::new(&elements).expect("a tuple too large to lay out")
+    }
+    pub fn size(&self, ty: &CraneType) -> u32 {
+        match ty {
+            CraneType::Enum { .. } => self.get(ty).map_or(0, |l| l.size),
+            CraneType::Struct { .. } => self.get_struct(ty).map_or(0, |l| l.size),
+            CraneType::Array { element, size } => self.size(element).saturating_mul(*size),
+            CraneType::Tuple { elements } => self.tuple(elements).size,
+            ty => ty.size(),
+        }
+    }
+    pub fn align(&self, ty: &CraneType) -> u32 {
+        match ty {
+            CraneType::Enum { .. } => self.get(ty).map_or(1, |l| l.align),
+            CraneType::Struct { .. } => self.get_struct(ty).map_or(1, |l| l.align),
+            CraneType::Array { element, .. } => self.align(element),
+            CraneType::Tuple { elements } => elements.iter().map(|e| self.align(e)).max().unwrap_or(1),
+            ty => ty.align(),
+        }
+    }
+}
+
+fn round_up(n: u32, to: u32) -> Option<u32> {
+    n.div_ceil(to).checked_mul(to)
 }
 
 // Define the struct for a variable
@@ -51,3 +451,86 @@ impl Variable {
         self.value = value;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //u8, u16, i32, u32, i64, u64, f32, f64, bool, str and void
+    fn primitives() -> Vec<CraneType> {
+        ["u8", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "bool", "str", "void"]
+            .into_iter()
+            .map(|name| CraneType::from_name(name).unwrap())
+            .collect()
+    }
+
+    //check `relation` for every pair of primitive types against a table
+    //with a row for every type converted from
+    fn check(relation: fn(&CraneType, &CraneType) -> bool, table: [&str; 11]) {
+        let types = primitives();
+        for (from, row) in types.iter().zip(table) {
+            let row: Vec<bool> = row.split_whitespace().map(|cell| cell == "x").collect();
+            for (to, &expected) in types.iter().zip(&row) {
+                assert_eq!(relation(from, to), expected, "{} to {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn integers_widen_to_types_holding_all_their_values() {
+        check(
+            CraneType::widens_to,
+            [
+                //u8 u16 i32 u32 i64 u64 f32 f64 bool str void
+                "x x x x x x . . . . .",
+                ". x x x x x . . . . .",
+                ". . x . x . . . . . .",
+                ". . . x x x . . . . .",
+                ". . . . x . . . . . .",
+                ". . . . . x . . . . .",
+                ". . . . . . . . . . .",
+                ". . . . . . . . . . .",
+                ". . . . . . . . . . .",
+                ". . . . . . . . . . .",
+                ". . . . . . . . . . .",
+            ],
+        );
+    }
+
+    #[test]
+    fn casts_convert_numbers_and_bool() {
+        check(
+            CraneType::casts_to,
+            [
+                //u8 u16 i32 u32 i64 u64 f32 f64 bool str void
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x x x . . .",
+                "x x x x x x . . x . .",
+                ". . . . . . . . . x .",
+                ". . . . . . . . . . x",
+            ],
+        );
+    }
+
+    #[test]
+    fn the_join_is_the_wider_type() {
+        let types = primitives();
+        for a in &types {
+            for b in &types {
+                let expected = match (a.widens_to(b), b.widens_to(a)) {
+                    (true, _) => Some(b.clone()),
+                    (_, true) => Some(a.clone()),
+                    _ => None,
+                };
+                assert_eq!(a.join(b), expected, "{} and {}", a, b);
+                assert_eq!(a.join(b), b.join(a), "{} and {}", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use super::asm::{Op, Program};
+use super::native::NATIVES;
+use crate::diagnostic::Span;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// Memory is one byte array: a guard at address 0 so null pointers fault, the
+// data section, the globals, the stack of frames and then the heap, which
+// grows as natives allocate
+const NULL_GUARD: usize = 16;
+const STACK_SIZE: usize = 1 << 20;
+// Deep enough for any sane recursion, shallow enough to report runaway ones
+const MAX_FRAMES: usize = 10_000;
+
+// Something the program did that stops it, with where it happened and the
+// calls that led there, innermost first
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+    pub trace: Vec<Span>,
+}
+
+// Values the VM keeps outside of memory, programs refer to them by handle
+#[derive(Debug, Clone)]
+pub enum Object {
+    Array(Vec<i64>),
+    Map(HashMap<i64, i64>),
+}
+
+struct Frame {
+    return_to: u32,
+    base: usize,
+    // Where the stack of frames ended before this call
+    saved_top: usize,
+    // How many values the operand stack had, without the arguments
+    depth: usize,
+}
+
+pub struct Vm<'a> {
+    program: &'a Program,
+    pub memory: Vec<u8>,
+    stack: Vec<u64>,
+    frames: Vec<Frame>,
+    pc: u32,
+    data_start: usize,
+    globals_start: usize,
+    stack_top: usize,
+    stack_end: usize,
+    pub objects: Vec<Object>,
+    pub out: &'a mut dyn Write,
+    pub input: &'a mut dyn BufRead,
+}
+
+fn align(n: usize, to: usize) -> usize {
+    n.div_ceil(to) * to
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program, out: &'a mut dyn Write, input: &'a mut dyn BufRead) -> Self {
+        let data_start = NULL_GUARD;
+        let globals_start = align(data_start + program.data.len(), 8);
+        let stack_start = align(globals_start + program.globals_size as usize, 8);
+        let stack_end = stack_start + STACK_SIZE;
+        let mut memory = vec![0; stack_end];
+        memory[data_start..data_start + program.data.len()].copy_from_slice(&program.data);
+        Self {
+            program,
+            memory,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            pc: 0,
+            data_start,
+            globals_start,
+            stack_top: stack_start,
+            stack_end,
+            objects: Vec::new(),
+            out,
+            input,
+        }
+    }
+
+    // Run the function at `entry` until it returns
+    pub fn run(&mut self, entry: u32) -> Result<(), RuntimeError> {
+        self.call(entry).map_err(|message| self.error(message))?;
+        let result = self.execute();
+        let _ = self.out.flush();
+        result
+    }
+
+    fn error(&self, message: String) -> RuntimeError {
+        let at = self.pc.saturating_sub(1) as usize;
+        RuntimeError {
+            message,
+            span: self.program.spans.get(at).copied().unwrap_or_default(),
+            trace: self
+                .frames
+                .iter()
+                .rev()
+                .filter_map(|f| f.return_to.checked_sub(1))
+                .map(|at| self.program.spans[at as usize])
+                .collect(),
+        }
+    }
+
+    fn pop(&mut self) -> u64 {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn push(&mut self, value: u64) {
+        self.stack.push(value);
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let op = self.program.code[self.pc as usize];
+            self.pc += 1;
+            match self.step(op) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(message) => return Err(self.error(message)),
+            }
+        }
+    }
+
+    // Run one instruction, false once the program is done
+    fn step(&mut self, op: Op) -> Result<bool, String> {
+        match op {
+            Op::Push(value) => self.push(value),
+            Op::Pop => {
+                self.pop();
+            }
+            Op::Dup => {
+                let value = *self.stack.last().expect("operand stack underflow");
+                self.push(value);
+            }
+            Op::LocalAddr(offset) => {
+                let base = self.frames.last().unwrap().base;
+                self.push((base + offset as usize) as u64);
+            }
+            Op::GlobalAddr(offset) => self.push((self.globals_start + offset as usize) as u64),
+            Op::DataAddr(offset) => self.push((self.data_start + offset as usize) as u64),
+            Op::Load { size, signed } => {
+                let address = self.pop();
+                let value = self.load(address, size, signed)?;
+                self.push(value);
+            }
+            Op::Store { size } => {
+                let value = self.pop();
+                let address = self.pop();
+                self.store(address, size, value)?;
+            }
+            Op::Copy { size } => {
+                let source = self.pop();
+                let destination = self.pop();
+                let from = self.check(source, size as usize)?;
+                let to = self.check(destination, size as usize)?;
+                self.memory.copy_within(from..from + size as usize, to);
+            }
+            Op::Add => self.binary(|a, b| Ok(a.wrapping_add(b)))?,
+            Op::Sub => self.binary(|a, b| Ok(a.wrapping_sub(b)))?,
+            Op::Mul => self.binary(|a, b| Ok(a.wrapping_mul(b)))?,
+            Op::Div { signed } => self.binary(|a, b| match (b, signed) {
+                (0, _) => Err("division by zero".to_string()),
+                (_, true) => Ok((a as i64).wrapping_div(b as i64) as u64),
+                (_, false) => Ok(a / b),
+            })?,
+            Op::Mod { signed } => self.binary(|a, b| match (b, signed) {
+                (0, _) => Err("remainder by zero".to_string()),
+                (_, true) => Ok((a as i64).wrapping_rem(b as i64) as u64),
+                (_, false) => Ok(a % b),
+            })?,
+            Op::Pow => self.binary(|mut base, mut exp| {
+                let mut result: u64 = 1;
+                while exp > 0 {
+                    if exp & 1 == 1 {
+                        result = result.wrapping_mul(base);
+                    }
+                    base = base.wrapping_mul(base);
+                    exp >>= 1;
+                }
+                Ok(result)
+            })?,
+            Op::And => self.binary(|a, b| Ok(a & b))?,
+            Op::Or => self.binary(|a, b| Ok(a | b))?,
+            Op::Xor => self.binary(|a, b| Ok(a ^ b))?,
+            Op::Neg => {
+                let value = self.pop();
+                self.push(value.wrapping_neg());
+            }
+            Op::Not => {
+                let value = self.pop();
+                self.push((value == 0) as u64);
+            }
+            Op::Eq => self.binary(|a, b| Ok((a == b) as u64))?,
+            Op::Ne => self.binary(|a, b| Ok((a != b) as u64))?,
+            Op::Lt { signed } => self.compare(signed, |o| o.is_lt())?,
+            Op::Le { signed } => self.compare(signed, |o| o.is_le())?,
+            Op::Gt { signed } => self.compare(signed, |o| o.is_gt())?,
+            Op::Ge { signed } => self.compare(signed, |o| o.is_ge())?,
+            Op::Wrap { bits, signed } => {
+                let value = self.pop();
+                self.push(wrap(value, bits, signed));
+            }
+            Op::FAdd { double } => self.float_binary(double, |a, b| a + b),
+            Op::FSub { double } => self.float_binary(double, |a, b| a - b),
+            Op::FMul { double } => self.float_binary(double, |a, b| a * b),
+            Op::FDiv { double } => self.float_binary(double, |a, b| a / b),
+            Op::FMod { double } => self.float_binary(double, |a, b| a % b),
+            Op::FPow { double } => self.float_binary(double, f64::powf),
+            Op::FNeg { double } => {
+                let value = self.pop();
+                self.push(from_float(-to_float(value, double), double));
+            }
+            Op::FEq { double } => self.float_compare(double, |a, b| a == b),
+            Op::FNe { double } => self.float_compare(double, |a, b| a != b),
+            Op::FLt { double } => self.float_compare(double, |a, b| a < b),
+            Op::FLe { double } => self.float_compare(double, |a, b| a <= b),
+            Op::FGt { double } => self.float_compare(double, |a, b| a > b),
+            Op::FGe { double } => self.float_compare(double, |a, b| a >= b),
+            Op::IntToFloat { signed, double } => {
+                let value = self.pop();
+                //converted straight to an f32, going through an f64 could round twice
+                self.push(match (signed, double) {
+                    (true, true) => (value as i64 as f64).to_bits(),
+                    (false, true) => (value as f64).to_bits(),
+                    (true, false) => (value as i64 as f32).to_bits() as u64,
+                    (false, false) => (value as f32).to_bits() as u64,
+                });
+            }
+            Op::FloatToInt { double, bits, signed } => {
+                let value = to_float(self.pop(), double);
+                let shift = bits.min(64) as u32;
+                let integer = if signed {
+                    let (min, max) = (i64::MIN >> (64 - shift), i64::MAX >> (64 - shift));
+                    (value as i64).clamp(min, max) as u64
+                } else {
+                    (value as u64).min(u64::MAX >> (64 - shift))
+                };
+                self.push(integer);
+            }
+            Op::FloatToFloat { double } => {
+                let value = self.pop();
+                self.push(from_float(to_float(value, !double), double));
+            }
+            Op::Jump(target) => self.pc = target,
+            Op::JumpIfFalse(target) => {
+                if self.pop() == 0 {
+                    self.pc = target;
+                }
+            }
+            Op::Call(function) => self.call(function)?,
+            Op::CallIndirect => {
+                let function = self.pop();
+                if function as usize >= self.program.functions.len() {
+                    return Err(format!("call of an invalid function {}", function));
+                }
+                self.call(function as u32)?
+            }
+            Op::Alloc { size } => {
+                let address = self.alloc(size as usize);
+                self.push(address);
+            }
+            Op::CheckIndex => {
+                let length = self.pop();
+                let index = *self.stack.last().expect("operand stack underflow");
+                if index >= length {
+                    return Err(format!("index out of bounds: the length is {} but the index is {}", length, index as i64));
+                }
+            }
+            Op::CheckRange => {
+                let length = self.pop();
+                let end = self.pop();
+                let start = self.pop();
+                if start > end || end > length {
+                    return Err(format!("range {}..{} out of bounds for length {}", start as i64, end as i64, length));
+                }
+            }
+            Op::Native { index, args } => {
+                let at = self.stack.len() - args as usize;
+                let args = self.stack.split_off(at);
+                if let Some(value) = (NATIVES[index as usize].run)(self, &args)? {
+                    self.push(value);
+                }
+            }
+            Op::Return { value } => {
+                let frame = self.frames.pop().unwrap();
+                self.stack_top = frame.saved_top;
+                //a return from inside an expression leaves its operands
+                let value = value.then(|| self.pop());
+                self.stack.truncate(frame.depth);
+                self.stack.extend(value);
+                if self.frames.is_empty() {
+                    return Ok(false);
+                }
+                self.pc = frame.return_to;
+            }
+            Op::Halt => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn binary(&mut self, f: impl Fn(u64, u64) -> Result<u64, String>) -> Result<(), String> {
+        let b = self.pop();
+        let a = self.pop();
+        let value = f(a, b)?;
+        self.push(value);
+        Ok(())
+    }
+
+    //an f32 operation is done on f64s and rounded to f32, which for these
+    //operations gives the same result as doing it on f32s
+    fn float_binary(&mut self, double: bool, f: impl Fn(f64, f64) -> f64) {
+        let b = to_float(self.pop(), double);
+        let a = to_float(self.pop(), double);
+        self.push(from_float(f(a, b), double));
+    }
+
+    fn float_compare(&mut self, double: bool, f: impl Fn(f64, f64) -> bool) {
+        let b = to_float(self.pop(), double);
+        let a = to_float(self.pop(), double);
+        self.push(f(a, b) as u64);
+    }
+
+    fn compare(&mut self, signed: bool, f: impl Fn(std::cmp::Ordering) -> bool) -> Result<(), String> {
+        self.binary(|a, b| {
+            let ordering = if signed { (a as i64).cmp(&(b as i64)) } else { a.cmp(&b) };
+            Ok(f(ordering) as u64)
+        })
+    }
+
+    fn call(&mut self, index: u32) -> Result<(), String> {
+        let function = &self.program.functions[index as usize];
+        let base = align(self.stack_top, 8);
+        let top = base + function.frame_size as usize;
+        if top > self.stack_end || self.frames.len() >= MAX_FRAMES {
+            return Err(format!("stack overflow in `{}`", function.name));
+        }
+        self.memory[base..top].fill(0);
+        let at = self.stack.len() - function.params.len();
+        let args = self.stack.split_off(at);
+        for (&(offset, size), value) in function.params.iter().zip(args) {
+            self.store((base + offset as usize) as u64, size, value)?;
+        }
+        self.frames.push(Frame {
+            return_to: self.pc,
+            base,
+            saved_top: self.stack_top,
+            depth: self.stack.len(),
+        });
+        self.stack_top = top;
+        self.pc = function.entry;
+        Ok(())
+    }
+
+    fn check(&self, address: u64, size: usize) -> Result<usize, String> {
+        let address = address as usize;
+        if address < NULL_GUARD {
+            return Err("null pointer dereference".to_string());
+        }
+        if address.checked_add(size).is_none_or(|end| end > self.memory.len()) {
+            return Err(format!("memory access out of bounds at address {}", address));
+        }
+        Ok(address)
+    }
+
+    pub fn load(&self, address: u64, size: u8, signed: bool) -> Result<u64, String> {
+        let address = self.check(address, size as usize)?;
+        let mut bytes = [0u8; 8];
+        bytes[..size as usize].copy_from_slice(&self.memory[address..address + size as usize]);
+        let value = u64::from_le_bytes(bytes);
+        Ok(wrap(value, size * 8, signed))
+    }
+
+    pub fn store(&mut self, address: u64, size: u8, value: u64) -> Result<(), String> {
+        let address = self.check(address, size as usize)?;
+        self.memory[address..address + size as usize].copy_from_slice(&value.to_le_bytes()[..size as usize]);
+        Ok(())
+    }
+
+    // Reserve bytes on the heap, they are never freed
+    pub fn alloc(&mut self, size: usize) -> u64 {
+        let address = align(self.memory.len(), 8);
+        self.memory.resize(address + size, 0);
+        address as u64
+    }
+
+    // The bytes of the string at an address, which holds the address of the
+    // bytes and how many there are
+    pub fn str_bytes(&self, address: u64) -> Result<&[u8], String> {
+        let (bytes, len) = (self.load(address, 8, false)?, self.load(address + 8, 8, false)?);
+        let start = self.check(bytes, len as usize)?;
+        Ok(&self.memory[start..start + len as usize])
+    }
+
+    pub fn read_str(&self, address: u64) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.str_bytes(address)?).into_owned())
+    }
+
+    // Copy a string to the heap, its bytes follow its address and length
+    pub fn alloc_str(&mut self, s: &str) -> u64 {
+        let address = self.alloc(16 + s.len());
+        let start = address as usize + 16;
+        self.memory[start..start + s.len()].copy_from_slice(s.as_bytes());
+        self.view(address, start as u64, s.len() as u64)
+    }
+
+    // Write a string or a slice to `address`, the address of the first byte
+    // or element and how many there are
+    pub fn view(&mut self, address: u64, first: u64, len: u64) -> u64 {
+        let start = address as usize;
+        self.memory[start..start + 8].copy_from_slice(&first.to_le_bytes());
+        self.memory[start + 8..start + 16].copy_from_slice(&len.to_le_bytes());
+        address
+    }
+
+    pub fn new_object(&mut self, object: Object) -> u64 {
+        self.objects.push(object);
+        self.objects.len() as u64
+    }
+
+    pub fn object(&mut self, handle: u64) -> Result<&mut Object, String> {
+        match handle.checked_sub(1).and_then(|i| self.objects.get_mut(i as usize)) {
+            Some(object) => Ok(object),
+            None => Err(format!("invalid handle {}", handle)),
+        }
+    }
+}
+
+// Keep the low `bits` of a value, sign extending them when the type is signed
+pub fn wrap(value: u64, bits: u8, signed: bool) -> u64 {
+    if bits >= 64 {
+        return value;
+    }
+    let shift = 64 - bits as u32;
+    if signed {
+        (((value << shift) as i64) >> shift) as u64
+    } else {
+        (value << shift) >> shift
+    }
+}
+
+// The value of the bits of an f32, or an f64 when `double` is set
+pub fn to_float(value: u64, double: bool) -> f64 {
+    if double {
+        f64::from_bits(value)
+    } else {
+        f32::from_bits(value as u32) as f64
+    }
+}
+
+// The bits of a float as an f64, or rounded to the nearest f32
+pub fn from_float(value: f64, double: bool) -> u64 {
+    if double {
+        value.to_bits()
+    } else {
+        (value as f32).to_bits() as u64
+    }
+}
@@ -0,0 +1,275 @@
+//every error the compiler can report has a stable code with a long form
+//explanation in explanations/<code>.md, shown by `crane explain <code>`
+//
+//codes are grouped by the phase that reports them:
+//E00xx lexer, E01xx parser, E02xx name resolution, E03xx types, E04xx attributes
+//codes are never reused, retired codes keep their explanation
+macro_rules! register_codes {
+    ($($code:ident: $title:expr,)*) => {
+        $(pub const $code: &str = stringify!($code);)*
+
+        pub static REGISTRY: &[ErrorCode] = &[
+            $(ErrorCode {
+                code: stringify!($code),
+                title: $title,
+                explanation: include_str!(concat!("explanations/", stringify!($code), ".md")),
+            },)*
+        ];
+    };
+}
+
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+register_codes! {
+    E0001: "unterminated string",
+    E0002: "invalid character in identifier",
+    E0003: "invalid escape sequence",
+    E0004: "invalid character literal",
+    E0005: "unexpected character",
+    E0006: "unclosed delimiter",
+    E0007: "unmatched closing delimiter",
+    E0008: "mismatched closing delimiter",
+    E0009: "unclosed hole in string",
+    E0100: "missing comma",
+    E0101: "expected a block",
+    E0102: "expected `(`",
+    E0103: "expected a function name",
+    E0104: "expected `)`",
+    E0105: "unexpected token",
+    E0106: "expected an expression",
+    E0107: "expected a parameter name",
+    E0108: "expected a variable name",
+    E0109: "missing initializer",
+    E0110: "expected a type",
+    E0111: "invalid assignment target",
+    E0112: "expected an attribute name",
+    E0113: "expected a lint name",
+    E0114: "misplaced attribute",
+    E0115: "expected a module path",
+    E0116: "misplaced `pub`",
+    E0117: "import inside a block",
+    E0118: "expected `import`",
+    E0119: "native function with a body",
+    E0120: "expected an enum name",
+    E0121: "expected a variant name",
+    E0122: "expected a field",
+    E0123: "expected a pattern",
+    E0124: "expected `=>`",
+    E0125: "expected a struct name",
+    E0126: "expected a type parameter",
+    E0127: "expected `>`",
+    E0128: "expected a trait name",
+    E0129: "expected `for` or `{`",
+    E0130: "expected a method",
+    E0131: "expected `]`",
+    E0132: "malformed `for` loop",
+    E0133: "malformed hole in string",
+    E0134: "constant inside a block",
+    E0200: "cannot find name",
+    E0201: "name defined twice",
+    E0202: "not a function",
+    E0203: "unresolved module",
+    E0204: "import cycle",
+    E0205: "private item",
+    E0206: "unknown native function",
+    E0207: "not an enum",
+    E0208: "not a struct",
+    E0209: "not a trait",
+    E0210: "functions of a foreign type",
+    E0300: "mismatched types",
+    E0301: "unknown type",
+    E0302: "literal out of range",
+    E0303: "wrong number of arguments",
+    E0304: "missing return value",
+    E0305: "non-exhaustive match",
+    E0306: "unknown variant",
+    E0307: "mismatched variant fields",
+    E0308: "recursive type",
+    E0309: "unknown or missing field",
+    E0310: "unsatisfied constraint",
+    E0311: "unknown constraint",
+    E0312: "cannot infer type arguments",
+    E0313: "wrong number of type arguments",
+    E0314: "instantiation too deep",
+    E0315: "unknown method",
+    E0316: "implementation does not match the trait",
+    E0317: "conflicting implementations",
+    E0318: "trait cannot be made into an object",
+    E0319: "type cannot have an implementation",
+    E0320: "function is not a value",
+    E0321: "cannot infer parameter type",
+    E0322: "cannot be indexed",
+    E0323: "index out of bounds",
+    E0324: "cannot infer array type",
+    E0325: "cannot be iterated over",
+    E0326: "array length is too large",
+    E0327: "operation needs an unsafe block",
+    E0328: "cannot take the address of a temporary",
+    E0329: "cannot be dereferenced",
+    E0330: "value cannot be shown as text",
+    E0331: "pattern does not match the tuple",
+    E0332: "`?` on a value that is not an Option or a Result",
+    E0333: "`?` does not fit the return type",
+    E0334: "cannot be evaluated when compiling",
+    E0335: "overflow when evaluating a constant",
+    E0336: "constant depends on itself",
+    E0337: "constant of a type without constant values",
+    E0338: "cannot assign to a constant",
+    E0339: "discriminant used twice",
+    E0340: "invalid cast",
+    E0341: "implicit narrowing conversion",
+    E0342: "`break` or `continue` outside of a loop",
+    E0343: "`break` with a value outside of `loop`",
+    E0344: "type is too large",
+    E0345: "parameter without a type",
+    E0400: "unknown attribute",
+}
+
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    REGISTRY.iter().find(|c| c.code.eq_ignore_ascii_case(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::SourceMap;
+    use std::path::Path;
+    use std::{fs, io};
+
+    fn sources(dir: &Path, files: &mut Vec<(String, String)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, files);
+            } else if path.extension().is_some_and(|e| e == "rs") {
+                let text = fs::read_to_string(&path).unwrap();
+                files.push((path.display().to_string(), text));
+            }
+        }
+    }
+
+    //every `E` followed by four digits in the compiler sources
+    fn codes_in(text: &str) -> Vec<String> {
+        let bytes = text.as_bytes();
+        let mut codes = Vec::new();
+        for i in 0..bytes.len().saturating_sub(4) {
+            let boundary = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+            let digits = bytes[i + 1..i + 5].iter().all(u8::is_ascii_digit);
+            let end = bytes.get(i + 5).is_none_or(|b| !b.is_ascii_alphanumeric());
+            if bytes[i] == b'E' && boundary && digits && end {
+                codes.push(text[i..i + 5].to_string());
+            }
+        }
+        codes
+    }
+
+    #[test]
+    fn every_emitted_code_is_documented() {
+        let mut files = Vec::new();
+        sources(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut files);
+        for (path, text) in files {
+            for code in codes_in(&text) {
+                assert!(lookup(&code).is_some(), "{} uses {} which is not registered", path, code);
+            }
+        }
+    }
+
+    #[test]
+    fn every_code_has_an_explanation_with_examples() {
+        for (i, entry) in REGISTRY.iter().enumerate() {
+            assert!(!entry.title.is_empty(), "{} has no title", entry.code);
+            assert!(
+                entry.explanation.contains("Erroneous code example:"),
+                "{} has no erroneous example",
+                entry.code
+            );
+            assert!(
+                entry.explanation.matches("```crane").count() >= 2,
+                "{} should show the broken and the fixed code",
+                entry.code
+            );
+            assert!(
+                REGISTRY[..i].iter().all(|other| other.code != entry.code),
+                "{} is registered twice",
+                entry.code
+            );
+        }
+    }
+
+    //the first code block after "Erroneous code example:"
+    fn erroneous_example(explanation: &str) -> &str {
+        let (_, after) = explanation.split_once("Erroneous code example:").unwrap();
+        let (_, block) = after.split_once("```crane\n").unwrap();
+        block.split_once("```").unwrap().0
+    }
+
+    //the last code block, which shows the code put right
+    fn corrected_example(explanation: &str) -> &str {
+        let (_, block) = explanation.rsplit_once("```crane\n").unwrap();
+        block.split_once("```").unwrap().0
+    }
+
+    //the files of an example, split at its `// in shapes.crane` lines, or
+    //the example as `main.crane`
+    fn files(example: &str) -> Vec<(String, String)> {
+        let mut files: Vec<(String, String)> = Vec::new();
+        for line in example.lines() {
+            if let Some(name) = line.strip_prefix("// in ") {
+                files.push((name.to_string(), String::new()));
+                continue;
+            }
+            if files.is_empty() {
+                files.push(("main.crane".to_string(), String::new()));
+            }
+            let text = &mut files.last_mut().unwrap().1;
+            text.push_str(line);
+            text.push('\n');
+        }
+        files
+    }
+
+    //the error codes an example gives, compiled from `main.crane` or its
+    //first file. Any other module it imports is missing
+    fn compile_example(example: &str) -> Vec<String> {
+        let files = files(example);
+        let mut options = crate::parse_args(&[], false);
+        if !files.iter().any(|(name, _)| *name == options.path) {
+            options.path = files[0].0.clone();
+        }
+        let mut sources = SourceMap::new();
+        let mut read = |path: &Path| match files.iter().find(|(name, _)| Path::new(name) == path) {
+            Some((_, text)) => Ok(text.clone()),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        let (diagnostics, _, _) = crate::compile(&mut sources, &options, &mut read);
+        diagnostics.iter().filter(|d| d.is_error()).filter_map(|d| d.code.clone()).collect()
+    }
+
+    #[test]
+    fn every_erroneous_example_reports_its_code() {
+        let mut wrong = Vec::new();
+        for entry in REGISTRY.iter() {
+            let codes = compile_example(erroneous_example(entry.explanation));
+            if !codes.iter().any(|code| code == entry.code) {
+                wrong.push(format!("{} gives {:?}", entry.code, codes));
+            }
+        }
+        assert!(wrong.is_empty(), "{}", wrong.join("\n"));
+    }
+
+    #[test]
+    fn every_corrected_example_compiles() {
+        let mut wrong = Vec::new();
+        for entry in REGISTRY.iter() {
+            let codes = compile_example(corrected_example(entry.explanation));
+            if !codes.is_empty() {
+                wrong.push(format!("{} gives {:?}", entry.code, codes));
+            }
+        }
+        assert!(wrong.is_empty(), "{}", wrong.join("\n"));
+    }
+}
@@ -0,0 +1,177 @@
+use super::{Applicability, Diagnostic, Suggestion};
+
+//the machine applicable suggestions for one file, in source order, with any
+//that overlap an earlier one dropped
+pub fn applicable(diagnostics: &[Diagnostic], file: usize) -> Vec<&Suggestion> {
+    let mut suggestions: Vec<&Suggestion> = diagnostics
+        .iter()
+        .flat_map(|d| &d.suggestions)
+        .filter(|s| s.applicability == Applicability::MachineApplicable && s.span.file == file)
+        .collect();
+    suggestions.sort_by_key(|s| (s.span.start, s.span.end));
+    let mut kept: Vec<&Suggestion> = Vec::new();
+    for suggestion in suggestions {
+        if let Some(last) = kept.last() {
+            //two insertions at the same point would both apply, keep only one
+            if suggestion.span.start < last.span.end || suggestion.span.start == last.span.start {
+                continue;
+            }
+        }
+        kept.push(suggestion);
+    }
+    kept
+}
+
+//replace every suggested span, the suggestions must be sorted and disjoint
+pub fn apply(src: &str, suggestions: &[&Suggestion]) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut at = 0;
+    for suggestion in suggestions {
+        out.push_str(&src[at..suggestion.span.start]);
+        out.push_str(&suggestion.replacement);
+        at = suggestion.span.end;
+    }
+    out.push_str(&src[at..]);
+    out
+}
+
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+//a unified diff of two versions of a file with three lines of context
+pub fn diff(name: &str, old: &str, new: &str) -> String {
+    let edits = line_edits(old, new);
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    //the line of each file every edit starts on
+    let mut old_line = 1;
+    let mut new_line = 1;
+    let mut positions = Vec::with_capacity(edits.len());
+    for edit in &edits {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Keep(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Remove(_) => old_line += 1,
+            Edit::Add(_) => new_line += 1,
+        }
+    }
+    let changed: Vec<usize> = (0..edits.len())
+        .filter(|&i| !matches!(edits[i], Edit::Keep(_)))
+        .collect();
+    let mut i = 0;
+    while i < changed.len() {
+        //join changes that are close enough to share their context
+        let start = changed[i].saturating_sub(3);
+        let mut end = changed[i];
+        while i < changed.len() && changed[i] <= end + 6 {
+            end = changed[i];
+            i += 1;
+        }
+        let end = (end + 4).min(edits.len());
+        let (old_start, new_start) = positions[start];
+        let mut old_count = 0;
+        let mut new_count = 0;
+        let mut body = String::new();
+        for edit in &edits[start..end] {
+            let (sign, line) = match edit {
+                Edit::Keep(line) => {
+                    old_count += 1;
+                    new_count += 1;
+                    (' ', line)
+                }
+                Edit::Remove(line) => {
+                    old_count += 1;
+                    ('-', line)
+                }
+                Edit::Add(line) => {
+                    new_count += 1;
+                    ('+', line)
+                }
+            };
+            body.push(sign);
+            body.push_str(line);
+            body.push('\n');
+        }
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n{}",
+            old_start, old_count, new_start, new_count, body
+        ));
+    }
+    out
+}
+
+//the shortest list of line removals and additions, from a longest common
+//subsequence table
+fn line_edits<'a>(old: &'a str, new: &'a str) -> Vec<Edit<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Keep(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || table[i + 1][j] >= table[i][j + 1]) {
+            edits.push(Edit::Remove(a[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(b[j]));
+            j += 1;
+        }
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Span;
+
+    fn suggest(file: usize, start: usize, end: usize, replacement: &str, applicability: Applicability) -> Diagnostic {
+        Diagnostic::warning("w").with_suggestion("s", Span::new(file, start, end), replacement, applicability)
+    }
+
+    #[test]
+    fn overlapping_and_uncertain_suggestions_are_left_out() {
+        let src = "let a = b\nlet c = d\n";
+        let diagnostics = [
+            suggest(0, 19, 19, ";", Applicability::MachineApplicable),
+            suggest(0, 4, 5, "_a", Applicability::MachineApplicable),
+            //overlaps the one before, inserts where another inserts, is only a guess, is in another file
+            suggest(0, 4, 9, "x", Applicability::MachineApplicable),
+            suggest(0, 19, 19, ",", Applicability::MachineApplicable),
+            suggest(0, 8, 9, "z", Applicability::MaybeIncorrect),
+            suggest(1, 0, 1, "w", Applicability::MachineApplicable),
+        ];
+        let suggestions = applicable(&diagnostics, 0);
+        let replacements: Vec<&str> = suggestions.iter().map(|s| s.replacement.as_str()).collect();
+        assert_eq!(replacements, ["_a", ";"]);
+        assert_eq!(apply(src, &suggestions), "let _a = b\nlet c = d;\n");
+    }
+
+    #[test]
+    fn diff_joins_close_changes_into_one_hunk() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n17\n18\n";
+        let new = "1\n2\nthree\n4\n5\n6\nseven\n8\n9\n10\n11\n12\n13\n14\n15\n16\n17\n";
+        let expected = "--- f\n+++ f\n\
+            @@ -1,10 +1,10 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n-7\n+seven\n 8\n 9\n 10\n\
+            @@ -15,4 +15,3 @@\n 15\n 16\n 17\n-18\n";
+        assert_eq!(diff("f", old, new), expected);
+    }
+}
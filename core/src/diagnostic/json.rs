@@ -0,0 +1,142 @@
+use super::{Diagnostic, Label, Renderer, SourceMap, Span};
+
+//bump this whenever a field changes meaning or goes away, new fields can be
+//added without a bump so consumers should ignore keys they do not know
+pub const FORMAT_VERSION: u32 = 1;
+
+//one diagnostic as a single line of json:
+//
+//{"version":1,"code":"E0001","lint":null,"severity":"error","message":"...",
+// "spans":[{"file":"main.crane","byte_start":4,"byte_end":9,"line_start":1,
+//           "line_end":1,"column_start":5,"column_end":10,"is_primary":true,
+//           "label":"..."}],
+// "notes":["..."],"helps":["..."],
+// "suggestions":[{"message":"...","span":{...},"replacement":"...",
+//                 "applicability":"machine-applicable"|"maybe-incorrect"}],
+// "rendered":"the same text --error-format=human would print"}
+//
+//a warning from a lint has no code but the name of the lint, "unused_variables",
+//and so does an error `@deny` or --deny-warnings turned it into
+//
+//lines and columns are 1 based and columns count characters, byte offsets are
+//0 based, every end is exclusive
+pub fn to_json(sources: &SourceMap, diagnostic: &Diagnostic) -> String {
+    let mut fields = vec![
+        format!("\"version\":{}", FORMAT_VERSION),
+        format!(
+            "\"code\":{}",
+            diagnostic.code.as_deref().map_or("null".to_string(), string)
+        ),
+        format!("\"lint\":{}", diagnostic.lint.map_or("null".to_string(), string)),
+        format!("\"severity\":{}", string(diagnostic.severity.as_str())),
+        format!("\"message\":{}", string(&diagnostic.message)),
+    ];
+    let spans: Vec<String> = diagnostic
+        .labels
+        .iter()
+        .map(|l| label(sources, l))
+        .collect();
+    fields.push(format!("\"spans\":[{}]", spans.join(",")));
+    fields.push(format!("\"notes\":{}", strings(&diagnostic.notes)));
+    fields.push(format!("\"helps\":{}", strings(&diagnostic.helps)));
+    let suggestions: Vec<String> = diagnostic
+        .suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "{{\"message\":{},\"span\":{},\"replacement\":{},\"applicability\":{}}}",
+                string(&s.message),
+                span(sources, s.span),
+                string(&s.replacement),
+                string(s.applicability.as_str())
+            )
+        })
+        .collect();
+    fields.push(format!("\"suggestions\":[{}]", suggestions.join(",")));
+    let rendered = Renderer::plain().render(sources, diagnostic);
+    fields.push(format!("\"rendered\":{}", string(&rendered)));
+    format!("{{{}}}", fields.join(","))
+}
+
+fn label(sources: &SourceMap, label: &Label) -> String {
+    let mut object = span(sources, label.span);
+    object.pop();
+    format!(
+        "{},\"is_primary\":{},\"label\":{}}}",
+        object,
+        label.primary,
+        if label.message.is_empty() {
+            "null".to_string()
+        } else {
+            string(&label.message)
+        }
+    )
+}
+
+fn span(sources: &SourceMap, span: Span) -> String {
+    let file = sources.file(span.file);
+    let (line_start, column_start) = file.line_col(span.start);
+    let (line_end, column_end) = file.line_col(span.end);
+    format!(
+        "{{\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"line_end\":{},\"column_start\":{},\"column_end\":{}}}",
+        string(&file.name),
+        span.start,
+        span.end,
+        line_start,
+        line_end,
+        column_start,
+        column_end
+    )
+}
+
+fn strings(list: &[String]) -> String {
+    let list: Vec<String> = list.iter().map(|s| string(s)).collect();
+    format!("[{}]", list.join(","))
+}
+
+fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Applicability;
+
+    //consumers parse this, a change to it needs a new FORMAT_VERSION
+    #[test]
+    fn json_format_is_stable() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.crane", "let x = \"é\"\nprint(y)\n".to_string());
+        let diagnostic = Diagnostic::error("E0200", "cannot find `y` in this scope")
+            .with_label(Span::new(file, 19, 20), "not found in this scope")
+            .with_secondary(Span::new(file, 4, 5), "")
+            .with_note("a \"note\"")
+            .with_help("a help")
+            .with_suggestion("use `x`", Span::new(file, 19, 20), "x", Applicability::MaybeIncorrect);
+        let expected = concat!(
+            r#"{"version":1,"code":"E0200","lint":null,"severity":"error","#,
+            r#""message":"cannot find `y` in this scope","spans":["#,
+            r#"{"file":"main.crane","byte_start":19,"byte_end":20,"line_start":2,"line_end":2,"column_start":7,"column_end":8,"is_primary":true,"label":"not found in this scope"},"#,
+            r#"{"file":"main.crane","byte_start":4,"byte_end":5,"line_start":1,"line_end":1,"column_start":5,"column_end":6,"is_primary":false,"label":null}],"#,
+            r#""notes":["a \"note\""],"helps":["a help"],"#,
+            r#""suggestions":[{"message":"use `x`","span":{"file":"main.crane","byte_start":19,"byte_end":20,"line_start":2,"line_end":2,"column_start":7,"column_end":8},"replacement":"x","applicability":"maybe-incorrect"}],"#,
+            r#""rendered":"error[E0200]: cannot find `y` in this scope\n"#,
+        );
+        let json = to_json(&sources, &diagnostic);
+        assert!(json.starts_with(expected), "{}", json);
+    }
+}
@@ -0,0 +1,274 @@
+pub mod codes;
+pub mod fix;
+mod json;
+mod render;
+pub use json::to_json;
+pub use render::Renderer;
+
+//a file that has been loaded into the compiler
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: &'static str,
+    line_starts: Vec<usize>,
+}
+
+//every file the compiler has seen, spans point into this by index
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+//a byte range inside one of the files of the source map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+//a span with a message attached, the primary label is where the error "is"
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+//how sure we are that a suggestion is right, `crane fix` only applies the
+//machine applicable ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    MachineApplicable,
+    MaybeIncorrect,
+}
+
+//an edit that would fix the problem: replace the span with the text
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    //the lint that reported a warning, or an error it was turned into
+    pub lint: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub helps: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+//how diagnostics are printed, picked with --error-format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl SourceFile {
+    pub fn new(name: String, src: &'static str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in src.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self {
+            name,
+            src,
+            line_starts,
+        }
+    }
+    // 0 based line index of a byte offset
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        }
+    }
+    // 1 based line and column (in characters) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_index(offset);
+        let start = self.line_starts[line];
+        let offset = offset.min(self.src.len());
+        let col = self.src[start..offset].chars().count();
+        (line + 1, col + 1)
+    }
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line]
+    }
+    // the text of a 0 based line without its line terminator
+    pub fn line_text(&self, line: usize) -> &'static str {
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.src.len());
+        self.src[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+    //the source is leaked so tokens and nodes can borrow it for the whole run
+    pub fn add(&mut self, name: &str, src: String) -> usize {
+        let src: &'static str = Box::leak(src.into_boxed_str());
+        self.files.push(SourceFile::new(name.to_string(), src));
+        self.files.len() - 1
+    }
+    pub fn file(&self, id: usize) -> &SourceFile {
+        &self.files[id]
+    }
+    pub fn source(&self, id: usize) -> &'static str {
+        self.files[id].src
+    }
+}
+
+impl Span {
+    pub fn new(file: usize, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+    //the smallest span covering both spans
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            lint: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            helps: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+    //errors always carry a code from the registry in `codes`
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message).with_code(code)
+    }
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.helps.push(help.into());
+        self
+    }
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        });
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|l| l.primary)
+            .or(self.labels.first())
+            .map(|l| l.span)
+    }
+}
+
+impl Applicability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+        }
+    }
+}
+
+impl ErrorFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+//print every diagnostic and say whether any of them was an error
+pub fn emit(sources: &SourceMap, diagnostics: &[Diagnostic], format: ErrorFormat) -> bool {
+    let renderer = Renderer::new();
+    for diagnostic in diagnostics {
+        match format {
+            ErrorFormat::Human => println!("{}", renderer.render(sources, diagnostic)),
+            ErrorFormat::Json => println!("{}", to_json(sources, diagnostic)),
+        }
+    }
+    diagnostics.iter().any(|d| d.is_error())
+}
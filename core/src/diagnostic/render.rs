@@ -0,0 +1,539 @@
+use super::{Diagnostic, Label, Severity, SourceFile, SourceMap};
+use ansi_term::{Colour, Style};
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+
+//turns diagnostics into rustc style text:
+//
+//error[E0001]: unterminated string
+// --> main.crane:2:11
+//  |
+//2 |     print("hello world)
+//  |           ^^^^^^^^^^^^^ the string starts here
+//  |
+//  = help: add a closing `"`
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    pub tab_width: usize,
+    pub color: bool,
+}
+
+//a label that starts and ends on the same line, columns are display columns
+struct Inline<'a> {
+    line: usize,
+    start: usize,
+    end: usize,
+    label: &'a Label,
+}
+
+//a label covering several lines, drawn with a bar in the left margin
+struct Multiline<'a> {
+    start_line: usize,
+    start_col: usize,
+    end_line: usize,
+    end_col: usize,
+    label: &'a Label,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    //colour is only used when stdout is a terminal and NO_COLOR is not set
+    pub fn new() -> Self {
+        Self {
+            tab_width: 4,
+            color: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+    pub fn plain() -> Self {
+        Self {
+            tab_width: 4,
+            color: false,
+        }
+    }
+    fn paint(&self, style: Style, s: &str) -> String {
+        if self.color && !s.is_empty() {
+            style.paint(s).to_string()
+        } else {
+            s.to_string()
+        }
+    }
+    fn severity_style(severity: Severity) -> Style {
+        match severity {
+            Severity::Error => Colour::Red.bold(),
+            Severity::Warning => Colour::Yellow.bold(),
+            Severity::Note => Colour::Green.bold(),
+            Severity::Help => Colour::Cyan.bold(),
+        }
+    }
+    fn label_style(&self, severity: Severity, label: &Label) -> Style {
+        if label.primary {
+            Self::severity_style(severity)
+        } else {
+            Colour::Blue.bold()
+        }
+    }
+    fn margin(&self) -> Style {
+        Colour::Blue.bold()
+    }
+
+    pub fn render(&self, sources: &SourceMap, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let style = Self::severity_style(diagnostic.severity);
+        let mut head = diagnostic.severity.as_str().to_string();
+        if let Some(code) = &diagnostic.code {
+            head.push_str(&format!("[{}]", code));
+        }
+        out.push_str(&self.paint(style, &head));
+        out.push_str(&self.paint(Style::new().bold(), &format!(": {}", diagnostic.message)));
+        out.push('\n');
+
+        //the file holding the primary label is shown first
+        let mut files: Vec<usize> = Vec::new();
+        if let Some(span) = diagnostic.primary_span() {
+            files.push(span.file);
+        }
+        for label in &diagnostic.labels {
+            if !files.contains(&label.span.file) {
+                files.push(label.span.file);
+            }
+        }
+        let width = diagnostic
+            .labels
+            .iter()
+            .map(|l| {
+                let file = sources.file(l.span.file);
+                let end = l.span.end.max(l.span.start + 1) - 1;
+                (file.line_index(end) + 1).to_string().len()
+            })
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+
+        for (i, &id) in files.iter().enumerate() {
+            let file = sources.file(id);
+            let labels: Vec<&Label> = diagnostic
+                .labels
+                .iter()
+                .filter(|l| l.span.file == id)
+                .collect();
+            let anchor = labels
+                .iter()
+                .find(|l| l.primary)
+                .unwrap_or(&labels[0])
+                .span;
+            let (line, col) = file.line_col(anchor.start);
+            let arrow = if i == 0 { "-->" } else { ":::" };
+            out.push_str(&format!(
+                "{}{} {}:{}:{}\n",
+                pad,
+                self.paint(self.margin(), arrow),
+                file.name,
+                line,
+                col
+            ));
+            out.push_str(&format!("{} {}\n", pad, self.paint(self.margin(), "|")));
+            self.render_file(&mut out, file, &labels, diagnostic.severity, width);
+        }
+
+        let suggestions: Vec<String> = diagnostic
+            .suggestions
+            .iter()
+            .map(|s| {
+                if s.replacement.is_empty() {
+                    s.message.clone()
+                } else {
+                    format!("{}: `{}`", s.message, s.replacement.trim())
+                }
+            })
+            .collect();
+        let helps: Vec<String> = diagnostic.helps.iter().cloned().chain(suggestions).collect();
+        if !diagnostic.notes.is_empty() || !helps.is_empty() {
+            if !diagnostic.labels.is_empty() {
+                out.push_str(&format!("{} {}\n", pad, self.paint(self.margin(), "|")));
+            }
+            for (kind, messages) in [("note", &diagnostic.notes), ("help", &helps)] {
+                for message in messages {
+                    let indent = " ".repeat(width + kind.len() + 5);
+                    let message = message.replace('\n', &format!("\n{}", indent));
+                    out.push_str(&format!(
+                        "{} {} {}: {}\n",
+                        pad,
+                        self.paint(self.margin(), "="),
+                        self.paint(Style::new().bold(), kind),
+                        message
+                    ));
+                }
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    //display column of a byte offset, tabs count as `tab_width` columns
+    fn display_col(&self, file: &SourceFile, line: usize, offset: usize) -> usize {
+        let text = file.line_text(line);
+        let start = file.line_start(line);
+        let offset = offset.saturating_sub(start).min(text.len());
+        text[..offset]
+            .chars()
+            .map(|c| if c == '\t' { self.tab_width } else { 1 })
+            .sum()
+    }
+
+    fn render_file(
+        &self,
+        out: &mut String,
+        file: &SourceFile,
+        labels: &[&Label],
+        severity: Severity,
+        width: usize,
+    ) {
+        let mut inline = Vec::new();
+        let mut multi = Vec::new();
+        for &label in labels {
+            let span = label.span;
+            let last = span.end.max(span.start + 1) - 1;
+            let start_line = file.line_index(span.start);
+            let end_line = file.line_index(last.min(file.src.len().saturating_sub(1)).max(span.start));
+            let start = self.display_col(file, start_line, span.start);
+            if start_line == end_line {
+                let end = self.display_col(file, start_line, span.end).max(start + 1);
+                inline.push(Inline {
+                    line: start_line,
+                    start,
+                    end,
+                    label,
+                });
+            } else {
+                multi.push(Multiline {
+                    start_line,
+                    start_col: start,
+                    end_line,
+                    end_col: self.display_col(file, end_line, last),
+                    label,
+                });
+            }
+        }
+
+        //long multi-line spans only show their first and last two lines
+        let mut lines = BTreeSet::new();
+        for i in &inline {
+            lines.insert(i.line);
+        }
+        for m in &multi {
+            if m.end_line - m.start_line <= 4 {
+                lines.extend(m.start_line..=m.end_line);
+            } else {
+                lines.extend([m.start_line, m.start_line + 1, m.end_line - 1, m.end_line]);
+            }
+        }
+
+        let pad = " ".repeat(width);
+        let bar = self.paint(self.margin(), "|");
+        let mut previous: Option<usize> = None;
+        for &line in &lines {
+            if previous.is_some_and(|p| line > p + 1) {
+                let gutter = self.gutter(&multi, line, severity);
+                push_line(out, format!("{} {}", self.paint(self.margin(), "..."), gutter));
+            }
+            previous = Some(line);
+
+            let text = file.line_text(line).replace('\t', &" ".repeat(self.tab_width));
+            let number = self.paint(self.margin(), &format!("{:>width$}", line + 1));
+            push_line(
+                out,
+                format!("{} {} {}{}", number, bar, self.gutter(&multi, line, severity), text),
+            );
+            let prefix = format!("{} {} {}", pad, bar, self.gutter(&multi, line, severity));
+
+            let here: Vec<&Inline> = inline.iter().filter(|i| i.line == line).collect();
+            if !here.is_empty() {
+                self.render_inline(out, &prefix, &here, severity);
+            }
+
+            //open the multi-line labels starting on this line
+            for (index, m) in multi.iter().enumerate() {
+                if m.start_line != line {
+                    continue;
+                }
+                let mut cells = self.gutter_cells(&multi, line);
+                for cell in cells.iter_mut().skip(index * 2 + 1) {
+                    *cell = ('_', Some(m.label));
+                }
+                cells[index * 2] = (' ', None);
+                cells.extend(std::iter::repeat_n(('_', Some(m.label)), m.start_col));
+                cells.push((if m.label.primary { '^' } else { '-' }, Some(m.label)));
+                push_line(
+                    out,
+                    format!("{} {} {}", pad, bar, self.paint_cells(&cells, severity)),
+                );
+            }
+            //and close the ones ending on it
+            for (index, m) in multi.iter().enumerate() {
+                if m.end_line != line {
+                    continue;
+                }
+                let mut cells = self.gutter_cells(&multi, line);
+                for cell in cells.iter_mut().skip(index * 2 + 1) {
+                    *cell = ('_', Some(m.label));
+                }
+                cells.extend(std::iter::repeat_n(('_', Some(m.label)), m.end_col));
+                cells.push((if m.label.primary { '^' } else { '-' }, Some(m.label)));
+                let style = self.label_style(severity, m.label);
+                push_line(
+                    out,
+                    format!(
+                        "{} {} {} {}",
+                        pad,
+                        bar,
+                        self.paint_cells(&cells, severity),
+                        self.paint(style, &m.label.message)
+                    ),
+                );
+            }
+        }
+    }
+
+    //the margin of a line, one column per multi-line label
+    fn gutter_cells<'a>(&self, multi: &[Multiline<'a>], line: usize) -> Vec<(char, Option<&'a Label>)> {
+        let mut cells = Vec::new();
+        for m in multi {
+            if m.start_line < line && line <= m.end_line {
+                cells.push(('|', Some(m.label)));
+            } else {
+                cells.push((' ', None));
+            }
+            cells.push((' ', None));
+        }
+        cells
+    }
+
+    fn gutter(&self, multi: &[Multiline], line: usize, severity: Severity) -> String {
+        self.paint_cells(&self.gutter_cells(multi, line), severity)
+    }
+
+    fn paint_cells(&self, cells: &[(char, Option<&Label>)], severity: Severity) -> String {
+        let mut out = String::new();
+        let mut run = String::new();
+        let mut current: Option<&Label> = None;
+        for &(c, label) in cells {
+            let same = match (current, label) {
+                (Some(a), Some(b)) => a.primary == b.primary,
+                (None, None) => true,
+                _ => false,
+            };
+            if !same {
+                out.push_str(&self.paint_run(&run, current, severity));
+                run.clear();
+            }
+            current = label;
+            run.push(c);
+        }
+        out.push_str(&self.paint_run(&run, current, severity));
+        out
+    }
+
+    fn paint_run(&self, run: &str, label: Option<&Label>, severity: Severity) -> String {
+        match label {
+            Some(label) => self.paint(self.label_style(severity, label), run),
+            None => run.to_string(),
+        }
+    }
+
+    //carets under a line, the rightmost message goes on the caret line and the
+    //others hang below it:
+    //
+    //  foo(a, b)
+    //  ^^^    - second
+    //  |
+    //  first
+    fn render_inline(&self, out: &mut String, prefix: &str, labels: &[&Inline], severity: Severity) {
+        let mut labels = labels.to_vec();
+        labels.sort_by_key(|i| (i.start, i.end));
+        let width = labels.iter().map(|i| i.end).max().unwrap_or(0);
+        let mut cells: Vec<(char, Option<&Label>)> = vec![(' ', None); width];
+        //longer labels are drawn first so that one inside another still shows,
+        //primary carets win over a secondary label of the same length
+        let mut drawn = labels.clone();
+        drawn.sort_by_key(|i| (std::cmp::Reverse(i.end - i.start), i.label.primary));
+        for i in drawn {
+            let c = if i.label.primary { '^' } else { '-' };
+            for cell in &mut cells[i.start..i.end] {
+                *cell = (c, Some(i.label));
+            }
+        }
+        let with_message: Vec<&&Inline> = labels.iter().filter(|i| !i.label.message.is_empty()).collect();
+        let mut carets = format!("{}{}", prefix, self.paint_cells(&cells, severity));
+        let hanging = match with_message.split_last() {
+            Some((last, rest)) if last.start >= rest.iter().map(|i| i.end).max().unwrap_or(0) => {
+                let style = self.label_style(severity, last.label);
+                carets.push(' ');
+                carets.push_str(&self.paint(style, &last.label.message));
+                rest.to_vec()
+            }
+            _ => with_message.clone(),
+        };
+        push_line(out, carets);
+        if hanging.is_empty() {
+            return;
+        }
+
+        //each hanging label has its own connector, where it starts unless that
+        //is too close to the one before for a message to not touch it
+        let mut columns: Vec<usize> = Vec::new();
+        for i in &hanging {
+            let column = columns.last().map_or(i.start, |&last| i.start.max(last + 2));
+            columns.push(column);
+        }
+        let connectors = |count: usize| {
+            let mut cells: Vec<(char, Option<&Label>)> = Vec::new();
+            for (i, &column) in hanging[..count].iter().zip(&columns) {
+                pad_cells(&mut cells, column);
+                cells.push(('|', Some(i.label)));
+            }
+            cells
+        };
+        push_line(
+            out,
+            format!("{}{}", prefix, self.paint_cells(&connectors(hanging.len()), severity)),
+        );
+        for (k, i) in hanging.iter().enumerate().rev() {
+            let mut cells = connectors(k);
+            pad_cells(&mut cells, columns[k]);
+            let style = self.label_style(severity, i.label);
+            push_line(
+                out,
+                format!(
+                    "{}{}{}",
+                    prefix,
+                    self.paint_cells(&cells, severity),
+                    self.paint(style, &i.label.message)
+                ),
+            );
+        }
+    }
+}
+
+fn pad_cells(cells: &mut Vec<(char, Option<&Label>)>, width: usize) {
+    while cells.len() < width {
+        cells.push((' ', None));
+    }
+}
+
+fn push_line(out: &mut String, line: String) {
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Span;
+
+    fn render(src: &str, diagnostic: impl Fn(usize) -> Diagnostic) -> String {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.crane", src.to_string());
+        Renderer::plain().render(&sources, &diagnostic(file))
+    }
+
+    #[test]
+    fn a_label_on_one_line() {
+        let rendered = render("let x = 1\nprint(\"hello)\n", |f| {
+            Diagnostic::error("E0001", "unterminated string")
+                .with_label(Span::new(f, 16, 23), "the string starts here")
+                .with_help("add a closing `\"`")
+        });
+        let expected = "\
+error[E0001]: unterminated string
+ --> main.crane:2:7
+  |
+2 | print(\"hello)
+  |       ^^^^^^^ the string starts here
+  |
+  = help: add a closing `\"`";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn two_labels_on_one_line() {
+        let rendered = render("let total = count + other\n", |f| {
+            Diagnostic::error("E0300", "mismatched types")
+                .with_label(Span::new(f, 12, 17), "this is `str`")
+                .with_secondary(Span::new(f, 20, 25), "this is `i32`")
+        });
+        let expected = "\
+error[E0300]: mismatched types
+ --> main.crane:1:13
+  |
+1 | let total = count + other
+  |             ^^^^^   ----- this is `i32`
+  |             |
+  |             this is `str`";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn a_long_multiline_span_leaves_out_its_middle() {
+        let src = "def f() {\n    let a = 1\n    let b = 2\n    let c = 3\n    let d = 4\n    let e = 5\n}\nf()\n";
+        let rendered = render(src, |f| {
+            Diagnostic::error("E0300", "mismatched types")
+                .with_label(Span::new(f, 8, 81), "expected `i32`, found `()`")
+                .with_secondary(Span::new(f, 82, 85), "called here")
+        });
+        let expected = "\
+error[E0300]: mismatched types
+ --> main.crane:1:9
+  |
+1 |   def f() {
+  |  _________^
+2 | |     let a = 1
+... |
+6 | |     let e = 5
+7 | | }
+  | |_^ expected `i32`, found `()`
+8 |   f()
+  |   --- called here";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn tabs_are_expanded_under_the_label() {
+        let rendered = render("\tlet x =\t1 + true\n", |f| {
+            Diagnostic::error("E0300", "mismatched types").with_label(Span::new(f, 13, 17), "expected `i32`")
+        });
+        let expected = "\
+error[E0300]: mismatched types
+ --> main.crane:1:14
+  |
+1 |     let x =    1 + true
+  |                    ^^^^ expected `i32`";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn labels_that_overlap_or_start_together() {
+        let rendered = render("let total = count + other\n", |f| {
+            Diagnostic::error("E0300", "mismatched types")
+                .with_label(Span::new(f, 12, 25), "cannot add these")
+                .with_secondary(Span::new(f, 12, 17), "this is `str`")
+                .with_secondary(Span::new(f, 20, 25), "this is `i32`")
+                .with_secondary(Span::new(f, 12, 17), "called from here")
+        });
+        let expected = "\
+error[E0300]: mismatched types
+ --> main.crane:1:13
+  |
+1 | let total = count + other
+  |             -----^^^-----
+  |             | | |   |
+  |             | | |   this is `i32`
+  |             | | cannot add these
+  |             | called from here
+  |             this is `str`";
+        assert_eq!(rendered, expected);
+    }
+}
@@ -0,0 +1,201 @@
+mod manifest;
+mod resolve;
+mod toml;
+mod version;
+use manifest::{Manifest, MANIFEST};
+use resolve::{Lockfile, Resolved, LOCKFILE};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::{env, fs};
+
+fn usage() -> ! {
+    println!("usage: feathers new <name>");
+    println!("       feathers build [--locked] [crane options]");
+    println!("       feathers run [--locked] [crane options]");
+    println!("       feathers test [--locked] [crane options]");
+    process::exit(1);
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    println!("error: {}", message);
+    process::exit(1);
+}
+
+//a new package with a program that prints a greeting and a test for it
+fn new(name: Option<&String>) {
+    let Some(name) = name else { usage() };
+    let dir = Path::new(name);
+    if dir.exists() {
+        fail(format!("`{}` already exists", dir.display()));
+    }
+    let manifest = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n", name);
+    //check the name the same way a build would
+    if let Err(e) = Manifest::parse(&manifest, dir) {
+        fail(e);
+    }
+    let files = [
+        (MANIFEST, manifest.as_str()),
+        ("src/main.crane", "pub def greeting() -> str {\n    return \"Hello, world!\"\n}\n\ndef main() {\n    println(greeting())\n}\n"),
+        ("tests/greeting.crane", &format!("from {} import greeting\nimport std.string\n\nassert(std.string.str_eq(greeting(), \"Hello, world!\"))\n", name)),
+        (".gitignore", "target/\n"),
+    ];
+    for (file, text) in files {
+        let path = dir.join(file);
+        let written = fs::create_dir_all(path.parent().unwrap()).and_then(|_| fs::write(&path, text));
+        if let Err(e) = written {
+            fail(format!("could not write {}: {}", path.display(), e));
+        }
+    }
+    println!("created package `{}`", name);
+}
+
+//the manifest of the package the current directory is in
+fn find_manifest() -> Manifest {
+    let cwd = env::current_dir().unwrap_or_else(|e| fail(format!("no current directory: {}", e)));
+    let Some(dir) = cwd.ancestors().find(|d| d.join(MANIFEST).is_file()) else {
+        fail(format!("could not find {} in {} or any parent directory", MANIFEST, cwd.display()));
+    };
+    Manifest::load(dir).unwrap_or_else(|e| fail(e))
+}
+
+//resolve the dependencies and bring the lockfile up to date, with
+//`--locked` a lockfile that would change is an error instead
+fn prepare(args: &[String]) -> (Manifest, Vec<Resolved>, Vec<String>) {
+    let locked = args.iter().any(|a| a == "--locked");
+    let crane_args: Vec<String> = args.iter().filter(|a| *a != "--locked").cloned().collect();
+    let root = find_manifest();
+    let lock = Lockfile::load(&root.dir).unwrap_or_else(|e| fail(e));
+    let resolved = resolve::resolve(&root, lock.as_ref()).unwrap_or_else(|e| fail(e));
+    let text = Lockfile::write(&root, &resolved);
+    let path = root.dir.join(LOCKFILE);
+    if fs::read_to_string(&path).ok().as_deref() != Some(text.as_str()) {
+        if locked {
+            fail(format!("{} needs to be updated but `--locked` was passed", LOCKFILE));
+        }
+        if let Err(e) = fs::write(&path, text) {
+            fail(format!("could not write {}: {}", path.display(), e));
+        }
+    }
+    for package in &resolved {
+        println!("   resolved {} {} ({})", package.manifest.name, package.manifest.version, package.source);
+    }
+    (root, resolved, crane_args)
+}
+
+//the compiler installed next to feathers, or the one on the path
+fn crane() -> PathBuf {
+    if let Some(path) = env::var_os("CRANE") {
+        return PathBuf::from(path);
+    }
+    let sibling = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(format!("crane{}", env::consts::EXE_SUFFIX))));
+    match sibling {
+        Some(path) if path.is_file() => path,
+        _ => PathBuf::from("crane"),
+    }
+}
+
+//a crane command with every dependency available to `import`
+fn command(subcommand: &str, resolved: &[Resolved], args: &[String]) -> Command {
+    let mut command = Command::new(crane());
+    command.arg(subcommand);
+    for package in resolved {
+        command.arg(format!("--extern={}={}", package.manifest.name, package.manifest.entry_path().display()));
+    }
+    command.args(args);
+    command
+}
+
+//builds keep what the compiler worked out in target/ so the next one only
+//checks the functions that changed, each test is a program of its own
+fn incremental(root: &Manifest, name: &str) -> String {
+    format!("--incremental={}", root.dir.join("target").join("incremental").join(name).display())
+}
+
+fn status(mut command: Command) -> i32 {
+    match command.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => fail(format!("could not run `{}`: {}", command.get_program().to_string_lossy(), e)),
+    }
+}
+
+fn build(args: &[String], run: bool) {
+    let (root, resolved, args) = prepare(args);
+    println!("  compiling {} {}", root.name, root.version);
+    let mut command = command(if run { "run" } else { "check" }, &resolved, &args);
+    command.arg(incremental(&root, "build"));
+    command.arg(root.entry_path());
+    let code = status(command);
+    if code != 0 {
+        process::exit(code);
+    }
+    if !run {
+        println!("   finished {}", root.name);
+    }
+}
+
+//run every file in tests/ as a program, a test passes when the program
+//finishes without a compile error or a panic. Tests import the package by
+//its name
+fn test(args: &[String]) {
+    let (root, mut resolved, args) = prepare(args);
+    let mut tests: Vec<PathBuf> = fs::read_dir(root.dir.join("tests"))
+        .map(|entries| entries.filter_map(|e| Some(e.ok()?.path())).collect())
+        .unwrap_or_default();
+    tests.retain(|p| p.extension().is_some_and(|e| e == "crane"));
+    tests.sort();
+    if tests.is_empty() {
+        println!("no tests in {}", root.dir.join("tests").display());
+        return;
+    }
+    resolved.push(Resolved {
+        manifest: root.clone(),
+        source: String::new(),
+        dependencies: Vec::new(),
+    });
+    println!("running {} {}", tests.len(), if tests.len() == 1 { "test" } else { "tests" });
+    let mut failed = Vec::new();
+    for test in &tests {
+        let name = test.file_stem().unwrap().to_string_lossy().to_string();
+        let mut command = command("run", &resolved, &args);
+        command.arg(incremental(&root, &format!("tests/{}", name)));
+        command.arg(test);
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(e) => fail(format!("could not run `{}`: {}", command.get_program().to_string_lossy(), e)),
+        };
+        if output.status.success() {
+            println!("test {} ... ok", name);
+        } else {
+            println!("test {} ... FAILED", name);
+            failed.push((name, output));
+        }
+    }
+    for (name, output) in &failed {
+        println!("\n---- {} ----", name);
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        print!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(command) = args.first() else { usage() };
+    match command.as_str() {
+        "new" => new(args.get(1)),
+        "build" => build(&args[1..], false),
+        "run" => build(&args[1..], true),
+        "test" => test(&args[1..]),
+        _ => usage(),
+    }
+}
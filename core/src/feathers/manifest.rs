@@ -0,0 +1,186 @@
+use super::toml::{self, Table, Value};
+use super::version::{Version, VersionReq};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &str = "Feathers.toml";
+
+//where a dependency comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    //a directory relative to the package that depends on it
+    Path(PathBuf),
+    //a directory in `vendor/` of the package being built, picked by version
+    Vendor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub req: VersionReq,
+    pub source: Source,
+}
+
+//a package as described by its Feathers.toml:
+//
+//  [package]
+//  name = "shapes"
+//  version = "0.1.0"
+//  entry = "src/main.crane"      (the default)
+//
+//  [dependencies]
+//  geometry = { path = "../geometry", version = "^1.0" }
+//  strings = "^0.3"              (from vendor/)
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    //relative to `dir`
+    pub entry: PathBuf,
+    pub dependencies: Vec<Dependency>,
+    //the directory the manifest is in
+    pub dir: PathBuf,
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Manifest::parse(&text, dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str, dir: &Path) -> Result<Manifest, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let package = match doc.get("package") {
+            Some(Value::Table(package)) => package,
+            _ => return Err("missing the `[package]` table".to_string()),
+        };
+        let name = string(package, "package", "name")?.ok_or("missing `name` in `[package]`")?;
+        check_name(&name)?;
+        let version = string(package, "package", "version")?.ok_or("missing `version` in `[package]`")?;
+        let version = Version::parse(&version)?;
+        let entry = string(package, "package", "entry")?.unwrap_or_else(|| "src/main.crane".to_string());
+        let mut dependencies = Vec::new();
+        match doc.get("dependencies") {
+            Some(Value::Table(table)) => {
+                for (name, spec) in table {
+                    check_name(name)?;
+                    dependencies.push(dependency(name, spec)?);
+                }
+            }
+            Some(other) => return Err(format!("`dependencies` should be a table, found {}", other.kind())),
+            None => {}
+        }
+        Ok(Manifest {
+            name,
+            version,
+            entry: PathBuf::from(entry),
+            dependencies,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    pub fn entry_path(&self) -> PathBuf {
+        self.dir.join(&self.entry)
+    }
+}
+
+fn string(table: &Table, section: &str, key: &str) -> Result<Option<String>, String> {
+    match table.get(key) {
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(other) => Err(format!("`{}` in `{}` should be a string, found {}", key, section, other.kind())),
+        None => Ok(None),
+    }
+}
+
+fn dependency(name: &str, spec: &Value) -> Result<Dependency, String> {
+    let section = format!("dependencies.{}", name);
+    let (req, path) = match spec {
+        Value::String(req) => (Some(req.clone()), None),
+        Value::Table(table) => {
+            if let Some(key) = table.keys().find(|k| *k != "version" && *k != "path") {
+                return Err(format!("unknown key `{}` in `{}`", key, section));
+            }
+            (string(table, &section, "version")?, string(table, &section, "path")?)
+        }
+        other => return Err(format!("`{}` should be a version or a table, found {}", section, other.kind())),
+    };
+    let source = match path {
+        Some(path) => Source::Path(PathBuf::from(path)),
+        None if req.is_some() => Source::Vendor,
+        None => return Err(format!("`{}` needs a `version` or a `path`", section)),
+    };
+    let req = match req {
+        Some(req) => VersionReq::parse(&req).map_err(|e| format!("`{}`: {}", section, e))?,
+        None => VersionReq::any(),
+    };
+    Ok(Dependency {
+        name: name.to_string(),
+        req,
+        source,
+    })
+}
+
+//package names are what programs write after `import`
+fn check_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let start = chars.next().is_some_and(|c| c.is_ascii_lowercase() || c == '_');
+    if !start || !chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        return Err(format!(
+            "`{}` is not a valid package name, use lowercase letters, digits and `_`",
+            name
+        ));
+    }
+    if name == "std" {
+        return Err("`std` is the name of the standard library".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Manifest, String> {
+        Manifest::parse(text, Path::new("pkg"))
+    }
+
+    #[test]
+    fn package_and_dependencies() {
+        let manifest = parse(
+            "[package]\nname = \"app\"\nversion = \"0.2.0\"\n\n[dependencies]\n\
+             geometry = { path = \"../geometry\", version = \"^1.0\" }\nstrings = \"0.3\"\nlocal = { path = \"libs/local\" }\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "app");
+        assert_eq!(manifest.version, Version::new(0, 2, 0));
+        assert_eq!(manifest.entry_path(), Path::new("pkg/src/main.crane"));
+        let deps = &manifest.dependencies;
+        assert_eq!(deps.len(), 3);
+        assert_eq!(deps[0].name, "geometry");
+        assert_eq!(deps[0].source, Source::Path(PathBuf::from("../geometry")));
+        assert!(deps[0].req.matches(&Version::new(1, 4, 0)));
+        assert_eq!(deps[1].name, "local");
+        assert_eq!(deps[1].req, VersionReq::any());
+        assert_eq!(deps[2].source, Source::Vendor);
+    }
+
+    #[test]
+    fn custom_entry() {
+        let manifest = parse("[package]\nname = \"lib\"\nversion = \"1.0.0\"\nentry = \"lib.crane\"\n").unwrap();
+        assert_eq!(manifest.entry, PathBuf::from("lib.crane"));
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("name = \"x\"").unwrap_err().contains("[package]"));
+        assert!(parse("[package]\nname = \"x\"\n").unwrap_err().contains("version"));
+        assert!(parse("[package]\nname = \"My-App\"\nversion = \"1.0.0\"\n").is_err());
+        assert!(parse("[package]\nname = \"x\"\nversion = \"1.0\"\n").is_err());
+        let bad_dep = "[package]\nname = \"x\"\nversion = \"1.0.0\"\n[dependencies]\n";
+        assert!(parse(&format!("{}y = {{}}\n", bad_dep)).unwrap_err().contains("needs a `version` or a `path`"));
+        assert!(parse(&format!("{}y = {{ git = \"url\" }}\n", bad_dep)).unwrap_err().contains("unknown key"));
+        assert!(parse(&format!("{}y = \"^x\"\n", bad_dep)).is_err());
+    }
+}
@@ -0,0 +1,495 @@
+use super::manifest::{Dependency, Manifest, Source, MANIFEST};
+use super::toml::{self, quote, Value};
+use super::version::{Version, VersionReq};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const LOCKFILE: &str = "Feathers.lock";
+pub const VENDOR: &str = "vendor";
+
+//a dependency picked for the build, every package appears once whoever
+//depends on it
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub manifest: Manifest,
+    //where it came from, as written in the lockfile: `path+../dir` or
+    //`vendor+vendor/dir`
+    pub source: String,
+    pub dependencies: Vec<String>,
+}
+
+//the versions of the last successful resolution, kept next to the manifest
+//so a build picks the same versions until the manifest changes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    //name, version and source of every dependency
+    pub packages: Vec<(String, Version, String)>,
+}
+
+impl Lockfile {
+    pub fn load(dir: &Path) -> Result<Option<Lockfile>, String> {
+        let path = dir.join(LOCKFILE);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        Lockfile::parse(&text).map(Some).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Lockfile, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let mut packages = Vec::new();
+        let entries = doc.get("package").and_then(Value::as_array).unwrap_or_default();
+        for entry in entries {
+            let field = |key: &str| {
+                entry
+                    .as_table()
+                    .and_then(|t| t.get(key))
+                    .and_then(Value::as_str)
+                    .ok_or(format!("a package is missing `{}`", key))
+            };
+            let version = Version::parse(field("version")?)?;
+            packages.push((field("name")?.to_string(), version, field("source")?.to_string()));
+        }
+        Ok(Lockfile { packages })
+    }
+
+    //the lockfile text, with the dependencies of each package so a reader
+    //can follow the graph
+    pub fn write(root: &Manifest, resolved: &[Resolved]) -> String {
+        let mut text = String::from("# This file is generated by feathers, do not edit it by hand\n");
+        let deps = |names: Vec<String>| {
+            let quoted: Vec<String> = names.iter().map(|n| quote(n)).collect();
+            format!("dependencies = [{}]\n", quoted.join(", "))
+        };
+        text.push_str(&format!(
+            "\n[root]\nname = {}\nversion = {}\n",
+            quote(&root.name),
+            quote(&root.version.to_string())
+        ));
+        let mut root_deps: Vec<String> = root.dependencies.iter().map(|d| d.name.clone()).collect();
+        root_deps.sort();
+        text.push_str(&deps(root_deps));
+        for package in resolved {
+            text.push_str(&format!(
+                "\n[[package]]\nname = {}\nversion = {}\nsource = {}\n",
+                quote(&package.manifest.name),
+                quote(&package.manifest.version.to_string()),
+                quote(&package.source)
+            ));
+            text.push_str(&deps(package.dependencies.clone()));
+        }
+        text
+    }
+
+    fn pinned(&self, name: &str) -> Option<Version> {
+        self.packages.iter().find(|(n, _, _)| n == name).map(|&(_, v, _)| v)
+    }
+}
+
+//why an attempt at resolving failed: a package already picked does not
+//satisfy a later requirement, so it has to be picked again
+enum Failure {
+    Conflict(String),
+    Error(String),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Error(message)
+    }
+}
+
+struct Resolver<'a> {
+    root: &'a Manifest,
+    //every vendored package by name, loaded once
+    vendored: HashMap<String, Vec<(Manifest, String)>>,
+    //every requirement on a package, with who made it
+    requirements: HashMap<String, Vec<(VersionReq, String)>>,
+}
+
+//pick one version of every package the root depends on, directly or not.
+//Path dependencies have one candidate, vendored ones take the highest
+//version that satisfies every requirement, or the locked version when it
+//still does
+pub fn resolve(root: &Manifest, lock: Option<&Lockfile>) -> Result<Vec<Resolved>, String> {
+    let mut resolver = Resolver {
+        root,
+        vendored: vendored(&root.dir)?,
+        requirements: HashMap::new(),
+    };
+    let mut pins: HashMap<String, Version> = HashMap::new();
+    if let Some(lock) = lock {
+        for (name, candidates) in &resolver.vendored {
+            if let Some(version) = lock.pinned(name) {
+                if candidates.iter().any(|(m, _)| m.version == version) {
+                    pins.insert(name.clone(), version);
+                }
+            }
+        }
+    }
+    //each conflict pins a package to a version no earlier attempt used
+    loop {
+        resolver.requirements.clear();
+        match resolver.attempt(&pins) {
+            Ok(resolved) => return Ok(resolved),
+            Err(Failure::Error(message)) => return Err(message),
+            Err(Failure::Conflict(name)) => {
+                let requirements = &resolver.requirements[&name];
+                let best = resolver
+                    .vendored
+                    .get(&name)
+                    .into_iter()
+                    .flatten()
+                    .map(|(m, _)| m.version)
+                    .filter(|v| requirements.iter().all(|(req, _)| req.matches(v)))
+                    .max();
+                match best {
+                    Some(version) if pins.get(&name) != Some(&version) => {
+                        pins.insert(name, version);
+                    }
+                    _ => return Err(conflict(&name, requirements)),
+                }
+            }
+        }
+    }
+}
+
+fn conflict(name: &str, requirements: &[(VersionReq, String)]) -> String {
+    let mut message = format!("no version of `{}` satisfies every requirement:", name);
+    for (req, by) in requirements {
+        message.push_str(&format!("\n  `{}` requires `{} {}`", by, name, req));
+    }
+    message
+}
+
+impl Resolver<'_> {
+    fn attempt(&mut self, pins: &HashMap<String, Version>) -> Result<Vec<Resolved>, Failure> {
+        let mut picked: BTreeMap<String, Resolved> = BTreeMap::new();
+        let mut queue = VecDeque::from([self.root.clone()]);
+        while let Some(package) = queue.pop_front() {
+            for dep in &package.dependencies {
+                self.requirements
+                    .entry(dep.name.clone())
+                    .or_default()
+                    .push((dep.req.clone(), package.name.clone()));
+                if dep.name == self.root.name {
+                    return Err(Failure::Error(format!("`{}` depends on `{}`, the package being built", package.name, dep.name)));
+                }
+                if let Some(existing) = picked.get(&dep.name) {
+                    if let Source::Path(path) = &dep.source {
+                        let source = self.path_source(&package.dir.join(path));
+                        if source != existing.source {
+                            return Err(Failure::Error(format!(
+                                "`{}` comes from both `{}` and `{}`",
+                                dep.name, existing.source, source
+                            )));
+                        }
+                    }
+                    if !dep.req.matches(&existing.manifest.version) {
+                        return Err(Failure::Conflict(dep.name.clone()));
+                    }
+                    continue;
+                }
+                let (manifest, source) = self.candidate(&package, dep, pins)?;
+                let mut dependencies: Vec<String> = manifest.dependencies.iter().map(|d| d.name.clone()).collect();
+                dependencies.sort();
+                queue.push_back(manifest.clone());
+                picked.insert(
+                    dep.name.clone(),
+                    Resolved {
+                        manifest,
+                        source,
+                        dependencies,
+                    },
+                );
+            }
+        }
+        Ok(picked.into_values().collect())
+    }
+
+    fn candidate(
+        &self,
+        package: &Manifest,
+        dep: &Dependency,
+        pins: &HashMap<String, Version>,
+    ) -> Result<(Manifest, String), String> {
+        match &dep.source {
+            Source::Path(path) => {
+                let dir = normalize(&package.dir.join(path));
+                let manifest = Manifest::load(&dir)?;
+                if manifest.name != dep.name {
+                    return Err(format!(
+                        "`{}` depends on `{}` at `{}`, but the package there is `{}`",
+                        package.name,
+                        dep.name,
+                        path.display(),
+                        manifest.name
+                    ));
+                }
+                if !dep.req.matches(&manifest.version) {
+                    return Err(format!(
+                        "`{}` requires `{} {}`, but `{}` is version {}",
+                        package.name,
+                        dep.name,
+                        dep.req,
+                        path.display(),
+                        manifest.version
+                    ));
+                }
+                let source = self.path_source(&dir);
+                Ok((manifest, source))
+            }
+            Source::Vendor => {
+                let candidates = self.vendored.get(&dep.name).map(Vec::as_slice).unwrap_or_default();
+                let matching = candidates.iter().filter(|(m, _)| dep.req.matches(&m.version));
+                let pinned = pins.get(&dep.name);
+                let best = match matching.clone().find(|(m, _)| Some(&m.version) == pinned) {
+                    Some(pinned) => Some(pinned),
+                    None => matching.max_by_key(|(m, _)| m.version),
+                };
+                match best {
+                    Some((manifest, source)) => Ok((manifest.clone(), source.clone())),
+                    None if candidates.is_empty() => Err(format!(
+                        "`{}` depends on `{}`, which is not in `{}/`",
+                        package.name, dep.name, VENDOR
+                    )),
+                    None => {
+                        let mut versions: Vec<Version> = candidates.iter().map(|(m, _)| m.version).collect();
+                        versions.sort();
+                        let versions: Vec<String> = versions.iter().map(Version::to_string).collect();
+                        Err(format!(
+                            "`{}` requires `{} {}`, but `{}/` only has {}",
+                            package.name,
+                            dep.name,
+                            dep.req,
+                            VENDOR,
+                            versions.join(", ")
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    //a path dependency as the lockfile records it, relative to the root
+    fn path_source(&self, dir: &Path) -> String {
+        format!("path+{}", relative(&self.root.dir, &normalize(dir)).display())
+    }
+}
+
+//every package in the vendor directory, whatever its directory is called
+fn vendored(root: &Path) -> Result<HashMap<String, Vec<(Manifest, String)>>, String> {
+    let mut packages: HashMap<String, Vec<(Manifest, String)>> = HashMap::new();
+    let Ok(entries) = fs::read_dir(root.join(VENDOR)) else {
+        return Ok(packages);
+    };
+    let mut dirs: Vec<PathBuf> = entries.filter_map(|e| Some(e.ok()?.path())).filter(|p| p.join(MANIFEST).is_file()).collect();
+    dirs.sort();
+    for dir in dirs {
+        let manifest = Manifest::load(&dir)?;
+        let source = format!("vendor+{}/{}", VENDOR, dir.file_name().unwrap().to_string_lossy());
+        packages.entry(manifest.name.clone()).or_default().push((manifest, source));
+    }
+    Ok(packages)
+}
+
+//remove `.` and `a/..` from a path without touching the file system
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normal.components().next_back(), Some(Component::Normal(_))) => {
+                normal.pop();
+            }
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+//`path` as seen from `from`, both normalized
+fn relative(from: &Path, path: &Path) -> PathBuf {
+    let from = normalize(from);
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = path.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    //different roots, or a `..` that cannot be undone
+    if from[common..].iter().any(|c| !matches!(c, Component::Normal(_))) {
+        return path.to_path_buf();
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    relative.extend(&to[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    //a directory of packages for one test, removed when it is dropped
+    struct Workspace(PathBuf);
+
+    impl Workspace {
+        fn new(name: &str) -> Workspace {
+            let dir = env::temp_dir().join(format!("feathers-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Workspace(dir)
+        }
+        fn package(&self, dir: &str, name: &str, version: &str, deps: &[&str]) -> &Self {
+            let dir = self.0.join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            let text = format!(
+                "[package]\nname = \"{}\"\nversion = \"{}\"\n\n[dependencies]\n{}\n",
+                name,
+                version,
+                deps.join("\n")
+            );
+            fs::write(dir.join(MANIFEST), text).unwrap();
+            self
+        }
+        fn root(&self) -> Manifest {
+            Manifest::load(&self.0.join("app")).unwrap()
+        }
+    }
+
+    impl Drop for Workspace {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn versions(resolved: &[Resolved]) -> Vec<String> {
+        resolved
+            .iter()
+            .map(|r| format!("{} {} {}", r.manifest.name, r.manifest.version, r.source))
+            .collect()
+    }
+
+    #[test]
+    fn path_dependencies_are_followed() {
+        let ws = Workspace::new("path");
+        ws.package("app", "app", "0.1.0", &["geometry = { path = \"../libs/geometry\" }"])
+            .package("libs/geometry", "geometry", "1.2.0", &["numbers = { path = \"../numbers\", version = \"^0.3\" }"])
+            .package("libs/numbers", "numbers", "0.3.1", &[]);
+        let resolved = resolve(&ws.root(), None).unwrap();
+        assert_eq!(
+            versions(&resolved),
+            [
+                "geometry 1.2.0 path+../libs/geometry",
+                "numbers 0.3.1 path+../libs/numbers"
+            ]
+        );
+        assert_eq!(resolved[0].dependencies, ["numbers"]);
+    }
+
+    #[test]
+    fn path_dependency_must_match_name_and_version() {
+        let ws = Workspace::new("path-mismatch");
+        ws.package("app", "app", "0.1.0", &["geometry = { path = \"../geo\", version = \"^2\" }"])
+            .package("geo", "geometry", "1.0.0", &[]);
+        let error = resolve(&ws.root(), None).unwrap_err();
+        assert!(error.contains("requires `geometry ^2`"), "{}", error);
+        ws.package("app", "app", "0.1.0", &["shapes = { path = \"../geo\" }"]);
+        let error = resolve(&ws.root(), None).unwrap_err();
+        assert!(error.contains("the package there is `geometry`"), "{}", error);
+    }
+
+    #[test]
+    fn vendored_takes_the_highest_matching_version() {
+        let ws = Workspace::new("vendor");
+        ws.package("app", "app", "0.1.0", &["strings = \"^1.1\""])
+            .package("app/vendor/strings-1.0.0", "strings", "1.0.0", &[])
+            .package("app/vendor/strings-1.4.2", "strings", "1.4.2", &[])
+            .package("app/vendor/strings-2.0.0", "strings", "2.0.0", &[]);
+        let resolved = resolve(&ws.root(), None).unwrap();
+        assert_eq!(versions(&resolved), ["strings 1.4.2 vendor+vendor/strings-1.4.2"]);
+    }
+
+    #[test]
+    fn requirements_are_unified() {
+        //app takes any 1.x, geometry only up to 1.2, so both get 1.2.0
+        let ws = Workspace::new("unify");
+        ws.package("app", "app", "0.1.0", &["strings = \"^1\"", "geometry = { path = \"geometry\" }"])
+            .package("app/geometry", "geometry", "0.1.0", &["strings = \">=1.0, <1.3\""])
+            .package("app/vendor/strings-1.2.0", "strings", "1.2.0", &[])
+            .package("app/vendor/strings-1.5.0", "strings", "1.5.0", &[]);
+        let resolved = resolve(&ws.root(), None).unwrap();
+        assert_eq!(
+            versions(&resolved),
+            ["geometry 0.1.0 path+geometry", "strings 1.2.0 vendor+vendor/strings-1.2.0"]
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_requirements_name_who_asked() {
+        let ws = Workspace::new("conflict");
+        ws.package("app", "app", "0.1.0", &["strings = \"^2\"", "geometry = { path = \"geometry\" }"])
+            .package("app/geometry", "geometry", "0.1.0", &["strings = \"^1\""])
+            .package("app/vendor/strings-1.0.0", "strings", "1.0.0", &[])
+            .package("app/vendor/strings-2.0.0", "strings", "2.0.0", &[]);
+        let error = resolve(&ws.root(), None).unwrap_err();
+        assert!(error.contains("no version of `strings`"), "{}", error);
+        assert!(error.contains("`app` requires `strings ^2`"), "{}", error);
+        assert!(error.contains("`geometry` requires `strings ^1`"), "{}", error);
+    }
+
+    #[test]
+    fn missing_vendored_package() {
+        let ws = Workspace::new("missing");
+        ws.package("app", "app", "0.1.0", &["strings = \"^3\""])
+            .package("app/vendor/strings", "strings", "1.0.0", &[]);
+        let error = resolve(&ws.root(), None).unwrap_err();
+        assert!(error.contains("only has 1.0.0"), "{}", error);
+        ws.package("app", "app", "0.1.0", &["numbers = \"1\""]);
+        let error = resolve(&ws.root(), None).unwrap_err();
+        assert!(error.contains("not in `vendor/`"), "{}", error);
+    }
+
+    #[test]
+    fn lockfile_keeps_versions_while_they_match() {
+        let ws = Workspace::new("lock");
+        ws.package("app", "app", "0.1.0", &["strings = \"^1\""])
+            .package("app/vendor/strings-1.0.0", "strings", "1.0.0", &[])
+            .package("app/vendor/strings-1.1.0", "strings", "1.1.0", &[]);
+        let root = ws.root();
+        let lock = Lockfile {
+            packages: vec![("strings".to_string(), Version::new(1, 0, 0), "vendor+vendor/strings-1.0.0".to_string())],
+        };
+        let resolved = resolve(&root, Some(&lock)).unwrap();
+        assert_eq!(versions(&resolved), ["strings 1.0.0 vendor+vendor/strings-1.0.0"]);
+        //the lockfile round trips through its text
+        let text = Lockfile::write(&root, &resolved);
+        assert_eq!(Lockfile::parse(&text).unwrap(), lock);
+        //a manifest that no longer accepts the locked version moves on
+        ws.package("app", "app", "0.1.0", &["strings = \"^1.1\""]);
+        let resolved = resolve(&ws.root(), Some(&lock)).unwrap();
+        assert_eq!(versions(&resolved), ["strings 1.1.0 vendor+vendor/strings-1.1.0"]);
+    }
+
+    #[test]
+    fn depending_on_the_root_is_an_error() {
+        let ws = Workspace::new("self");
+        ws.package("app", "app", "0.1.0", &["lib = { path = \"lib\" }"])
+            .package("app/lib", "lib", "0.1.0", &["app = { path = \"..\" }"]);
+        assert!(resolve(&ws.root(), None).unwrap_err().contains("the package being built"));
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize(Path::new("a/./b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize(Path::new("../a/..")), PathBuf::from(".."));
+        assert_eq!(relative(Path::new("/w/app"), Path::new("/w/libs/x")), PathBuf::from("../libs/x"));
+        assert_eq!(relative(Path::new("/w/app"), Path::new("/w/app/x")), PathBuf::from("x"));
+    }
+}
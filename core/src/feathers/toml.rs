@@ -0,0 +1,336 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+
+//the part of TOML that manifests and lockfiles use: `[table]` and
+//`[[array of tables]]` headers, `key = value` pairs, strings, booleans,
+//single line arrays and inline tables, and `#` comments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Table, Error> {
+    let mut root = Table::new();
+    //the header the following pairs belong to, and whether it is `[[..]]`
+    let mut current: Option<(String, bool)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let mut reader = Reader {
+            chars: line.chars().collect(),
+            pos: 0,
+            line: index + 1,
+        };
+        reader.skip_space();
+        if reader.done() {
+            continue;
+        }
+        if reader.eat('[') {
+            let array = reader.eat('[');
+            reader.skip_space();
+            let name = reader.key()?;
+            reader.skip_space();
+            if !reader.eat(']') || (array && !reader.eat(']')) {
+                return Err(reader.error("expected `]` after the table name"));
+            }
+            reader.end()?;
+            match root.entry(name.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(if array {
+                        Value::Array(vec![Value::Table(Table::new())])
+                    } else {
+                        Value::Table(Table::new())
+                    });
+                }
+                Entry::Occupied(mut entry) => match entry.get_mut() {
+                    Value::Array(tables) if array => tables.push(Value::Table(Table::new())),
+                    _ => return Err(reader.error(format!("`{}` is defined twice", name))),
+                },
+            }
+            current = Some((name, array));
+            continue;
+        }
+        let key = reader.key()?;
+        reader.skip_space();
+        if !reader.eat('=') {
+            return Err(reader.error(format!("expected `=` after `{}`", key)));
+        }
+        reader.skip_space();
+        let value = reader.value()?;
+        reader.end()?;
+        let table = match &current {
+            None => &mut root,
+            Some((name, false)) => match root.get_mut(name) {
+                Some(Value::Table(t)) => t,
+                _ => unreachable!(),
+            },
+            Some((name, true)) => match root.get_mut(name) {
+                Some(Value::Array(tables)) => match tables.last_mut() {
+                    Some(Value::Table(t)) => t,
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+        };
+        if table.insert(key.clone(), value).is_some() {
+            return Err(reader.error(format!("`{}` is defined twice", key)));
+        }
+    }
+    Ok(root)
+}
+
+//a string as it is written in TOML
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Reader {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+    //nothing but a comment is left on the line
+    fn done(&self) -> bool {
+        self.peek().is_none_or(|c| c == '#')
+    }
+    fn end(&mut self) -> Result<(), Error> {
+        self.skip_space();
+        if !self.done() {
+            return Err(self.error("unexpected text after the value"));
+        }
+        Ok(())
+    }
+    fn key(&mut self) -> Result<String, Error> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a key"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+    fn string(&mut self) -> Result<String, Error> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let c = match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        _ => return Err(self.error("unknown escape in string")),
+                    };
+                    s.push(c);
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    s.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space();
+                    if self.eat(']') {
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_space();
+                    if !self.eat(',') {
+                        self.skip_space();
+                        if !self.eat(']') {
+                            return Err(self.error("expected `,` or `]` in the array"));
+                        }
+                        return Ok(Value::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Table::new();
+                self.skip_space();
+                if self.eat('}') {
+                    return Ok(Value::Table(table));
+                }
+                loop {
+                    self.skip_space();
+                    let key = self.key()?;
+                    self.skip_space();
+                    if !self.eat('=') {
+                        return Err(self.error(format!("expected `=` after `{}`", key)));
+                    }
+                    self.skip_space();
+                    let value = self.value()?;
+                    if table.insert(key.clone(), value).is_some() {
+                        return Err(self.error(format!("`{}` is defined twice", key)));
+                    }
+                    self.skip_space();
+                    if self.eat('}') {
+                        return Ok(Value::Table(table));
+                    }
+                    if !self.eat(',') {
+                        return Err(self.error("expected `,` or `}` in the inline table"));
+                    }
+                }
+            }
+            _ => {
+                let word = self.key().map_err(|_| self.error("expected a value"))?;
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(self.error(format!("expected a value, found `{}`", word))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn tables_and_pairs() {
+        let doc = parse("# a comment\nname = \"x\"\n\n[package]\nversion = \"1.0.0\" # trailing\nlib = true\n").unwrap();
+        assert_eq!(doc["name"], string("x"));
+        let package = doc["package"].as_table().unwrap();
+        assert_eq!(package["version"], string("1.0.0"));
+        assert_eq!(package["lib"], Value::Bool(true));
+    }
+
+    #[test]
+    fn inline_tables_and_arrays() {
+        let doc = parse("dep = { path = \"../dep\", version = \"^1\" }\nlist = [\"a\", \"b\",]\nempty = []\n").unwrap();
+        let dep = doc["dep"].as_table().unwrap();
+        assert_eq!(dep["path"], string("../dep"));
+        assert_eq!(dep["version"], string("^1"));
+        assert_eq!(doc["list"], Value::Array(vec![string("a"), string("b")]));
+        assert_eq!(doc["empty"], Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let doc = parse("[[package]]\nname = \"a\"\n\n[[package]]\nname = \"b\"\n").unwrap();
+        let packages = doc["package"].as_array().unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[1].as_table().unwrap()["name"], string("b"));
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let text = format!("s = {}\n", quote("a \"quoted\" \\ path"));
+        assert_eq!(parse(&text).unwrap()["s"], string("a \"quoted\" \\ path"));
+    }
+
+    #[test]
+    fn errors_have_lines() {
+        assert_eq!(parse("a = \"1\"\na = \"2\"\n").unwrap_err().line, 2);
+        assert_eq!(parse("\n\nname \"x\"").unwrap_err().line, 3);
+        assert!(parse("s = \"open").is_err());
+        assert!(parse("[package\n").is_err());
+        assert!(parse("[a]\n[a]\n").is_err());
+        assert!(parse("n = 1\n").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+
+//a package version, major.minor.patch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self { major, minor, patch }
+    }
+
+    pub fn parse(s: &str) -> Result<Version, String> {
+        let (version, parts) = partial(s)?;
+        if parts != 3 {
+            return Err(format!("`{}` is not a version, write major.minor.patch", s));
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+//one to three numbers, the missing ones are zero
+fn partial(s: &str) -> Result<(Version, usize), String> {
+    let mut numbers = Vec::new();
+    for part in s.trim().split('.') {
+        match part.parse::<u64>() {
+            Ok(n) if !part.starts_with('+') => numbers.push(n),
+            _ => return Err(format!("`{}` is not a version", s.trim())),
+        }
+    }
+    if numbers.len() > 3 {
+        return Err(format!("`{}` has more than three parts", s.trim()));
+    }
+    let parts = numbers.len();
+    numbers.resize(3, 0);
+    Ok((Version::new(numbers[0], numbers[1], numbers[2]), parts))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+//which versions of a dependency are accepted, as written in a manifest:
+//
+//  1.2, ^1.2     1.2.0 up to but not including 2.0.0; for 0.x versions the
+//                first non zero part may not change
+//  ~1.2.3        1.2.3 up to but not including 1.3.0
+//  =1.2.3        only 1.2.3
+//  >=1.0, <1.5   every comparison has to hold
+//  *             any version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<(Op, Version)>,
+    text: String,
+}
+
+impl VersionReq {
+    pub fn any() -> Self {
+        Self {
+            comparators: Vec::new(),
+            text: "*".to_string(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<VersionReq, String> {
+        let text = s.trim().to_string();
+        if text == "*" {
+            return Ok(Self::any());
+        }
+        let mut comparators = Vec::new();
+        for part in text.split(',') {
+            let part = part.trim();
+            let (op, rest) = if let Some(rest) = part.strip_prefix(">=") {
+                (Some(Op::GreaterEq), rest)
+            } else if let Some(rest) = part.strip_prefix("<=") {
+                (Some(Op::LessEq), rest)
+            } else if let Some(rest) = part.strip_prefix('>') {
+                (Some(Op::Greater), rest)
+            } else if let Some(rest) = part.strip_prefix('<') {
+                (Some(Op::Less), rest)
+            } else if let Some(rest) = part.strip_prefix('=') {
+                (Some(Op::Exact), rest)
+            } else if let Some(rest) = part.strip_prefix('~') {
+                let (version, parts) = partial(rest)?;
+                //~1 allows any 1.x, ~1.2 and ~1.2.3 any 1.2.x
+                let upper = if parts == 1 {
+                    Version::new(version.major + 1, 0, 0)
+                } else {
+                    Version::new(version.major, version.minor + 1, 0)
+                };
+                comparators.push((Op::GreaterEq, version));
+                comparators.push((Op::Less, upper));
+                continue;
+            } else {
+                (None, part.strip_prefix('^').unwrap_or(part))
+            };
+            let (version, parts) = partial(rest)?;
+            match op {
+                Some(op) => comparators.push((op, version)),
+                None => {
+                    comparators.push((Op::GreaterEq, version));
+                    comparators.push((Op::Less, caret_upper(version, parts)));
+                }
+            }
+        }
+        Ok(Self { comparators, text })
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|(op, bound)| match op {
+            Op::Exact => version == bound,
+            Op::Greater => version > bound,
+            Op::GreaterEq => version >= bound,
+            Op::Less => version < bound,
+            Op::LessEq => version <= bound,
+        })
+    }
+}
+
+//the first version a caret requirement no longer accepts
+fn caret_upper(version: Version, parts: usize) -> Version {
+    match (version.major, version.minor, parts) {
+        (0, 0, 3) => Version::new(0, 0, version.patch + 1),
+        (0, 0, 2) => Version::new(0, 1, 0),
+        (0, minor, p) if p > 1 => Version::new(0, minor + 1, 0),
+        (major, _, _) => Version::new(major + 1, 0, 0),
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::parse(s).unwrap()
+    }
+
+    fn accepts(req: &str, versions: &[&str]) -> Vec<bool> {
+        let req = VersionReq::parse(req).unwrap();
+        versions.iter().map(|s| req.matches(&v(s))).collect()
+    }
+
+    #[test]
+    fn versions_parse_and_order() {
+        assert_eq!(v("1.2.3"), Version::new(1, 2, 3));
+        assert!(v("1.10.0") > v("1.9.9"));
+        assert_eq!(v("0.1.0").to_string(), "0.1.0");
+        assert!(Version::parse("1.2").is_err());
+        assert!(Version::parse("1.x.0").is_err());
+        assert!(Version::parse("1.2.3.4").is_err());
+    }
+
+    #[test]
+    fn caret() {
+        let versions = ["1.2.0", "1.2.5", "1.9.0", "2.0.0", "1.1.9"];
+        assert_eq!(accepts("1.2", &versions), [true, true, true, false, false]);
+        assert_eq!(accepts("^1.2.5", &versions), [false, true, true, false, false]);
+        assert_eq!(accepts("^0.2.3", &["0.2.3", "0.2.9", "0.3.0"]), [true, true, false]);
+        assert_eq!(accepts("^0.0.3", &["0.0.3", "0.0.4"]), [true, false]);
+        assert_eq!(accepts("0", &["0.0.1", "0.9.0", "1.0.0"]), [true, true, false]);
+    }
+
+    #[test]
+    fn tilde() {
+        assert_eq!(accepts("~1.2.3", &["1.2.3", "1.2.9", "1.3.0", "1.2.2"]), [true, true, false, false]);
+        assert_eq!(accepts("~1", &["1.0.0", "1.9.0", "2.0.0"]), [true, true, false]);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(accepts("=1.2.3", &["1.2.3", "1.2.4"]), [true, false]);
+        assert_eq!(accepts(">=1.0, <1.5", &["0.9.0", "1.0.0", "1.4.9", "1.5.0"]), [false, true, true, false]);
+        assert_eq!(accepts("> 1.0.0, <= 2.0.0", &["1.0.0", "1.0.1", "2.0.0", "2.0.1"]), [false, true, true, false]);
+        assert_eq!(accepts("*", &["0.0.1", "99.0.0"]), [true, true]);
+    }
+
+    #[test]
+    fn bad_requirements() {
+        assert!(VersionReq::parse("").is_err());
+        assert!(VersionReq::parse("^a.b").is_err());
+        assert!(VersionReq::parse(">=1.0,").is_err());
+    }
+}
@@ -1,22 +1,51 @@
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
 
-#[macro_export]
-macro_rules! throw {
-    ($error:expr, $line:expr) => {
-        println!("{}: {}", Red.paint(format!("Error on line {}", $line)), $error);
-        std::process::exit(0);
-    };
-    ($error:expr) => {
-        println!("{}: {}", Red.paint("Error"), $error);
-        std::process::exit(0);
-    };
-    //3rd arg, true or false (kill program)
-    ($error:expr, $line:expr, $kill:expr) => {
-        if $kill {
-            println!("{}: {}", Red.paint(format!("Error on line {}", $line)), $error);
-            std::process::exit(0);
-        } else {
-            println!("{}: {}", Red.paint(format!("Error on line {}", $line)), $error);
-        }
-    };
+pub fn unterminated_string(open: Span) -> Diagnostic {
+    Diagnostic::error(E0001, "unterminated string")
+        .with_label(open, "the string starts here")
+        .with_help("add a closing `\"` at the end of the string")
 }
 
+pub fn invalid_identifier_char(c: char, span: Span) -> Diagnostic {
+    Diagnostic::error(E0002, format!("invalid character in identifier: '{}'", c))
+        .with_label(span, "not allowed in an identifier")
+}
+
+pub fn invalid_escape(c: char, span: Span) -> Diagnostic {
+    Diagnostic::error(E0003, format!("invalid escape character: '\\{}'", c))
+        .with_label(span, "unknown escape")
+        .with_note("valid escapes are \\n \\t \\r \\0 \\\" \\' and \\\\")
+}
+
+pub fn invalid_char_literal(span: Span) -> Diagnostic {
+    Diagnostic::error(E0004, "invalid character literal")
+        .with_label(span, "expected a single character followed by `'`")
+}
+
+pub fn unexpected_char(c: char, span: Span) -> Diagnostic {
+    Diagnostic::error(E0005, format!("unexpected character: '{}'", c))
+        .with_label(span, "not valid here")
+}
+
+pub fn unclosed_delimiter(open: char, span: Span) -> Diagnostic {
+    Diagnostic::error(E0006, format!("unclosed delimiter '{}'", open))
+        .with_label(span, "never closed")
+}
+
+pub fn unmatched_delimiter(close: char, span: Span) -> Diagnostic {
+    Diagnostic::error(E0007, format!("unexpected closing delimiter '{}'", close))
+        .with_label(span, "no matching opening delimiter")
+}
+
+pub fn mismatched_delimiter(open: char, open_span: Span, close: char, close_span: Span) -> Diagnostic {
+    Diagnostic::error(E0008, format!("mismatched closing delimiter '{}'", close))
+        .with_label(close_span, format!("does not close '{}'", open))
+        .with_secondary(open_span, "opened here")
+}
+
+pub fn unclosed_hole(span: Span) -> Diagnostic {
+    Diagnostic::error(E0009, "unclosed `{` in string")
+        .with_label(span, "this hole is never closed")
+        .with_help("close it with `}`, or write `{{` for a `{` in the text")
+}
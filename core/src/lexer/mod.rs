@@ -1,111 +1,249 @@
 #![allow(dead_code)]
 mod tokentype;
-use std::process;
-use std::collections::HashMap;
 pub use tokentype::Token;
 pub use tokentype::TokenType;
 mod error;
-use crate::throw;
-use ansi_term::Colour::Red;
+use crate::diagnostic::{Diagnostic, Span};
 //create a lexer struct that uses peekable iterator for the source code
 #[derive(Debug, Clone)]
 pub struct Lexer {
     source: std::iter::Peekable<std::str::Chars<'static>>,
+    //the whole source, the holes of a string are lexed from it
+    text: &'static str,
     line: usize,
+    //byte offset of the next character, used for spans
+    pos: usize,
+    file: usize,
     pub tokens: Vec<Token>,
+    pub diagnostics: Vec<Diagnostic>,
 }
 impl Lexer {
-    pub fn new(source: &'static str) -> Self {
+    pub fn new(source: &'static str, file: usize) -> Self {
         Self {
             source: source.chars().peekable(),
+            text: source,
             line: 1,
+            pos: 0,
+            file,
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
     fn next(&mut self) -> Option<char> {
-        self.source.next().and_then(|c| (c != '\0').then_some(c))
+        let c = self.source.next()?;
+        self.pos += c.len_utf8();
+        (c != '\0').then_some(c)
     }
     fn peek(&mut self) -> Option<&char> {
         self.source.peek()
     }
-    fn read_string(&mut self) -> String {
+    fn span(&self, start: usize) -> Span {
+        Span::new(self.file, start, self.pos)
+    }
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+    //a string, whose `{...}` holes are lexed where they are: a string with
+    //holes is `FormatStart`, its text and holes in order and `FormatEnd`, one
+    //without is a single `Str`. `{{` and `}}` are a brace in the text
+    fn read_string(&mut self, start: usize) {
+        let open = self.span(start);
+        let mut pieces = Vec::new();
         let mut string = String::new();
-        let mut last: char = '\0';
+        let mut from = self.pos;
+        let mut closed = false;
         while let Some(c) = self.next() {
-            last = c;
-            if c == '\0' {
-                throw!("Unterminated string", self.line);
+            match c {
+                '"' => {
+                    closed = true;
+                    break;
+                }
+                '\n' => {
+                    self.line += 1;
+                    string.push(c);
+                }
+                '\\' => {
+                    let Some(c) = self.next() else { break };
+                    string.push(match c {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '0' => '\0',
+                        _ => c,
+                    });
+                }
+                '{' | '}' if self.peek() == Some(&c) => {
+                    self.next();
+                    string.push(c);
+                }
+                '{' => {
+                    let hole = self.pos - 1;
+                    let Some(end) = self.hole_end() else {
+                        self.error(error::unclosed_hole(Span::new(self.file, hole, self.pos)));
+                        string.push(c);
+                        continue;
+                    };
+                    if !string.is_empty() {
+                        pieces.push(self.piece(std::mem::take(&mut string), from, hole));
+                    }
+                    self.read_hole(hole, end, &mut pieces);
+                    from = self.pos;
+                }
+                _ => string.push(c),
             }
+        }
+        if !closed {
+            self.error(error::unterminated_string(open));
+        }
+        if pieces.is_empty() {
+            self.tokens.push(Token::new(TokenType::Str(string), self.line));
+            return;
+        }
+        let quote = self.pos - closed as usize;
+        if !string.is_empty() {
+            pieces.push(self.piece(string, from, quote));
+        }
+        self.tokens.push(Token::new(TokenType::FormatStart, self.line));
+        self.tokens.append(&mut pieces);
+        let mut end = Token::new(TokenType::FormatEnd, self.line);
+        end.span = self.span(quote);
+        self.tokens.push(end);
+    }
+    //the text of a string between `from` and `to`
+    fn piece(&self, text: String, from: usize, to: usize) -> Token {
+        let mut token = Token::new(TokenType::Str(text), self.line);
+        token.span = Span::new(self.file, from, to);
+        token
+    }
+    //where the hole whose `{` was just read ends, at the `}` closing it on
+    //the same line. A string cannot be written inside a hole
+    fn hole_end(&self) -> Option<usize> {
+        let mut depth = 0;
+        for (i, c) in self.text[self.pos..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some(self.pos + i),
+                '}' => depth -= 1,
+                '"' | '\n' => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+    //the tokens of the hole from the `{` at `hole` to the `}` at `end`,
+    //between a `LeftBrace` and a `RightBrace`
+    fn read_hole(&mut self, hole: usize, end: usize, pieces: &mut Vec<Token>) {
+        let mut brace = Token::new(TokenType::LeftBrace, self.line);
+        brace.span = Span::new(self.file, hole, hole + 1);
+        pieces.push(brace);
+        let mut lexer = Lexer {
+            source: self.text[self.pos..end].chars().peekable(),
+            text: self.text,
+            line: self.line,
+            pos: self.pos,
+            file: self.file,
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        lexer.lex();
+        lexer.tokens.pop();
+        pieces.append(&mut lexer.tokens);
+        self.diagnostics.append(&mut lexer.diagnostics);
+        while self.pos <= end {
+            self.next();
+        }
+        let mut brace = Token::new(TokenType::RightBrace, self.line);
+        brace.span = Span::new(self.file, end, end + 1);
+        pieces.push(brace);
+    }
+    //`r"..."`, the text as it is written, without escapes or holes
+    fn read_raw(&mut self, start: usize) -> String {
+        let mut string = String::new();
+        while let Some(c) = self.next() {
             if c == '"' {
-                break;
+                return string;
             }
             if c == '\n' {
                 self.line += 1;
             }
-            if c == '\\' {
-                let c = self.next().unwrap();
-                match c {
-                    'n' => string.push('\n'),
-                    't' => string.push('\t'),
-                    'r' => string.push('\r'),
-                    '0' => string.push('\0'),
-                    '"' => string.push('"'),
-                    '\'' => string.push('\''),
-                    '\\' => string.push('\\'),
-                    _ => string.push(c),
-                }
-                continue;
-            }
             string.push(c);
         }
-        if last != '"' {
-            throw!("Unterminated string", self.line);
-        }
+        self.error(error::unterminated_string(Span::new(self.file, start, start + 2)));
         string
     }
+    //`"""` and the lines up to the next `"""`, which like a raw string have
+    //no escapes or holes
+    fn read_block(&mut self, start: usize) -> String {
+        self.next();
+        self.next();
+        let mut text = String::new();
+        while !self.text[self.pos..].starts_with("\"\"\"") {
+            let Some(c) = self.next() else {
+                self.error(error::unterminated_string(Span::new(self.file, start, start + 3)));
+                return dedent(&text);
+            };
+            if c == '\n' {
+                self.line += 1;
+            }
+            text.push(c);
+        }
+        for _ in 0..3 {
+            self.next();
+        }
+        dedent(&text)
+    }
     fn read_number(&mut self, c: char) -> String {
         let mut number = String::from(c);
-        while let Some(c) = self.peek() {
-            if c.is_ascii_digit() {
+        self.read_digits(&mut number);
+        number
+    }
+    fn read_digits(&mut self, number: &mut String) {
+        while self.peek().is_some_and(char::is_ascii_digit) {
+            number.push(self.next().unwrap());
+        }
+    }
+    //the fraction and exponent of a float after its integer digits, if it
+    //has them. A `.` is only a decimal point before a digit, `0..n` is a
+    //range, and never right after a `.`, `t.0.1` is a field of a field
+    fn read_float(&mut self, number: &mut String) -> bool {
+        let rest = &self.text.as_bytes()[self.pos..];
+        let field = self.tokens.last().is_some_and(|t| t.token_type == TokenType::Dot);
+        let mut float = false;
+        if rest.first() == Some(&b'.') && rest.get(1).is_some_and(u8::is_ascii_digit) && !field {
+            number.push(self.next().unwrap());
+            self.read_digits(number);
+            float = true;
+        }
+        let rest = &self.text.as_bytes()[self.pos..];
+        let sign = matches!(rest.get(1), Some(b'+' | b'-')) as usize;
+        if matches!(rest.first(), Some(b'e' | b'E')) && rest.get(1 + sign).is_some_and(u8::is_ascii_digit) && !field {
+            for _ in 0..=sign {
                 number.push(self.next().unwrap());
-            } else {
-                break;
             }
+            self.read_digits(number);
+            float = true;
         }
-        number
+        float
     }
     fn read_identifier(&mut self, c: char) -> String {
-        let line = self.line;
         let mut identifier = String::from(c);
         while let Some(c) = self.peek() {
-            if c == &'('
-                || c == &')'
-                || c == &'{'
-                || c == &'}'
-                || c == &'['
-                || c == &']'
-                || c == &'<'
-                || c == &'>'
-                || c == &','
-                || c == &';'
-                || c == &'='
-                || c == &':'
-                || c == &'+'
-                || c == &'-'
-                || c == &'*'
-                || c == &'/'
-            {
+            if matches!(
+                c,
+                '(' | ')' | '{' | '}' | '[' | ']' | '<' | '>' | ',' | ';' | '=' | ':' | '+' | '-'
+                    | '*' | '/' | '%' | '^' | '!' | '&' | '|' | '@' | '.' | '?'
+            ) {
                 break;
             }
-            if c.is_ascii_alphanumeric() || c == &'_' || c == &'.' {
+            if c.is_ascii_alphanumeric() || c == &'_' {
                 identifier.push(self.next().unwrap());
             }
-            //if c is a symbol, throw an error
+            //if c is a symbol, report it and skip over it
             else if c.is_ascii_punctuation() {
-                if c == &'"' {
-                    throw!("Unterminated String", line);
-                }
-                throw!(format!("Invalid character in identifier: '{}'", c), line);
+                let start = self.pos;
+                let c = self.next().unwrap();
+                let span = self.span(start);
+                self.error(error::invalid_identifier_char(c, span));
             } else {
                 break;
             }
@@ -113,9 +251,15 @@ impl Lexer {
         identifier
     }
     fn read_char(&mut self) -> char {
-        let c = self.next().unwrap();
+        let start = self.pos - 1;
+        let Some(c) = self.next() else {
+            let span = self.span(start);
+            self.error(error::invalid_char_literal(span));
+            return '\0';
+        };
         if c == '\\' {
-            let c = self.next().unwrap();
+            let escape = self.pos - 1;
+            let c = self.next().unwrap_or('\0');
             let f = match c {
                 'n' => '\n',
                 't' => '\t',
@@ -125,15 +269,20 @@ impl Lexer {
                 '\'' => '\'',
                 '\\' => '\\',
                 _ => {
-                    throw!("Invalid escape character: {}", self.line);
+                    let span = self.span(escape);
+                    self.error(error::invalid_escape(c, span));
+                    c
                 }
             };
-            self.next();
+            if self.next() != Some('\'') {
+                let span = self.span(start);
+                self.error(error::invalid_char_literal(span));
+            }
             f
         } else {
-            let f = self.next();
-            if f.is_none() || f.unwrap() != '\'' {
-                throw!(format!("Invalid character literal"), self.line);
+            if self.next() != Some('\'') {
+                let span = self.span(start);
+                self.error(error::invalid_char_literal(span));
             }
             c
         }
@@ -141,21 +290,51 @@ impl Lexer {
     pub fn lex(&mut self) {
         while self.peek().is_some() {
             use TokenType::*;
-            let c = self.next().unwrap();
+            let start = self.pos;
+            let count = self.tokens.len();
+            let Some(c) = self.next() else { continue };
             match c {
                 '\n' => self.line += 1,
                 '(' => self.tokens.push(Token::new(LeftParen, self.line)),
                 ')' => self.tokens.push(Token::new(RightParen, self.line)),
                 '{' => self.tokens.push(Token::new(LeftBrace, self.line)),
                 '}' => self.tokens.push(Token::new(RightBrace, self.line)),
+                '[' => self.tokens.push(Token::new(LeftBracket, self.line)),
+                ']' => self.tokens.push(Token::new(RightBracket, self.line)),
                 ',' => self.tokens.push(Token::new(Comma, self.line)),
-                '.' => self.tokens.push(Token::new(Dot, self.line)),
+                ';' => self.tokens.push(Token::new(Semicolon, self.line)),
+                '?' => self.tokens.push(Token::new(Question, self.line)),
+                '.' => {
+                    let token_type = match self.peek() {
+                        Some(&'.') => {
+                            self.next();
+                            DotDot
+                        }
+                        _ => Dot,
+                    };
+                    self.tokens.push(Token::new(token_type, self.line));
+                }
+                ':' => {
+                    let token_type = match self.peek() {
+                        Some(&':') => {
+                            self.next();
+                            DoubleColon
+                        }
+                        _ => Colon,
+                    };
+                    self.tokens.push(Token::new(token_type, self.line));
+                }
+                '@' => self.tokens.push(Token::new(At, self.line)),
                 '-' => {
                     let token_type = match self.peek() {
                         Some(&'=') => {
                             self.next();
                             Operator("SubEq".to_string())
                         }
+                        Some(&'>') => {
+                            self.next();
+                            Arrow
+                        }
                         _ => Operator("Sub".to_string()),
                     };
                     self.tokens.push(Token::new(token_type, self.line));
@@ -181,6 +360,13 @@ impl Lexer {
                     self.tokens.push(Token::new(token_type, self.line));
                 }
                 '/' => {
+                    //a comment runs to the end of the line
+                    if self.peek() == Some(&'/') {
+                        while self.peek().is_some_and(|c| *c != '\n') {
+                            self.next();
+                        }
+                        continue;
+                    }
                     let token_type = match self.peek() {
                         Some(&'=') => {
                             self.next();
@@ -216,6 +402,10 @@ impl Lexer {
                             self.next();
                             Operator("AndEq".to_string())
                         }
+                        Some(&'&') => {
+                            self.next();
+                            Operator("AndAnd".to_string())
+                        }
                         _ => Operator("And".to_string()),
                     };
                     self.tokens.push(Token::new(token_type, self.line));
@@ -226,6 +416,10 @@ impl Lexer {
                             self.next();
                             Operator("OrEq".to_string())
                         }
+                        Some(&'|') => {
+                            self.next();
+                            Operator("OrOr".to_string())
+                        }
                         _ => Operator("Or".to_string()),
                     };
                     self.tokens.push(Token::new(token_type, self.line));
@@ -246,6 +440,10 @@ impl Lexer {
                             self.next();
                             Operator("EqEq".to_string())
                         }
+                        Some(&'>') => {
+                            self.next();
+                            FatArrow
+                        }
                         _ => Operator("Eq".to_string()),
                     };
                     self.tokens.push(Token::new(token_type, self.line));
@@ -270,27 +468,53 @@ impl Lexer {
                     };
                     self.tokens.push(Token::new(token_type, self.line));
                 }
-                '"' => {
-                    let string = self.read_string();
+                '"' if self.text[self.pos..].starts_with("\"\"") => {
+                    let string = self.read_block(start);
                     self.tokens.push(Token::new(Str(string), self.line));
                 }
+                '"' => self.read_string(start),
                 ' ' | '\r' | '\t' => {}
-                '1'..='9' => {
-                    let number = self.read_number(c);
-                    self.tokens.push(Token::new(Number(number), self.line));
+                '0'..='9' => {
+                    let mut number = self.read_number(c);
+                    let token_type = match self.read_float(&mut number) {
+                        true => Float(number),
+                        false => Number(number),
+                    };
+                    self.tokens.push(Token::new(token_type, self.line));
                 }
-                'a'..='z' | 'A'..='Z' => {
+                'r' if self.peek() == Some(&'"') => {
+                    self.next();
+                    let string = self.read_raw(start);
+                    self.tokens.push(Token::new(Str(string), self.line));
+                }
+                'a'..='z' | 'A'..='Z' | '_' => {
                     let identifier = self.read_identifier(c);
                     match &*identifier {
                         "if" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "else" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "while" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "for" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "in" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "break" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "continue" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "loop" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "return" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "def" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "let" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "const" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "static" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "import" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "from" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "pub" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "enum" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "match" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "struct" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "trait" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "impl" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "dyn" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "unsafe" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "null" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
+                        "as" => self.tokens.push(Token::new(Keyword(identifier), self.line)),
                         "True" => self.tokens.push(Token::new(True, self.line)),
                         "true" => self.tokens.push(Token::new(True, self.line)),
                         "False" => self.tokens.push(Token::new(False, self.line)),
@@ -307,97 +531,91 @@ impl Lexer {
                         .push(Token::new(Character(c.to_string()), self.line));
                 }
                 _ => {
-                    throw!(format!("Unexpected character: {}", c), self.line);
+                    let span = self.span(start);
+                    self.error(error::unexpected_char(c, span));
                 }
             }
+            //whatever was pushed covers the characters consumed in this round
+            if let Some(token) = self.tokens.get_mut(count) {
+                token.span = Span::new(self.file, start, self.pos);
+            }
         }
-        self.tokens.push(Token::new(TokenType::Eof, self.line));
-        check_for_unclosed_brackets(&self.tokens);
+        let mut eof = Token::new(TokenType::Eof, self.line);
+        eof.span = self.span(self.pos);
+        self.tokens.push(eof);
+        let unclosed = check_for_unclosed_brackets(&self.tokens);
+        self.diagnostics.extend(unclosed);
     }
 }
 
-//fn check_for_unclosed_brackets(tokens: &Vec<Token>) {
-//this function checks for unclosed brackets, braces, and parentheses, error for every single unclosed bracket
-//use a hashmap to store the line number of the opening bracket
-//use another hashmap to store the line number of the closing bracket
-//if the closing bracket is empty, throw an error for the opening bracket
-//if the opening bracket is empty, throw an error for the closing bracketwuse std::collections::HashMap;
-fn check_for_unclosed_brackets(tokens: &Vec<Token>) {
-    let mut left_paren_count = 0;
-    let mut right_paren_count = 0;
-    let mut left_paren_lines = Vec::new();
-    let mut right_paren_lines = Vec::new();
-
-    let mut left_brace_count = 0;
-    let mut right_brace_count = 0;
-    let mut left_brace_lines = Vec::new();
-    let mut right_brace_lines = Vec::new();
-
-    let mut err: Result<(), ()> = Ok(());
-
-    for token in tokens {
-        match token.token_type {
-            TokenType::LeftParen => {
-                left_paren_count += 1;
-                left_paren_lines.push(token.line);
-            }
-            TokenType::RightParen => {
-                right_paren_count += 1;
-                right_paren_lines.push(token.line);
-            }
-            TokenType::LeftBrace => {
-                left_brace_count += 1;
-                left_brace_lines.push(token.line);
-            }
-            TokenType::RightBrace => {
-                right_brace_count += 1;
-                right_brace_lines.push(token.line);
-            }
-            _ => {}
-        }
+//the lines of a `"""` string without the line break after the opening quotes
+//and the indentation the lines share. The line of the closing quotes is left
+//out when there is nothing else on it
+fn dedent(text: &str) -> String {
+    let text = text.strip_prefix('\n').or_else(|| text.strip_prefix("\r\n")).unwrap_or(text);
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if lines.len() > 1 && lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
     }
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines.iter().map(|line| line.get(indent..).unwrap_or("")).collect();
+    lines.join("\n")
+}
 
-    // Check for unclosed parentheses
-    if left_paren_count != right_paren_count {
-        let unclosed_count = left_paren_count - right_paren_count;
-        if unclosed_count > 0 {
-            for _ in 0..unclosed_count {
-                if let Some(line) = left_paren_lines.pop() {
-                    throw!(format!("Unclosed '(' at line {}", line), line, false);
-                    err = Err(());
-                }
-            }
-        } else {
-            for _ in 0..-unclosed_count {
-                if let Some(line) = right_paren_lines.pop() {
-                    throw!(format!("Unclosed ')' at line {}", line), line, false);
-                    err = Err(());
-                }
-            }
+//match every bracket and brace against the nearest open one, reporting the
+//closers with no opener, the closers of the wrong kind and the openers that
+//are never closed
+fn check_for_unclosed_brackets(tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut open: Vec<(char, Span)> = Vec::new();
+    for token in tokens {
+        let (c, opening) = match token.token_type {
+            TokenType::LeftParen => ('(', true),
+            TokenType::LeftBrace => ('{', true),
+            TokenType::LeftBracket => ('[', true),
+            TokenType::RightParen => (')', false),
+            TokenType::RightBrace => ('}', false),
+            TokenType::RightBracket => (']', false),
+            _ => continue,
+        };
+        if opening {
+            open.push((c, token.span));
+            continue;
         }
-    }
-
-    // Check for unclosed braces
-    if left_brace_count != right_brace_count {
-        let unclosed_count = left_brace_count - right_brace_count;
-        if unclosed_count > 0 {
-            for _ in 0..unclosed_count {
-                if let Some(line) = left_brace_lines.pop() {
-                    throw!(format!("Unclosed '{{' at line {}", line), line, false);
-                    err = Err(());
-                }
+        let expected = match c {
+            ')' => '(',
+            ']' => '[',
+            _ => '{',
+        };
+        match open.last() {
+            Some(&(o, _)) if o == expected => {
+                open.pop();
             }
-        } else {
-            for _ in 0..-unclosed_count {
-                if let Some(line) = right_brace_lines.pop() {
-                    throw!(format!("Unclosed '}}' at line {}", line), line, false);
-                    err = Err(());
+            Some(&(o, span)) => {
+                //a missing closer is more likely than a stray one if the
+                //matching opener is further down the stack
+                if open.iter().any(|&(o, _)| o == expected) {
+                    diagnostics.push(error::mismatched_delimiter(o, span, c, token.span));
+                    open.pop();
+                    if let Some(&(o, _)) = open.last() {
+                        if o == expected {
+                            open.pop();
+                        }
+                    }
+                } else {
+                    diagnostics.push(error::unmatched_delimiter(c, token.span));
                 }
             }
+            None => diagnostics.push(error::unmatched_delimiter(c, token.span)),
         }
     }
-
-    if err == Err(()) {
-        process::exit(0);
+    for (c, span) in open {
+        diagnostics.push(error::unclosed_delimiter(c, span));
     }
+    diagnostics
 }
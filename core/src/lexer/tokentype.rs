@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use crate::diagnostic::Span;
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[allow(non_camel_case_types, dead_code)]
 pub enum TokenType {
@@ -7,8 +8,18 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDot,
+    Semicolon,
+    Colon,
+    At,
+    Arrow,
+    FatArrow,
+    DoubleColon,
+    Question,
 
     // Literals.
     Operator(String),
@@ -17,7 +28,13 @@ pub enum TokenType {
     False,
     None,
     Str(String),
+    //around the text and `{...}` holes of a string with holes, the tokens of
+    //each hole are between a `LeftBrace` and a `RightBrace`
+    FormatStart,
+    FormatEnd,
     Number(String),
+    //a number with a fraction or an exponent, `2.5` or `1e-3`
+    Float(String),
     Character(String),
     Keyword(String),
     DataType(String),
@@ -29,6 +46,7 @@ impl TokenType {
             TokenType::Identifier(s) => Some(s),
             TokenType::Str(s) => Some(s),
             TokenType::Number(s) => Some(s),
+            TokenType::Float(s) => Some(s),
             TokenType::Character(s) => Some(s),
             TokenType::Keyword(s) => Some(s),
             TokenType::DataType(s) => Some(s),
@@ -41,10 +59,15 @@ impl TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
+    pub span: Span,
 }
 impl Token {
     pub fn new(token_type: TokenType, line: usize) -> Self {
-        Self { token_type, line }
+        Self {
+            token_type,
+            line,
+            span: Span::default(),
+        }
     }
     pub fn as_string(&self) -> String {
         match self.token_type {
@@ -52,18 +75,31 @@ impl Token {
             TokenType::RightParen => "RightParen".to_string(),
             TokenType::LeftBrace => "LeftBrace".to_string(),
             TokenType::RightBrace => "RightBrace".to_string(),
+            TokenType::LeftBracket => "LeftBracket".to_string(),
+            TokenType::RightBracket => "RightBracket".to_string(),
             TokenType::Comma => "Comma".to_string(),
             TokenType::Dot => "Dot".to_string(),
-            TokenType::Operator(ref s) => format!("{}", s),
-            TokenType::Identifier(ref s) => format!("{}", s),
+            TokenType::DotDot => "DotDot".to_string(),
+            TokenType::Semicolon => "Semicolon".to_string(),
+            TokenType::Colon => "Colon".to_string(),
+            TokenType::Arrow => "Arrow".to_string(),
+            TokenType::FatArrow => "FatArrow".to_string(),
+            TokenType::DoubleColon => "DoubleColon".to_string(),
+            TokenType::Question => "Question".to_string(),
+            TokenType::At => "At".to_string(),
+            TokenType::Operator(ref s) => s.to_string(),
+            TokenType::Identifier(ref s) => s.to_string(),
             TokenType::True => "True".to_string(),
             TokenType::False => "False".to_string(),
             TokenType::None => "None".to_string(),
-            TokenType::Str(ref s) => format!("{}", s),
-            TokenType::Number(ref s) => format!("{}", s),
-            TokenType::Character(ref s) => format!("{}", s),
-            TokenType::Keyword(ref s) => format!("{}", s),
-            TokenType::DataType(ref s) => format!("{}", s),
+            TokenType::Str(ref s) => s.to_string(),
+            TokenType::FormatStart => "FormatStart".to_string(),
+            TokenType::FormatEnd => "FormatEnd".to_string(),
+            TokenType::Number(ref s) => s.to_string(),
+            TokenType::Float(ref s) => s.to_string(),
+            TokenType::Character(ref s) => s.to_string(),
+            TokenType::Keyword(ref s) => s.to_string(),
+            TokenType::DataType(ref s) => s.to_string(),
             TokenType::Eof => "Eof".to_string(),
         }
     }
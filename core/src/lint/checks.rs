@@ -0,0 +1,89 @@
+use super::{Emission, CONSTANT_CONDITION, UNREACHABLE_CODE};
+use crate::diagnostic::Diagnostic;
+use crate::parser::{Node, NodeType, Tree};
+
+//the lints that only need the shape of the tree
+pub fn check(tree: &Tree) -> Vec<Emission> {
+    let mut emissions = Vec::new();
+    walk(&tree.nodes, &mut emissions);
+    emissions
+}
+
+fn walk(nodes: &[Node], emissions: &mut Vec<Emission>) {
+    unreachable(nodes, emissions);
+    for node in nodes {
+        if node.is(NodeType::Conditional) {
+            constant_condition(&node.children[0], "if", emissions);
+        }
+        if let Some(condition) = node.loop_condition() {
+            constant_condition(condition, node.value(), emissions);
+        }
+        walk(&node.children, emissions);
+    }
+}
+
+//statements after one that always returns never run, only the first of
+//them is reported
+fn unreachable(statements: &[Node], emissions: &mut Vec<Emission>) {
+    let Some(index) = statements.iter().position(Node::diverges) else {
+        return;
+    };
+    let Some(next) = statements.get(index + 1) else {
+        return;
+    };
+    let span = next.span.to(statements.last().unwrap().span);
+    let diagnostic = Diagnostic::warning("unreachable statement")
+        .with_label(span, "unreachable statement")
+        .with_secondary(statements[index].span, "any code following this is unreachable");
+    emissions.push(Emission::new(&UNREACHABLE_CODE, diagnostic));
+}
+
+//the condition of an `if`, or of a `while` or `for` loop
+fn constant_condition(condition: &Node, keyword: &str, emissions: &mut Vec<Emission>) {
+    if let Some(value) = constant(condition) {
+        let diagnostic = Diagnostic::warning(format!("this condition is always {}", value))
+            .with_label(condition.span, format!("always `{}`", value))
+            .with_help(match (value, keyword) {
+                (true, "if") => "remove the `if` and keep its body",
+                (true, _) => "a loop that only ends with `break` is written `loop { ... }`",
+                (false, _) => "the body never runs, remove it or fix the condition",
+            });
+        emissions.push(Emission::new(&CONSTANT_CONDITION, diagnostic));
+    }
+}
+
+//the value of a condition made only of literals
+fn constant(node: &Node) -> Option<bool> {
+    match node.node_type {
+        NodeType::Bool => Some(node.value() == "true"),
+        NodeType::UnaryExpression if node.value() == "Not" => constant(&node.children[0]).map(|b| !b),
+        NodeType::Expression => {
+            let (left, right) = (&node.children[0], &node.children[1]);
+            match node.value() {
+                "AndAnd" => Some(constant(left)? && constant(right)?),
+                "OrOr" => Some(constant(left)? || constant(right)?),
+                op => {
+                    let (l, r) = (number(left)?, number(right)?);
+                    match op {
+                        "EqEq" => Some(l == r),
+                        "NotEq" => Some(l != r),
+                        "Less" => Some(l < r),
+                        "LessEq" => Some(l <= r),
+                        "Greater" => Some(l > r),
+                        "GreaterEq" => Some(l >= r),
+                        _ => None,
+                    }
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+fn number(node: &Node) -> Option<i128> {
+    match node.node_type {
+        NodeType::Number => node.value().parse().ok(),
+        NodeType::UnaryExpression if node.value() == "Sub" => number(&node.children[0]).map(|n| -n),
+        _ => None,
+    }
+}
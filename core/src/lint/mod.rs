@@ -0,0 +1,254 @@
+mod checks;
+pub use checks::check;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::parser::{Node, NodeType, Tree};
+
+//how a lint is reported, set with @allow(name) @warn(name) @deny(name) on a
+//function or @!allow(name) ... at the top of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default: Level,
+    pub description: &'static str,
+}
+
+//a lint that fired, its level is only decided once every attribute is known
+#[derive(Debug, Clone)]
+pub struct Emission {
+    pub lint: &'static Lint,
+    pub diagnostic: Diagnostic,
+}
+
+pub static UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    default: Level::Warn,
+    description: "variables and parameters that are never read",
+};
+pub static UNUSED_FUNCTIONS: Lint = Lint {
+    name: "unused_functions",
+    default: Level::Warn,
+    description: "functions that are never called",
+};
+pub static UNREACHABLE_CODE: Lint = Lint {
+    name: "unreachable_code",
+    default: Level::Warn,
+    description: "statements that can never run because of an earlier `return`, `break` or `continue`",
+};
+pub static CONSTANT_CONDITION: Lint = Lint {
+    name: "constant_condition",
+    default: Level::Warn,
+    description: "conditions that are always true or always false",
+};
+pub static INVARIANT_LOOP_CONDITION: Lint = Lint {
+    name: "invariant_loop_condition",
+    default: Level::Warn,
+    description: "loop conditions on variables that are never assigned, the loop never ends once it starts",
+};
+pub static SHADOWED_NAMES: Lint = Lint {
+    name: "shadowed_names",
+    default: Level::Warn,
+    description: "variables that hide another variable of the same function",
+};
+pub static USELESS_COMPARISONS: Lint = Lint {
+    name: "useless_comparisons",
+    default: Level::Warn,
+    description: "comparisons whose result is fixed by the range of the operand type",
+};
+pub static UNREACHABLE_PATTERNS: Lint = Lint {
+    name: "unreachable_patterns",
+    default: Level::Warn,
+    description: "`match` arms that can never match because of the arms before them",
+};
+pub static UNUSED_UNSAFE: Lint = Lint {
+    name: "unused_unsafe",
+    default: Level::Warn,
+    description: "`unsafe` blocks with nothing in them that needs one",
+};
+pub static UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default: Level::Warn,
+    description: "lint names in attributes that do not exist",
+};
+
+pub static LINTS: &[&Lint] = &[
+    &UNUSED_VARIABLES,
+    &UNUSED_FUNCTIONS,
+    &UNREACHABLE_CODE,
+    &CONSTANT_CONDITION,
+    &INVARIANT_LOOP_CONDITION,
+    &SHADOWED_NAMES,
+    &USELESS_COMPARISONS,
+    &UNREACHABLE_PATTERNS,
+    &UNUSED_UNSAFE,
+    &UNKNOWN_LINTS,
+];
+
+pub fn find(name: &str) -> Option<&'static Lint> {
+    LINTS.iter().copied().find(|l| l.name == name)
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Level> {
+        match s {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+}
+
+impl Emission {
+    pub fn new(lint: &'static Lint, diagnostic: Diagnostic) -> Self {
+        Self { lint, diagnostic }
+    }
+}
+
+//the attributes in effect over a range of source code
+struct LevelScope {
+    span: Span,
+    whole_file: bool,
+    levels: Vec<(&'static str, Level, Span)>,
+}
+
+//turn the fired lints into diagnostics using the attributes of every file
+pub fn apply(trees: &[&Tree], mut emissions: Vec<Emission>, deny_warnings: bool) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut scopes = Vec::new();
+    for tree in trees {
+        for node in &tree.nodes {
+            if node.is(NodeType::Attribute) {
+                let levels = read_attribute(node, &mut diagnostics, &mut emissions);
+                scopes.push(LevelScope {
+                    span: node.span,
+                    whole_file: true,
+                    levels,
+                });
+            }
+        }
+    }
+    for tree in trees {
+        collect_scopes(&tree.nodes, &mut scopes, &mut diagnostics, &mut emissions);
+    }
+
+    for emission in emissions {
+        let Some(span) = emission.diagnostic.primary_span() else {
+            continue;
+        };
+        let mut level = emission.lint.default;
+        let mut source = None;
+        //file attributes come first so the ones on functions override them
+        for scope in &scopes {
+            let covers = if scope.whole_file {
+                scope.span.file == span.file
+            } else {
+                scope.span.file == span.file && scope.span.start <= span.start && span.end <= scope.span.end
+            };
+            if !covers {
+                continue;
+            }
+            for &(name, l, attribute) in &scope.levels {
+                if name == emission.lint.name {
+                    level = l;
+                    source = Some(attribute);
+                }
+            }
+        }
+        let mut diagnostic = emission.diagnostic;
+        diagnostic.lint = Some(emission.lint.name);
+        let promoted = deny_warnings && level == Level::Warn;
+        diagnostic.severity = match level {
+            Level::Allow => continue,
+            Level::Warn if !promoted => Severity::Warning,
+            _ => Severity::Error,
+        };
+        diagnostic = match source {
+            Some(attribute) => diagnostic.with_secondary(attribute, "the lint level is set here"),
+            None => diagnostic.with_note(format!(
+                "`@{}({})` on by default",
+                emission.lint.default.as_str(),
+                emission.lint.name
+            )),
+        };
+        if promoted {
+            diagnostic = diagnostic.with_note("`--deny-warnings` turns every warning into an error");
+        }
+        diagnostics.push(diagnostic);
+    }
+    diagnostics
+}
+
+fn collect_scopes(
+    nodes: &[Node],
+    scopes: &mut Vec<LevelScope>,
+    diagnostics: &mut Vec<Diagnostic>,
+    emissions: &mut Vec<Emission>,
+) {
+    for node in nodes {
+        if node.is(NodeType::Function) {
+            let mut levels = Vec::new();
+            for attribute in node.attributes() {
+                levels.extend(read_attribute(attribute, diagnostics, emissions));
+            }
+            if !levels.is_empty() {
+                let end = node.last().map_or(node.span, |n| n.span);
+                scopes.push(LevelScope {
+                    span: node.span.to(end),
+                    whole_file: false,
+                    levels,
+                });
+            }
+        }
+        collect_scopes(&node.children, scopes, diagnostics, emissions);
+    }
+}
+
+fn read_attribute(
+    attribute: &Node,
+    diagnostics: &mut Vec<Diagnostic>,
+    emissions: &mut Vec<Emission>,
+) -> Vec<(&'static str, Level, Span)> {
+    //`@native` is read by the parser and the code generator
+    if attribute.value() == "native" {
+        return Vec::new();
+    }
+    let Some(level) = Level::parse(attribute.value()) else {
+        diagnostics.push(
+            Diagnostic::error(E0400, format!("unknown attribute `{}`", attribute.value()))
+                .with_label(attribute.span, "not a known attribute")
+                .with_help("the known attributes are `allow`, `warn`, `deny` and `native`"),
+        );
+        return Vec::new();
+    };
+    let mut levels = Vec::new();
+    for name in &attribute.children {
+        match find(name.value()) {
+            Some(lint) => levels.push((lint.name, level, attribute.span)),
+            None => emissions.push(Emission::new(
+                &UNKNOWN_LINTS,
+                Diagnostic::warning(format!("unknown lint `{}`", name.value()))
+                    .with_label(name.span, "no lint with this name")
+                    .with_help(format!(
+                        "the known lints are {}",
+                        LINTS.iter().map(|l| format!("`{}`", l.name)).collect::<Vec<_>>().join(", ")
+                    )),
+            )),
+        }
+    }
+    levels
+}
@@ -1,24 +1,1380 @@
-#[macro_use]
-mod lexer;
 mod compiler;
+mod diagnostic;
+mod lexer;
+mod lint;
+mod module;
 mod parser;
-use std::{arch::x86_64, fs};
+mod query;
+mod resolver;
+mod typeck;
+use compiler::asm::Program;
+use compiler::codegen::Codegen;
+use compiler::vm::{RuntimeError, Vm};
+use diagnostic::{Diagnostic, ErrorFormat, Severity, SourceMap, Span};
+use module::{ModuleGraph, Package};
+use parser::Tree;
+use query::Cache;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, process};
+
+struct Options {
+    path: String,
+    error_format: ErrorFormat,
+    deny_warnings: bool,
+    dry_run: bool,
+    packages: Vec<Package>,
+    //where results are kept between builds, `None` compiles from scratch
+    incremental: Option<PathBuf>,
+    //leave out the bounds checks of indexing
+    release: bool,
+}
+
+fn usage() -> ! {
+    println!("usage: crane [--error-format=human|json] [--deny-warnings] [--extern=name=file]...");
+    println!("             [--incremental[=dir]] [--release] [file]");
+    println!("       crane check [options] [file]");
+    println!("       crane run [options] [file]");
+    println!("       crane fix [--dry-run] [file]");
+    println!("       crane explain <code|lint>");
+    process::exit(1);
+}
+
+//print the long form explanation of an error code, or what a lint is about
+fn explain(code: Option<&String>) {
+    let Some(code) = code else { usage() };
+    if let Some(lint) = lint::find(code) {
+        println!("{}: {}\n\nOn by default as `@{}({})`.", lint.name, lint.description, lint.default.as_str(), lint.name);
+        return;
+    }
+    match diagnostic::codes::lookup(code) {
+        Some(entry) => println!("{}: {}\n\n{}", entry.code, entry.title, entry.explanation.trim_end()),
+        None => {
+            println!("error: `{}` is not a known error code or lint", code);
+            process::exit(1);
+        }
+    }
+}
+
+//`fix` takes the same options as a build, plus --dry-run
+fn parse_args(args: &[String], fix: bool) -> Options {
+    let mut options = Options {
+        path: "main.crane".to_string(),
+        error_format: ErrorFormat::Human,
+        deny_warnings: false,
+        dry_run: false,
+        packages: Vec::new(),
+        incremental: None,
+        release: false,
+    };
+    let mut incremental = None;
+    for arg in args.iter().cloned() {
+        if let Some(format) = arg.strip_prefix("--error-format=") {
+            match ErrorFormat::parse(format) {
+                Some(format) => options.error_format = format,
+                None => {
+                    println!("error: unknown error format `{}`", format);
+                    usage();
+                }
+            }
+        } else if arg == "--deny-warnings" {
+            options.deny_warnings = true;
+        } else if let Some(package) = arg.strip_prefix("--extern=") {
+            let Some((name, entry)) = package.split_once('=') else {
+                println!("error: expected `--extern=name=file`, found `{}`", arg);
+                usage();
+            };
+            options.packages.push(Package {
+                name: name.to_string(),
+                entry: PathBuf::from(entry),
+            });
+        } else if arg == "--release" {
+            options.release = true;
+        } else if arg == "--incremental" {
+            incremental = Some(None);
+        } else if let Some(dir) = arg.strip_prefix("--incremental=") {
+            incremental = Some(Some(PathBuf::from(dir)));
+        } else if fix && arg == "--dry-run" {
+            options.dry_run = true;
+        } else if arg.starts_with("--") {
+            println!("error: unknown option `{}`", arg);
+            usage();
+        } else {
+            options.path = arg;
+        }
+    }
+    //the cache goes in target/ next to the root file unless told otherwise
+    options.incremental = incremental.map(|dir| {
+        dir.unwrap_or_else(|| {
+            let root = Path::new(&options.path).parent().unwrap_or(Path::new(""));
+            root.join("target").join("incremental")
+        })
+    });
+    options
+}
+
+//load the root file and everything it imports, then run every phase until
+//one of them reports an error. The program is only generated without errors
+fn compile(
+    sources: &mut SourceMap,
+    options: &Options,
+    read: &mut dyn FnMut(&Path) -> io::Result<String>,
+) -> (Vec<Diagnostic>, ModuleGraph, Option<Program>) {
+    let path = Path::new(&options.path);
+    let mut graph = match ModuleGraph::load(sources, path, &options.packages, read) {
+        Ok(graph) => graph,
+        Err(e) => {
+            let diagnostic = Diagnostic::new(Severity::Error, format!("could not read {}: {}", path.display(), e));
+            return (vec![diagnostic], ModuleGraph::default(), None);
+        }
+    };
+    let diagnostics = std::mem::take(&mut graph.diagnostics);
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return (diagnostics, graph, None);
+    }
+    let mut cache = options.incremental.as_deref().map(Cache::load);
+    if let Some(cache) = cache.as_mut() {
+        cache.begin(&graph);
+        cache.release = options.release;
+    }
+    let mut resolver = resolver::Resolver::new();
+    resolver.resolve(&graph);
+    let mut checker = typeck::TypeChecker::new(&resolver.resolution);
+    checker.cache = cache.as_mut();
+    for &module in &graph.order {
+        let module = &mut graph.modules[module];
+        checker.check(&module.name, &mut module.tree);
+    }
+    let mut emissions = resolver.lints;
+    emissions.append(&mut checker.lints);
+    for module in &graph.modules {
+        emissions.extend(lint::check(&module.tree));
+    }
+    let mut diagnostics = diagnostics;
+    diagnostics.append(&mut resolver.diagnostics);
+    diagnostics.append(&mut checker.diagnostics);
+    let trees: Vec<&Tree> = graph.modules.iter().map(|m| &m.tree).collect();
+    diagnostics.extend(lint::apply(&trees, emissions, options.deny_warnings));
+    let program = if diagnostics.iter().any(Diagnostic::is_error) {
+        None
+    } else {
+        let mut codegen = Codegen::new(
+            &resolver.resolution,
+            &checker.functions,
+            &checker.enums,
+            &checker.structs,
+            &checker.traits,
+            &checker.instances,
+            &checker.layouts,
+        );
+        codegen.cache = checker.cache.take();
+        codegen.checks = !options.release;
+        codegen.constants = std::mem::take(&mut checker.constants);
+        codegen.generate(&graph);
+        Some(codegen.program)
+    };
+    if let Some(cache) = &cache {
+        if let Err(e) = cache.save() {
+            diagnostics.push(Diagnostic::warning(format!("could not write the incremental cache: {}", e)));
+        }
+    }
+    (diagnostics, graph, program)
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+}
+
+//a file `fix` changed, with what it was and what it is now
+type Edited = (PathBuf, String, String);
+
+//apply every machine applicable suggestion in every module, fixing one error
+//can let a later phase run and suggest more so this repeats until nothing
+//changes. While there are errors only theirs are applied, a warning about
+//code that does not compile can stop being true once it does. Gives how many
+//suggestions were applied and each edited file before and after, or why the
+//root file could not be read
+fn fixes(
+    options: &Options,
+    read: &mut dyn FnMut(&Path) -> io::Result<String>,
+) -> Result<(usize, Vec<Edited>), Vec<Diagnostic>> {
+    let mut originals: HashMap<PathBuf, String> = HashMap::new();
+    let mut edited: HashMap<PathBuf, String> = HashMap::new();
+    let mut applied = 0;
+    //a bad suggestion could keep rewriting the files forever
+    for _ in 0..10 {
+        let mut sources = SourceMap::new();
+        let mut read_edited = |path: &Path| match edited.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => read(path),
+        };
+        let (mut diagnostics, graph, _) = compile(&mut sources, options, &mut read_edited);
+        if graph.modules.is_empty() {
+            return Err(diagnostics);
+        }
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            diagnostics.retain(Diagnostic::is_error);
+        }
+        let mut changed = false;
+        //the standard library and the packages are not the user's to edit
+        for module in graph.modules.iter().filter(|m| !m.std && m.package.is_none()) {
+            let suggestions = diagnostic::fix::applicable(&diagnostics, module.file);
+            if suggestions.is_empty() {
+                continue;
+            }
+            let source = sources.source(module.file);
+            originals.entry(module.path.clone()).or_insert_with(|| source.to_string());
+            edited.insert(module.path.clone(), diagnostic::fix::apply(source, &suggestions));
+            applied += suggestions.len();
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut files: Vec<Edited> = edited
+        .into_iter()
+        .map(|(path, source)| {
+            let original = originals.remove(&path).unwrap();
+            (path, original, source)
+        })
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok((applied, files))
+}
 
-fn double(x: i32) -> i32 {
-    x * 2
+fn fix(options: &Options) {
+    let (applied, files) = match fixes(options, &mut read) {
+        Ok(fixes) => fixes,
+        Err(diagnostics) => {
+            diagnostic::emit(&SourceMap::new(), &diagnostics, options.error_format);
+            process::exit(1);
+        }
+    };
+    if applied == 0 {
+        println!("nothing to fix in {}", options.path);
+        return;
+    }
+    for (path, original, edited) in &files {
+        let name = path.display().to_string();
+        if options.dry_run {
+            print!("{}", diagnostic::fix::diff(&name, original, edited));
+        } else if let Err(e) = fs::write(path, edited) {
+            println!("error: could not write {}: {}", name, e);
+            process::exit(1);
+        }
+    }
+    if !options.dry_run {
+        println!(
+            "fixed {} {} in {} {}",
+            applied,
+            if applied == 1 { "problem" } else { "problems" },
+            files.len(),
+            if files.len() == 1 { "file" } else { "files" }
+        );
+    }
 }
 
-fn println<T: std::fmt::Display>(x: T) {
-    println!("{}", x);
+//compile and run the program, a runtime error is reported like a compile
+//error with the calls that led to it
+fn run(options: &Options) {
+    let mut sources = SourceMap::new();
+    let (diagnostics, _, program) = compile(&mut sources, options, &mut read);
+    if diagnostic::emit(&sources, &diagnostics, options.error_format) {
+        process::exit(1);
+    }
+    let program = program.unwrap();
+    let (mut stdout, stdin) = (io::stdout(), io::stdin());
+    let mut input = stdin.lock();
+    let mut vm = Vm::new(&program, &mut stdout, &mut input);
+    if let Err(error) = vm.run(0) {
+        diagnostic::emit(&sources, &[runtime_error(error)], options.error_format);
+        process::exit(101);
+    }
+}
+
+//how many call sites a runtime error shows, a stack overflow has thousands
+const TRACE_LIMIT: usize = 8;
+
+fn runtime_error(error: RuntimeError) -> Diagnostic {
+    let mut diagnostic = Diagnostic::new(Severity::Error, format!("the program panicked: {}", error.message))
+        .with_label(error.span, "panicked here");
+    //calls made by the entry point have no span in the source, and a call
+    //made again and again by a recursion is shown once with how many times
+    let mut sites: Vec<(Span, usize)> = Vec::new();
+    for span in error.trace.into_iter().filter(|&s| s != Span::default()) {
+        match sites.iter_mut().find(|(site, _)| *site == span) {
+            Some((_, count)) => *count += 1,
+            None => sites.push((span, 1)),
+        }
+    }
+    let hidden: usize = sites.iter().skip(TRACE_LIMIT).map(|&(_, count)| count).sum();
+    for &(span, count) in sites.iter().take(TRACE_LIMIT) {
+        diagnostic = match count {
+            1 => diagnostic.with_secondary(span, "called from here"),
+            _ => diagnostic.with_secondary(span, format!("called {} times from here", count)),
+        };
+    }
+    if hidden > 0 {
+        diagnostic = diagnostic.with_note(format!(
+            "{} more {} left out",
+            hidden,
+            if hidden == 1 { "call is" } else { "calls are" }
+        ));
+    }
+    diagnostic
 }
 
 fn main() {
-    let source = fs::read_to_string("main.crane").unwrap();
-    let source: &'static str = Box::leak(source.into_boxed_str());
-    let mut lexer = lexer::Lexer::new(source);
-    lexer.lex();
-    let mut parser = parser::Parser::new(lexer.tokens);
-    parser.parse();
-    println!("{:#?}", parser.tree);
-}
-fn drop<T>(_: T) {}
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "explain") {
+        explain(args.get(1));
+        return;
+    }
+    if args.first().is_some_and(|a| a == "fix") {
+        fix(&parse_args(&args[1..], true));
+        return;
+    }
+    if args.first().is_some_and(|a| a == "run") {
+        run(&parse_args(&args[1..], false));
+        return;
+    }
+    //`check` only reports the diagnostics
+    let check = args.first().is_some_and(|a| a == "check");
+    let options = parse_args(if check { &args[1..] } else { &args }, false);
+    let mut sources = SourceMap::new();
+    let (diagnostics, graph, _) = compile(&mut sources, &options, &mut read);
+    if diagnostic::emit(&sources, &diagnostics, options.error_format) {
+        process::exit(1);
+    }
+    if !check && options.error_format == ErrorFormat::Human {
+        println!("{:#?}", graph.root().tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compiler::types::CraneType;
+
+    const INTEGERS: [&str; 6] = ["u8", "u16", "i32", "u32", "i64", "u64"];
+
+    //compile a program given as text, with its diagnostics
+    fn build(source: &str) -> (Vec<Diagnostic>, Option<Program>) {
+        let options = parse_args(&[], false);
+        let mut sources = SourceMap::new();
+        let (diagnostics, _, program) = compile(&mut sources, &options, &mut |_: &Path| Ok(source.to_string()));
+        (diagnostics, program)
+    }
+
+    //compile a program of several files from `main.crane`, each path relative to it
+    fn build_files(files: &[(&str, &str)]) -> (Vec<Diagnostic>, Option<Program>) {
+        let options = parse_args(&[], false);
+        let mut sources = SourceMap::new();
+        let mut read = |path: &Path| match files.iter().find(|(name, _)| Path::new(name) == path) {
+            Some((_, text)) => Ok(text.to_string()),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        let (diagnostics, _, program) = compile(&mut sources, &options, &mut read);
+        (diagnostics, program)
+    }
+
+    //build a program that has to have no errors, run it and give the lines it printed
+    fn run(source: &str) -> Vec<String> {
+        run_files(&[("main.crane", source)])
+    }
+
+    fn run_files(files: &[(&str, &str)]) -> Vec<String> {
+        let (diagnostics, program) = build_files(files);
+        assert_eq!(errors(&diagnostics), Vec::<&str>::new());
+        let mut out = Vec::new();
+        Vm::new(&program.unwrap(), &mut out, &mut io::empty()).run(0).unwrap();
+        String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+    }
+
+    fn errors(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter(|d| d.is_error()).filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    //the lints a program fires, sorted, each with whether it is an error
+    fn lints(source: &str, args: &[&str]) -> Vec<(&'static str, bool)> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let options = parse_args(&args, false);
+        let mut sources = SourceMap::new();
+        let (diagnostics, _, _) = compile(&mut sources, &options, &mut |_: &Path| Ok(source.to_string()));
+        let mut lints: Vec<_> = diagnostics.iter().filter_map(|d| Some((d.lint?, d.is_error()))).collect();
+        lints.sort();
+        lints
+    }
+
+    //the values at the edges of a type and a few in between
+    fn samples(ty: &CraneType) -> Vec<i128> {
+        let (min, max) = ty.range().unwrap();
+        let mut values = vec![min, max, 0, 1, 300, -1, min + 1, max - 1];
+        values.retain(|&v| v >= min && v <= max);
+        values
+    }
+
+    //what `as` gives, the low bits of the value read as the type
+    fn convert(value: i128, ty: &CraneType) -> i128 {
+        let (min, _) = ty.range().unwrap();
+        let bits = ty.bits().unwrap();
+        let low = value.rem_euclid(1 << bits);
+        if min < 0 && low >= 1 << (bits - 1) {
+            low - (1 << bits)
+        } else {
+            low
+        }
+    }
+
+    #[test]
+    fn casts_between_every_pair_of_integers() {
+        let mut body = String::new();
+        let mut cases = Vec::new();
+        for from in INTEGERS {
+            let from_ty = CraneType::from_name(from).unwrap();
+            for value in samples(&from_ty) {
+                for to in INTEGERS {
+                    let expected = convert(value, &CraneType::from_name(to).unwrap());
+                    body += &format!("    let x{}: {} = {}\n", cases.len(), from, value);
+                    body += &format!("    println(x{} as {} == {})\n", cases.len(), to, expected);
+                    cases.push(format!("{} {} as {} is {}", value, from, to, expected));
+                }
+            }
+        }
+        for from in ["true", "false"] {
+            for to in INTEGERS.iter().chain(&["bool"]) {
+                let expected = match (*to, from) {
+                    ("bool", _) => from,
+                    (_, "true") => "1",
+                    _ => "0",
+                };
+                body += &format!("    println({} as {} == {})\n", from, to, expected);
+                cases.push(format!("{} as {} is {}", from, to, expected));
+            }
+        }
+        let source = format!("def main() {{\n{}}}\n", body);
+        let results = run(&source);
+        assert_eq!(results.len(), cases.len());
+        for (case, result) in cases.iter().zip(results) {
+            assert_eq!(result, "true", "{}", case);
+        }
+    }
+
+    #[test]
+    fn casts_between_integers_and_floats() {
+        let floats = [0.0, -0.0, 1.5, -1.5, 255.9, 256.0, -129.7, 1e10, -1e10, 1e20, -1e20, f64::INFINITY, f64::NEG_INFINITY, f64::NAN];
+        //a variable of a type with a value, what is printed of it as `x` and what that shows
+        let mut cases: Vec<(&str, String, String, String)> = Vec::new();
+        for from in ["f32", "f64"] {
+            for value in floats {
+                let literal = match value {
+                    _ if value.is_nan() => "0.0 / 0.0".to_string(),
+                    _ if value.is_infinite() => format!("{}1.0 / 0.0", if value < 0.0 { "-" } else { "" }),
+                    _ => format!("{:?}", value),
+                };
+                let rounded = if from == "f32" { value as f32 as f64 } else { value };
+                //to an integer towards zero and saturated, compared as a `u8` is shown as a character
+                for to in INTEGERS {
+                    let (min, max) = CraneType::from_name(to).unwrap().range().unwrap();
+                    let check = format!("x as {} == {}", to, (rounded as i128).clamp(min, max));
+                    cases.push((from, literal.clone(), check, "true".to_string()));
+                }
+                cases.push((from, literal.clone(), "x as f32".to_string(), format!("{:?}", rounded as f32)));
+                cases.push((from, literal.clone(), "x as f64".to_string(), format!("{:?}", rounded)));
+            }
+        }
+        //from an integer to the nearest float
+        for from in INTEGERS {
+            for value in samples(&CraneType::from_name(from).unwrap()) {
+                cases.push((from, value.to_string(), "x as f32".to_string(), format!("{:?}", value as f32)));
+                cases.push((from, value.to_string(), "x as f64".to_string(), format!("{:?}", value as f64)));
+            }
+        }
+        let mut body = String::new();
+        for (i, (ty, value, shown, _)) in cases.iter().enumerate() {
+            body += &format!("    let x{}: {} = {}\n", i, ty, value);
+            body += &format!("    println({})\n", shown.replace('x', &format!("x{}", i)));
+        }
+        let source = format!("def main() {{\n{}}}\n", body);
+        let results = run(&source);
+        for ((ty, value, shown, expected), result) in cases.iter().zip(&results) {
+            assert_eq!(result, expected, "{} of `{}` = {}", shown, ty, value);
+        }
+        assert_eq!(results.len(), cases.len());
+    }
+
+    #[test]
+    fn constants_evaluate_floats_as_the_vm_does() {
+        let expressions = [
+            ("f64", "1.0 / 3.0"),
+            ("f32", "1.0 / 3.0"),
+            ("f64", "0.1 + 0.2"),
+            ("f32", "0.1 + 0.2"),
+            ("f64", "1e308 * 10.0"),
+            ("f64", "-1.0 / 0.0"),
+            ("f64", "0.0 / 0.0"),
+            ("f32", "16777217 as f32"),
+            ("f32", "0.1 as f32 * 3.0"),
+            ("f64", "0.1 as f32 as f64"),
+            ("f64", "7.5 % -2.0"),
+            ("f64", "2.0 ^ 0.5"),
+            ("f64", "-(2.5 - 5.0)"),
+            ("i32", "-7.9 as i32"),
+            ("u8", "300.5 as u8 as i32 as u8"),
+            ("i64", "1e30 as i64"),
+            ("bool", "0.0 / 0.0 == 0.0 / 0.0"),
+            ("bool", "0.1 + 0.2 > 0.3"),
+        ];
+        let mut constants = String::new();
+        let mut body = String::new();
+        for (i, (ty, expression)) in expressions.iter().enumerate() {
+            constants += &format!("const C{}: {} = {}\n", i, ty, expression);
+            body += &format!("    let v{}: {} = {}\n    println(C{})\n    println(v{})\n", i, ty, expression, i, i);
+        }
+        let source = format!("{}\ndef main() {{\n{}}}\n", constants, body);
+        let results = run(&source);
+        assert_eq!(results.len(), 2 * expressions.len());
+        for (pair, (_, expression)) in results.chunks(2).zip(expressions) {
+            assert_eq!(pair[0], pair[1], "{}", expression);
+        }
+    }
+
+    #[test]
+    fn checked_wrapping_and_saturating_arithmetic_on_every_integer() {
+        let mut body = String::new();
+        let mut expected = Vec::new();
+        for name in INTEGERS {
+            let ty = CraneType::from_name(name).unwrap();
+            let (min, max) = ty.range().unwrap();
+            //shown as the widest type of the same signedness, a `u8` is shown as a character
+            let shown = if ty.is_signed() { "i64" } else { "u64" };
+            let values: Vec<i128> = samples(&ty).into_iter().take(5).collect();
+            for &a in &values {
+                for &b in &values {
+                    //`u64::MAX * u64::MAX` does not fit in an i128 either, wrapping keeps its low bits
+                    let product = a.checked_mul(b).unwrap_or(i128::MAX);
+                    for (operator, exact, low) in [("add", a + b, a + b), ("sub", a - b, a - b), ("mul", product, a.wrapping_mul(b))] {
+                        body += &format!("    let a{}: {} = {}\n", expected.len(), name, a);
+                        body += &format!("    let b{}: {} = {}\n", expected.len(), name, b);
+                        let (x, y) = (format!("a{}", expected.len()), format!("b{}", expected.len()));
+                        body += &format!("    match checked_{}({}, {}) {{\n", operator, x, y);
+                        body += &format!("        Some(v) => println(v as {}),\n        None => println(\"none\"),\n    }}\n", shown);
+                        body += &format!("    println(wrapping_{}({}, {}) as {})\n", operator, x, y, shown);
+                        body += &format!("    println(saturating_{}({}, {}) as {})\n", operator, x, y, shown);
+                        let fits = exact >= min && exact <= max;
+                        expected.push(if fits { exact.to_string() } else { "none".to_string() });
+                        expected.push(convert(low, &ty).to_string());
+                        expected.push(exact.clamp(min, max).to_string());
+                    }
+                }
+            }
+        }
+        let source = format!("def main() {{\n{}}}\n", body);
+        let results = run(&source);
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn range_and_c_style_loops_stop_at_the_edges_of_every_integer() {
+        let mut body = String::new();
+        let mut expected = Vec::new();
+        for ty in INTEGERS {
+            let (min, max) = CraneType::from_name(ty).unwrap().range().unwrap();
+            for (start, end) in [(min, min + 3), (max - 3, max), ((-2).max(min), 2)] {
+                //how many times each loop goes around, the last value of the
+                //range, and the C form skipping its first time with `continue`
+                body += &format!("    {{\n        let s: {ty} = {start}\n        let e: {ty} = {end}\n");
+                body += "        let n: u64 = 0\n        let last = s\n";
+                body += "        for i in s..e {\n            n += 1\n            last = i\n        }\n";
+                body += &format!("        println(n)\n        println(last == {})\n", end - 1);
+                body += &format!("        let m: u64 = 0\n        for (let j: {ty} = s; j < e; j += 1) {{\n");
+                body += "            if (j == s) {\n                continue\n            }\n            m += 1\n        }\n";
+                body += "        println(m)\n    }\n";
+                let count = end - start;
+                expected.extend([count.to_string(), "true".to_string(), (count - 1).to_string()]);
+            }
+        }
+        let source = format!("def main() {{\n{}}}\n", body);
+        let results = run(&source);
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn for_goes_through_iterators_and_their_adaptors() {
+        let source = "from std.iter import values, map, filter, enumerate, zip
+
+def square(x: i32) -> i32 {
+    return x * x
+}
+
+def odd(x: i32) -> bool {
+    return x % 2 == 1
+}
+
+def total<I: Iterator<i32>>(values: I) -> i32 {
+    let sum = 0
+    for x in values {
+        sum += x
+    }
+    return sum
+}
+
+def main() {
+    let xs = [1, 2, 3, 4, 5]
+    for x in map(filter(values(xs), odd), square) {
+        println(x)
+    }
+    for pair in zip(enumerate(values(xs)), values([\"a\", \"b\"])) {
+        println(\"{pair.0.0} {pair.0.1} {pair.1}\")
+    }
+    println(total(values(xs)))
+}
+";
+        let results = run(source);
+        assert_eq!(results, ["1", "9", "25", "0 1 a", "1 2 b", "15"]);
+    }
+
+    #[test]
+    fn a_runtime_error_folds_a_recursion_and_caps_its_trace() {
+        //a recursion 10000 calls deep under 12 different call sites
+        let mut trace = vec![Span::new(0, 10, 20); 10000];
+        trace.extend((0..12).map(|i| Span::new(0, 30 + i, 31 + i)));
+        let error = RuntimeError { message: "stack overflow".to_string(), span: Span::new(0, 0, 5), trace };
+        let diagnostic = runtime_error(error);
+        let labels: Vec<&str> = diagnostic.labels.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(labels.len(), 1 + TRACE_LIMIT);
+        assert_eq!(labels[1], "called 10000 times from here");
+        assert_eq!(diagnostic.notes, ["5 more calls are left out"]);
+    }
+
+    #[test]
+    fn array_contains_goes_through_a_large_array() {
+        let source = "from std.array import array_new, array_push, array_contains
+
+def main() {
+    let a = array_new()
+    for i in 0..100000 {
+        array_push(a, i as i64)
+    }
+    println(array_contains(a, 99999))
+    println(array_contains(a, -1))
+}
+";
+        assert_eq!(run(source), ["true", "false"]);
+    }
+
+    #[test]
+    fn pow_takes_large_exponents_and_wraps() {
+        let source = "from std.math import pow_u8, pow_i32, pow_u64
+
+def main() {
+    println(pow_u64(1, 100000))
+    println(pow_u64(3, 0))
+    println(pow_u8(2, 9) as i32)
+    println(pow_i32(-3, 3))
+}
+";
+        assert_eq!(run(source), ["1", "1", "0", "-27"]);
+    }
+
+    #[test]
+    fn arrays_and_maps_of_the_standard_library_are_iterators() {
+        let source = "from std.array import array_new, array_push, array_values
+from std.map import map_new, map_insert, map_entries
+from std.iter import enumerate
+
+def main() {
+    let a = array_new()
+    array_push(a, 3)
+    array_push(a, 5)
+    for x in enumerate(array_values(a)) {
+        let (i, value) = x
+        println(i as i64 * value)
+    }
+    let m = map_new()
+    map_insert(m, 20, 2)
+    map_insert(m, -1, 7)
+    map_insert(m, 10, 1)
+    for entry in map_entries(m) {
+        let (key, value) = entry
+        println(key * value)
+    }
+}
+";
+        assert_eq!(run(source), ["0", "5", "-7", "10", "40"]);
+    }
+
+    #[test]
+    fn lints_warn_by_default() {
+        let source = "def helper() -> i32 {
+    return 1
+}
+
+def main() {
+    let unused = 3
+    let x: u8 = 4
+    if (x > 255) {
+        println(x)
+    }
+    if (true) {
+        println(x)
+    }
+    let y = 1
+    if (y == 1) {
+        let y = 2
+        println(y)
+    }
+    return
+    println(y)
+}
+";
+        assert_eq!(
+            lints(source, &[]),
+            [
+                ("constant_condition", false),
+                ("shadowed_names", false),
+                ("unreachable_code", false),
+                ("unused_functions", false),
+                ("unused_variables", false),
+                ("useless_comparisons", false),
+            ]
+        );
+        let (diagnostics, program) = build(source);
+        assert_eq!(errors(&diagnostics), Vec::<&str>::new());
+        assert!(program.is_some());
+    }
+
+    #[test]
+    fn lint_levels_come_from_attributes_files_and_the_command_line() {
+        let source = "@!allow(unused_variables)
+@!deny(unused_functions)
+
+def main() {
+    let a = 1
+    if (true) {
+        println(a)
+    }
+    noisy()
+}
+
+@allow(constant_condition)
+def quiet() {
+    if (true) {
+        println(1)
+    }
+}
+
+@deny(constant_condition)
+@warn(unused_variables, no_such_lint)
+def noisy() {
+    let b = 2
+    if (false) {
+        println(3)
+    }
+}
+";
+        assert_eq!(
+            lints(source, &[]),
+            [
+                ("constant_condition", false),
+                ("constant_condition", true),
+                ("unknown_lints", false),
+                ("unused_functions", true),
+                ("unused_variables", false),
+            ]
+        );
+        assert_eq!(
+            lints(source, &["--deny-warnings"]),
+            [
+                ("constant_condition", true),
+                ("constant_condition", true),
+                ("unknown_lints", true),
+                ("unused_functions", true),
+                ("unused_variables", true),
+            ]
+        );
+        let (diagnostics, program) = build(source);
+        assert!(diagnostics.iter().any(|d| d.is_error()));
+        assert!(program.is_none());
+    }
+
+    #[test]
+    fn modules_are_imported_by_path_and_by_name() {
+        let circle = "pub def area(r: i32) -> i32 {
+    return 3 * r * r
+}
+";
+        let util = "pub def double(n: i32) -> i32 {
+    return helper(n) * 2
+}
+
+def helper(n: i32) -> i32 {
+    return n
+}
+";
+        let main = "import shapes.circle
+from util import double
+
+def main() {
+    println(shapes.circle.area(2))
+    println(double(21))
+}
+";
+        let files = [("main.crane", main), ("shapes/circle.crane", circle), ("util.crane", util)];
+        assert_eq!(run_files(&files), ["12", "42"]);
+    }
+
+    #[test]
+    fn modules_keep_private_items_and_refuse_cycles() {
+        let util = "def helper(n: i32) -> i32 {
+    return n
+}
+";
+        let (diagnostics, _) = build_files(&[("main.crane", "from util import helper\n"), ("util.crane", util)]);
+        assert_eq!(errors(&diagnostics), ["E0205"]);
+        let main = "import util\n\ndef main() {\n    println(util.helper(1))\n}\n";
+        let (diagnostics, _) = build_files(&[("main.crane", main), ("util.crane", util)]);
+        assert_eq!(errors(&diagnostics), ["E0205"]);
+
+        let (diagnostics, _) = build_files(&[("main.crane", "import a\n"), ("a.crane", "import b\n"), ("b.crane", "import a\n")]);
+        assert_eq!(errors(&diagnostics), ["E0204"]);
+        let (diagnostics, _) = build_files(&[("main.crane", "import missing\n")]);
+        assert_eq!(errors(&diagnostics), ["E0203"]);
+    }
+
+    #[test]
+    fn only_the_standard_library_declares_natives() {
+        let (diagnostics, _) = build("@native\ndef str_len(s: i32) -> i32\n\ndef main() {\n    println(str_len(3))\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0206"]);
+    }
+
+    #[test]
+    fn print_takes_only_what_a_hole_can_show() {
+        for value in ["Some(1)", "P { x: 3 }", "(1, 2)", "[1, 2]"] {
+            let source = format!("struct P {{ x: i32 }}\n\ndef main() {{\n    println({})\n}}\n", value);
+            let (diagnostics, _) = build(&source);
+            assert_eq!(errors(&diagnostics), ["E0330"], "{}", value);
+        }
+    }
+
+    #[test]
+    fn fix_leaves_the_warnings_of_code_with_errors_alone() {
+        let source = "def add(a: i32, b: i32) -> i32 {\n    return a + b\n}\n\n\
+            def main() {\n    let total = 1\n    println(add(totl 2))\n}\n";
+        let options = parse_args(&[], true);
+        let (applied, files) = fixes(&options, &mut |_: &Path| Ok(source.to_string())).unwrap();
+        //the missing comma, then the misspelling it was hiding
+        assert_eq!(applied, 2);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1, source);
+        assert_eq!(files[0].2, source.replace("totl 2", "total, 2"));
+    }
+
+    #[test]
+    fn fix_adds_a_comma_only_before_what_can_be_an_item() {
+        let fix = |source: &str| {
+            let options = parse_args(&[], true);
+            let (applied, files) = fixes(&options, &mut |_: &Path| Ok(source.to_string())).unwrap();
+            (applied, files.into_iter().map(|(_, _, fixed)| fixed).collect::<Vec<_>>())
+        };
+        let source = "def main() {\n    let x = 1\n    println(x 2)\n}\n";
+        assert_eq!(fix(source), (1, vec![source.replace("x 2", "x, 2")]));
+        //the stray `@` is more likely the mistake than either missing comma
+        let source = "def main() {\n    let x = 1\n    println(x @ 2)\n}\n";
+        assert_eq!(fix(source), (0, vec![]));
+    }
+
+    #[test]
+    fn instantiations_with_growing_types_stop() {
+        //a struct that doubles in size at each call becomes too large to lay
+        //out, a tuple that doubles in size is too many types well before
+        for (value, code) in [("W { a: x, b: x }", "E0344"), ("(x, x)", "E0314")] {
+            let source = format!(
+                "struct W<T> {{ a: T, b: T }}\n\ndef deep<T>(x: T, n: u64) -> u64 {{\n    \
+                 if (n == 0) {{\n        return 0\n    }}\n    return deep({}, n - 1)\n}}\n\n\
+                 def main() {{\n    println(deep(1, 3))\n}}\n",
+                value
+            );
+            let (diagnostics, _) = build(&source);
+            assert_eq!(errors(&diagnostics), [code], "{}", value);
+        }
+    }
+
+    #[test]
+    fn arrays_too_large_to_lay_out_are_reported() {
+        for (array, first) in [("[[0; 100000]; 100000]", "a[0][0]"), ("[0; 4000000000]", "a[0]"), ("[0; 5000000000]", "a[0]")] {
+            let source = format!("def main() {{\n    let a = {}\n    println({})\n}}\n", array, first);
+            let (diagnostics, _) = build(&source);
+            assert_eq!(errors(&diagnostics).len(), 1, "{}", array);
+            assert!(matches!(errors(&diagnostics)[0], "E0326" | "E0344"), "{}", array);
+        }
+    }
+
+    #[test]
+    fn every_parameter_has_a_type_and_its_own_name() {
+        let (diagnostics, _) = build("def f(a) {\n    return a + 1\n}\n\ndef main() {\n    println(f(\"s\"))\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0345"]);
+        let (diagnostics, _) = build("def g(a: i32, a: i32) -> i32 {\n    return a\n}\n\ndef main() {\n    println(g(1, 2))\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0201"]);
+    }
+
+    #[test]
+    fn fields_and_methods_of_any_value() {
+        let source = "struct V { n: i32 }
+
+impl V {
+    def new() -> V {
+        return V { n: 3 }
+    }
+    def get(self) -> i32 {
+        return self.n
+    }
+}
+
+struct C { x: i32 }
+
+def main() {
+    println(V::new().get())
+    println(\"abc\".len())
+    let c = [C { x: 1 }, C { x: 2 }]
+    println(c[1].x)
+    c[1].x = 7
+    c[0].x += 4
+    println(c[1].x + c[0].x)
+}
+";
+        assert_eq!(run(source), ["3", "3", "2", "12"]);
+        let (diagnostics, _) = build("def main() {\n    println(\"abc\".size())\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0315"]);
+    }
+
+    #[test]
+    fn tuples_are_returned_destructured_and_indexed() {
+        let source = "def divmod(a: i32, b: i32) -> (i32, i32) {
+    return (a / b, a % b)
+}
+
+def main() {
+    println(divmod(17, 5).0)
+    println(divmod(17, 5).1)
+    let (q, r) = divmod(9, 4)
+    println(q + r)
+    let nested = (divmod(7, 2), true)
+    println(nested.0.1)
+}
+";
+        assert_eq!(run(source), ["3", "2", "3", "1"]);
+        let (diagnostics, _) = build("def main() {\n    println((1, 2).2)\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0309"]);
+    }
+
+    #[test]
+    fn match_tests_tags_payloads_guards_and_nested_patterns() {
+        let source = "enum Shape {
+    Circle(i32),
+    Rect { w: i32, h: i32 },
+    Empty,
+}
+
+struct P { x: i32, y: i32 }
+
+def describe(s: Option<Shape>) -> i32 {
+    return match s {
+        Some(Shape::Circle(0)) => 0,
+        Some(Shape::Circle(r)) if r > 10 => 1000 + r,
+        Some(Shape::Circle(r)) => r,
+        Some(Shape::Rect { w, h: 1 }) => w,
+        Some(Shape::Rect { w, h }) => w * h,
+        Some(Shape::Empty) => -1,
+        None => -2,
+    }
+}
+
+def corner(p: P) -> i32 {
+    return match p {
+        P { x: 0, y } => y,
+        P { x, y: 0 } => x * 10,
+        P { .. } => -3,
+    }
+}
+
+def main() {
+    println(describe(Some(Shape::Circle(0))))
+    println(describe(Some(Shape::Circle(12))))
+    println(describe(Some(Shape::Circle(7))))
+    println(describe(Some(Shape::Rect { w: 4, h: 1 })))
+    println(describe(Some(Shape::Rect { w: 4, h: 5 })))
+    println(describe(Some(Shape::Empty)))
+    println(describe(None))
+    println(corner(P { x: 0, y: 6 }))
+    println(corner(P { x: 2, y: 0 }))
+    println(corner(P { x: 2, y: 2 }))
+}
+";
+        assert_eq!(run(source), ["0", "1012", "7", "4", "20", "-1", "-2", "6", "20", "-3"]);
+        let (diagnostics, _) = build("def main() {\n    let n = 3\n    println(match n {\n        1 => 2,\n    })\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0305"]);
+        assert_eq!(diagnostics[0].message, "non-exhaustive match: not every `i32` is covered");
+    }
+
+    #[test]
+    fn only_widening_is_implicit() {
+        let primitives = ["u8", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "bool", "str"];
+        let values = ["1", "1", "1", "1", "1", "1", "1.5", "1.5", "true", "\"one\""];
+        for (from, value) in primitives.iter().zip(values) {
+            let from_ty = CraneType::from_name(from).unwrap();
+            for to in primitives {
+                let to_ty = CraneType::from_name(to).unwrap();
+                let source = format!("def main() {{\n    let a: {} = {}\n    let b: {} = a\n    println(b)\n}}\n", from, value, to);
+                let expected = match (from_ty == to_ty || from_ty.widens_to(&to_ty), from_ty.is_integer() && to_ty.is_integer()) {
+                    (true, _) => vec![],
+                    (false, true) => vec!["E0341"],
+                    (false, false) => vec!["E0300"],
+                };
+                let (diagnostics, _) = build(&source);
+                assert_eq!(errors(&diagnostics), expected, "{} as {}", from, to);
+                let source = format!("def f(a: {}) -> {} {{\n    return a\n}}\n\ndef main() {{\n    println(f({}))\n}}\n", from, to, value);
+                let (diagnostics, _) = build(&source);
+                assert_eq!(errors(&diagnostics), expected, "return {} as {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn traits_dispatch_statically_and_through_vtables_and_overload_operators() {
+        let source = "struct Square { side: i64 }
+struct Rect { w: i64, h: i64 }
+
+trait Shape {
+    def area(self) -> i64
+    def name(self) -> i64 {
+        return self.area() * 10
+    }
+}
+
+impl Shape for Square {
+    def area(self) -> i64 {
+        return self.side * self.side
+    }
+}
+
+impl Shape for Rect {
+    def area(self) -> i64 {
+        return self.w * self.h
+    }
+    def name(self) -> i64 {
+        return -1
+    }
+}
+
+def total<T: Shape>(shape: T) -> i64 {
+    return shape.area() + shape.name()
+}
+
+def describe(shape: dyn Shape) -> i64 {
+    return shape.area() + shape.name()
+}
+
+struct V { x: i64, y: i64 }
+
+impl Add for V {
+    def add(self, other: V) -> V {
+        return V { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+impl Eq for V {
+    def eq(self, other: V) -> bool {
+        return self.x == other.x && self.y == other.y
+    }
+}
+
+def main() {
+    println(total(Square { side: 3 }))
+    println(describe(Square { side: 3 }))
+    println(describe(Rect { w: 2, h: 5 }))
+    let shapes: [dyn Shape; 2] = [Square { side: 2 }, Rect { w: 1, h: 7 }]
+    for shape in shapes {
+        println(shape.area())
+    }
+    let v = V { x: 1, y: 2 } + V { x: 10, y: 20 }
+    println(v.x * 100 + v.y)
+    println(v == V { x: 11, y: 22 })
+    println(v != V { x: 11, y: 22 })
+}
+";
+        assert_eq!(run(source), ["99", "99", "9", "4", "7", "1122", "true", "false"]);
+    }
+
+    #[test]
+    fn closures_capture_by_value_and_by_reference_and_outlive_their_frame() {
+        let source = "def apply(f: def(i64) -> i64, value: i64) -> i64 {
+    return f(value)
+}
+
+def double(n: i64) -> i64 {
+    return n * 2
+}
+
+def adder(step: i64) -> dyn def(i64) -> i64 {
+    return def (n: i64) -> i64 { return n + step }
+}
+
+def counter() -> dyn def() -> i64 {
+    let count = 0
+    return def () -> i64 {
+        count = count + 1
+        return count
+    }
+}
+
+def main() {
+    println(apply(double, 5))
+    println(apply(def (x) { return x * x }, 7))
+    let add3 = adder(3)
+    let add10 = adder(10)
+    println(add3(1) + add10(1))
+    let next = counter()
+    next()
+    next()
+    println(next())
+    let other = counter()
+    println(other())
+    let total: i64 = 0
+    let add = def (n: i64) { total = total + n }
+    add(4)
+    add(5)
+    println(total)
+}
+";
+        assert_eq!(run(source), ["10", "49", "15", "3", "1", "9"]);
+    }
+
+    #[test]
+    fn pointers_scale_by_their_pointee_and_stop_at_null() {
+        let source = "struct P { x: i32, y: i64 }
+
+def set(target: *i64, value: i64) {
+    unsafe {
+        *target = value
+    }
+}
+
+def main() {
+    let values: [i32; 4] = [10, 20, 30, 40]
+    let first = &values[0]
+    unsafe {
+        println(*(first + 2))
+        let third = first + 3
+        println(*(third - 1))
+        *(first + 1) = 25
+    }
+    println(values[1])
+    let p = P { x: 1, y: 2 }
+    set(&p.y, 7)
+    println(p.y)
+    let wide: [i64; 2] = [5, 6]
+    let q = &wide[0]
+    unsafe {
+        println(*(q + 1))
+    }
+    let nothing: *i64 = null
+    println(nothing == null)
+    println(q == null)
+}
+";
+        assert_eq!(run(source), ["30", "30", "25", "7", "6", "true", "false"]);
+
+        let source = "def main() {\n    let p: *i64 = null\n    unsafe {\n        println(*p)\n    }\n}\n";
+        let (_, program) = build(source);
+        let error = Vm::new(&program.unwrap(), &mut Vec::new(), &mut io::empty()).run(0).unwrap_err();
+        assert_eq!(error.message, "null pointer dereference");
+        let (diagnostics, _) = build("def main() {\n    let v = 1\n    let p = &v\n    println(*p)\n}\n");
+        assert_eq!(errors(&diagnostics), ["E0327"]);
+    }
+
+    #[test]
+    fn strings_interpolate_slice_and_go_through_their_characters() {
+        let source = r#"from std.string import str_upper, str_find
+
+def main() {
+    let name = "crané"
+    let n = 3
+    println("{name} has {len(name)} bytes and {n * 2} legs")
+    println("{{braces}} stay")
+    let word = "héllo, world"
+    println(word[0..6])
+    println(word[8..len(word)])
+    let count = 0
+    for _c in word {
+        count = count + 1
+    }
+    println(count)
+    for c in "aé€" {
+        println(c)
+    }
+    let greeting = "hello, " + name
+    println(greeting)
+    println(greeting == "hello, crané")
+    println(str_upper(name))
+    println(str_find(word, "world"))
+    println(r"no {holes} \n here")
+    let block = """
+        first
+          second
+        """
+    print(block)
+}
+"#;
+        let expected = [
+            "crané has 6 bytes and 6 legs",
+            "{braces} stay",
+            "héllo",
+            "world",
+            "12",
+            "97",
+            "233",
+            "8364",
+            "hello, crané",
+            "true",
+            "CRANÉ",
+            "8",
+            r"no {holes} \n here",
+            "first",
+            "  second",
+        ];
+        assert_eq!(run(source), expected);
+
+        let (_, program) = build("def main() {\n    println(\"héllo\"[0..2])\n}\n");
+        let error = Vm::new(&program.unwrap(), &mut Vec::new(), &mut io::empty()).run(0).unwrap_err();
+        assert_eq!(error.message, "byte index 2 is not on a character boundary");
+    }
+
+    #[test]
+    fn question_mark_returns_the_error_or_none_early() {
+        let source = r#"from std.string import str_to_int
+
+def parse(s: str) -> Result<i64, str> {
+    println("parse '{s}'")
+    if (len(s) == 0) {
+        return Err("empty")
+    }
+    return Ok(str_to_int(s))
+}
+
+def sum(a: str, b: str) -> Result<i64, str> {
+    let x = parse(a)?
+    let y = parse(b)?
+    return Ok(x + y)
+}
+
+def half(n: i64) -> Option<i64> {
+    if (n % 2 == 1) {
+        return None
+    }
+    return Some(n / 2)
+}
+
+def quarter(n: i64) -> Option<i64> {
+    return Some(half(half(n)?)?)
+}
+
+def shown(o: Option<i64>) -> str {
+    return match o {
+        Some(v) => "some {v}",
+        None => "none",
+    }
+}
+
+def show(r: Result<i64, str>) -> str {
+    return match r {
+        Ok(v) => "ok {v}",
+        Err(e) => "error {e}",
+    }
+}
+
+def main() {
+    println(show(sum("2", "40")))
+    println(show(sum("2", "")))
+    println(show(sum("", "x")))
+    println(shown(quarter(12)))
+    println(shown(quarter(6)))
+    println(shown(quarter(5)))
+}
+"#;
+        let expected = [
+            "parse '2'",
+            "parse '40'",
+            "ok 42",
+            "parse '2'",
+            "parse ''",
+            "error empty",
+            "parse ''",
+            "error empty",
+            "some 3",
+            "none",
+            "none",
+        ];
+        assert_eq!(run(source), expected);
+    }
+
+    #[test]
+    fn a_discriminant_that_fails_leaves_the_variants_after_it_unnumbered() {
+        let source = "const N: i64 = 1
+
+enum Color {
+    Red = 1,
+    Green = N as i32,
+    Blue,
+    Gray = 0,
+    White = 1,
+}
+";
+        let (diagnostics, _) = build(source);
+        assert_eq!(errors(&diagnostics), ["E0341", "E0339"]);
+        let duplicate = diagnostics.iter().find(|d| d.code.as_deref() == Some("E0339")).unwrap();
+        assert_eq!(duplicate.message, "the discriminant 1 is used twice");
+        assert_eq!(duplicate.labels[0].message, "`White` is 1");
+    }
+
+    #[test]
+    fn constants_are_worked_out_when_compiling_and_statics_change_at_runtime() {
+        let source = "const def square(n: i64) -> i64 {
+    return n * n
+}
+
+const SIDE: i64 = 3
+const AREA: i64 = square(SIDE) + 1
+const SIZE: u64 = 2 * 2
+
+static COUNT: i64 = 0
+
+enum Level {
+    Low = 20,
+    Mid,
+    High = AREA as u32,
+}
+
+def bump() -> i64 {
+    COUNT = COUNT + 1
+    return COUNT
+}
+
+def main() {
+    println(AREA)
+    let slots: [i32; SIZE] = [1, 2, 3, 4]
+    println(slots.len())
+    bump()
+    bump()
+    println(bump())
+    println(Level::Mid as i64)
+    println(Level::High as i64)
+    println(square(5))
+}
+";
+        assert_eq!(run(source), ["10", "4", "3", "21", "10", "25"]);
+        let (diagnostics, _) = build("const BIG: u8 = 200 + 100\n");
+        assert_eq!(errors(&diagnostics), ["E0335"]);
+    }
+}
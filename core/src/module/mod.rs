@@ -0,0 +1,295 @@
+pub mod stdlib;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, SourceMap, Span};
+use crate::lexer::Lexer;
+use crate::parser::{NodeType, Parser, Tree};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//one file of the program, named by its path from the directory of the root
+//file with `.` between the parts: foo/bar.crane is the module `foo.bar`
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    pub file: usize,
+    pub tree: Tree,
+    //part of the standard library, its source is not a file on disk
+    pub std: bool,
+    //the package the module belongs to, `None` for the program itself
+    pub package: Option<usize>,
+    //the modules this one imports and the span of each import
+    pub imports: Vec<(usize, Span)>,
+    //each module this one imports by the path written in the import
+    aliases: HashMap<String, usize>,
+}
+
+//a library the program depends on: `import name` loads its entry file and
+//`import name.a.b` the file a/b.crane next to the entry. The modules of a
+//package are named with the package name in front, whatever the package
+//itself writes in its imports
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub entry: PathBuf,
+}
+
+//every module reachable from the root file through imports, built before any
+//name is resolved
+//without modules when the root file could not be read
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+    //dependencies come before the modules that import them
+    pub order: Vec<usize>,
+    pub diagnostics: Vec<Diagnostic>,
+    root_dir: PathBuf,
+    packages: Vec<Package>,
+    by_name: HashMap<String, usize>,
+}
+
+pub const ROOT: usize = 0;
+
+impl ModuleGraph {
+    //`read` gives the text of a file, so callers can compile edited sources
+    //that are not on disk yet
+    pub fn load(
+        sources: &mut SourceMap,
+        path: &Path,
+        packages: &[Package],
+        read: &mut dyn FnMut(&Path) -> io::Result<String>,
+    ) -> io::Result<ModuleGraph> {
+        let root_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let name = path
+            .file_stem()
+            .map_or("main".to_string(), |s| s.to_string_lossy().to_string());
+        let mut graph = ModuleGraph {
+            modules: Vec::new(),
+            order: Vec::new(),
+            diagnostics: Vec::new(),
+            root_dir,
+            packages: packages.to_vec(),
+            by_name: HashMap::new(),
+        };
+        let source = read(path)?;
+        graph.add(sources, name, path.to_path_buf(), source, None);
+        graph.add_std(sources, stdlib::PRELUDE);
+        //modules are appended as they are found, so this also visits them
+        let mut next = 0;
+        while next < graph.modules.len() {
+            graph.load_imports(sources, next, read);
+            next += 1;
+        }
+        graph.sort();
+        Ok(graph)
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.by_name.get(name).copied()
+    }
+
+    //the module a module imports as `path`
+    pub fn import(&self, module: usize, path: &str) -> Option<usize> {
+        self.modules[module].aliases.get(path).copied()
+    }
+
+    pub fn root(&self) -> &Module {
+        &self.modules[ROOT]
+    }
+
+    fn add(
+        &mut self,
+        sources: &mut SourceMap,
+        name: String,
+        path: PathBuf,
+        source: String,
+        package: Option<usize>,
+    ) -> usize {
+        let file = sources.add(&path.display().to_string(), source);
+        let mut lexer = Lexer::new(sources.source(file), file);
+        lexer.lex();
+        let lexed = !lexer.diagnostics.iter().any(Diagnostic::is_error);
+        self.diagnostics.append(&mut lexer.diagnostics);
+        let tree = if lexed {
+            let mut parser = Parser::new(lexer.tokens, sources.source(file));
+            parser.parse();
+            self.diagnostics.append(&mut parser.diagnostics);
+            parser.tree
+        } else {
+            Tree::new()
+        };
+        let id = self.modules.len();
+        self.by_name.insert(name.clone(), id);
+        self.modules.push(Module {
+            name,
+            path,
+            file,
+            tree,
+            std: false,
+            package,
+            imports: Vec::new(),
+            aliases: HashMap::new(),
+        });
+        id
+    }
+
+    fn add_std(&mut self, sources: &mut SourceMap, name: &str) -> Option<usize> {
+        let source = stdlib::source(name)?;
+        let path = PathBuf::from(format!("<{}>", name));
+        let id = self.add(sources, name.to_string(), path, source.to_string(), None);
+        self.modules[id].std = true;
+        Some(id)
+    }
+
+    fn load_imports(
+        &mut self,
+        sources: &mut SourceMap,
+        module: usize,
+        read: &mut dyn FnMut(&Path) -> io::Result<String>,
+    ) {
+        let wanted: Vec<(String, Span)> = self.modules[module]
+            .tree
+            .nodes
+            .iter()
+            .filter(|n| n.is(NodeType::Import))
+            .map(|n| (n.value().to_string(), n.span))
+            .collect();
+        //the prelude is imported everywhere, it imports nothing itself
+        if self.modules[module].name != stdlib::PRELUDE {
+            let prelude = self.find(stdlib::PRELUDE).unwrap();
+            self.modules[module].imports.push((prelude, Span::default()));
+        }
+        for (written, span) in wanted {
+            let (name, path, package) = self.locate(module, &written);
+            let id = match self.find(&name) {
+                Some(id) => id,
+                None if stdlib::is_std(&name) => match self.add_std(sources, &name) {
+                    Some(id) => id,
+                    None => {
+                        let names: Vec<&str> = stdlib::names().collect();
+                        self.diagnostics.push(
+                            Diagnostic::error(E0203, format!("cannot find module `{}`", name))
+                                .with_label(span, "not in the standard library")
+                                .with_note(format!("the standard library has `{}`", names.join("`, `"))),
+                        );
+                        continue;
+                    }
+                },
+                None => {
+                    match read(&path) {
+                        Ok(source) => self.add(sources, name, path, source, package),
+                        Err(_) => {
+                            self.diagnostics.push(
+                                Diagnostic::error(E0203, format!("cannot find module `{}`", name))
+                                    .with_label(span, "no file for this module")
+                                    .with_note(format!("looked for `{}`", path.display())),
+                            );
+                            continue;
+                        }
+                    }
+                }
+            };
+            self.modules[module].imports.push((id, span));
+            self.modules[module].aliases.insert(written, id);
+        }
+    }
+
+    //the name, file and package of the module that `module` imports as
+    //`written`
+    fn locate(&self, module: usize, written: &str) -> (String, PathBuf, Option<usize>) {
+        if stdlib::is_std(written) {
+            return (written.to_string(), PathBuf::from(format!("<{}>", written)), None);
+        }
+        let (first, rest) = match written.split_once('.') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (written, None),
+        };
+        let (name, dir, rest, package) = match self.packages.iter().position(|p| p.name == first) {
+            Some(package) => {
+                let entry = &self.packages[package].entry;
+                let Some(rest) = rest else {
+                    return (written.to_string(), entry.clone(), Some(package));
+                };
+                (written.to_string(), entry.parent().unwrap_or(Path::new("")), rest, Some(package))
+            }
+            None => match self.modules[module].package {
+                Some(package) => {
+                    let Package { name, entry } = &self.packages[package];
+                    let dir = entry.parent().unwrap_or(Path::new(""));
+                    (format!("{}.{}", name, written), dir, written, Some(package))
+                }
+                None => (written.to_string(), self.root_dir.as_path(), written, None),
+            },
+        };
+        let mut path = dir.to_path_buf();
+        path.extend(rest.split('.'));
+        path.set_extension("crane");
+        (name, path, package)
+    }
+
+    //order the modules so every one comes after its imports, and report every
+    //cycle of imports
+    fn sort(&mut self) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            Open,
+            Done,
+        }
+        fn visit(graph: &mut ModuleGraph, module: usize, marks: &mut [Mark], stack: &mut Vec<(usize, Span)>) {
+            marks[module] = Mark::Open;
+            for (import, span) in graph.modules[module].imports.clone() {
+                match marks[import] {
+                    Mark::New => {
+                        stack.push((module, span));
+                        visit(graph, import, marks, stack);
+                        stack.pop();
+                    }
+                    Mark::Open => {
+                        stack.push((module, span));
+                        graph.report_cycle(import, stack);
+                        stack.pop();
+                    }
+                    Mark::Done => {}
+                }
+            }
+            marks[module] = Mark::Done;
+            graph.order.push(module);
+        }
+        let mut marks = vec![Mark::New; self.modules.len()];
+        for module in 0..self.modules.len() {
+            if marks[module] == Mark::New {
+                visit(self, module, &mut marks, &mut Vec::new());
+            }
+        }
+    }
+
+    //`stack` ends with the import that closes the cycle back to `start`
+    fn report_cycle(&mut self, start: usize, stack: &[(usize, Span)]) {
+        let first = stack.iter().position(|&(m, _)| m == start).unwrap();
+        let cycle = &stack[first..];
+        let names: Vec<&str> = cycle
+            .iter()
+            .map(|&(m, _)| self.modules[m].name.as_str())
+            .chain([self.modules[start].name.as_str()])
+            .collect();
+        let (_, last) = cycle[cycle.len() - 1];
+        let mut diagnostic = Diagnostic::error(E0204, format!("import cycle between {}", quote(&names)))
+            .with_label(last, format!("`{}` imports `{}`, closing the cycle", names[names.len() - 2], names[0]));
+        for (i, &(_, span)) in cycle[..cycle.len() - 1].iter().enumerate() {
+            diagnostic = diagnostic.with_secondary(span, format!("`{}` imports `{}`", names[i], names[i + 1]));
+        }
+        self.diagnostics.push(
+            diagnostic.with_help("move what both modules need into a module that neither of them imports"),
+        );
+    }
+}
+
+fn quote(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|n| format!("`{}`", n))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
@@ -0,0 +1,26 @@
+//the standard library, compiled into the compiler so a program can import
+//it from anywhere. `std.prelude` is imported by every module without asking
+pub const PRELUDE: &str = "std.prelude";
+
+const MODULES: &[(&str, &str)] = &[
+    ("std.prelude", include_str!("../../std/prelude.crane")),
+    ("std.string", include_str!("../../std/string.crane")),
+    ("std.math", include_str!("../../std/math.crane")),
+    ("std.array", include_str!("../../std/array.crane")),
+    ("std.map", include_str!("../../std/map.crane")),
+    ("std.io", include_str!("../../std/io.crane")),
+    ("std.iter", include_str!("../../std/iter.crane")),
+];
+
+pub fn is_std(name: &str) -> bool {
+    name == "std" || name.starts_with("std.")
+}
+
+pub fn source(name: &str) -> Option<&'static str> {
+    MODULES.iter().find(|(n, _)| *n == name).map(|&(_, source)| source)
+}
+
+//the modules there are, for the note of a missing one
+pub fn names() -> impl Iterator<Item = &'static str> {
+    MODULES.iter().map(|&(name, _)| name)
+}
@@ -0,0 +1,238 @@
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Applicability, Diagnostic, Span};
+
+//`after` is the end of the previous item, where the comma should go. It is
+//only `certain` to be missing before something that can begin an item, a
+//stray token is as likely to be the mistake
+pub fn missing_comma(what: &str, span: Span, after: Span, certain: bool) -> Diagnostic {
+    let applicability = if certain {
+        Applicability::MachineApplicable
+    } else {
+        Applicability::MaybeIncorrect
+    };
+    Diagnostic::error(E0100, format!("expected a comma in {}", what))
+        .with_label(span, "expected `,` before this")
+        .with_suggestion(
+            "separate the items with a comma",
+            Span::new(after.file, after.end, after.end),
+            ",",
+            applicability,
+        )
+}
+
+pub fn expected_block(after: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(E0101, format!("expected `{{` after {}", after))
+        .with_label(span, "expected `{` here")
+}
+
+pub fn expected_left_paren(after: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(E0102, format!("expected `(` after {}", after))
+        .with_label(span, "expected `(` here")
+}
+
+pub fn expected_function_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0103, "expected a function name after `def`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_right_paren(after: &str, span: Span, open: Span) -> Diagnostic {
+    Diagnostic::error(E0104, format!("expected `)` after {}", after))
+        .with_label(span, "expected `)` here")
+        .with_secondary(open, "to close this")
+}
+
+pub fn unexpected_token(token: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(E0105, format!("unexpected token `{}`", token))
+        .with_label(span, "not expected here")
+}
+
+pub fn expected_expression(span: Span) -> Diagnostic {
+    Diagnostic::error(E0106, "expected an expression")
+        .with_label(span, "expected a value here")
+}
+
+pub fn expected_parameter(span: Span) -> Diagnostic {
+    Diagnostic::error(E0107, "expected a parameter name")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_variable_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0108, "expected a variable name after `let`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_initializer(span: Span, name: Span) -> Diagnostic {
+    Diagnostic::error(E0109, "expected `=` in a `let`")
+        .with_label(span, "expected `=` here")
+        .with_secondary(name, "every variable needs a value")
+}
+
+pub fn expected_type(span: Span) -> Diagnostic {
+    Diagnostic::error(E0110, "expected a type")
+        .with_label(span, "expected a type name")
+}
+
+pub fn invalid_assignment_target(target: Span, operator: Span) -> Diagnostic {
+    Diagnostic::error(E0111, "invalid left-hand side of assignment")
+        .with_label(target, "cannot assign to this")
+        .with_secondary(operator, "assignment here")
+}
+
+pub fn expected_attribute_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0112, "expected an attribute name after `@`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_lint_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0113, "expected a lint name")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn misplaced_attribute(attribute: Span, item: Span) -> Diagnostic {
+    Diagnostic::error(E0114, "attributes can only be put on functions")
+        .with_label(attribute, "this attribute")
+        .with_secondary(item, "is not a function")
+        .with_help("use `@!` to apply an attribute to the whole file")
+}
+
+pub fn expected_module_path(span: Span) -> Diagnostic {
+    Diagnostic::error(E0115, "expected a module path")
+        .with_label(span, "expected a name like `foo` or `foo.bar`")
+}
+
+pub fn misplaced_pub(pub_span: Span, item: Span) -> Diagnostic {
+    Diagnostic::error(E0116, "`pub` can only be put on `def`, `enum`, `struct`, `trait`, `const` and `static`")
+        .with_label(pub_span, "this `pub`")
+        .with_secondary(item, "is not a function, an enum, a struct, a trait, a constant or a static")
+}
+
+pub fn nested_constant(span: Span) -> Diagnostic {
+    Diagnostic::error(E0134, "constants and statics must be at the top level of a file")
+        .with_label(span, "this is inside a block")
+        .with_help("move it to the top level, or use `let` for a value of the block")
+}
+
+pub fn nested_import(span: Span) -> Diagnostic {
+    Diagnostic::error(E0117, "imports must be at the top level of a file")
+        .with_label(span, "this import is inside a block")
+        .with_help("move it to the top of the file")
+}
+
+pub fn expected_import(span: Span) -> Diagnostic {
+    Diagnostic::error(E0118, "expected `import` after the module path")
+        .with_label(span, "expected `import` here")
+        .with_help("write `from module import name`")
+}
+
+pub fn native_body(name: Span, body: Span) -> Diagnostic {
+    Diagnostic::error(E0119, "a native function cannot have a body")
+        .with_label(body, "remove this body")
+        .with_secondary(name, "this function is implemented by the VM")
+}
+
+pub fn expected_enum_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0120, "expected an enum name after `enum`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_variant(span: Span) -> Diagnostic {
+    Diagnostic::error(E0121, "expected a variant name")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_field_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0122, "expected a field name")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_field_colon(span: Span, name: Span) -> Diagnostic {
+    Diagnostic::error(E0122, "expected `:` after the field name")
+        .with_label(span, "expected `:` here")
+        .with_secondary(name, "the type of this field is missing")
+}
+
+pub fn expected_pattern(span: Span) -> Diagnostic {
+    Diagnostic::error(E0123, "expected a pattern")
+        .with_label(span, "expected a literal, a name, `_` or a variant")
+}
+
+pub fn expected_fat_arrow(span: Span, pattern: Span) -> Diagnostic {
+    Diagnostic::error(E0124, "expected `=>` after the pattern")
+        .with_label(span, "expected `=>` here")
+        .with_secondary(pattern, "this arm has no value")
+}
+
+pub fn expected_struct_name(span: Span) -> Diagnostic {
+    Diagnostic::error(E0125, "expected a struct name after `struct`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_type_param(span: Span) -> Diagnostic {
+    Diagnostic::error(E0126, "expected a type parameter")
+        .with_label(span, "expected an identifier like `T`")
+}
+
+pub fn expected_constraint(span: Span, param: Span) -> Diagnostic {
+    Diagnostic::error(E0126, "expected a constraint after `:`")
+        .with_label(span, "expected a name like `Integer`")
+        .with_secondary(param, "constrains this type parameter")
+}
+
+pub fn expected_right_angle(after: &str, span: Span, open: Span) -> Diagnostic {
+    Diagnostic::error(E0127, format!("expected `>` after {}", after))
+        .with_label(span, "expected `>` here")
+        .with_secondary(open, "to close this")
+}
+
+pub fn expected_trait_name(after: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(E0128, format!("expected a trait name after {}", after))
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_for(span: Span, name: Span) -> Diagnostic {
+    Diagnostic::error(E0129, "expected `for` or `{` after the name in an `impl`")
+        .with_label(span, "expected `for` or `{` here")
+        .with_secondary(name, "the trait or type being implemented")
+        .with_help("implement a trait with `impl Trait for Type { ... }`, or add methods with `impl Type { ... }`")
+}
+
+pub fn expected_method(what: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(E0130, format!("expected a method in {}", what))
+        .with_label(span, "expected `def` here")
+}
+
+pub fn expected_right_bracket(after: &str, span: Span, open: Span) -> Diagnostic {
+    Diagnostic::error(E0131, format!("expected `]` after {}", after))
+        .with_label(span, "expected `]` here")
+        .with_secondary(open, "to close this")
+}
+
+pub fn expected_loop_variable(span: Span) -> Diagnostic {
+    Diagnostic::error(E0132, "expected a variable name after `for`")
+        .with_label(span, "expected an identifier")
+}
+
+pub fn expected_in(span: Span, variable: Span) -> Diagnostic {
+    Diagnostic::error(E0132, "expected `in` after the variable of `for`")
+        .with_label(span, "expected `in` here")
+        .with_secondary(variable, "each value is bound to this")
+}
+
+pub fn expected_semicolon(after: &str, span: Span, open: Span) -> Diagnostic {
+    Diagnostic::error(E0132, format!("expected `;` after {}", after))
+        .with_label(span, "expected `;` here")
+        .with_secondary(open, "the clauses of `for` start here")
+}
+
+pub fn expected_for_condition(span: Span, init: Span) -> Diagnostic {
+    Diagnostic::error(E0132, "expected a condition in `for`")
+        .with_label(span, "expected a condition here")
+        .with_secondary(init, "after the init")
+        .with_help("a loop that only ends with `break` is written `loop { ... }`")
+}
+
+pub fn expected_right_brace_in_string(span: Span, open: Span) -> Diagnostic {
+    Diagnostic::error(E0133, "expected `}` after the value of a hole in a string")
+        .with_label(span, "expected `}` here")
+        .with_secondary(open, "the hole starts here")
+}
@@ -1,326 +1,1788 @@
 #![allow(dead_code, unused_imports)]
+mod error;
 mod tree;
+use crate::diagnostic::{Diagnostic, Span};
 use crate::lexer::Token;
 use crate::lexer::TokenType;
-use crate::throw;
-use ansi_term::Colour::{Red, Yellow};
-use tree::Node;
-use tree::NodeType;
-use tree::Tree;
+pub use tree::Node;
+pub use tree::NodeType;
+pub use tree::Tree;
 //create a parser struct that uses peekable iterator for the tokens
 #[derive(Debug, Clone)]
 pub struct Parser {
     tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    //the last token handed out, calls must open their `(` on its line
+    previous: Option<Token>,
+    consumed: usize,
+    //set by `@native` for the function that follows it
+    native: bool,
+    //set while parsing the value of a `match`, where `{` opens the arms
+    no_struct: bool,
+    //set for a method of a trait, which needs no body
+    in_trait: bool,
+    //the text the tokens were lexed from, to quote a token as it is written
+    source: String,
     pub tree: Tree,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 //initialize the parser struct
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+    pub fn new(tokens: Vec<Token>, source: &str) -> Parser {
         Parser {
             tokens: tokens.into_iter().peekable(),
+            previous: None,
+            consumed: 0,
+            native: false,
+            no_struct: false,
+            in_trait: false,
+            source: source.to_string(),
             tree: Tree::new(),
+            diagnostics: Vec::new(),
         }
     }
     fn next(&mut self) -> Option<Token> {
-        self.tokens.next()
+        let token = self.tokens.next()?;
+        self.consumed += 1;
+        self.previous = Some(token.clone());
+        Some(token)
     }
     fn peek(&mut self) -> Option<&Token> {
         self.tokens.peek()
     }
+    fn peek_type(&mut self) -> TokenType {
+        self.peek().map_or(TokenType::Eof, |t| t.token_type.clone())
+    }
+    fn at_eof(&mut self) -> bool {
+        self.peek_type() == TokenType::Eof
+    }
+    fn check(&mut self, token_type: &TokenType) -> bool {
+        self.peek_type() == *token_type
+    }
+    fn check_keyword(&mut self, keyword: &str) -> bool {
+        matches!(self.peek_type(), TokenType::Keyword(k) if k == keyword)
+    }
+    fn check_operator(&mut self, operator: &str) -> bool {
+        matches!(self.peek_type(), TokenType::Operator(o) if o == operator)
+    }
+    //whether the next token can begin an expression, a type or a pattern,
+    //the items of every list
+    fn begins_item(&mut self) -> bool {
+        match self.peek_type() {
+            TokenType::Identifier(_)
+            | TokenType::DataType(_)
+            | TokenType::Number(_)
+            | TokenType::Float(_)
+            | TokenType::Str(_)
+            | TokenType::FormatStart
+            | TokenType::Character(_)
+            | TokenType::True
+            | TokenType::False
+            | TokenType::None
+            | TokenType::LeftParen
+            | TokenType::LeftBracket => true,
+            TokenType::Operator(op) => matches!(op.as_str(), "Sub" | "Not" | "And" | "Mul"),
+            TokenType::Keyword(kw) => matches!(kw.as_str(), "match" | "loop" | "def" | "null" | "dyn"),
+            _ => false,
+        }
+    }
+    //report the comma missing after `last`, which is only certain between
+    //two items that parsed
+    fn missing_comma(&mut self, what: &str, last: &Node) {
+        let span = self.peek_span();
+        let certain = !last.is(NodeType::Err) && self.begins_item();
+        self.diagnostics.push(error::missing_comma(what, span, last.span(), certain));
+    }
+    //whether the next token sits on the same line as the one before it
+    fn same_line(&mut self) -> bool {
+        let line = self.previous.as_ref().map_or(0, |t| t.line);
+        self.peek().is_some_and(|t| t.line == line)
+    }
+    fn peek_span(&mut self) -> Span {
+        match self.peek() {
+            Some(token) => token.span,
+            None => self.previous_span(),
+        }
+    }
+    fn previous_span(&self) -> Span {
+        self.previous.as_ref().map_or(Span::default(), |t| t.span)
+    }
+    // Report an error and hand back a node marking where it happened
+    fn error(&mut self, diagnostic: Diagnostic, token: &Token) -> Node {
+        self.diagnostics.push(diagnostic);
+        Node::new(NodeType::Err, None, token.line, token.span)
+    }
+    fn error_here(&mut self, diagnostic: Diagnostic) -> Node {
+        self.diagnostics.push(diagnostic);
+        let span = self.peek_span();
+        let line = self.peek().map_or(0, |t| t.line);
+        Node::new(NodeType::Err, None, line, span)
+    }
+    // Consume the next token if it has the given type
+    fn eat(&mut self, token_type: &TokenType) -> Option<Token> {
+        if self.check(token_type) {
+            self.next()
+        } else {
+            None
+        }
+    }
+    fn node(&self, node_type: NodeType, value: Option<String>, token: &Token) -> Node {
+        Node::new(node_type, value, token.line, token.span)
+    }
 }
 impl Parser {
     // Parse the tokens
     pub fn parse(&mut self) {
-        while let Some(token) = self.next() {
-            let node = self.parse_token(token);
+        while !self.at_eof() {
+            let before = self.consumed;
+            let node = self.parse_statement();
+            //make sure a broken statement never stalls the parser
+            if self.consumed == before {
+                self.next();
+            }
             self.tree.add_node(node);
         }
     }
 
-    // Parse a token
-    fn parse_token(&mut self, token: Token) -> Node {
-        match token.token_type {
-            TokenType::Number(ref f) => {
-                if let Some(next_token) = self.peek() {
-                    if let TokenType::Operator(_) = next_token.token_type {
-                        return self.parse_expression(token);
-                    }
+    fn parse_statement(&mut self) -> Node {
+        match self.peek_type() {
+            TokenType::At => self.parse_attributed(),
+            TokenType::LeftBrace => self.parse_block(),
+            TokenType::Keyword(kw) => match kw.as_str() {
+                "def" => self.parse_function(),
+                "let" => self.parse_let(),
+                "const" | "static" => self.parse_constant(),
+                "if" => self.parse_if(),
+                "while" => self.parse_while(),
+                "for" => self.parse_for(),
+                "loop" => self.parse_expression_statement(),
+                "break" => self.parse_break(),
+                "continue" => {
+                    let token = self.next().unwrap();
+                    self.node(NodeType::Continue, None, &token)
+                }
+                "unsafe" => self.parse_unsafe(),
+                "return" => self.parse_return(),
+                "import" => self.parse_import(),
+                "from" => self.parse_from_import(),
+                "pub" => self.parse_pub(),
+                "enum" => self.parse_enum(),
+                "struct" => self.parse_struct(),
+                "trait" => self.parse_trait(),
+                "impl" => self.parse_impl(),
+                "match" => self.parse_expression_statement(),
+                _ => {
+                    let token = self.next().unwrap();
+                    self.node(NodeType::Keyword, Some(kw), &token)
                 }
-                Node::new(NodeType::Number, Some(f.to_string()), token.line)
             },
-            TokenType::Str(s) => Node::new(NodeType::String, Some(s), token.line),
-            TokenType::Identifier(s) => {
-                //check if next token is an operator
-                
-                // This could be a function call, a variable, or a keyword
-                // Check if the next token is a parenthesis
-                if self.peek().unwrap().token_type == TokenType::LeftParen {
-                    self.next();
-                    // This is a function call
-                    let mut node = Node::new(NodeType::FunctionCall, Some(s.clone()), token.line);
-                    // Parse the arguments
-                    let mut args = Vec::new();
-                    let mut expect_comma = false;
-                    while let Some(token) = self.next() {
-                        if token.token_type == TokenType::RightParen {
-                            break;
-                        }
-                        if expect_comma {
-                            if token.token_type != TokenType::Comma {
-                                throw!(format!("Expected a comma in: {}",Yellow.paint(s)), token.line);
-                            }
-                            expect_comma = false;
-                            continue;
-                        }
-                        let arg = self.parse_token(token);
-                        args.push(arg);
-                        expect_comma = true;
-                    }
-                    // Add the arguments to the node
-                    for arg in args {
-                        node.add_child(arg);
-                    }
-                    node
-                } else if self.peek().unwrap().token_type == TokenType::Operator("Eq".to_string()) {
-                    // Consume the '=' operator
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    // @allow(lint) in front of a function, or @!allow(lint) for the whole file
+    fn parse_attributed(&mut self) -> Node {
+        let at = self.next().unwrap();
+        let inner = self.check_operator("Not");
+        if inner {
+            self.next();
+        }
+        let name = match self.peek_type() {
+            TokenType::Identifier(name) => {
+                self.next();
+                name
+            }
+            _ => {
+                let span = self.peek_span();
+                return self.error_here(error::expected_attribute_name(span));
+            }
+        };
+        let mut attribute = self.node(NodeType::Attribute, Some(name), &at);
+        if let Some(open) = self.eat(&TokenType::LeftParen) {
+            attribute.children = self.parse_list(&open, "the attribute", |p| match p.peek_type() {
+                TokenType::Identifier(lint) => {
+                    let token = p.next().unwrap();
+                    p.node(NodeType::Identifier, Some(lint), &token)
+                }
+                _ => {
+                    let span = p.peek_span();
+                    p.error_here(error::expected_lint_name(span))
+                }
+            });
+        }
+        attribute.span = at.span.to(self.previous_span());
+        if inner {
+            return attribute;
+        }
+        //a native function is implemented by the VM and has no body
+        if attribute.value() == "native" {
+            self.native = true;
+        }
+        let mut item = self.parse_statement();
+        self.native = false;
+        if item.is(NodeType::Function) {
+            item.children.insert(0, attribute);
+        } else {
+            self.diagnostics
+                .push(error::misplaced_attribute(attribute.span, item.span));
+        }
+        item
+    }
+
+    // pub def, pub enum, pub struct, pub trait, pub const or pub static, marks
+    // an item as visible to other modules
+    fn parse_pub(&mut self) -> Node {
+        let token = self.next().unwrap();
+        let marker = self.node(NodeType::Keyword, Some("pub".to_string()), &token);
+        let mut item = self.parse_statement();
+        let items = [
+            NodeType::Function,
+            NodeType::Enum,
+            NodeType::Struct,
+            NodeType::Trait,
+            NodeType::Const,
+            NodeType::Static,
+        ];
+        if items.contains(&item.node_type) {
+            item.children.insert(0, marker);
+        } else if !item.is(NodeType::Err) {
+            self.diagnostics.push(error::misplaced_pub(token.span, item.span));
+        }
+        item
+    }
+
+    // a dotted module path, foo.bar.baz
+    fn parse_path(&mut self) -> Option<(String, Span)> {
+        let TokenType::Identifier(mut path) = self.peek_type() else {
+            let span = self.peek_span();
+            self.diagnostics.push(error::expected_module_path(span));
+            return None;
+        };
+        let first = self.next().unwrap();
+        while self.check(&TokenType::Dot) {
+            self.next();
+            match self.peek_type() {
+                TokenType::Identifier(name) => {
                     self.next();
+                    path.push('.');
+                    path.push_str(&name);
+                }
+                _ => {
+                    let span = self.peek_span();
+                    self.diagnostics.push(error::expected_module_path(span));
+                    return None;
+                }
+            }
+        }
+        Some((path, first.span.to(self.previous_span())))
+    }
 
-                    // The left-hand side should be the identifier
-                    let lhs = Node::new(NodeType::Identifier, Some(s), token.line);
+    // import foo.bar
+    fn parse_import(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let Some((path, span)) = self.parse_path() else {
+            return Node::new(NodeType::Err, None, kw.line, kw.span);
+        };
+        Node::new(NodeType::Import, Some(path), kw.line, span)
+    }
 
-                    // Parse the right-hand side expression
-                    let nxt = self.next().unwrap();
-                    let rhs = self.parse_expression(nxt);
+    // from foo.bar import baz, qux
+    fn parse_from_import(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let Some((path, span)) = self.parse_path() else {
+            return Node::new(NodeType::Err, None, kw.line, kw.span);
+        };
+        let mut node = Node::new(NodeType::Import, Some(path), kw.line, span);
+        if !self.check_keyword("import") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_import(span));
+        }
+        self.next();
+        loop {
+            match self.peek_type() {
+                TokenType::Identifier(name) => {
+                    let token = self.next().unwrap();
+                    node.add_child(self.node(NodeType::Identifier, Some(name), &token));
+                }
+                _ => {
+                    let span = self.peek_span();
+                    self.diagnostics.push(error::expected_variable_name(span));
+                    break;
+                }
+            }
+            if !self.check(&TokenType::Comma) {
+                break;
+            }
+            self.next();
+        }
+        node
+    }
 
-                    // Create a reassignment node
-                    let mut reassignment_node = Node::new(NodeType::Reassignment, None, token.line);
-                    reassignment_node.add_child(lhs);
-                    reassignment_node.add_child(rhs);
+    // def name(a: u16, b: *char) -> bool { ... }, the types are optional, or
+    // def name<T: Integer>(a: T) -> T { ... } for a generic function
+    fn parse_function(&mut self) -> Node {
+        let def = self.next().unwrap();
+        let in_trait = std::mem::take(&mut self.in_trait);
+        let name = match self.peek_type() {
+            TokenType::Identifier(name) => {
+                self.next();
+                name
+            }
+            _ => {
+                let span = self.peek_span();
+                return self.error_here(error::expected_function_name(span));
+            }
+        };
+        let name_span = self.previous_span();
+        let mut function = Node::new(NodeType::Function, Some(name.clone()), def.line, name_span);
+        function.children = self.parse_type_params(&name);
+        let Some(open) = self.eat(&TokenType::LeftParen) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_left_paren(&format!("`def {}`", name), span));
+        };
+        let params = self.parse_params(&open, &format!("the parameters of `{}`", name));
+        function.children.extend(params);
+        let mut after = "the parameters";
+        if self.eat(&TokenType::Arrow).is_some() {
+            let ty = self.parse_type();
+            function.add_child(ty);
+            after = "the return type";
+        }
+        if self.native {
+            self.native = false;
+            if self.check(&TokenType::LeftBrace) {
+                let body = self.parse_block();
+                self.diagnostics.push(error::native_body(name_span, body.span));
+            }
+            return function;
+        }
+        //a method of a trait without a body has to be implemented
+        if in_trait && !self.check(&TokenType::LeftBrace) {
+            return function;
+        }
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            let diagnostic = error::expected_block(&format!("{} of `{}`", after, name), span)
+                .with_secondary(def.span.to(name_span), "in this function");
+            return self.error_here(diagnostic);
+        }
+        let body = self.parse_block();
+        function.add_child(body);
+        function
+    }
 
-                    reassignment_node
-                } else {
-                    Node::new(NodeType::Identifier, Some(s), token.line)
+    // The parameters of a function after the `(`, `a: u16, b`
+    fn parse_params(&mut self, open: &Token, what: &str) -> Vec<Node> {
+        self.parse_list(open, what, |p| match p.peek_type() {
+            TokenType::Identifier(param) => {
+                let token = p.next().unwrap();
+                let mut node = p.node(NodeType::Param, Some(param), &token);
+                if p.eat(&TokenType::Colon).is_some() {
+                    let ty = p.parse_type();
+                    node.add_child(ty);
                 }
-            },
-            // If the token is an operator, create a node with the operator type, left child, and right child
-            TokenType::Operator(op) => Node::new(NodeType::Operator, Some(op), token.line),
-            TokenType::Keyword(kw) => {
-                // If the keyword is a function definition
-                if kw == "def" {
-                    // The next token should be an identifier
-                    let name_token = self.next().unwrap();
-                    if let TokenType::Identifier(name) = name_token.token_type {
-                        // The next token should be a left parenthesis
-                        let left_paren = self.next().unwrap();
-                        if let TokenType::LeftParen = left_paren.token_type {
-                            // Parse the arguments
-                            let mut args = Vec::new();
-                            let mut expect_comma = false;
-                            while let Some(token) = self.next() {
-                                if token.token_type == TokenType::RightParen {
-                                    break;
-                                }
-                                if expect_comma {
-                                    if token.token_type != TokenType::Comma {
-                                        throw!(format!("Expected a comma in: {} {}", Yellow.paint("fn"), Yellow.paint(kw)), token.line);
-                                    }
-                                    expect_comma = false;
-                                    continue;
-                                }
-                                if token.token_type == TokenType::RightParen || token.token_type == TokenType::LeftParen {
-                                    drop(token);
-                                } else {
-                                    let arg = self.parse_token(token);
-                                    args.push(arg);
-                                    expect_comma = true;
-                                }
-                            }
-                            // The next token should be a left brace
-                            let left_brace = self.next().unwrap();
-                            if let TokenType::LeftBrace = left_brace.token_type {
-                                // Parse the function body
-                                let mut body = Node::new(NodeType::Block, None, left_brace.line);
-                                let mut brace_count = 1;
-                                while let Some(token) = self.next() {
-                                    if token.token_type == TokenType::LeftBrace {
-                                        brace_count += 1;
-                                    } else if token.token_type == TokenType::RightBrace {
-                                        brace_count -= 1;
-                                        if brace_count == 0 {
-                                            break;
-                                        }
-                                    }
-                                    if token.token_type == TokenType::RightBrace || token.token_type == TokenType::LeftBrace {
-                                        drop(token);
-                                    } else {
-                                        let node = self.parse_token(token);
-                                        body.add_child(node);
-                                    }
-                                }
-                                // Create a function node
-                                let mut function_node = Node::new(NodeType::Function, Some(name), token.line);
-                                // Add the arguments to the function node
-                                for arg in args {
-                                    function_node.add_child(arg);
-                                }
-                                // Add the body to the function node
-                                function_node.add_child(body);
-                                function_node
-                            } else {
-                                throw!(format!("Expected a left brace after: {} {}", Yellow.paint("fn"), Yellow.paint(kw)), left_brace.line);
-                                Node::new(NodeType::Err, None, left_brace.line)
-                            }
-                        } else {
-                            throw!(format!("Expected a left parenthesis after: {} {}", Yellow.paint("fn"), Yellow.paint(kw)), left_paren.line);
-                            Node::new(NodeType::Err, None, left_paren.line)
-                        }
-                    } else {
-                        throw!(format!("Expected an identifier after: {} {}", Yellow.paint("fn"), Yellow.paint(kw)), name_token.line);
-                        Node::new(NodeType::Err, None, name_token.line)
-                    }
-                } else if kw == "if" {
-                    // the next token should be a left parenthesis, if not throw an error
-                    let left_paren = self.next().unwrap();
-                    if let TokenType::LeftParen = left_paren.token_type {
-                        //there will be a full conditional expression within () so parse it
-                        let nx = self.next().unwrap();
-                        let condition = self.parse_token(nx);
-                        // the next token should be a right parenthesis, if not throw an error
-                        let right_paren = self.next().unwrap();
-                        if let TokenType::RightParen = right_paren.token_type {
-                            //pass
-                        } else {throw!(format!("Expected a right parenthesis after: {}", Yellow.paint(kw)), right_paren.line);
-                        Node::new(NodeType::Err, None, right_paren.line);}
-                        // the next token should be a left brace, if not throw an error
-                        let left_brace = self.next().unwrap();
-                        if let TokenType::LeftBrace = left_brace.token_type {
-                            // Parse the body
-                            let mut body = Node::new(NodeType::Block, None, left_brace.line);
-                            let mut brace_count = 1;
-                            while let Some(token) = self.next() {
-                                if token.token_type == TokenType::LeftBrace {
-                                    brace_count += 1;
-                                } else if token.token_type == TokenType::RightBrace {
-                                    brace_count -= 1;
-                                    if brace_count == 0 {
-                                        break;
-                                    }
-                                }
-                                if token.token_type == TokenType::RightBrace || token.token_type == TokenType::LeftBrace {
-                                    drop(token);
-                                } else {
-                                    let node = self.parse_token(token);
-                                    body.add_child(node);
-                                }
-                            }
-                            // Create an if node
-                            let mut if_node = Node::new(NodeType::Conditional, Some(kw), token.line);
-                            // Add the condition to the if node
-                            if_node.add_child(condition);
-                            // Add the body to the if node
-                            if_node.add_child(body);
-                            if_node
-                        } else {
-                            throw!(format!("Expected a left brace after: {}", Yellow.paint(kw)), left_brace.line);
-                            Node::new(NodeType::Err, None, left_brace.line)
-                        }
-                    } else {
-                        throw!(format!("Expected a left parenthesis after: {}", Yellow.paint(kw)), left_paren.line);
-                        Node::new(NodeType::Err, None, left_paren.line)
-                    }
-                } else {
-                    Node::new(NodeType::Keyword, Some(kw), token.line)
+                node
+            }
+            _ => {
+                let span = p.peek_span();
+                p.error_here(error::expected_parameter(span))
+            }
+        })
+    }
+
+    // def (x: i64) -> i64 { return x + step }, a function as a value
+    fn parse_lambda(&mut self) -> Node {
+        let def = self.next().unwrap();
+        let mut lambda = self.node(NodeType::Lambda, None, &def);
+        let Some(open) = self.eat(&TokenType::LeftParen) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_left_paren("`def` in an expression", span));
+        };
+        lambda.children = self.parse_params(&open, "the parameters of the function");
+        let mut after = "the parameters of the function";
+        if self.eat(&TokenType::Arrow).is_some() {
+            let ty = self.parse_type();
+            lambda.add_child(ty);
+            after = "the return type of the function";
+        }
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block(after, span));
+        }
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        let body = self.parse_block();
+        self.no_struct = no_struct;
+        lambda.span = def.span.to(body.span);
+        lambda.add_child(body);
+        lambda
+    }
+
+    // def(i64, bool) -> u8, the type of a function. The return type is the
+    // last child, void when there is no arrow
+    fn parse_function_type(&mut self) -> Node {
+        let def = self.next().unwrap();
+        let mut node = self.node(NodeType::Type, Some("def".to_string()), &def);
+        let Some(open) = self.eat(&TokenType::LeftParen) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_left_paren("`def` in a type", span));
+        };
+        node.children = self.parse_list(&open, "the parameter types", |p| p.parse_type());
+        let ret = match self.eat(&TokenType::Arrow) {
+            Some(_) => self.parse_type(),
+            None => self.node(NodeType::Type, Some("void".to_string()), &def),
+        };
+        node.span = def.span.to(self.previous_span());
+        node.add_child(ret);
+        node
+    }
+
+    // enum Shape { Circle(i32), Rect { w: i32, h: i32 }, Empty }, or enum Option<T> { ... }
+    fn parse_enum(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let name = match self.peek_type() {
+            TokenType::Identifier(name) => {
+                self.next();
+                name
+            }
+            _ => {
+                let span = self.peek_span();
+                return self.error_here(error::expected_enum_name(span));
+            }
+        };
+        let name_span = self.previous_span();
+        let mut node = Node::new(NodeType::Enum, Some(name.clone()), kw.line, name_span);
+        node.children = self.parse_type_params(&name);
+        if self.eat(&TokenType::LeftBrace).is_none() {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block(&format!("`enum {}`", name), span));
+        }
+        let what = format!("the variants of `{}`", name);
+        let variants = self.parse_braced(&what, |p| p.parse_variant());
+        node.children.extend(variants);
+        node
+    }
+
+    // struct Point { x: i32, y: i32 } or struct Pair<T> { first: T, second: T }
+    fn parse_struct(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let TokenType::Identifier(name) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_struct_name(span));
+        };
+        self.next();
+        let name_span = self.previous_span();
+        let mut node = Node::new(NodeType::Struct, Some(name.clone()), kw.line, name_span);
+        node.children = self.parse_type_params(&name);
+        if self.eat(&TokenType::LeftBrace).is_none() {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block(&format!("`struct {}`", name), span));
+        }
+        let what = format!("the fields of `{}`", name);
+        let fields = self.parse_braced(&what, |p| p.parse_field(None, Parser::parse_type));
+        node.children.extend(fields);
+        node
+    }
+
+    // trait Shape { def area(self) -> i64  def twice(self) -> i64 { ... } }
+    // trait Iterator<T> { def next(self) -> Option<T> }
+    fn parse_trait(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let TokenType::Identifier(name) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_trait_name("`trait`", span));
+        };
+        self.next();
+        let mut node = Node::new(NodeType::Trait, Some(name.clone()), kw.line, self.previous_span());
+        node.children = self.parse_type_params(&name);
+        node.children.extend(self.parse_methods(&format!("`trait {}`", name), true));
+        node
+    }
+
+    // impl Shape for Square { def area(self) -> i64 { ... } }
+    // impl Square { def new(side: i64) -> Square { ... } }
+    // impl<T> Iterator<T> for Repeat<T> { ... }
+    fn parse_impl(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let params = self.parse_type_params("impl");
+        let TokenType::Identifier(name) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_trait_name("`impl`", span));
+        };
+        let token = self.next().unwrap();
+        let Some((name, name_span)) = self.parse_dotted(name, token.span) else {
+            return Node::new(NodeType::Err, None, token.line, self.previous_span());
+        };
+        let mut args = Vec::new();
+        if self.check_operator("Less") {
+            let open = self.next().unwrap();
+            let what = format!("the type arguments of `{}`", name);
+            args = self.parse_angled(&open, &what, |p| p.parse_type());
+        }
+        //the methods of the type itself
+        if self.check(&TokenType::LeftBrace) {
+            let mut node = Node::new(NodeType::Impl, None, kw.line, name_span);
+            let what = format!("`impl {}`", name);
+            let mut ty = Node::new(NodeType::Type, Some(name), token.line, name_span);
+            ty.children = args;
+            node.add_child(ty);
+            node.children.extend(params);
+            node.children.extend(self.parse_methods(&what, false));
+            return node;
+        }
+        if !self.check_keyword("for") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_for(span, name_span));
+        }
+        self.next();
+        let ty = self.parse_type();
+        let mut node = Node::new(NodeType::Impl, Some(name.clone()), kw.line, name_span);
+        let what = format!("`impl {} for {}`", name, ty.value());
+        node.add_child(ty);
+        node.children.extend(params);
+        node.children.extend(args);
+        node.children.extend(self.parse_methods(&what, false));
+        node
+    }
+
+    // The functions in the braces of a trait or impl
+    fn parse_methods(&mut self, what: &str, in_trait: bool) -> Vec<Node> {
+        let Some(open) = self.eat(&TokenType::LeftBrace) else {
+            let span = self.peek_span();
+            return vec![self.error_here(error::expected_block(what, span))];
+        };
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.at_eof() {
+            if !self.check_keyword("def") {
+                let span = self.peek_span();
+                self.diagnostics.push(error::expected_method(what, span).with_secondary(open.span, "in this block"));
+                //skip to the next method
+                while !self.check_keyword("def") && !self.check(&TokenType::RightBrace) && !self.at_eof() {
+                    self.next();
                 }
-            },
-            _ => {println!("{:#?}", token);Node::new(NodeType::Err, None, token.line)},
+                continue;
+            }
+            self.in_trait = in_trait;
+            methods.push(self.parse_function());
         }
+        //an unclosed block has already been reported by the lexer
+        self.eat(&TokenType::RightBrace);
+        methods
     }
 
-    // Parse an expression
-    fn parse_expression(&mut self, first_token: Token) -> Node {
-        let mut output_queue: Vec<Node> = Vec::new();
-        let mut operator_stack: Vec<Token> = Vec::new();
-
-        // Helper function to get precedence
-        fn get_precedence(op: &str) -> i32 {
-            //match statement, include precendence of boolean operators and arithmetic operators, and comparison operators
-            match op {
-                "Or" => 1,
-                "And" => 2,
-                "Eq" | "Ne" => 3,
-                "Lt" | "Gt" | "Le" | "Ge" => 4,
-                "Add" | "Sub" => 5,
-                "Mul" | "Div" => 6,
-                _ => 0,
-            }
-        }
-
-        // Collect all tokens for the expression
-        let mut current_token = Some(first_token.clone());
-        let mut open = 0;
-        while let Some(token) = current_token {
-            match &token.token_type {
-                TokenType::Number(f) => {
-                    output_queue.push(Node::new(NodeType::Number, Some(f.to_string()), token.line));
+    // <T, U: Integer + Eq> after the name of a function, struct, enum or trait, or
+    // after `impl`, if there is one. A bound by a generic trait has its type
+    // arguments, `I: Iterator<T>`
+    fn parse_type_params(&mut self, name: &str) -> Vec<Node> {
+        if !self.check_operator("Less") {
+            return Vec::new();
+        }
+        let open = self.next().unwrap();
+        let what = format!("the type parameters of `{}`", name);
+        self.parse_angled(&open, &what, |p| {
+            let TokenType::Identifier(param) = p.peek_type() else {
+                let span = p.peek_span();
+                return p.error_here(error::expected_type_param(span));
+            };
+            let token = p.next().unwrap();
+            let mut node = p.node(NodeType::TypeParam, Some(param), &token);
+            if p.eat(&TokenType::Colon).is_none() {
+                return node;
+            }
+            loop {
+                let TokenType::Identifier(constraint) = p.peek_type() else {
+                    let span = p.peek_span();
+                    p.diagnostics.push(error::expected_constraint(span, token.span));
+                    break;
+                };
+                let constraint_token = p.next().unwrap();
+                let mut bound = p.node(NodeType::Identifier, Some(constraint.clone()), &constraint_token);
+                if p.check_operator("Less") {
+                    let open = p.next().unwrap();
+                    let what = format!("the type arguments of `{}`", constraint);
+                    bound.children = p.parse_angled(&open, &what, |p| p.parse_type());
                 }
-                TokenType::Str(s) => {
-                    output_queue.push(Node::new(NodeType::String, Some(s.clone()), token.line));
+                node.add_child(bound);
+                if !p.check_operator("Add") {
+                    break;
                 }
-                TokenType::Identifier(s) => {
-                    output_queue.push(Node::new(NodeType::Identifier, Some(s.clone()), token.line));
+                p.next();
+            }
+            node
+        })
+    }
+
+    // A comma separated list closed by `>`, the `<` has been consumed
+    fn parse_angled(
+        &mut self,
+        open: &Token,
+        what: &str,
+        mut item: impl FnMut(&mut Parser) -> Node,
+    ) -> Vec<Node> {
+        let mut items: Vec<Node> = Vec::new();
+        loop {
+            if self.check_operator("Greater") {
+                self.next();
+                break;
+            }
+            //a tuple can be the first item, `Option<(u64, T)>`
+            let paren = self.check(&TokenType::LeftParen) && !items.is_empty();
+            let closing = paren || matches!(self.peek_type(), TokenType::LeftBrace | TokenType::RightParen | TokenType::RightBrace);
+            if self.at_eof() || closing {
+                let span = self.peek_span();
+                self.diagnostics
+                    .push(error::expected_right_angle(what, span, open.span));
+                break;
+            }
+            if let Some(last) = items.last() {
+                if self.eat(&TokenType::Comma).is_none() {
+                    self.missing_comma(what, last);
+                } else if self.check_operator("Greater") {
+                    self.next();
+                    break;
                 }
-                TokenType::Operator(op) => {
-                    while let Some(top_op) = operator_stack.last() {
-                        if let TokenType::Operator(top_op_str) = &top_op.token_type {
-                            if get_precedence(top_op_str) >= get_precedence(op) {
-                                let top_token = operator_stack.pop().unwrap();
-                                let mut node = Node::new(NodeType::Operator, Some(top_token.as_string()), top_token.line);
-                                let right = output_queue.pop().unwrap();
-                                let left = output_queue.pop().unwrap();
-                                node.add_child(left);
-                                node.add_child(right);
-                                output_queue.push(node);
-                            } else {
-                                break;
-                            }
-                        }
-                    }
-                    operator_stack.push(token);
+            }
+            let before = self.consumed;
+            items.push(item(self));
+            if self.consumed == before {
+                self.next();
+            }
+        }
+        items
+    }
+
+    // Circle, Circle(i32, i32) or Rect { w: i32, h: i32 }
+    fn parse_variant(&mut self) -> Node {
+        let Some(name) = self.peek_variant() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_variant(span));
+        };
+        let token = self.next().unwrap();
+        let mut node = self.node(NodeType::Variant, Some(name.clone()), &token);
+        let what = format!("the variant `{}`", name);
+        if let Some(open) = self.eat(&TokenType::LeftParen) {
+            node.children = self.parse_list(&open, &what, |p| p.parse_type());
+        } else if self.eat(&TokenType::LeftBrace).is_some() {
+            node.children = self.parse_braced(&what, |p| p.parse_field(None, Parser::parse_type));
+        }
+        //`Red = 1`, the value of the tag
+        if self.check_operator("Eq") {
+            let eq = self.next().unwrap();
+            let mut discriminant = self.node(NodeType::Discriminant, None, &eq);
+            discriminant.add_child(self.parse_expression());
+            node.add_child(discriminant);
+        }
+        node
+    }
+
+    // name: value, or just `name` when `shorthand` says what it stands for
+    fn parse_field(
+        &mut self,
+        shorthand: Option<NodeType>,
+        value: impl FnOnce(&mut Parser) -> Node,
+    ) -> Node {
+        let TokenType::Identifier(name) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_field_name(span));
+        };
+        let token = self.next().unwrap();
+        let mut field = self.node(NodeType::Field, Some(name.clone()), &token);
+        match shorthand {
+            Some(kind) if !self.check(&TokenType::Colon) => {
+                field.add_child(self.node(kind, Some(name), &token));
+            }
+            _ => {
+                if self.eat(&TokenType::Colon).is_none() {
+                    let span = self.peek_span();
+                    self.diagnostics.push(error::expected_field_colon(span, token.span));
                 }
-                TokenType::LeftParen => {
-                    // Recursively parse the expression inside the parentheses
-                    let nx = self.next().unwrap();
-                    let expr_node = self.parse_expression(nx);
-                    output_queue.push(expr_node);
-                    open += 1;
+                field.add_child(value(self));
+            }
+        }
+        field
+    }
+
+    // A comma separated list closed by `}`, the `{` has been consumed
+    fn parse_braced(&mut self, what: &str, mut item: impl FnMut(&mut Parser) -> Node) -> Vec<Node> {
+        let mut items: Vec<Node> = Vec::new();
+        loop {
+            //an unclosed brace has already been reported by the lexer
+            if self.eat(&TokenType::RightBrace).is_some() || self.at_eof() {
+                break;
+            }
+            if let Some(last) = items.last() {
+                if self.eat(&TokenType::Comma).is_none() {
+                    self.missing_comma(what, last);
+                } else if self.eat(&TokenType::RightBrace).is_some() {
+                    break;
                 }
-                TokenType::RightParen => {
-                    // Stop parsing the current expression
-                    open -= 1;
+            }
+            let before = self.consumed;
+            items.push(item(self));
+            if self.consumed == before {
+                self.next();
+            }
+        }
+        items
+    }
+
+    // A comma separated list closed by `)`, the `(` has been consumed
+    fn parse_list(
+        &mut self,
+        open: &Token,
+        what: &str,
+        item: impl FnMut(&mut Parser) -> Node,
+    ) -> Vec<Node> {
+        self.parse_until(&TokenType::RightParen, open, what, Vec::new(), item)
+    }
+
+    // the rest of a list closed by `close` after the `items` already parsed
+    fn parse_until(
+        &mut self,
+        close: &TokenType,
+        open: &Token,
+        what: &str,
+        mut items: Vec<Node>,
+        mut item: impl FnMut(&mut Parser) -> Node,
+    ) -> Vec<Node> {
+        loop {
+            if self.eat(close).is_some() {
+                break;
+            }
+            if self.at_eof() || self.check(&TokenType::RightBrace) {
+                let span = self.peek_span();
+                let diagnostic = match close {
+                    TokenType::RightBracket => error::expected_right_bracket(what, span, open.span),
+                    _ => error::expected_right_paren(what, span, open.span),
+                };
+                self.diagnostics.push(diagnostic);
+                break;
+            }
+            if let Some(last) = items.last() {
+                if self.eat(&TokenType::Comma).is_none() {
+                    self.missing_comma(what, last);
+                } else if self.eat(close).is_some() {
+                    //a trailing comma is fine
                     break;
                 }
+            }
+            let before = self.consumed;
+            items.push(item(self));
+            if self.consumed == before {
+                self.next();
+            }
+        }
+        items
+    }
+
+    fn parse_block(&mut self) -> Node {
+        let Some(open) = self.eat(&TokenType::LeftBrace) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("this point", span));
+        };
+        let mut block = self.node(NodeType::Block, None, &open);
+        while !self.check(&TokenType::RightBrace) && !self.at_eof() {
+            let before = self.consumed;
+            let node = self.parse_statement();
+            if self.consumed == before {
+                self.next();
+            }
+            if node.is(NodeType::Import) {
+                self.diagnostics.push(error::nested_import(node.span));
+            }
+            if node.is(NodeType::Const) || node.is(NodeType::Static) {
+                self.diagnostics.push(error::nested_constant(node.span));
+            }
+            block.add_child(node);
+        }
+        //an unclosed block has already been reported by the lexer
+        self.eat(&TokenType::RightBrace);
+        block.span = open.span.to(self.previous_span());
+        block
+    }
+
+    // unsafe { ... }, where raw pointers can be dereferenced
+    fn parse_unsafe(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("`unsafe`", span));
+        }
+        let mut block = self.parse_block();
+        block.value = Some("unsafe".to_string());
+        block.span = kw.span.to(block.span());
+        block
+    }
+
+    // let name = value, or let name: type = value
+    fn parse_let(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        if self.check(&TokenType::LeftParen) {
+            return self.parse_destructure(kw);
+        }
+        let name = match self.peek_type() {
+            TokenType::Identifier(name) => {
+                self.next();
+                name
+            }
+            _ => {
+                let span = self.peek_span();
+                return self.error_here(error::expected_variable_name(span));
+            }
+        };
+        let name_span = self.previous_span();
+        let mut node = Node::new(NodeType::Assignment, Some(name), kw.line, name_span);
+        if self.eat(&TokenType::Colon).is_some() {
+            let ty = self.parse_type();
+            node.add_child(ty);
+        }
+        if !self.check_operator("Eq") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_initializer(span, name_span));
+        }
+        self.next();
+        let value = self.parse_expression();
+        node.add_child(value);
+        node
+    }
+
+    // const LIMIT: u32 = 4 * 16, a value worked out when compiling, or static
+    // COUNT: i64 = 0, a global starting with such a value. `const def` is a
+    // function that can also be called when compiling
+    fn parse_constant(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let constant = kw.token_type == TokenType::Keyword("const".to_string());
+        if constant && self.check_keyword("def") {
+            let marker = self.node(NodeType::Keyword, Some("const".to_string()), &kw);
+            let mut function = self.parse_function();
+            if function.is(NodeType::Function) {
+                function.children.insert(0, marker);
+            }
+            return function;
+        }
+        let name = match self.peek_type() {
+            TokenType::Identifier(name) => {
+                self.next();
+                name
+            }
+            _ => {
+                let span = self.peek_span();
+                return self.error_here(error::expected_variable_name(span));
+            }
+        };
+        let name_span = self.previous_span();
+        let node_type = if constant { NodeType::Const } else { NodeType::Static };
+        let mut node = Node::new(node_type, Some(name), kw.line, name_span);
+        if self.eat(&TokenType::Colon).is_some() {
+            let ty = self.parse_type();
+            node.add_child(ty);
+        }
+        if !self.check_operator("Eq") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_initializer(span, name_span));
+        }
+        self.next();
+        let value = self.parse_expression();
+        node.add_child(value);
+        node
+    }
+
+    // let (a, b) = value, or let (a, (b, _)): type = value, the `let` has
+    // been consumed
+    fn parse_destructure(&mut self, kw: Token) -> Node {
+        let pattern = self.parse_tuple_pattern();
+        let mut node = self.node(NodeType::Destructure, None, &kw);
+        node.span = kw.span.to(pattern.span());
+        node.add_child(pattern);
+        if self.eat(&TokenType::Colon).is_some() {
+            let ty = self.parse_type();
+            node.add_child(ty);
+        }
+        if !self.check_operator("Eq") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_initializer(span, node.span));
+        }
+        self.next();
+        let value = self.parse_expression();
+        node.add_child(value);
+        node
+    }
+
+    // a name, `_`, or `(a, (b, _))` for the values of a tuple
+    fn parse_tuple_pattern(&mut self) -> Node {
+        let Some(token) = self.peek().cloned() else {
+            let span = self.previous_span();
+            return self.error_here(error::expected_pattern(span));
+        };
+        match token.token_type {
+            TokenType::LeftParen => {
+                self.next();
+                let mut node = self.node(NodeType::Tuple, None, &token);
+                node.children = self.parse_list(&token, "the tuple pattern", |p| p.parse_tuple_pattern());
+                node.span = token.span.to(self.previous_span());
+                node
+            }
+            TokenType::Identifier(ref name) if name == "_" => {
+                self.next();
+                self.node(NodeType::Wildcard, None, &token)
+            }
+            TokenType::Identifier(ref name) => {
+                self.next();
+                self.node(NodeType::Binding, Some(name.clone()), &token)
+            }
+            _ => self.error_here(error::expected_pattern(token.span)),
+        }
+    }
+
+    // a type name, `module.Name` for an enum or struct of another module,
+    // `Pair<i32>` with type arguments, `*` and the type pointed to, or the
+    // types of a tuple, `(u16, bool)`
+    fn parse_type(&mut self) -> Node {
+        if let Some(open) = self.eat(&TokenType::LeftParen) {
+            if self.eat(&TokenType::RightParen).is_some() {
+                let mut node = self.node(NodeType::Type, Some("()".to_string()), &open);
+                node.span = open.span.to(self.previous_span());
+                return node;
+            }
+            //`(u16)` is just `u16`, `(u16,)` a tuple of one
+            let first = self.parse_type();
+            if self.eat(&TokenType::RightParen).is_some() {
+                return first;
+            }
+            let mut node = self.node(NodeType::Type, Some("()".to_string()), &open);
+            node.children = self.parse_until(&TokenType::RightParen, &open, "the tuple type", vec![first], |p| p.parse_type());
+            node.span = open.span.to(self.previous_span());
+            return node;
+        }
+        //`[u16; 4]` for an array, `[u16]` for a slice of one
+        if let Some(open) = self.eat(&TokenType::LeftBracket) {
+            let mut node = self.node(NodeType::Type, Some("[]".to_string()), &open);
+            node.add_child(self.parse_type());
+            if self.eat(&TokenType::Semicolon).is_some() {
+                node.add_child(self.parse_expression());
+            }
+            self.expect_bracket("the array type", &open);
+            node.span = open.span.to(self.previous_span());
+            return node;
+        }
+        if self.check_keyword("def") {
+            return self.parse_function_type();
+        }
+        if self.check_keyword("dyn") {
+            let kw = self.next().unwrap();
+            //a function with the variables it captured
+            if self.check_keyword("def") {
+                let function = self.parse_function_type();
+                let mut node = self.node(NodeType::Type, Some("dyn".to_string()), &kw);
+                node.span = kw.span.to(function.span);
+                node.add_child(function);
+                return node;
+            }
+            let TokenType::Identifier(name) = self.peek_type() else {
+                let span = self.peek_span();
+                return self.error_here(error::expected_trait_name("`dyn`", span));
+            };
+            let token = self.next().unwrap();
+            let Some((name, span)) = self.parse_dotted(name, token.span) else {
+                return Node::new(NodeType::Err, None, token.line, self.previous_span());
+            };
+            let mut node = self.node(NodeType::Type, Some("dyn".to_string()), &kw);
+            node.span = kw.span.to(span);
+            node.add_child(Node::new(NodeType::Type, Some(name), token.line, span));
+            return node;
+        }
+        if self.check_operator("Mul") {
+            let star = self.next().unwrap();
+            let pointee = self.parse_type();
+            let mut node = self.node(NodeType::Type, Some("*".to_string()), &star);
+            node.span = star.span.to(pointee.span());
+            node.add_child(pointee);
+            return node;
+        }
+        match self.peek_type() {
+            TokenType::Identifier(name) => {
+                let token = self.next().unwrap();
+                let Some((name, span)) = self.parse_dotted(name, token.span) else {
+                    return Node::new(NodeType::Err, None, token.line, self.previous_span());
+                };
+                let mut node = Node::new(NodeType::Type, Some(name.clone()), token.line, span);
+                if self.check_operator("Less") && self.same_line() {
+                    let open = self.next().unwrap();
+                    let what = format!("the type arguments of `{}`", name);
+                    node.children = self.parse_angled(&open, &what, |p| p.parse_type());
+                    node.span = span.to(self.previous_span());
+                }
+                node
+            }
+            _ => {
+                let span = self.peek_span();
+                self.error_here(error::expected_type(span))
+            }
+        }
+    }
+
+    // if (condition) { ... } else if (condition) { ... } else { ... }
+    fn parse_if(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Conditional, Some("if".to_string()), &kw);
+        let Some(open) = self.eat(&TokenType::LeftParen) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_left_paren("`if`", span));
+        };
+        let condition = self.parse_expression();
+        if self.eat(&TokenType::RightParen).is_none() {
+            let span = self.peek_span();
+            let diagnostic = error::expected_right_paren("the condition of `if`", span, open.span);
+            return self.error_here(diagnostic);
+        }
+        node.add_child(condition);
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("the condition of `if`", span));
+        }
+        let body = self.parse_block();
+        node.add_child(body);
+        if self.check_keyword("else") {
+            self.next();
+            let other = if self.check_keyword("if") {
+                self.parse_if()
+            } else if self.check(&TokenType::LeftBrace) {
+                self.parse_block()
+            } else {
+                let span = self.peek_span();
+                self.error_here(error::expected_block("`else`", span))
+            };
+            node.add_child(other);
+        }
+        node
+    }
+
+    // while (condition) { ... }
+    fn parse_while(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Loop, Some("while".to_string()), &kw);
+        let Some(open) = self.eat(&TokenType::LeftParen) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_left_paren("`while`", span));
+        };
+        let condition = self.parse_expression();
+        if self.eat(&TokenType::RightParen).is_none() {
+            let span = self.peek_span();
+            let diagnostic = error::expected_right_paren("the condition of `while`", span, open.span);
+            return self.error_here(diagnostic);
+        }
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("the condition of `while`", span));
+        }
+        let body = self.parse_block();
+        node.children = vec![condition, body];
+        node
+    }
+
+    // loop { ... }, which only ends with `break`, as a statement or a value
+    fn parse_loop(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Loop, Some("loop".to_string()), &kw);
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("`loop`", span));
+        }
+        let body = self.parse_block();
+        node.span = kw.span.to(body.span());
+        node.add_child(body);
+        node
+    }
+
+    // break, or break value when the value starts on the same line
+    fn parse_break(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Break, None, &kw);
+        if self.same_line() && !self.check(&TokenType::RightBrace) && !self.at_eof() {
+            let value = self.parse_expression();
+            node.span = kw.span.to(value.span());
+            node.add_child(value);
+        }
+        node
+    }
+
+    // for (init; condition; step) { ... }, where the init and the step can
+    // be left out. An empty Block stands for one that is
+    fn parse_for_clauses(&mut self, kw: &Token) -> Node {
+        let open = self.next().unwrap();
+        let mut node = self.node(NodeType::Loop, Some("for(;;)".to_string()), kw);
+        let empty = |p: &mut Self| {
+            let span = p.peek_span();
+            Node::new(NodeType::Block, None, kw.line, span)
+        };
+        let init = match self.check(&TokenType::Semicolon) {
+            true => empty(self),
+            false => self.parse_statement(),
+        };
+        let Some(first) = self.eat(&TokenType::Semicolon) else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_semicolon("the init of `for`", span, open.span));
+        };
+        //the rest of the loop is still parsed without the condition
+        let condition = match self.check(&TokenType::Semicolon) {
+            true => {
+                let span = self.peek_span();
+                self.error_here(error::expected_for_condition(span, first.span))
+            }
+            false => self.parse_expression(),
+        };
+        if self.eat(&TokenType::Semicolon).is_none() {
+            let span = self.peek_span();
+            return self.error_here(error::expected_semicolon("the condition of `for`", span, open.span));
+        }
+        let step = match self.check(&TokenType::RightParen) {
+            true => empty(self),
+            false => self.parse_expression_statement(),
+        };
+        if self.eat(&TokenType::RightParen).is_none() {
+            let span = self.peek_span();
+            return self.error_here(error::expected_right_paren("the step of `for`", span, open.span));
+        }
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("the clauses of `for`", span));
+        }
+        let body = self.parse_block();
+        node.children = vec![init, condition, step, body];
+        node
+    }
+
+    // for x in values { ... } or for i in 0..n { ... }, or the C form with its
+    // clauses in parentheses
+    fn parse_for(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        if self.check(&TokenType::LeftParen) {
+            return self.parse_for_clauses(&kw);
+        }
+        let mut node = self.node(NodeType::Loop, Some("for".to_string()), &kw);
+        let TokenType::Identifier(name) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_loop_variable(span));
+        };
+        let token = self.next().unwrap();
+        let binding = self.node(NodeType::Binding, Some(name), &token);
+        if !self.check_keyword("in") {
+            let span = self.peek_span();
+            return self.error_here(error::expected_in(span, token.span));
+        }
+        self.next();
+        let no_struct = std::mem::replace(&mut self.no_struct, true);
+        let values = self.parse_range();
+        self.no_struct = no_struct;
+        if !self.check(&TokenType::LeftBrace) {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("the values of `for`", span));
+        }
+        let body = self.parse_block();
+        node.children = vec![binding, values, body];
+        node
+    }
+
+    // return, or return value when the value starts on the same line
+    fn parse_return(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Return, None, &kw);
+        if self.same_line() && !self.check(&TokenType::RightBrace) && !self.at_eof() {
+            let value = self.parse_expression();
+            node.span = kw.span.to(value.span());
+            node.add_child(value);
+        }
+        node
+    }
+
+    // match value { pattern => value, pattern if guard => { ... } }
+    fn parse_match(&mut self) -> Node {
+        let kw = self.next().unwrap();
+        let mut node = self.node(NodeType::Match, None, &kw);
+        let no_struct = std::mem::replace(&mut self.no_struct, true);
+        let scrutinee = self.parse_expression();
+        self.no_struct = no_struct;
+        node.span = kw.span.to(scrutinee.span());
+        node.add_child(scrutinee);
+        if self.eat(&TokenType::LeftBrace).is_none() {
+            let span = self.peek_span();
+            return self.error_here(error::expected_block("the value of `match`", span));
+        }
+        loop {
+            //an unclosed brace has already been reported by the lexer
+            if self.eat(&TokenType::RightBrace).is_some() || self.at_eof() {
+                break;
+            }
+            let before = self.consumed;
+            let arm = self.parse_arm();
+            if self.consumed == before {
+                self.next();
+                continue;
+            }
+            //arms with a block don't need a comma after them, a broken arm
+            //has been skipped up to and including its comma
+            let block = arm.is(NodeType::Err) || arm.last().is_some_and(|body| body.is(NodeType::Block));
+            if self.eat(&TokenType::Comma).is_none() && !block && !self.check(&TokenType::RightBrace) {
+                self.missing_comma("the arms of `match`", &arm);
+            }
+            node.add_child(arm);
+        }
+        node
+    }
+
+    // pattern => value, or pattern if guard => { ... }
+    fn parse_arm(&mut self) -> Node {
+        let pattern = self.parse_pattern();
+        let mut arm = Node::new(NodeType::Arm, None, pattern.line, pattern.span());
+        arm.add_child(pattern);
+        if self.check_keyword("if") {
+            let kw = self.next().unwrap();
+            let condition = self.parse_expression();
+            let mut guard = self.node(NodeType::Guard, None, &kw);
+            guard.span = kw.span.to(condition.span());
+            guard.add_child(condition);
+            arm.add_child(guard);
+        }
+        if self.eat(&TokenType::FatArrow).is_none() {
+            let span = self.peek_span();
+            let node = self.error_here(error::expected_fat_arrow(span, arm.span));
+            self.skip_arm();
+            return node;
+        }
+        let body = if self.check(&TokenType::LeftBrace) {
+            self.parse_block()
+        } else {
+            self.parse_expression()
+        };
+        arm.span = arm.span.to(body.span());
+        arm.add_child(body);
+        arm
+    }
+
+    // skip the rest of a broken arm, up to the `,` after it or the `}` closing the match
+    fn skip_arm(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek_type() {
+                TokenType::Eof => break,
+                TokenType::Comma if depth == 0 => {
+                    self.next();
+                    break;
+                }
+                TokenType::RightBrace if depth == 0 => break,
+                TokenType::LeftBrace | TokenType::LeftParen => depth += 1,
+                TokenType::RightBrace | TokenType::RightParen => depth -= 1,
                 _ => {}
             }
-            current_token = self.next();
+            self.next();
         }
+    }
 
-        // Pop all operators from the stack
-        while let Some(top_token) = operator_stack.pop() {
-            let mut node = Node::new(NodeType::Expression, Some(top_token.as_string()), top_token.line);
-            let right = output_queue.pop().unwrap();
-            let left = output_queue.pop().unwrap();
+    // a literal, `_`, a name to bind, or a struct or a variant with patterns
+    // for its fields
+    fn parse_pattern(&mut self) -> Node {
+        let Some(token) = self.peek().cloned() else {
+            let span = self.previous_span();
+            return self.error_here(error::expected_pattern(span));
+        };
+        if let Some(path) = prelude_enum(&token.token_type) {
+            return self.parse_construct(path.to_string(), token.line, token.span, true);
+        }
+        match token.token_type {
+            TokenType::Number(_) | TokenType::Character(_) | TokenType::True | TokenType::False => {
+                self.parse_primary()
+            }
+            TokenType::Operator(ref op) if op == "Sub" => {
+                let negative = self.parse_unary();
+                if negative.children[0].is(NodeType::Number) {
+                    return negative;
+                }
+                self.diagnostics.push(error::expected_pattern(negative.span()));
+                Node::new(NodeType::Err, None, token.line, negative.span())
+            }
+            TokenType::Identifier(ref name) if name == "_" => {
+                self.next();
+                self.node(NodeType::Wildcard, None, &token)
+            }
+            TokenType::Identifier(ref name) => {
+                self.next();
+                let Some((path, span)) = self.parse_dotted(name.clone(), token.span) else {
+                    return Node::new(NodeType::Err, None, token.line, self.previous_span());
+                };
+                if self.eat(&TokenType::DoubleColon).is_some() {
+                    return self.parse_construct(path, token.line, span, true);
+                }
+                if self.check(&TokenType::LeftBrace) && self.same_line() {
+                    return self.parse_struct_pattern(path, &token, span);
+                }
+                if path.contains('.') {
+                    self.diagnostics.push(error::expected_pattern(span));
+                    return Node::new(NodeType::Err, None, token.line, span);
+                }
+                self.node(NodeType::Binding, Some(path), &token)
+            }
+            _ => self.error_here(error::expected_pattern(token.span)),
+        }
+    }
+
+    // the rest of a name from another module, foo.bar.baz, the first part has been consumed
+    fn parse_dotted(&mut self, mut name: String, mut span: Span) -> Option<(String, Span)> {
+        while self.check(&TokenType::Dot) && self.same_line() {
+            self.next();
+            //`t.0`, a value of a tuple
+            let (TokenType::Identifier(part) | TokenType::Number(part)) = self.peek_type() else {
+                let span = self.peek_span();
+                self.diagnostics.push(error::expected_variable_name(span));
+                return None;
+            };
+            self.next();
+            name = format!("{}.{}", name, part);
+            span = span.to(self.previous_span());
+        }
+        Some((name, span))
+    }
+
+    // the name of a variant, `None` is lexed on its own
+    fn peek_variant(&mut self) -> Option<String> {
+        match self.peek_type() {
+            TokenType::Identifier(name) => Some(name),
+            TokenType::None => Some("None".to_string()),
+            _ => None,
+        }
+    }
+
+    // Shape::Circle(value), Shape::Rect { w: value } or Shape::Empty, with
+    // patterns instead of values in a match arm, the `::` has been consumed
+    fn parse_construct(&mut self, path: String, line: usize, span: Span, pattern: bool) -> Node {
+        let Some(variant) = self.peek_variant() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_variant(span));
+        };
+        self.next();
+        let path = format!("{}::{}", path, variant);
+        let mut node = Node::new(NodeType::Construct, Some(path.clone()), line, span);
+        let what = format!("`{}`", path);
+        if self.check(&TokenType::LeftParen) && self.same_line() {
+            let open = self.next().unwrap();
+            node.children = if pattern {
+                self.parse_list(&open, &what, |p| p.parse_pattern())
+            } else {
+                self.parse_list(&open, &what, |p| p.parse_expression())
+            };
+        } else if self.check(&TokenType::LeftBrace) && self.same_line() && !self.no_struct {
+            self.next();
+            node.children = if pattern {
+                self.parse_field_patterns(&what)
+            } else {
+                let no_struct = std::mem::replace(&mut self.no_struct, false);
+                let fields = self.parse_braced(&what, |p| {
+                    p.parse_field(Some(NodeType::Identifier), Parser::parse_expression)
+                });
+                self.no_struct = no_struct;
+                fields
+            };
+        }
+        node.span = span.to(self.previous_span());
+        node
+    }
+
+    // Pair { first: value, second } with the name consumed
+    fn parse_struct_literal(&mut self, name: &str, token: &Token) -> Node {
+        self.next();
+        let mut node = self.node(NodeType::StructLiteral, Some(name.to_string()), token);
+        let what = format!("`{}`", name);
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        node.children = self.parse_braced(&what, |p| {
+            p.parse_field(Some(NodeType::Identifier), Parser::parse_expression)
+        });
+        self.no_struct = no_struct;
+        node.span = token.span.to(self.previous_span());
+        node
+    }
+
+    // `P { x: 0, y }` matching a struct, with the name consumed
+    fn parse_struct_pattern(&mut self, name: String, token: &Token, span: Span) -> Node {
+        self.next();
+        let what = format!("`{}`", name);
+        let mut node = self.node(NodeType::StructLiteral, Some(name), token);
+        node.children = self.parse_field_patterns(&what);
+        node.span = span.to(self.previous_span());
+        node
+    }
+
+    // the fields of a struct or a variant in a pattern, the `{` has been consumed
+    fn parse_field_patterns(&mut self, what: &str) -> Vec<Node> {
+        self.parse_braced(what, |p| {
+            if p.check(&TokenType::DotDot) {
+                return p.parse_rest();
+            }
+            p.parse_field(Some(NodeType::Binding), Parser::parse_pattern)
+        })
+    }
+
+    // `..` in a struct pattern, the fields it doesn't name can be anything
+    fn parse_rest(&mut self) -> Node {
+        let token = self.next().unwrap();
+        self.node(NodeType::Wildcard, Some("..".to_string()), &token)
+    }
+
+    // An expression, or an assignment to a name
+    fn parse_expression_statement(&mut self) -> Node {
+        let target = self.parse_expression();
+        let operator = match self.peek_type() {
+            TokenType::Operator(op) if is_assignment(&op) => op,
+            _ => return target,
+        };
+        let token = self.next().unwrap();
+        let value = self.parse_expression();
+        if !is_place(&target) {
+            let diagnostic = error::invalid_assignment_target(target.span(), token.span);
+            return self.error(diagnostic, &token);
+        }
+        let span = target.span().to(value.span());
+        let mut node = Node::new(NodeType::Reassignment, Some(operator), target.line, span);
+        node.add_child(target);
+        node.add_child(value);
+        node
+    }
+
+    // Parse an expression
+    pub fn parse_expression(&mut self) -> Node {
+        self.parse_binary(1)
+    }
+
+    // precedence climbing, `^` is the only right associative operator
+    fn parse_binary(&mut self, min: u8) -> Node {
+        let mut left = self.parse_cast();
+        while let TokenType::Operator(op) = self.peek_type() {
+            let Some(precedence) = get_precedence(&op) else { break };
+            //a line starting with `*` or `&` is a dereference or an address
+            if precedence < min || (matches!(op.as_str(), "Mul" | "And") && !self.same_line()) {
+                break;
+            }
+            self.next();
+            let next = if op == "Pow" { precedence } else { precedence + 1 };
+            let right = self.parse_binary(next);
+            let span = left.span().to(right.span());
+            let mut node = Node::new(NodeType::Expression, Some(op), left.line, span);
             node.add_child(left);
             node.add_child(right);
-            output_queue.push(node);
+            left = node;
+        }
+        left
+    }
+
+    // `value as u8`, which binds tighter than any binary operator and looser
+    // than the unary ones, `-x as u64` converts `-x`
+    fn parse_cast(&mut self) -> Node {
+        let mut node = self.parse_unary();
+        while self.check_keyword("as") && self.same_line() {
+            self.next();
+            let ty = self.parse_type();
+            let span = node.span().to(ty.span());
+            let mut cast = Node::new(NodeType::Cast, None, node.line, span);
+            cast.children = vec![node, ty];
+            node = cast;
+        }
+        node
+    }
+
+    // -x, !x, the address &x and the value *p that p points to
+    fn parse_unary(&mut self) -> Node {
+        if ["Sub", "Not", "And", "Mul"].iter().any(|op| self.check_operator(op)) {
+            let token = self.next().unwrap();
+            let operand = self.parse_unary();
+            let mut node = self.node(NodeType::UnaryExpression, Some(token.as_string()), &token);
+            node.span = token.span.to(operand.span());
+            node.add_child(operand);
+            return node;
+        }
+        self.parse_postfix()
+    }
+
+    // a value and the indexes after it on the same line, `grid[1][2]` or
+    // `a[1..3]`, `?` to return early unless it holds a value, `parse(text)?`,
+    // or a field or method of it, `divmod(a, b).0` or `V::new().get()`
+    fn parse_postfix(&mut self) -> Node {
+        let mut node = self.parse_primary();
+        loop {
+            if self.check(&TokenType::Dot) && self.same_line() {
+                node = self.parse_member(node);
+                continue;
+            }
+            if self.check(&TokenType::Question) && self.same_line() {
+                let mark = self.next().unwrap();
+                let mut tried = Node::new(NodeType::Try, None, node.line, node.span().to(mark.span));
+                tried.add_child(node);
+                node = tried;
+                continue;
+            }
+            if !self.check(&TokenType::LeftBracket) || !self.same_line() {
+                break;
+            }
+            let open = self.next().unwrap();
+            let no_struct = std::mem::replace(&mut self.no_struct, false);
+            let index = self.parse_range();
+            self.no_struct = no_struct;
+            self.expect_bracket("the index", &open);
+            let span = node.span().to(self.previous_span());
+            let mut indexed = Node::new(NodeType::Index, None, node.line, span);
+            indexed.children = vec![node, index];
+            node = indexed;
+        }
+        node
+    }
+
+    // `.name`, `.0` or `.name(args)` after a value that is not a name, a
+    // name takes its fields and methods in `parse_dotted`
+    fn parse_member(&mut self, object: Node) -> Node {
+        self.next();
+        let (TokenType::Identifier(name) | TokenType::Number(name)) = self.peek_type() else {
+            let span = self.peek_span();
+            return self.error_here(error::expected_field_name(span));
+        };
+        let method = matches!(self.peek_type(), TokenType::Identifier(_));
+        self.next();
+        let line = object.line;
+        let mut children = vec![object];
+        let node_type = if method && self.check(&TokenType::LeftParen) && self.same_line() {
+            let open = self.next().unwrap();
+            let what = format!("the call to `{}`", name);
+            children.extend(self.parse_list(&open, &what, |p| p.parse_expression()));
+            NodeType::MethodCall
+        } else {
+            NodeType::Member
+        };
+        let mut node = Node::new(node_type, Some(name), line, children[0].span().to(self.previous_span()));
+        node.children = children;
+        node
+    }
+
+    // `a..b`, either end can be left out, or just a value
+    fn parse_range(&mut self) -> Node {
+        let start = (!self.check(&TokenType::DotDot)).then(|| self.parse_expression());
+        let Some(dots) = self.eat(&TokenType::DotDot) else {
+            return start.unwrap();
+        };
+        let open = matches!(self.peek_type(), TokenType::RightBracket | TokenType::RightParen | TokenType::LeftBrace);
+        let end = (!open).then(|| self.parse_expression());
+        let form = format!("{}..{}", if start.is_some() { "a" } else { "" }, if end.is_some() { "b" } else { "" });
+        let first = start.as_ref().map_or(dots.span, Node::span);
+        let last = end.as_ref().map_or(dots.span, Node::span);
+        let mut node = Node::new(NodeType::Range, Some(form), dots.line, first.to(last));
+        node.children = start.into_iter().chain(end).collect();
+        node
+    }
+
+    // [1, 2, 3], or [0; 4] for four zeros
+    fn parse_array(&mut self) -> Node {
+        let open = self.next().unwrap();
+        let mut node = self.node(NodeType::ArrayLiteral, None, &open);
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        let mut items = Vec::new();
+        if !self.check(&TokenType::RightBracket) && !self.at_eof() {
+            let first = self.parse_expression();
+            if self.eat(&TokenType::Semicolon).is_some() {
+                let count = self.parse_expression();
+                node.value = Some("repeat".to_string());
+                items = vec![first, count];
+                self.expect_bracket("the array", &open);
+            } else {
+                items.push(first);
+            }
+        }
+        if node.value.is_none() {
+            items = self.parse_until(&TokenType::RightBracket, &open, "the array", items, |p| p.parse_expression());
+        }
+        self.no_struct = no_struct;
+        node.children = items;
+        node.span = open.span.to(self.previous_span());
+        node
+    }
+
+    // "x = {x}", the lexer hands over the text and the tokens of each hole
+    fn parse_format(&mut self) -> Node {
+        let start = self.next().unwrap();
+        let mut node = self.node(NodeType::Format, None, &start);
+        while let Some(token) = self.next() {
+            match token.token_type {
+                TokenType::Str(ref text) => node.add_child(self.node(NodeType::String, Some(text.clone()), &token)),
+                TokenType::LeftBrace => {
+                    node.add_child(self.parse_expression());
+                    if self.eat(&TokenType::RightBrace).is_none() {
+                        let span = self.peek_span();
+                        self.diagnostics.push(error::expected_right_brace_in_string(span, token.span));
+                        //skip the rest of the hole
+                        while !matches!(self.peek_type(), TokenType::RightBrace | TokenType::FormatEnd | TokenType::Eof) {
+                            self.next();
+                        }
+                        self.eat(&TokenType::RightBrace);
+                    }
+                }
+                _ => break,
+            }
+        }
+        node
+    }
+
+    fn expect_bracket(&mut self, what: &str, open: &Token) {
+        if self.eat(&TokenType::RightBracket).is_none() {
+            let span = self.peek_span();
+            self.diagnostics.push(error::expected_right_bracket(what, span, open.span));
+        }
+    }
+
+    fn parse_primary(&mut self) -> Node {
+        let Some(token) = self.peek().cloned() else {
+            let span = self.previous_span();
+            return self.error_here(error::expected_expression(span));
+        };
+        if let Some(path) = prelude_enum(&token.token_type) {
+            return self.parse_construct(path.to_string(), token.line, token.span, false);
+        }
+        match token.token_type {
+            TokenType::Number(ref n) => {
+                self.next();
+                self.node(NodeType::Number, Some(n.clone()), &token)
+            }
+            TokenType::Float(ref n) => {
+                self.next();
+                self.node(NodeType::Float, Some(n.clone()), &token)
+            }
+            TokenType::Str(ref s) => {
+                self.next();
+                self.node(NodeType::String, Some(s.clone()), &token)
+            }
+            TokenType::FormatStart => self.parse_format(),
+            TokenType::Character(ref c) => {
+                self.next();
+                self.node(NodeType::Character, Some(c.clone()), &token)
+            }
+            TokenType::True | TokenType::False => {
+                self.next();
+                let value = if token.token_type == TokenType::True { "true" } else { "false" };
+                self.node(NodeType::Bool, Some(value.to_string()), &token)
+            }
+            TokenType::Keyword(ref kw) if kw == "match" => self.parse_match(),
+            TokenType::Keyword(ref kw) if kw == "loop" => self.parse_loop(),
+            TokenType::Keyword(ref kw) if kw == "def" => self.parse_lambda(),
+            TokenType::Keyword(ref kw) if kw == "null" => {
+                let token = self.next().unwrap();
+                self.node(NodeType::Null, None, &token)
+            }
+            TokenType::LeftBracket => self.parse_array(),
+            TokenType::Identifier(ref name) => {
+                self.next();
+                //a name from another module, foo.bar.baz
+                let mut token = token.clone();
+                let Some((name, span)) = self.parse_dotted(name.clone(), token.span) else {
+                    return Node::new(NodeType::Err, None, token.line, self.previous_span());
+                };
+                token.span = span;
+                let mut type_args = Vec::new();
+                if self.eat(&TokenType::DoubleColon).is_some() {
+                    if !self.check_operator("Less") {
+                        return self.parse_construct(name, token.line, span, false);
+                    }
+                    //the type arguments of a generic function, max::<i64>(a, b)
+                    let open = self.next().unwrap();
+                    let what = format!("the type arguments of `{}`", name);
+                    type_args = self.parse_angled(&open, &what, |p| p.parse_type());
+                    if !self.check(&TokenType::LeftParen) {
+                        let span = self.peek_span();
+                        return self.error_here(error::expected_left_paren("the type arguments", span));
+                    }
+                }
+                let name = &name;
+                // This could be a function call or a variable
+                if self.check(&TokenType::LeftParen) && self.same_line() {
+                    let open = self.next().unwrap();
+                    let mut node = self.node(NodeType::FunctionCall, Some(name.clone()), &token);
+                    let what = format!("the call to `{}`", name);
+                    node.children = type_args;
+                    node.children.extend(self.parse_list(&open, &what, |p| p.parse_expression()));
+                    node.span = token.span.to(self.previous_span());
+                    return node;
+                }
+                if self.check(&TokenType::LeftBrace) && self.same_line() && !self.no_struct {
+                    return self.parse_struct_literal(name, &token);
+                }
+                self.node(NodeType::Identifier, Some(name.clone()), &token)
+            }
+            TokenType::LeftParen => {
+                // A parenthesized expression is just the expression inside
+                let open = self.next().unwrap();
+                let no_struct = std::mem::replace(&mut self.no_struct, false);
+                let inner = self.parse_expression();
+                //`(a, b)` is a tuple
+                if self.check(&TokenType::Comma) {
+                    let mut node = self.node(NodeType::Tuple, None, &open);
+                    node.children = self.parse_until(&TokenType::RightParen, &open, "the tuple", vec![inner], |p| p.parse_expression());
+                    node.span = open.span.to(self.previous_span());
+                    self.no_struct = no_struct;
+                    return node;
+                }
+                self.no_struct = no_struct;
+                if self.eat(&TokenType::RightParen).is_none() {
+                    let span = self.peek_span();
+                    self.diagnostics
+                        .push(error::expected_right_paren("the expression", span, open.span));
+                }
+                inner
+            }
+            TokenType::Eof | TokenType::RightBrace | TokenType::RightParen => {
+                self.error_here(error::expected_expression(token.span))
+            }
+            _ => {
+                self.next();
+                let text = self.source.get(token.span.start..token.span.end).unwrap_or_default();
+                let diagnostic = error::unexpected_token(text, token.span);
+                self.error(diagnostic, &token)
+            }
         }
-        // The final node should be the root of the expression tree
-        output_queue.pop().unwrap()
     }
 }
 
-fn drop<T>(_x: T) {
-    // This function is used to drop the value of x
-    // so that the compiler does not throw a warning
-    // about unused variables
-}
\ No newline at end of file
+// Binding power of the binary operators, higher binds tighter
+fn get_precedence(op: &str) -> Option<u8> {
+    match op {
+        "OrOr" => Some(1),
+        "AndAnd" => Some(2),
+        "EqEq" | "NotEq" => Some(3),
+        "Less" | "LessEq" | "Greater" | "GreaterEq" => Some(4),
+        "Or" => Some(5),
+        "And" => Some(6),
+        "Add" | "Sub" => Some(7),
+        "Mul" | "Div" | "Mod" => Some(8),
+        "Pow" => Some(9),
+        _ => None,
+    }
+}
+
+// The enum of a variant of the prelude written without it, `Some(x)`, `None`,
+// `Ok(x)` and `Err(e)`
+fn prelude_enum(token: &TokenType) -> Option<&'static str> {
+    match token {
+        TokenType::None => Some("Option"),
+        TokenType::Identifier(name) if name == "Some" => Some("Option"),
+        TokenType::Identifier(name) if name == "Ok" || name == "Err" => Some("Result"),
+        _ => None,
+    }
+}
+
+//what can be assigned to, a variable, one of its fields or an element
+fn is_place(node: &Node) -> bool {
+    match node.node_type {
+        NodeType::Identifier => true,
+        NodeType::UnaryExpression => node.value() == "Mul",
+        NodeType::Index => is_place(&node.children[0]) && !node.children[1].is(NodeType::Range),
+        NodeType::Member => is_place(&node.children[0]),
+        _ => false,
+    }
+}
+
+fn is_assignment(op: &str) -> bool {
+    matches!(
+        op,
+        "Eq" | "AddEq" | "SubEq" | "MulEq" | "DivEq" | "ModEq" | "PowEq" | "AndEq" | "OrEq"
+    )
+}
@@ -1,8 +1,100 @@
+use crate::compiler::types::CraneType;
+use crate::diagnostic::Span;
 //create an enum for the node types
-#[derive(Debug, Clone)]
+//
+//the children of each kind, `?` marks an optional child:
+//  Function      value: name      [Attribute*, Keyword("pub")?, Keyword("const")?, TypeParam*,
+//                                 Param*, Type?, Block?]
+//                                 (Type is the return type, only `@native` functions
+//                                 have no Block, `const def` can be called when compiling)
+//  Param         value: name      [Type?]
+//  Assignment    value: name      [Type?, value]           (let)
+//  Const         value: name      [Keyword("pub")?, Type?, value]  (const N: u32 = 4)
+//  Static        value: name      [Keyword("pub")?, Type?, value]  (static COUNT: i64 = 0)
+//  Destructure                    [Tuple, Type?, value]    (let (a, b) = value, the Tuple
+//                                 holds Bindings, Wildcards and Tuples)
+//  Reassignment  value: operator  [Identifier | Index | UnaryExpression, value]
+//                                 (= += -= ..., to a variable, an element or `*p`)
+//  Return                         [value?]
+//  Conditional   value: "if"      [condition, Block, Block | Conditional ?]
+//  Loop          value: "for"     [Binding, value, Block]  (for x in values { ... }, or
+//                                 over the integers of a Range, for i in 0..n { ... })
+//                or  value: "while" [condition, Block]     (while (condition) { ... })
+//                or  value: "for(;;)" [init, condition, step, Block]
+//                                 (for (let i = 0; i < n; i += 1) { ... }, an init or
+//                                 step that is left out is an empty Block)
+//                or  value: "loop" [Block]               (loop { ... }, also a value, that
+//                                 of its `break`s)
+//  Break                          [value?]                 (break, or break value in `loop`)
+//  Continue                                                (continue)
+//  Block                          [statement*]
+//                or  value: "unsafe" [statement*]        (unsafe { ... })
+//  Expression    value: operator  [left, right]
+//  UnaryExpression value: operator [operand]             (-x, !x, &x or *p)
+//  Null                                                    (null, a pointer to nothing)
+//  Float         value: text                               (2.5 or 1e-3, as it is written)
+//  Format                         [String | value ...]     ("x = {x}", the text and the
+//                                 values of the holes in order)
+//  FunctionCall  value: name      [Type*, argument*]       (the Types are those of `max::<i64>(x)`)
+//  Attribute     value: level     [Identifier*]            (@allow(name))
+//  Type          value: name      [Type*]  or  value: "*" [Type]  (Pair<i32>, a pointer)
+//                or  value: "dyn" [Type]                 (a trait object, dyn Shape)
+//                or  value: "def" [Type*, Type]          (a function, def(i32) -> bool, the
+//                                 last Type is the return type, void when there is none)
+//                or  value: "[]"  [Type, length?]        (an array, [u16; 4], or a slice, [u16])
+//                or  value: "()"  [Type*]                (a tuple, (u16, bool))
+//  TypeParam     value: name      [Identifier*]            (T: Integer + Eq, a bound by a
+//                                 generic trait has its type arguments, I: Iterator<T>)
+//  Import        value: path      [Identifier*]            (import a.b, from a.b import c)
+//  Enum          value: name      [Keyword("pub")?, TypeParam*, Variant*]
+//  Variant       value: name      [Type*]  or  [Field*], then Discriminant?
+//                                 (Circle(i32), Rect { w: i32 }, Red = 1)
+//  Discriminant                   [value]                  (the tag of a variant)
+//  Field         value: name      [Type | value | pattern]
+//  Construct     value: path      [value*]  or  [Field*]   (Shape::Circle(1), Shape::Rect { w: 1 })
+//                                 (or a call of a function of the type, Square::new(1). The
+//                                 variants of the prelude are written alone, `Some(1)` is
+//                                 `Option::Some(1)`, `Err(e)` is `Result::Err(e)`)
+//  Match                          [value, Arm*]
+//  Arm                            [pattern, Guard?, Block | value]
+//  Guard                          [condition]              (if condition)
+//  Struct        value: name      [Keyword("pub")?, TypeParam*, Field*]
+//  StructLiteral value: name      [Field*]                 (Pair { a: 1, b: 2 })
+//  Trait         value: name      [Keyword("pub")?, TypeParam*, Function*]
+//                                 (a method without a Block has to be implemented,
+//                                 one with a Block is a default)
+//  Lambda                         [Param*, Type?, Block]   (def (x: i32) -> i32 { ... } as a value)
+//  ArrayLiteral                   [value*]                 ([1, 2, 3])
+//                or  value: "repeat" [value, length]     ([0; 4])
+//  Tuple                          [value*]                 ((1, true))
+//  Index                          [value, index | Range]   (a[i], or the slice a[1..3])
+//  Try                            [value]                  (value?, an Option or a Result)
+//  Cast                           [value, Type]            (value as u8)
+//  Range         value: form      [start?, end?]           (a..b, where the form, "a..b",
+//                                 "a..", "..b" or "..", says which ends there are)
+//  Impl          value: trait     [Type, TypeParam*, Type*, Function*]
+//                                 (impl Shape for Square, the Types after the
+//                                 TypeParams are the type arguments of the trait,
+//                                 impl<T> Iterator<T> for Repeat<T>)
+//                or  no value     [Type, TypeParam*, Function*]
+//                                 (the methods of the type, impl Square)
+//
+//the first parameter of a method is `self`, without a type
+//
+//a pattern is a Number, a UnaryExpression("Sub") of a Number, a Character, a Bool,
+//a Binding (value: name), a Wildcard (`_`), or a Construct whose children are
+//patterns, where a Wildcard with the value ".." stands for the fields not named
+//
+//a dotted Identifier is a name from another module, or when it starts with a
+//variable the fields of that variable, `p.a.b`. A dotted FunctionCall that
+//starts with a variable calls a method, `p.a.area()`
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NodeType {
     Number,
+    Float,
     String,
+    Character,
+    Bool,
     Identifier,
     Operator,
     Keyword,
@@ -16,22 +108,70 @@ pub enum NodeType {
     Assignment,
     Reassignment,
     Function,
+    Param,
+    Return,
     Block,
     Loop,
+    Break,
+    Continue,
     Conditional,
+    Attribute,
+    Type,
+    Import,
+    Enum,
+    Variant,
+    Field,
+    Construct,
+    Match,
+    Arm,
+    Guard,
+    Binding,
+    Wildcard,
+    Struct,
+    StructLiteral,
+    TypeParam,
+    Trait,
+    Impl,
+    Lambda,
+    ArrayLiteral,
+    Index,
+    Range,
+    Null,
+    Format,
+    Tuple,
+    Destructure,
+    Try,
+    Const,
+    Static,
+    Discriminant,
+    Cast,
+    Member,
+    MethodCall,
 }
 //create a node struct
 #[derive(Debug, Clone)]
 pub struct Node {
-    node_type: NodeType,
-    value: Option<String>,
-    line: usize,
-    children: Vec<Node>,
+    pub node_type: NodeType,
+    pub value: Option<String>,
+    pub line: usize,
+    pub span: Span,
+    pub children: Vec<Node>,
+    //filled in by the type checker
+    pub ty: Option<CraneType>,
+    //the type arguments of a call to a generic function, filled in by the
+    //type checker for the instantiation it calls
+    pub type_args: Option<Vec<CraneType>>,
+    //the function a method call or an overloaded operator calls, filled in
+    //by the type checker
+    pub target: Option<usize>,
+    //the trait object a value is turned into where one is expected, filled
+    //in by the type checker
+    pub coercion: Option<CraneType>,
 }
 //create a tree struct
 #[derive(Debug, Clone)]
 pub struct Tree {
-    nodes: Vec<Node>,
+    pub nodes: Vec<Node>,
 }
 
 impl Tree {
@@ -43,15 +183,122 @@ impl Tree {
     }
 }
 impl Node {
-    pub fn new(node_type: NodeType, value: Option<String>, line: usize) -> Self {
+    pub fn new(node_type: NodeType, value: Option<String>, line: usize, span: Span) -> Self {
         Self {
             node_type,
             value,
             line,
+            span,
             children: Vec::new(),
+            ty: None,
+            type_args: None,
+            target: None,
+            coercion: None,
         }
     }
     pub fn add_child(&mut self, child: Node) {
         self.children.push(child);
     }
-}
\ No newline at end of file
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn value(&self) -> &str {
+        self.value.as_deref().unwrap_or("")
+    }
+    pub fn is(&self, node_type: NodeType) -> bool {
+        self.node_type == node_type
+    }
+    //the first child of the given kind
+    pub fn child(&self, node_type: NodeType) -> Option<&Node> {
+        self.children.iter().find(|c| c.node_type == node_type)
+    }
+    //the last child, the value of lets and reassignments and the body of functions
+    pub fn last(&self) -> Option<&Node> {
+        self.children.last()
+    }
+    pub fn attributes(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|c| c.is(NodeType::Attribute))
+    }
+    pub fn params(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|c| c.is(NodeType::Param))
+    }
+    pub fn type_params(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|c| c.is(NodeType::TypeParam))
+    }
+    //a function or struct with type parameters
+    pub fn is_generic(&self) -> bool {
+        self.type_params().next().is_some()
+    }
+    //the arguments of a call, without the type arguments
+    pub fn arguments(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|c| !c.is(NodeType::Type))
+    }
+    pub fn arguments_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.children.iter_mut().filter(|c| !c.is(NodeType::Type))
+    }
+    //a function implemented by the VM
+    pub fn is_native(&self) -> bool {
+        self.attributes().any(|a| a.value() == "native")
+    }
+    //the body of a function
+    pub fn body(&self) -> Option<&Node> {
+        self.child(NodeType::Block)
+    }
+    //the methods of a trait or impl
+    pub fn methods(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|c| c.is(NodeType::Function))
+    }
+    //a `const def`, which can be called when compiling
+    pub fn is_const(&self) -> bool {
+        self.children
+            .iter()
+            .any(|c| c.is(NodeType::Keyword) && c.value() == "const")
+    }
+    //whether a function can be used from other modules
+    pub fn is_pub(&self) -> bool {
+        self.children
+            .iter()
+            .any(|c| c.is(NodeType::Keyword) && c.value() == "pub")
+    }
+    //the declared return type of a function
+    pub fn return_type(&self) -> Option<&Node> {
+        self.child(NodeType::Type)
+    }
+    //whether control never falls through the end of a statement
+    pub fn diverges(&self) -> bool {
+        match self.node_type {
+            NodeType::Return | NodeType::Break | NodeType::Continue => true,
+            NodeType::Block => self.children.iter().any(Node::diverges),
+            NodeType::Conditional => {
+                self.children.len() == 3 && self.children[1].diverges() && self.children[2].diverges()
+            }
+            //every arm ends in a block that diverges
+            NodeType::Match => {
+                self.children.len() > 1
+                    && self.children[1..]
+                        .iter()
+                        .all(|arm| arm.last().is_some_and(Node::diverges))
+            }
+            //`loop` without a `break` of its own never ends
+            NodeType::Loop => self.value() == "loop" && !self.children[0].breaks(),
+            _ => false,
+        }
+    }
+    //the condition checked before every time around a `while` or C style `for`
+    pub fn loop_condition(&self) -> Option<&Node> {
+        match (&self.node_type, self.value()) {
+            (NodeType::Loop, "while") => self.children.first(),
+            (NodeType::Loop, "for(;;)") => self.children.get(1),
+            _ => None,
+        }
+    }
+    //whether a `break` in this node leaves the loop it is in, the ones in
+    //nested loops and function expressions leave those
+    pub fn breaks(&self) -> bool {
+        match self.node_type {
+            NodeType::Break => true,
+            NodeType::Loop | NodeType::Lambda => false,
+            _ => self.children.iter().any(Node::breaks),
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use crate::compiler::asm::Op;
+use crate::compiler::types::CraneType;
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+//a cache entry is a stream of words and quoted strings separated by spaces,
+//strings escape their line breaks so every entry stays on one line
+#[derive(Default)]
+pub struct Writer {
+    pub out: String,
+}
+
+impl Writer {
+    pub fn word(&mut self, word: impl Display) {
+        self.space();
+        self.out.push_str(&word.to_string());
+    }
+    pub fn string(&mut self, s: &str) {
+        self.space();
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+    pub fn ty(&mut self, ty: Option<&CraneType>) {
+        match ty {
+            Some(ty) => self.string(&type_text(ty)),
+            None => self.word("?"),
+        }
+    }
+    fn space(&mut self) {
+        if !self.out.is_empty() {
+            self.out.push(' ');
+        }
+    }
+}
+
+//reads what a `Writer` wrote, `None` means the file is damaged and the whole
+//cache is thrown away
+pub struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+        }
+    }
+    fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+    pub fn done(&mut self) -> bool {
+        self.skip_space();
+        self.chars.peek().is_none()
+    }
+    pub fn word(&mut self) -> Option<String> {
+        self.skip_space();
+        let mut word = String::new();
+        while let Some(c) = self.chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+            word.push(c);
+        }
+        (!word.is_empty()).then_some(word)
+    }
+    pub fn number<T: FromStr>(&mut self) -> Option<T> {
+        self.word()?.parse().ok()
+    }
+    pub fn string(&mut self) -> Option<String> {
+        self.skip_space();
+        self.chars.next_if_eq(&'"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(s),
+                '\\' => s.push(match self.chars.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    c => c,
+                }),
+                c => s.push(c),
+            }
+        }
+    }
+    //a type written by `Writer::ty`, the outer option is whether it could be read
+    pub fn ty(&mut self) -> Option<Option<CraneType>> {
+        self.skip_space();
+        if self.chars.peek() == Some(&'?') {
+            self.word();
+            return Some(None);
+        }
+        parse_type(&self.string()?).map(Some)
+    }
+}
+
+//a type as it is displayed: `i32`, `*char`, `[u8; 4]`, except that enums
+//and structs are written with their module, `%shapes.Shape`, and tuples
+//start with `#`
+fn type_text(ty: &CraneType) -> String {
+    match ty {
+        CraneType::Pointer { pointee } => format!("*{}", type_text(pointee)),
+        CraneType::Array { element, size } => format!("[{}; {}]", type_text(element), size),
+        CraneType::Slice { element } => format!("[{}]", type_text(element)),
+        CraneType::Tuple { elements } => {
+            let elements: Vec<String> = elements.iter().map(type_text).collect();
+            format!("#({})", elements.join(", "))
+        }
+        CraneType::Enum { name, args } if args.is_empty() => format!("%{}", name),
+        CraneType::Struct { name, args } if args.is_empty() => format!("&{}", name),
+        CraneType::Enum { name, args } | CraneType::Struct { name, args } => {
+            let sigil = if matches!(ty, CraneType::Enum { .. }) { '%' } else { '&' };
+            let args: Vec<String> = args.iter().map(type_text).collect();
+            format!("{}{}<{}>", sigil, name, args.join(", "))
+        }
+        CraneType::Param { name } => format!("'{}", name),
+        CraneType::Dyn { name } => format!("^{}", name),
+        CraneType::Function { params, ret } => {
+            let params: Vec<String> = params.iter().map(type_text).collect();
+            format!("({}){}", params.join(", "), type_text(ret))
+        }
+        CraneType::Closure { params, ret } => {
+            let params: Vec<String> = params.iter().map(type_text).collect();
+            format!("$({}){}", params.join(", "), type_text(ret))
+        }
+        ty => ty.to_string(),
+    }
+}
+
+//the counterpart of `type_text`
+pub fn parse_type(text: &str) -> Option<CraneType> {
+    if let Some(text) = text.strip_prefix('%') {
+        let Some((name, args)) = text.split_once('<') else {
+            return Some(CraneType::Enum { name: text.to_string(), args: Vec::new() });
+        };
+        let args = parse_list(args.strip_suffix('>')?)?;
+        return Some(CraneType::Enum { name: name.to_string(), args });
+    }
+    if let Some(name) = text.strip_prefix('\'') {
+        return Some(CraneType::Param { name: name.to_string() });
+    }
+    if let Some(name) = text.strip_prefix('^') {
+        return Some(CraneType::Dyn { name: name.to_string() });
+    }
+    if let Some(text) = text.strip_prefix('&') {
+        let Some((name, args)) = text.split_once('<') else {
+            return Some(CraneType::Struct { name: text.to_string(), args: Vec::new() });
+        };
+        let args = parse_list(args.strip_suffix('>')?)?;
+        return Some(CraneType::Struct { name: name.to_string(), args });
+    }
+    if let Some(elements) = text.strip_prefix("#(").and_then(|t| t.strip_suffix(')')) {
+        return Some(CraneType::Tuple { elements: parse_list(elements)? });
+    }
+    let (closure, function) = match text.strip_prefix('$') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    if let Some(function) = function.strip_prefix('(') {
+        //the parameters end at the parenthesis that closes the first one
+        let mut depth = 1;
+        let end = function.char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        })?;
+        let params = parse_list(&function[..end])?;
+        let ret = Box::new(parse_type(&function[end + 1..])?);
+        return Some(match closure {
+            true => CraneType::Closure { params, ret },
+            false => CraneType::Function { params, ret },
+        });
+    }
+    if let Some(pointee) = text.strip_prefix('*') {
+        return Some(CraneType::Pointer {
+            pointee: Box::new(parse_type(pointee)?),
+        });
+    }
+    if let Some(array) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        //a slice of arrays, `[[u8; 4]]`, ends in a bracket instead of a size
+        let sized = array.rsplit_once("; ").and_then(|(element, size)| Some((element, size.parse().ok()?)));
+        return Some(match sized {
+            Some((element, size)) => CraneType::Array {
+                element: Box::new(parse_type(element)?),
+                size,
+            },
+            None => CraneType::Slice {
+                element: Box::new(parse_type(array)?),
+            },
+        });
+    }
+    CraneType::from_name(text)
+}
+
+//split a list of types at the commas outside of nested `<>`, `[]` and `()`
+fn parse_list(text: &str) -> Option<Vec<CraneType>> {
+    if text.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut parsed = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' | '[' | '(' => depth += 1,
+            '>' | ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parsed.push(parse_type(text[start..i].trim())?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parsed.push(parse_type(text[start..].trim())?);
+    Some(parsed)
+}
+
+//the instructions that refer to other functions, globals, strings or code
+//positions are written by the caller with what they refer to instead
+pub fn write_op(w: &mut Writer, op: &Op) {
+    let b = |b: &bool| *b as u8;
+    match op {
+        Op::Push(value) => {
+            w.word("push");
+            w.word(value);
+        }
+        Op::Pop => w.word("pop"),
+        Op::Dup => w.word("dup"),
+        Op::LocalAddr(offset) => {
+            w.word("local");
+            w.word(offset);
+        }
+        Op::Load { size, signed } => {
+            w.word("load");
+            w.word(size);
+            w.word(b(signed));
+        }
+        Op::Store { size } => {
+            w.word("store");
+            w.word(size);
+        }
+        Op::Copy { size } => {
+            w.word("copy");
+            w.word(size);
+        }
+        Op::Add => w.word("add"),
+        Op::Sub => w.word("sub"),
+        Op::Mul => w.word("mul"),
+        Op::Div { signed } => {
+            w.word("div");
+            w.word(b(signed));
+        }
+        Op::Mod { signed } => {
+            w.word("mod");
+            w.word(b(signed));
+        }
+        Op::Pow => w.word("pow"),
+        Op::And => w.word("and"),
+        Op::Or => w.word("or"),
+        Op::Xor => w.word("xor"),
+        Op::Neg => w.word("neg"),
+        Op::Not => w.word("not"),
+        Op::Eq => w.word("eq"),
+        Op::Ne => w.word("ne"),
+        Op::Lt { signed } => {
+            w.word("lt");
+            w.word(b(signed));
+        }
+        Op::Le { signed } => {
+            w.word("le");
+            w.word(b(signed));
+        }
+        Op::Gt { signed } => {
+            w.word("gt");
+            w.word(b(signed));
+        }
+        Op::Ge { signed } => {
+            w.word("ge");
+            w.word(b(signed));
+        }
+        Op::Wrap { bits, signed } => {
+            w.word("wrap");
+            w.word(bits);
+            w.word(b(signed));
+        }
+        Op::FAdd { double } => {
+            w.word("fadd");
+            w.word(b(double));
+        }
+        Op::FSub { double } => {
+            w.word("fsub");
+            w.word(b(double));
+        }
+        Op::FMul { double } => {
+            w.word("fmul");
+            w.word(b(double));
+        }
+        Op::FDiv { double } => {
+            w.word("fdiv");
+            w.word(b(double));
+        }
+        Op::FMod { double } => {
+            w.word("fmod");
+            w.word(b(double));
+        }
+        Op::FPow { double } => {
+            w.word("fpow");
+            w.word(b(double));
+        }
+        Op::FNeg { double } => {
+            w.word("fneg");
+            w.word(b(double));
+        }
+        Op::FEq { double } => {
+            w.word("feq");
+            w.word(b(double));
+        }
+        Op::FNe { double } => {
+            w.word("fne");
+            w.word(b(double));
+        }
+        Op::FLt { double } => {
+            w.word("flt");
+            w.word(b(double));
+        }
+        Op::FLe { double } => {
+            w.word("fle");
+            w.word(b(double));
+        }
+        Op::FGt { double } => {
+            w.word("fgt");
+            w.word(b(double));
+        }
+        Op::FGe { double } => {
+            w.word("fge");
+            w.word(b(double));
+        }
+        Op::IntToFloat { signed, double } => {
+            w.word("itof");
+            w.word(b(signed));
+            w.word(b(double));
+        }
+        Op::FloatToInt { double, bits, signed } => {
+            w.word("ftoi");
+            w.word(b(double));
+            w.word(bits);
+            w.word(b(signed));
+        }
+        Op::FloatToFloat { double } => {
+            w.word("ftof");
+            w.word(b(double));
+        }
+        Op::Native { index, args } => {
+            w.word("native");
+            w.word(index);
+            w.word(args);
+        }
+        Op::Alloc { size } => {
+            w.word("alloc");
+            w.word(size);
+        }
+        Op::CallIndirect => w.word("icall"),
+        Op::CheckIndex => w.word("checkindex"),
+        Op::CheckRange => w.word("checkrange"),
+        Op::Return { value } => {
+            w.word("return");
+            w.word(b(value));
+        }
+        Op::Halt => w.word("halt"),
+        Op::GlobalAddr(_) | Op::DataAddr(_) | Op::Jump(_) | Op::JumpIfFalse(_) | Op::Call(_) => {
+            unreachable!("{:?} is written with what it refers to", op)
+        }
+    }
+}
+
+//the counterpart of `write_op` for the instruction named by `word`
+pub fn read_op(r: &mut Reader, word: &str) -> Option<Op> {
+    let flag = |r: &mut Reader| r.number::<u8>().map(|n| n == 1);
+    Some(match word {
+        "push" => Op::Push(r.number()?),
+        "pop" => Op::Pop,
+        "dup" => Op::Dup,
+        "local" => Op::LocalAddr(r.number()?),
+        "load" => Op::Load {
+            size: r.number()?,
+            signed: flag(r)?,
+        },
+        "store" => Op::Store { size: r.number()? },
+        "copy" => Op::Copy { size: r.number()? },
+        "add" => Op::Add,
+        "sub" => Op::Sub,
+        "mul" => Op::Mul,
+        "div" => Op::Div { signed: flag(r)? },
+        "mod" => Op::Mod { signed: flag(r)? },
+        "pow" => Op::Pow,
+        "and" => Op::And,
+        "or" => Op::Or,
+        "xor" => Op::Xor,
+        "neg" => Op::Neg,
+        "not" => Op::Not,
+        "eq" => Op::Eq,
+        "ne" => Op::Ne,
+        "lt" => Op::Lt { signed: flag(r)? },
+        "le" => Op::Le { signed: flag(r)? },
+        "gt" => Op::Gt { signed: flag(r)? },
+        "ge" => Op::Ge { signed: flag(r)? },
+        "wrap" => Op::Wrap {
+            bits: r.number()?,
+            signed: flag(r)?,
+        },
+        "fadd" => Op::FAdd { double: flag(r)? },
+        "fsub" => Op::FSub { double: flag(r)? },
+        "fmul" => Op::FMul { double: flag(r)? },
+        "fdiv" => Op::FDiv { double: flag(r)? },
+        "fmod" => Op::FMod { double: flag(r)? },
+        "fpow" => Op::FPow { double: flag(r)? },
+        "fneg" => Op::FNeg { double: flag(r)? },
+        "feq" => Op::FEq { double: flag(r)? },
+        "fne" => Op::FNe { double: flag(r)? },
+        "flt" => Op::FLt { double: flag(r)? },
+        "fle" => Op::FLe { double: flag(r)? },
+        "fgt" => Op::FGt { double: flag(r)? },
+        "fge" => Op::FGe { double: flag(r)? },
+        "itof" => Op::IntToFloat {
+            signed: flag(r)?,
+            double: flag(r)?,
+        },
+        "ftoi" => Op::FloatToInt {
+            double: flag(r)?,
+            bits: r.number()?,
+            signed: flag(r)?,
+        },
+        "ftof" => Op::FloatToFloat { double: flag(r)? },
+        "native" => Op::Native {
+            index: r.number()?,
+            args: r.number()?,
+        },
+        "alloc" => Op::Alloc { size: r.number()? },
+        "icall" => Op::CallIndirect,
+        "checkindex" => Op::CheckIndex,
+        "checkrange" => Op::CheckRange,
+        "return" => Op::Return { value: flag(r)? },
+        "halt" => Op::Halt,
+        _ => return None,
+    })
+}
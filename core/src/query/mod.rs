@@ -0,0 +1,520 @@
+pub mod codec;
+use crate::compiler::asm::Op;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::{Applicability, Diagnostic, Label, Severity, Span, Suggestion};
+use crate::lint::{self, Emission};
+use crate::module::ModuleGraph;
+use crate::parser::{Node, NodeType};
+use codec::{Reader, Writer};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+//a cache written by another version of the compiler is thrown away
+const FORMAT: &str = concat!("crane-incremental ", env!("CARGO_PKG_VERSION"), " 2");
+pub const CACHE_FILE: &str = "queries";
+
+//a 64 bit FNV-1a hash. Every part written is followed by a separator so
+//`ab` `c` and `a` `bc` hash differently
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+    pub fn write(&mut self, part: &str) {
+        for byte in part.bytes().chain([0xff]) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+//the source a node and its children cover, a function node itself only
+//spans its name
+pub fn extent(node: &Node) -> Span {
+    node.children.iter().map(extent).fold(node.span, Span::to)
+}
+
+//the names of the modules and of every function, enum, constant, static and
+//top level variable, so results can point at them in a later build where
+//they have moved. Globals are named with how many of the same name come
+//before them in the module, `let x` can be written twice at the top level
+#[derive(Debug, Default)]
+pub struct Index {
+    modules: HashMap<usize, String>,
+    files: HashMap<String, usize>,
+    names: HashMap<Span, String>,
+    spans: HashMap<String, Span>,
+}
+
+impl Index {
+    pub fn new(graph: &ModuleGraph) -> Self {
+        let mut index = Index::default();
+        for module in &graph.modules {
+            index.modules.insert(module.file, module.name.clone());
+            index.files.insert(module.name.clone(), module.file);
+            let mut seen: HashMap<&str, usize> = HashMap::new();
+            for node in &module.tree.nodes {
+                let name = match node.node_type {
+                    NodeType::Function | NodeType::Enum | NodeType::Struct | NodeType::Const | NodeType::Static => {
+                        format!("{}::{}", module.name, node.value())
+                    }
+                    NodeType::Assignment => {
+                        let count = seen.entry(node.value()).or_default();
+                        *count += 1;
+                        format!("{}::{}#{}", module.name, node.value(), *count - 1)
+                    }
+                    _ => continue,
+                };
+                index.names.insert(node.span, name.clone());
+                index.spans.insert(name, node.span);
+            }
+        }
+        index
+    }
+    pub fn module(&self, file: usize) -> &str {
+        self.modules.get(&file).map_or("", String::as_str)
+    }
+    //the name of the item declared by the node at `span`
+    pub fn name(&self, span: Span) -> Option<&str> {
+        self.names.get(&span).map(String::as_str)
+    }
+    pub fn span(&self, name: &str) -> Option<Span> {
+        self.spans.get(name).copied()
+    }
+}
+
+//what checking the body of one function gave. The types are those of every
+//node of the function in pre-order, so a function that is not checked again
+//still has a typed tree for code generation
+#[derive(Debug, Clone, Default)]
+pub struct Checked {
+    pub ret: Option<CraneType>,
+    pub types: Vec<Option<CraneType>>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub lints: Vec<Emission>,
+}
+
+//an instruction of a cached function, with what it refers to by name
+#[derive(Debug, Clone)]
+pub enum Inst {
+    Op(Op),
+    Call(String),
+    Global(String),
+    Data(String),
+    //relative to the entry of the function
+    Jump(u32),
+    JumpIfFalse(u32),
+}
+
+//the code generated for one function
+#[derive(Debug, Clone, Default)]
+pub struct Code {
+    pub params: Vec<(u32, u8)>,
+    pub frame_size: u32,
+    //the spans are those of the current build
+    pub insts: Vec<(Inst, Span)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Query {
+    Check,
+    Codegen,
+    //the code of a release build, which has no bounds checks
+    Release,
+}
+
+impl Query {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Query::Check => "check",
+            Query::Codegen => "codegen",
+            Query::Release => "release",
+        }
+    }
+}
+
+//results of earlier builds, stored in `dir`. Each result is keyed by the
+//fingerprint of a function: its text and what it knows about everything it
+//refers to, the signatures of the functions it calls and the types of the
+//globals it uses. Editing a body changes the fingerprint of that function
+//and of the functions whose view of it changes, nothing else is checked again
+pub struct Cache {
+    dir: PathBuf,
+    entries: HashMap<(Query, u64), String>,
+    //what this build looked up or added, the rest is dropped on save
+    live: HashSet<(Query, u64)>,
+    pub index: Index,
+    //the fingerprint of every function checked in this build, by its span
+    pub keys: HashMap<Span, u64>,
+    //functions checked in this build and ones taken from the cache
+    pub checked: usize,
+    pub reused: usize,
+    //whether code is looked up and stored for a release build
+    pub release: bool,
+}
+
+impl Cache {
+    //a missing or damaged cache is an empty one
+    pub fn load(dir: &Path) -> Cache {
+        let mut cache = Cache {
+            dir: dir.to_path_buf(),
+            entries: HashMap::new(),
+            live: HashSet::new(),
+            index: Index::default(),
+            keys: HashMap::new(),
+            checked: 0,
+            reused: 0,
+            release: false,
+        };
+        let Ok(text) = fs::read_to_string(dir.join(CACHE_FILE)) else {
+            return cache;
+        };
+        let mut lines = text.lines();
+        if lines.next() != Some(FORMAT) {
+            return cache;
+        }
+        for line in lines {
+            let mut parts = line.splitn(3, ' ');
+            let query = match parts.next() {
+                Some("check") => Query::Check,
+                Some("codegen") => Query::Codegen,
+                Some("release") => Query::Release,
+                _ => continue,
+            };
+            let (Some(key), Some(entry)) = (parts.next(), parts.next()) else { continue };
+            if let Ok(key) = u64::from_str_radix(key, 16) {
+                cache.entries.insert((query, key), entry.to_string());
+            }
+        }
+        cache
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut live: Vec<&(Query, u64)> = self.live.iter().filter(|k| self.entries.contains_key(k)).collect();
+        live.sort_by_key(|(query, key)| (query.as_str(), *key));
+        let mut text = format!("{}\n", FORMAT);
+        for entry in live {
+            text.push_str(&format!("{} {:016x} {}\n", entry.0.as_str(), entry.1, self.entries[entry]));
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(CACHE_FILE), text)
+    }
+
+    //the items of this build, spans in results are relative to them
+    pub fn begin(&mut self, graph: &ModuleGraph) {
+        self.index = Index::new(graph);
+        self.keys.clear();
+        self.checked = 0;
+        self.reused = 0;
+    }
+
+    fn lookup(&mut self, query: Query, key: u64) -> Option<&str> {
+        self.live.insert((query, key));
+        self.entries.get(&(query, key)).map(String::as_str)
+    }
+
+    //the result of checking the function at `item`
+    pub fn checked(&mut self, key: u64, item: Span) -> Option<Checked> {
+        let text = self.lookup(Query::Check, key)?.to_string();
+        let checked = read_checked(&mut Reader::new(&text), &self.index, item);
+        if checked.is_some() {
+            self.reused += 1;
+        }
+        checked
+    }
+
+    pub fn store_checked(&mut self, key: u64, item: Span, checked: &Checked) {
+        let mut w = Writer::default();
+        write_checked(&mut w, &self.index, item, checked);
+        self.checked += 1;
+        self.live.insert((Query::Check, key));
+        self.entries.insert((Query::Check, key), w.out);
+    }
+
+    pub fn code(&mut self, key: u64, item: Span) -> Option<Code> {
+        let text = self.lookup(self.code_query(), key)?.to_string();
+        read_code(&mut Reader::new(&text), &self.index, item)
+    }
+
+    pub fn store_code(&mut self, key: u64, item: Span, code: &Code) {
+        let mut w = Writer::default();
+        write_code(&mut w, &self.index, item, code);
+        self.live.insert((self.code_query(), key));
+        self.entries.insert((self.code_query(), key), w.out);
+    }
+
+    fn code_query(&self) -> Query {
+        match self.release {
+            true => Query::Release,
+            false => Query::Codegen,
+        }
+    }
+}
+
+//spans inside the item are stored relative to its start, spans of other
+//items by their name
+fn write_span(w: &mut Writer, index: &Index, item: Span, span: Span) {
+    if span == Span::default() {
+        w.word("none");
+    } else if span.file == item.file && span.start >= item.start && span.end <= item.end {
+        w.word("own");
+        w.word(span.start - item.start);
+        w.word(span.end - item.start);
+    } else if let Some(name) = index.name(span) {
+        w.word("item");
+        w.string(name);
+    } else {
+        w.word("at");
+        w.string(index.module(span.file));
+        w.word(span.start);
+        w.word(span.end);
+    }
+}
+
+fn read_span(r: &mut Reader, index: &Index, item: Span) -> Option<Span> {
+    match r.word()?.as_str() {
+        "none" => Some(Span::default()),
+        "own" => Some(Span::new(item.file, item.start + r.number::<usize>()?, item.start + r.number::<usize>()?)),
+        "item" => index.span(&r.string()?),
+        "at" => {
+            let file = *index.files.get(&r.string()?)?;
+            Some(Span::new(file, r.number()?, r.number()?))
+        }
+        _ => None,
+    }
+}
+
+fn write_diagnostic(w: &mut Writer, index: &Index, item: Span, diagnostic: &Diagnostic) {
+    w.word(diagnostic.severity.as_str());
+    w.word(diagnostic.code.as_deref().unwrap_or("-"));
+    w.string(&diagnostic.message);
+    w.word(diagnostic.labels.len());
+    for label in &diagnostic.labels {
+        write_span(w, index, item, label.span);
+        w.string(&label.message);
+        w.word(label.primary as u8);
+    }
+    for list in [&diagnostic.notes, &diagnostic.helps] {
+        w.word(list.len());
+        for text in list {
+            w.string(text);
+        }
+    }
+    w.word(diagnostic.suggestions.len());
+    for suggestion in &diagnostic.suggestions {
+        w.string(&suggestion.message);
+        write_span(w, index, item, suggestion.span);
+        w.string(&suggestion.replacement);
+        w.word(match suggestion.applicability {
+            Applicability::MachineApplicable => "machine",
+            Applicability::MaybeIncorrect => "maybe",
+        });
+    }
+}
+
+fn read_diagnostic(r: &mut Reader, index: &Index, item: Span) -> Option<Diagnostic> {
+    let severity = match r.word()?.as_str() {
+        "error" => Severity::Error,
+        "warning" => Severity::Warning,
+        "note" => Severity::Note,
+        "help" => Severity::Help,
+        _ => return None,
+    };
+    let code = r.word()?;
+    let mut diagnostic = Diagnostic::new(severity, r.string()?);
+    diagnostic.code = (code != "-").then_some(code);
+    for _ in 0..r.number::<usize>()? {
+        diagnostic.labels.push(Label {
+            span: read_span(r, index, item)?,
+            message: r.string()?,
+            primary: r.number::<u8>()? == 1,
+        });
+    }
+    for _ in 0..r.number::<usize>()? {
+        diagnostic.notes.push(r.string()?);
+    }
+    for _ in 0..r.number::<usize>()? {
+        diagnostic.helps.push(r.string()?);
+    }
+    for _ in 0..r.number::<usize>()? {
+        diagnostic.suggestions.push(Suggestion {
+            message: r.string()?,
+            span: read_span(r, index, item)?,
+            replacement: r.string()?,
+            applicability: match r.word()?.as_str() {
+                "machine" => Applicability::MachineApplicable,
+                _ => Applicability::MaybeIncorrect,
+            },
+        });
+    }
+    Some(diagnostic)
+}
+
+fn write_checked(w: &mut Writer, index: &Index, item: Span, checked: &Checked) {
+    w.ty(checked.ret.as_ref());
+    w.word(checked.types.len());
+    for ty in &checked.types {
+        w.ty(ty.as_ref());
+    }
+    w.word(checked.diagnostics.len());
+    for diagnostic in &checked.diagnostics {
+        write_diagnostic(w, index, item, diagnostic);
+    }
+    w.word(checked.lints.len());
+    for emission in &checked.lints {
+        w.word(emission.lint.name);
+        write_diagnostic(w, index, item, &emission.diagnostic);
+    }
+}
+
+fn read_checked(r: &mut Reader, index: &Index, item: Span) -> Option<Checked> {
+    let mut checked = Checked {
+        ret: r.ty()?,
+        ..Checked::default()
+    };
+    for _ in 0..r.number::<usize>()? {
+        checked.types.push(r.ty()?);
+    }
+    for _ in 0..r.number::<usize>()? {
+        checked.diagnostics.push(read_diagnostic(r, index, item)?);
+    }
+    for _ in 0..r.number::<usize>()? {
+        let lint = lint::find(&r.word()?)?;
+        checked.lints.push(Emission::new(lint, read_diagnostic(r, index, item)?));
+    }
+    r.done().then_some(checked)
+}
+
+fn write_code(w: &mut Writer, index: &Index, item: Span, code: &Code) {
+    w.word(code.frame_size);
+    w.word(code.params.len());
+    for (offset, size) in &code.params {
+        w.word(offset);
+        w.word(size);
+    }
+    w.word(code.insts.len());
+    for (inst, span) in &code.insts {
+        write_span(w, index, item, *span);
+        match inst {
+            Inst::Op(op) => codec::write_op(w, op),
+            Inst::Call(name) => {
+                w.word("call");
+                w.string(name);
+            }
+            Inst::Global(name) => {
+                w.word("global");
+                w.string(name);
+            }
+            Inst::Data(value) => {
+                w.word("data");
+                w.string(value);
+            }
+            Inst::Jump(target) => {
+                w.word("jump");
+                w.word(target);
+            }
+            Inst::JumpIfFalse(target) => {
+                w.word("jumpf");
+                w.word(target);
+            }
+        }
+    }
+}
+
+fn read_code(r: &mut Reader, index: &Index, item: Span) -> Option<Code> {
+    let mut code = Code {
+        frame_size: r.number()?,
+        ..Code::default()
+    };
+    for _ in 0..r.number::<usize>()? {
+        code.params.push((r.number()?, r.number()?));
+    }
+    for _ in 0..r.number::<usize>()? {
+        let span = read_span(r, index, item)?;
+        let inst = match r.word()?.as_str() {
+            "call" => Inst::Call(r.string()?),
+            "global" => Inst::Global(r.string()?),
+            "data" => Inst::Data(r.string()?),
+            "jump" => Inst::Jump(r.number()?),
+            "jumpf" => Inst::JumpIfFalse(r.number()?),
+            word => Inst::Op(codec::read_op(r, word)?),
+        };
+        code.insts.push((inst, span));
+    }
+    r.done().then_some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::SourceMap;
+    use crate::resolver::Resolver;
+    use crate::typeck::TypeChecker;
+
+    const PROGRAM: &str = "def helper(x: i32) {\n    return x * 2\n}\n\n\
+                           def twice(x: i32) -> i32 {\n    return helper(x) + helper(x)\n}\n\n\
+                           def other() -> u8 {\n    let y: u8 = 1\n    if (y > 300) {\n        return 0\n    }\n    return y\n}\n";
+
+    //check a program with the cache, giving the lines of its diagnostics
+    fn check(cache: &mut Cache, source: &str) -> Vec<usize> {
+        let mut sources = SourceMap::new();
+        let mut read = |_: &Path| Ok(source.to_string());
+        let mut graph = ModuleGraph::load(&mut sources, Path::new("main.crane"), &[], &mut read).unwrap();
+        cache.begin(&graph);
+        let mut resolver = Resolver::new();
+        resolver.resolve(&graph);
+        let mut checker = TypeChecker::new(&resolver.resolution);
+        checker.cache = Some(cache);
+        for &module in &graph.order {
+            let module = &mut graph.modules[module];
+            checker.check(&module.name, &mut module.tree);
+        }
+        let spans = checker.diagnostics.iter().chain(checker.lints.iter().map(|e| &e.diagnostic));
+        spans
+            .filter_map(Diagnostic::primary_span)
+            .map(|span| sources.file(span.file).line_col(span.start).0)
+            .collect()
+    }
+
+    #[test]
+    fn only_changed_functions_are_checked_again() {
+        let mut cache = Cache::load(Path::new("no such directory"));
+        check(&mut cache, PROGRAM);
+        let functions = cache.checked;
+        assert_eq!(cache.reused, 0);
+        check(&mut cache, PROGRAM);
+        assert_eq!((cache.checked, cache.reused), (0, functions));
+        //same return type, the callers are not affected
+        check(&mut cache, &PROGRAM.replace("x * 2", "x * 3"));
+        assert_eq!(cache.checked, 1);
+        //a new inferred return type changes what `twice` sees
+        check(&mut cache, &PROGRAM.replace("return x * 2", "return true"));
+        assert_eq!(cache.checked, 2);
+    }
+
+    #[test]
+    fn moved_functions_keep_their_diagnostics() {
+        let mut cache = Cache::load(Path::new("no such directory"));
+        assert_eq!(check(&mut cache, PROGRAM), [11]);
+        let moved = format!("\n\n{}", PROGRAM);
+        assert_eq!(check(&mut cache, &moved), [13]);
+        assert_eq!(cache.checked, 0);
+    }
+
+    #[test]
+    fn cache_survives_a_save() {
+        let dir = std::env::temp_dir().join(format!("crane-query-test-{}", std::process::id()));
+        let mut cache = Cache::load(&dir);
+        check(&mut cache, PROGRAM);
+        cache.save().unwrap();
+        let mut cache = Cache::load(&dir);
+        assert_eq!(check(&mut cache, PROGRAM), [11]);
+        assert_eq!(cache.checked, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
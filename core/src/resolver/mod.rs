@@ -0,0 +1,1040 @@
+use crate::compiler::native;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Applicability, Diagnostic, Span};
+use crate::lint::{self, Emission};
+use crate::module::{stdlib, ModuleGraph};
+use crate::parser::{Node, NodeType};
+use crate::typeck::Constraint;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Function,
+    Variable,
+    Param,
+    Enum,
+    Struct,
+    //a type parameter of a generic function or struct, or `Self` in a
+    //trait or impl
+    TypeParam,
+    Trait,
+    //a value worked out when compiling, and a global starting with one
+    Const,
+    Static,
+}
+
+//something a name can refer to
+#[derive(Debug, Clone)]
+pub struct Def {
+    pub name: String,
+    pub kind: DefKind,
+    pub span: Span,
+    pub used: bool,
+    //visible to other modules
+    pub public: bool,
+}
+
+//what every identifier in the tree refers to, keyed by the span of the
+//identifier, and the span of every definition to its def
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    pub defs: Vec<Def>,
+    pub uses: HashMap<Span, usize>,
+    pub declarations: HashMap<Span, usize>,
+    //the dotted names that are a variable and its fields, `p.x`
+    pub field_paths: HashSet<Span>,
+    //the variables of enclosing functions every function expression uses,
+    //by the span of the expression, and the variables written to after
+    //their declaration
+    pub captures: HashMap<Span, Vec<usize>>,
+    pub assigned: HashSet<usize>,
+}
+
+struct Scope {
+    names: HashMap<String, usize>,
+    //the outermost scope of a function body, shadowing is only reported
+    //between scopes of the same function
+    function_root: bool,
+}
+
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    //the module being resolved
+    module: usize,
+    current_function: Option<usize>,
+    //the top level functions, enums and structs of every module resolved so far, by module
+    items: HashMap<usize, HashMap<String, usize>>,
+    //the modules the current module imports with `import a.b`
+    imported: HashSet<String>,
+    //the functions of `impl Square { ... }`, by the def of the type and name
+    associated: HashMap<(usize, String), usize>,
+    //how many function bodies the current node is in, the depth every
+    //variable is declared at, and the function expressions being resolved
+    //with their depth. Top level variables are at depth 0 and never captured
+    depth: usize,
+    depths: HashMap<usize, usize>,
+    lambdas: Vec<(Span, usize)>,
+    //the condition of every `while` and C style `for` that only `return` or
+    //`break` could leave, with the variables it reads, which are known to
+    //never change once every module is resolved
+    loops: Vec<(Span, Span, Vec<usize>)>,
+    pub resolution: Resolution,
+    pub diagnostics: Vec<Diagnostic>,
+    pub lints: Vec<Emission>,
+}
+
+impl Resolution {
+    pub fn id_of(&self, node: &Node) -> Option<usize> {
+        self.uses.get(&node.span).copied()
+    }
+    //the def introduced by a let, param, function, enum, struct, type
+    //parameter or binding node
+    pub fn declared_by(&self, node: &Node) -> Option<usize> {
+        self.declarations.get(&node.span).copied()
+    }
+    pub fn is_field_path(&self, node: &Node) -> bool {
+        self.field_paths.contains(&node.span)
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            module: 0,
+            current_function: None,
+            items: HashMap::new(),
+            imported: HashSet::new(),
+            associated: HashMap::new(),
+            depth: 0,
+            depths: HashMap::new(),
+            lambdas: Vec::new(),
+            loops: Vec::new(),
+            resolution: Resolution::default(),
+            diagnostics: Vec::new(),
+            lints: Vec::new(),
+        }
+    }
+
+    //resolve every module, imports first so their functions are known. The
+    //outermost scope holds the public functions of the prelude
+    pub fn resolve(&mut self, graph: &ModuleGraph) {
+        self.push_scope(false);
+        for &module in &graph.order {
+            self.resolve_module(graph, module);
+            if graph.modules[module].name == stdlib::PRELUDE {
+                let public: Vec<(String, usize)> = self.items[&module]
+                    .iter()
+                    .filter(|(_, &id)| self.resolution.defs[id].public)
+                    .map(|(name, &id)| (name.clone(), id))
+                    .collect();
+                self.scopes[0].names.extend(public);
+            }
+        }
+        self.pop_scope();
+        self.report_unused();
+        self.report_invariant();
+    }
+
+    fn resolve_module(&mut self, graph: &ModuleGraph, module: usize) {
+        let tree = &graph.modules[module].tree;
+        self.module = module;
+        self.push_scope(false);
+        self.imported.clear();
+        for node in tree.nodes.iter().filter(|n| n.is(NodeType::Import)) {
+            self.import(graph, node);
+        }
+        //functions, enums, structs, traits, constants and statics can be used
+        //before they are defined
+        let mut items = HashMap::new();
+        for node in &tree.nodes {
+            let item = [NodeType::Function, NodeType::Enum, NodeType::Struct, NodeType::Trait, NodeType::Const, NodeType::Static];
+            if item.contains(&node.node_type) {
+                if let Some(id) = self.declare_item(node) {
+                    items.insert(node.value().to_string(), id);
+                }
+            }
+        }
+        for node in tree.nodes.iter().filter(|n| n.is(NodeType::Impl) && n.value.is_none()) {
+            self.declare_associated(&tree.nodes, node);
+        }
+        for node in tree.nodes.iter().filter(|n| !n.is(NodeType::Function)) {
+            self.resolve_node(graph, node);
+        }
+        for node in tree.nodes.iter().filter(|n| n.is(NodeType::Function)) {
+            self.resolve_node(graph, node);
+        }
+        self.items.insert(module, items);
+        self.pop_scope();
+    }
+
+    fn import(&mut self, graph: &ModuleGraph, node: &Node) {
+        //a module that could not be loaded has already been reported
+        let Some(module) = graph.import(self.module, node.value()) else {
+            return;
+        };
+        if node.children.is_empty() {
+            self.imported.insert(node.value().to_string());
+            return;
+        }
+        for name in &node.children {
+            if let Some(id) = self.item(graph, module, name, node.value()) {
+                self.resolution.uses.insert(name.span, id);
+                self.scopes.last_mut().unwrap().names.insert(name.value().to_string(), id);
+            }
+        }
+    }
+
+    //a public function, enum, struct or trait of another module
+    fn item(&mut self, graph: &ModuleGraph, module: usize, name: &Node, path: &str) -> Option<usize> {
+        let item = name.value().rsplit('.').next().unwrap();
+        //the module is part of an import cycle, which has been reported
+        let items = self.items.get(&module)?;
+        let Some(&id) = items.get(item) else {
+            let mut diagnostic = Diagnostic::error(E0200, format!("cannot find `{}` in module `{}`", item, path))
+                .with_label(name.span, format!("not found in `{}`", path));
+            let public: Vec<String> = items
+                .iter()
+                .filter(|(_, &id)| self.resolution.defs[id].public)
+                .map(|(name, _)| format!("`{}`", name))
+                .collect();
+            if !public.is_empty() {
+                let mut public = public;
+                public.sort();
+                diagnostic = diagnostic.with_note(format!("`{}` exports {}", graph.modules[module].name, public.join(", ")));
+            }
+            self.diagnostics.push(diagnostic);
+            return None;
+        };
+        let def = &self.resolution.defs[id];
+        if !def.public {
+            let (what, keyword) = match def.kind {
+                DefKind::Enum => ("enum", "enum"),
+                DefKind::Struct => ("struct", "struct"),
+                DefKind::Trait => ("trait", "trait"),
+                DefKind::Const => ("constant", "const"),
+                DefKind::Static => ("static", "static"),
+                _ => ("function", "def"),
+            };
+            let diagnostic = Diagnostic::error(E0205, format!("`{}` is private", item))
+                .with_label(name.span, format!("private {}", what))
+                .with_secondary(def.span, "defined here without `pub`")
+                .with_help(format!("mark it `pub {} {}` to use it from other modules", keyword, item));
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        Some(id)
+    }
+
+    fn push_scope(&mut self, function_root: bool) {
+        self.scopes.push(Scope {
+            names: HashMap::new(),
+            function_root,
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, kind: DefKind, span: Span) -> usize {
+        let id = self.new_def(name, kind, span);
+        if matches!(kind, DefKind::Variable | DefKind::Param) {
+            self.depths.insert(id, self.depth);
+        }
+        self.resolution.declarations.insert(span, id);
+        self.scopes.last_mut().unwrap().names.insert(name.to_string(), id);
+        id
+    }
+
+    fn new_def(&mut self, name: &str, kind: DefKind, span: Span) -> usize {
+        self.resolution.defs.push(Def {
+            name: name.to_string(),
+            kind,
+            span,
+            used: false,
+            public: false,
+        });
+        self.resolution.defs.len() - 1
+    }
+
+    fn declare_item(&mut self, node: &Node) -> Option<usize> {
+        let name = node.value();
+        //an item of the module hides a prelude item of the same name
+        if let Some(&previous) = self.scopes.last().unwrap().names.get(name) {
+            let previous = &self.resolution.defs[previous];
+            let diagnostic = Diagnostic::error(E0201, format!("`{}` is defined twice", name))
+                .with_label(node.span, format!("`{}` redefined here", name))
+                .with_secondary(previous.span, "first defined here");
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        let kind = match node.node_type {
+            NodeType::Enum => DefKind::Enum,
+            NodeType::Struct => DefKind::Struct,
+            NodeType::Trait => DefKind::Trait,
+            NodeType::Const => DefKind::Const,
+            NodeType::Static => DefKind::Static,
+            _ => DefKind::Function,
+        };
+        let id = self.declare(name, kind, node.span);
+        self.resolution.defs[id].public = node.is_pub();
+        Some(id)
+    }
+
+    //the functions of `impl Square { ... }`, which are found through the
+    //type, `Square::new(1)` and `s.area()`. Only the module of a type can
+    //give it functions, names that are not found are left to the type checker
+    fn declare_associated(&mut self, nodes: &[Node], node: &Node) {
+        let ty = &node.children[0];
+        let local = nodes
+            .iter()
+            .find(|n| matches!(n.node_type, NodeType::Enum | NodeType::Struct) && n.value() == ty.value());
+        let Some(item) = local else {
+            //`from shapes import Square` or `shapes.Square`
+            let imported = self.lookup(ty.value()).is_some_and(|id| {
+                let def = &self.resolution.defs[id];
+                def.span.file != node.span.file && matches!(def.kind, DefKind::Enum | DefKind::Struct)
+            });
+            if imported || ty.value().contains('.') {
+                let diagnostic = Diagnostic::error(E0210, format!("cannot define functions of `{}` outside of its module", ty.value()))
+                    .with_label(ty.span, "a type of another module")
+                    .with_help("move the `impl` to the module that defines the type");
+                self.diagnostics.push(diagnostic);
+            }
+            return;
+        };
+        let Some(id) = self.resolution.declared_by(item) else { return };
+        for method in node.methods() {
+            let variant = item.children.iter().find(|c| c.is(NodeType::Variant) && c.value() == method.value());
+            let key = (id, method.value().to_string());
+            let previous = variant.map(|v| v.span).or_else(|| self.associated.get(&key).map(|&f| self.resolution.defs[f].span));
+            if let Some(previous) = previous {
+                let what = if variant.is_some() { "a variant" } else { "a function" };
+                let diagnostic = Diagnostic::error(E0201, format!("`{}::{}` is defined twice", ty.value(), method.value()))
+                    .with_label(method.span, format!("`{}` redefined here", method.value()))
+                    .with_secondary(previous, format!("first defined here as {}", what));
+                self.diagnostics.push(diagnostic);
+                continue;
+            }
+            let function = self.new_def(method.value(), DefKind::Function, method.span);
+            self.resolution.declarations.insert(method.span, function);
+            self.resolution.defs[function].used = true;
+            self.associated.insert(key, function);
+        }
+    }
+
+    //a let or a parameter, warns when it hides a variable of the same function
+    fn declare_variable(&mut self, node: &Node, kind: DefKind) {
+        let name = node.value();
+        if kind == DefKind::Variable && !name.starts_with('_') {
+            for scope in self.scopes.iter().rev() {
+                if let Some(&previous) = scope.names.get(name) {
+                    let previous = &self.resolution.defs[previous];
+                    if matches!(previous.kind, DefKind::Variable | DefKind::Param) {
+                        let diagnostic = Diagnostic::warning(format!("`{}` shadows an earlier variable", name))
+                            .with_label(node.span, format!("this `{}` hides the earlier one", name))
+                            .with_secondary(previous.span, "previously declared here")
+                            .with_help("give the new variable a different name");
+                        self.lints.push(Emission::new(&lint::SHADOWED_NAMES, diagnostic));
+                    }
+                    break;
+                }
+                if scope.function_root {
+                    break;
+                }
+            }
+        }
+        self.declare(name, kind, node.span);
+    }
+
+    //a variable of an enclosing function used in a function expression is
+    //captured by it and by every function expression between the two
+    fn capture(&mut self, id: usize) {
+        let Some(&declared) = self.depths.get(&id).filter(|&&depth| depth > 0) else {
+            return;
+        };
+        for (span, depth) in &self.lambdas {
+            if *depth > declared {
+                let captures = self.resolution.captures.entry(*span).or_default();
+                if !captures.contains(&id) {
+                    captures.push(id);
+                }
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(name).copied())
+    }
+
+    //`a.b.c` is the function `c` of the module `a.b`
+    fn use_path(&mut self, graph: &ModuleGraph, node: &Node) -> Option<usize> {
+        let (path, _) = node.value().rsplit_once('.').unwrap();
+        let module = graph.import(self.module, path).filter(|_| self.imported.contains(path));
+        let Some(module) = module else {
+            let diagnostic = Diagnostic::error(E0200, format!("cannot find module `{}` in this scope", path))
+                .with_label(node.span, format!("`{}` is not imported", path))
+                .with_suggestion(
+                    "import it at the top of the file",
+                    Span::new(node.span.file, 0, 0),
+                    format!("import {}\n", path),
+                    Applicability::MaybeIncorrect,
+                );
+            self.diagnostics.push(diagnostic);
+            return None;
+        };
+        let id = self.item(graph, module, node, path)?;
+        self.resolution.defs[id].used = true;
+        self.resolution.uses.insert(node.span, id);
+        Some(id)
+    }
+
+    fn use_name(&mut self, graph: &ModuleGraph, node: &Node) -> Option<usize> {
+        if node.value().contains('.') {
+            //`p.x` is the field `x` of the variable `p`
+            if let Some(id) = self.variable(node) {
+                self.resolution.defs[id].used = true;
+                self.resolution.uses.insert(node.span, id);
+                self.resolution.field_paths.insert(node.span);
+                self.capture(id);
+                return Some(id);
+            }
+            return self.use_path(graph, node);
+        }
+        match self.lookup(node.value()) {
+            Some(id) => {
+                //recursive calls do not count as uses
+                if Some(id) != self.current_function {
+                    self.resolution.defs[id].used = true;
+                }
+                self.resolution.uses.insert(node.span, id);
+                self.capture(id);
+                Some(id)
+            }
+            None => {
+                let mut diagnostic = Diagnostic::error(E0200, format!("cannot find `{}` in this scope", node.value()))
+                    .with_label(node.span, "not found in this scope");
+                if let Some((name, applicability)) = self.similar_name(node) {
+                    let def = &self.resolution.defs[self.lookup(&name).unwrap()];
+                    let what = match def.kind {
+                        DefKind::Function => "a function",
+                        _ => "a variable",
+                    };
+                    //a call spans its arguments too, only the name is replaced
+                    let span = Span::new(node.span.file, node.span.start, node.span.start + node.value().len());
+                    diagnostic = diagnostic.with_suggestion(
+                        format!("{} with a similar name exists", what),
+                        span,
+                        name,
+                        applicability,
+                    );
+                }
+                self.diagnostics.push(diagnostic);
+                None
+            }
+        }
+    }
+
+    //the variable a dotted name starts with, when it names a field of one
+    fn variable(&self, node: &Node) -> Option<usize> {
+        let head = node.value().split('.').next().unwrap();
+        let id = self.lookup(head)?;
+        matches!(self.resolution.defs[id].kind, DefKind::Variable | DefKind::Param).then_some(id)
+    }
+
+    //a type written by name, names that are not found are left to the type
+    //checker which knows what types exist
+    fn use_type(&mut self, graph: &ModuleGraph, node: &Node) {
+        if node.value() == "dyn" && node.children[0].value() != "def" {
+            self.use_trait(graph, &node.children[0]);
+            return;
+        }
+        //the length of an array is an expression
+        if node.value() == "[]" {
+            self.use_type(graph, &node.children[0]);
+            if let Some(length) = node.children.get(1) {
+                self.resolve_node(graph, length);
+            }
+            return;
+        }
+        //the pointee, the type arguments of `Pair<i32>`, or the parameter
+        //and return types of a function
+        for argument in &node.children {
+            self.use_type(graph, argument);
+        }
+        if matches!(node.value(), "*" | "dyn" | "def") || CraneType::from_name(node.value()).is_some() {
+            return;
+        }
+        if node.value().contains('.') {
+            self.use_path(graph, node);
+        } else if let Some(id) = self.lookup(node.value()) {
+            self.resolution.defs[id].used = true;
+            self.resolution.uses.insert(node.span, id);
+        }
+    }
+
+    //the enum of `Shape::Circle` or `geometry.Shape::Circle`, recorded under
+    //the span of the whole construction or pattern. `Square::new(1)` is a
+    //call of a function of the type, which is recorded instead
+    fn use_enum(&mut self, graph: &ModuleGraph, node: &Node) {
+        let (path, item) = node.value().split_once("::").unwrap();
+        let name = Node::new(NodeType::Identifier, Some(path.to_string()), node.line, node.span);
+        let Some(id) = self.use_name(graph, &name) else {
+            return;
+        };
+        let call = !node.children.iter().any(|c| c.is(NodeType::Field));
+        if let Some(&function) = self.associated.get(&(id, item.to_string())).filter(|_| call) {
+            self.resolution.uses.insert(node.span, function);
+            return;
+        }
+        let def = &self.resolution.defs[id];
+        if def.kind != DefKind::Enum {
+            let mut diagnostic = Diagnostic::error(E0207, format!("`{}` is not an enum", path))
+                .with_label(node.span, "expected an enum before `::`")
+                .with_secondary(def.span, format!("`{}` is defined here", def.name));
+            if def.kind == DefKind::Struct {
+                diagnostic = diagnostic.with_note(format!("`{}` has no function `{}`", def.name, item));
+            }
+            self.diagnostics.push(diagnostic);
+            self.resolution.uses.remove(&node.span);
+        }
+    }
+
+    //the struct of `Pair { first: 1, second: 2 }`
+    fn use_struct(&mut self, graph: &ModuleGraph, node: &Node) {
+        let name = Node::new(NodeType::Identifier, Some(node.value().to_string()), node.line, node.span);
+        let Some(id) = self.use_name(graph, &name) else {
+            return;
+        };
+        let def = &self.resolution.defs[id];
+        if def.kind != DefKind::Struct {
+            let mut diagnostic = Diagnostic::error(E0208, format!("`{}` is not a struct", node.value()))
+                .with_label(node.span, "expected a struct before `{`")
+                .with_secondary(def.span, format!("`{}` is defined here", def.name));
+            if def.kind == DefKind::Enum {
+                diagnostic = diagnostic.with_help(format!("build a variant with `{}::Variant {{ ... }}`", def.name));
+            }
+            self.diagnostics.push(diagnostic);
+            self.resolution.uses.remove(&node.span);
+        }
+    }
+
+    //the trait of an impl, a trait object or a bound
+    fn use_trait(&mut self, graph: &ModuleGraph, node: &Node) -> Option<usize> {
+        let name = Node::new(NodeType::Identifier, Some(node.value().to_string()), node.line, node.span);
+        let id = self.use_name(graph, &name)?;
+        let def = &self.resolution.defs[id];
+        if def.kind != DefKind::Trait {
+            let diagnostic = Diagnostic::error(E0209, format!("`{}` is not a trait", node.value()))
+                .with_label(node.span, "expected a trait")
+                .with_secondary(def.span, format!("`{}` is defined here", def.name));
+            self.diagnostics.push(diagnostic);
+            self.resolution.uses.remove(&node.span);
+            return None;
+        }
+        Some(id)
+    }
+
+    //the type parameters of a generic function or struct, in the scope of
+    //the item, and the traits they are bound by. Bounds that are not found
+    //are left to the type checker, which knows the built-in constraints
+    fn declare_type_params(&mut self, graph: &ModuleGraph, node: &Node) {
+        self.check_unique(node.type_params(), "type parameter");
+        for param in node.type_params() {
+            let id = self.declare(param.value(), DefKind::TypeParam, param.span);
+            self.resolution.defs[id].used = true;
+        }
+        for bound in node.type_params().flat_map(|p| &p.children) {
+            let known = self.lookup(bound.value()).is_some() || bound.value().contains('.');
+            if Constraint::from_name(bound.value()).is_none() && known {
+                self.use_trait(graph, bound);
+            }
+            for arg in &bound.children {
+                self.use_type(graph, arg);
+            }
+        }
+    }
+
+    //the methods of a trait or impl, which are not in scope by their name.
+    //`Self` is the type implementing the trait, or the type of the methods.
+    //Those of the type itself have been declared with the type. The type
+    //parameters of a generic trait or impl are in scope in all of them
+    fn resolve_methods(&mut self, graph: &ModuleGraph, node: &Node) {
+        if node.value.is_some() {
+            self.check_unique(node.methods(), "method");
+        }
+        self.push_scope(false);
+        self.declare_type_params(graph, node);
+        if node.is(NodeType::Impl) {
+            if node.value.is_some() {
+                self.use_trait(graph, node);
+            }
+            for ty in node.children.iter().filter(|c| c.is(NodeType::Type)) {
+                self.use_type(graph, ty);
+            }
+        }
+        let id = self.new_def("Self", DefKind::TypeParam, node.span);
+        self.resolution.defs[id].used = true;
+        self.scopes.last_mut().unwrap().names.insert("Self".to_string(), id);
+        for method in node.methods() {
+            if self.resolution.declared_by(method).is_none() {
+                let id = self.new_def(method.value(), DefKind::Function, method.span);
+                self.resolution.declarations.insert(method.span, id);
+                self.resolution.defs[id].used = true;
+            }
+            self.resolve_node(graph, method);
+        }
+        self.pop_scope();
+    }
+
+    //reports a variant or field whose name is already taken in the same item
+    fn check_unique<'a>(&mut self, names: impl Iterator<Item = &'a Node>, what: &str) {
+        let mut seen: HashMap<&str, Span> = HashMap::new();
+        for node in names {
+            if let Some(previous) = seen.insert(node.value(), node.span) {
+                let diagnostic = Diagnostic::error(E0201, format!("the {} `{}` is defined twice", what, node.value()))
+                    .with_label(node.span, format!("`{}` redefined here", node.value()))
+                    .with_secondary(previous, "first defined here");
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    //the visible name closest to a misspelt one, only certain enough to apply
+    //without asking when no other name is as close
+    fn similar_name(&self, node: &Node) -> Option<(String, Applicability)> {
+        let wanted = node.value();
+        let calling = node.is(NodeType::FunctionCall);
+        let mut best: Vec<&str> = Vec::new();
+        let mut best_distance = wanted.chars().count().div_ceil(3).max(1);
+        for scope in &self.scopes {
+            for (name, &id) in &scope.names {
+                let kind = self.resolution.defs[id].kind;
+                if (kind == DefKind::Function) != calling || kind == DefKind::TypeParam {
+                    continue;
+                }
+                let distance = edit_distance(wanted, name);
+                if distance < best_distance {
+                    best_distance = distance;
+                    best.clear();
+                }
+                if distance == best_distance && !best.contains(&name.as_str()) {
+                    best.push(name);
+                }
+            }
+        }
+        match best.as_slice() {
+            [] => None,
+            [name] => Some((name.to_string(), Applicability::MachineApplicable)),
+            names => Some((names.iter().min().unwrap().to_string(), Applicability::MaybeIncorrect)),
+        }
+    }
+
+    fn resolve_node(&mut self, graph: &ModuleGraph, node: &Node) {
+        match node.node_type {
+            NodeType::Function => {
+                let id = self.resolution.declared_by(node);
+                let previous = std::mem::replace(&mut self.current_function, id);
+                self.depth += 1;
+                let native = node.is_native();
+                //the VM trusts the signature of a native function, only the
+                //standard library declares them
+                let message = match native {
+                    true if !graph.modules[self.module].std => Some("only the standard library can declare `@native` functions".to_string()),
+                    true if native::find(node.value()).is_none() => Some(format!("the VM has no native function `{}`", node.value())),
+                    _ => None,
+                };
+                if let Some(message) = message {
+                    let diagnostic = Diagnostic::error(E0206, message)
+                        .with_label(node.span, "declared `@native` here")
+                        .with_help("remove `@native` and give the function a body");
+                    self.diagnostics.push(diagnostic);
+                }
+                self.push_scope(true);
+                self.declare_type_params(graph, node);
+                if let Some(ty) = node.return_type() {
+                    self.use_type(graph, ty);
+                }
+                self.check_unique(node.params(), "parameter");
+                for param in node.params() {
+                    if let Some(ty) = param.child(NodeType::Type) {
+                        self.use_type(graph, ty);
+                    }
+                    self.declare_variable(param, DefKind::Param);
+                    //the parameters of a native function are read by the VM, a
+                    //required trait method has no body to use them in, and a
+                    //method may not need the value it is called on
+                    if native || node.body().is_none() || param.value() == "self" {
+                        let id = self.resolution.declared_by(param).unwrap();
+                        self.resolution.defs[id].used = true;
+                    }
+                }
+                if let Some(body) = node.child(NodeType::Block) {
+                    self.resolve_node(graph, body);
+                }
+                self.pop_scope();
+                self.depth -= 1;
+                self.current_function = previous;
+            }
+            NodeType::Lambda => {
+                self.depth += 1;
+                self.lambdas.push((node.span, self.depth));
+                self.push_scope(true);
+                if let Some(ty) = node.return_type() {
+                    self.use_type(graph, ty);
+                }
+                self.check_unique(node.params(), "parameter");
+                for param in node.params() {
+                    if let Some(ty) = param.child(NodeType::Type) {
+                        self.use_type(graph, ty);
+                    }
+                    self.declare_variable(param, DefKind::Param);
+                }
+                if let Some(body) = node.body() {
+                    self.resolve_node(graph, body);
+                }
+                self.pop_scope();
+                self.lambdas.pop();
+                self.depth -= 1;
+            }
+            NodeType::Block => {
+                self.push_scope(false);
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+                self.pop_scope();
+            }
+            NodeType::Assignment => {
+                if let Some(ty) = node.child(NodeType::Type) {
+                    self.use_type(graph, ty);
+                }
+                //the value is resolved first so `let x = x + 1` sees the outer x
+                if let Some(value) = node.last() {
+                    self.resolve_node(graph, value);
+                }
+                self.declare_variable(node, DefKind::Variable);
+            }
+            NodeType::Destructure => {
+                if let Some(ty) = node.child(NodeType::Type) {
+                    self.use_type(graph, ty);
+                }
+                self.resolve_node(graph, node.last().unwrap());
+                let mut bound = HashMap::new();
+                self.declare_pattern(&node.children[0], &mut bound);
+            }
+            //writing an element, `a[i] = v`, reads the index and the array it
+            //is in, writing through a pointer, `*p = v`, reads the pointer
+            NodeType::Reassignment if !node.children[0].is(NodeType::Identifier) => {
+                self.resolve_node(graph, &node.children[0]);
+                self.assign(&node.children[0]);
+                self.resolve_node(graph, &node.children[1]);
+            }
+            NodeType::Reassignment => {
+                let target = &node.children[0];
+                //writing to a variable or one of its fields is not a use of it
+                let id = if let Some(id) = self.lookup(target.value()) {
+                    self.resolution.uses.insert(target.span, id);
+                    self.capture(id);
+                    Some(id)
+                } else if let Some(id) = self.variable(target).filter(|_| target.value().contains('.')) {
+                    self.resolution.uses.insert(target.span, id);
+                    self.resolution.field_paths.insert(target.span);
+                    self.capture(id);
+                    Some(id)
+                } else {
+                    self.use_name(graph, target)
+                };
+                self.resolution.assigned.extend(id);
+                self.resolve_node(graph, &node.children[1]);
+            }
+            NodeType::Identifier => {
+                self.use_name(graph, node);
+            }
+            //a variable can be written through its address
+            NodeType::UnaryExpression if node.value() == "And" => {
+                self.resolve_node(graph, &node.children[0]);
+                self.assign(&node.children[0]);
+            }
+            //the variable of the init is only visible in the loop
+            NodeType::Loop if node.value() == "for(;;)" => {
+                self.push_scope(false);
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+                self.pop_scope();
+                self.loop_condition(node);
+            }
+            NodeType::Loop if node.value() == "while" => {
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+                self.loop_condition(node);
+            }
+            //the values are resolved outside the loop, `for x in x` goes over the outer x
+            NodeType::Loop if node.value() == "for" => {
+                self.resolve_node(graph, &node.children[1]);
+                self.push_scope(false);
+                self.declare_variable(&node.children[0], DefKind::Variable);
+                self.resolve_node(graph, &node.children[2]);
+                self.pop_scope();
+            }
+            //lint names are not variables, imports are resolved before the rest
+            NodeType::Attribute | NodeType::Import | NodeType::Wildcard => {}
+            NodeType::Type => self.use_type(graph, node),
+            NodeType::Enum => {
+                self.push_scope(false);
+                self.declare_type_params(graph, node);
+                self.check_unique(node.children.iter().filter(|c| c.is(NodeType::Variant)), "variant");
+                for variant in node.children.iter().filter(|c| c.is(NodeType::Variant)) {
+                    self.check_unique(variant.children.iter().filter(|c| c.is(NodeType::Field)), "field");
+                    for child in &variant.children {
+                        self.resolve_node(graph, child);
+                    }
+                }
+                self.pop_scope();
+            }
+            NodeType::Struct => {
+                self.push_scope(false);
+                self.declare_type_params(graph, node);
+                self.check_unique(node.children.iter().filter(|c| c.is(NodeType::Field)), "field");
+                for field in node.children.iter().filter(|c| c.is(NodeType::Field)) {
+                    self.resolve_node(graph, field);
+                }
+                self.pop_scope();
+            }
+            NodeType::Trait | NodeType::Impl => self.resolve_methods(graph, node),
+            NodeType::StructLiteral => {
+                self.use_struct(graph, node);
+                self.check_unique(node.children.iter(), "field");
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+            }
+            NodeType::Construct => {
+                self.use_enum(graph, node);
+                self.check_unique(node.children.iter().filter(|c| c.is(NodeType::Field)), "field");
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+            }
+            //the names bound by the pattern are only visible in the guard and the value
+            NodeType::Arm => {
+                self.push_scope(false);
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+                self.pop_scope();
+            }
+            NodeType::Binding => {
+                if let Some(&previous) = self.scopes.last().unwrap().names.get(node.value()) {
+                    let previous = self.resolution.defs[previous].span;
+                    let diagnostic = Diagnostic::error(E0201, format!("`{}` is bound twice in the same pattern", node.value()))
+                        .with_label(node.span, format!("`{}` bound again here", node.value()))
+                        .with_secondary(previous, "first bound here");
+                    self.diagnostics.push(diagnostic);
+                    return;
+                }
+                self.declare(node.value(), DefKind::Variable, node.span);
+            }
+            NodeType::FunctionCall => {
+                //a method call, `p.area()`, is resolved by the type checker
+                //which knows the type of `p`
+                let method = |r: &Self| r.resolution.is_field_path(node);
+                if let Some(id) = self.use_name(graph, node).filter(|_| !method(self)) {
+                    let def = &self.resolution.defs[id];
+                    //a variable may hold a function, which the type checker knows
+                    if !matches!(def.kind, DefKind::Function | DefKind::Variable | DefKind::Param) {
+                        let what = match def.kind {
+                            DefKind::Enum => "an enum",
+                            DefKind::Struct => "a struct",
+                            DefKind::TypeParam => "a type parameter",
+                            DefKind::Const => "a constant",
+                            DefKind::Static => "a static",
+                            _ => "a trait",
+                        };
+                        let mut diagnostic = Diagnostic::error(E0202, format!("`{}` is not a function", node.value()))
+                            .with_label(node.span, "called here")
+                            .with_secondary(def.span, format!("`{}` is {}", def.name, what));
+                        if def.kind == DefKind::Enum {
+                            diagnostic = diagnostic.with_help(format!("build a variant with `{}::Variant(...)`", def.name));
+                        } else if def.kind == DefKind::Struct {
+                            diagnostic = diagnostic.with_help(format!("build a value with `{} {{ field: value }}`", def.name));
+                        }
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+                for arg in &node.children {
+                    self.resolve_node(graph, arg);
+                }
+            }
+            _ => {
+                for child in &node.children {
+                    self.resolve_node(graph, child);
+                }
+            }
+        }
+    }
+
+    //the names of `let (a, (b, _)) = value`, each bound once
+    fn declare_pattern(&mut self, pattern: &Node, bound: &mut HashMap<String, Span>) {
+        match pattern.node_type {
+            NodeType::Tuple => {
+                for child in &pattern.children {
+                    self.declare_pattern(child, bound);
+                }
+            }
+            NodeType::Binding => {
+                if let Some(&previous) = bound.get(pattern.value()) {
+                    let diagnostic = Diagnostic::error(E0201, format!("`{}` is bound twice in the same pattern", pattern.value()))
+                        .with_label(pattern.span, format!("`{}` bound again here", pattern.value()))
+                        .with_secondary(previous, "first bound here");
+                    self.diagnostics.push(diagnostic);
+                    return;
+                }
+                bound.insert(pattern.value().to_string(), pattern.span);
+                self.declare_variable(pattern, DefKind::Variable);
+            }
+            _ => {}
+        }
+    }
+
+    //mark the variable an element or a field of, `a[i].x`, or the variable
+    //itself as assigned, once the place has been resolved
+    fn assign(&mut self, place: &Node) {
+        let mut base = place;
+        while base.is(NodeType::Index) || base.is(NodeType::Member) {
+            base = &base.children[0];
+        }
+        if base.is(NodeType::Identifier) {
+            let id = self.resolution.uses.get(&base.span).copied();
+            self.resolution.assigned.extend(id);
+        }
+    }
+
+    //remember the variables of a loop condition made only of variables,
+    //constants and operators on them
+    fn loop_condition(&mut self, node: &Node) {
+        let body = node.last().unwrap();
+        if body.breaks() || returns(body) {
+            return;
+        }
+        let condition = node.loop_condition().unwrap();
+        let mut ids = Vec::new();
+        if self.reads(condition, &mut ids) && !ids.is_empty() {
+            self.loops.push((condition.span, node.span, ids));
+        }
+    }
+
+    //the variables an expression reads, false when it could change without
+    //one of them being assigned: it calls a function, reads a static or
+    //reads through a pointer
+    fn reads(&self, node: &Node, ids: &mut Vec<usize>) -> bool {
+        match node.node_type {
+            NodeType::Identifier => match self.resolution.uses.get(&node.span) {
+                Some(&id) => match self.resolution.defs[id].kind {
+                    DefKind::Variable | DefKind::Param => {
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
+                        true
+                    }
+                    DefKind::Const => true,
+                    _ => false,
+                },
+                None => false,
+            },
+            NodeType::UnaryExpression if node.value() == "Mul" => false,
+            NodeType::Expression
+            | NodeType::UnaryExpression
+            | NodeType::Index
+            | NodeType::Member
+            | NodeType::Cast
+            | NodeType::Tuple => {
+                node.children.iter().all(|child| child.is(NodeType::Type) || self.reads(child, ids))
+            }
+            NodeType::Number | NodeType::Float | NodeType::Bool | NodeType::Character | NodeType::String => true,
+            _ => false,
+        }
+    }
+
+    //a loop whose condition only reads variables that are never assigned
+    //either never runs or never ends
+    fn report_invariant(&mut self) {
+        for (condition, span, ids) in std::mem::take(&mut self.loops) {
+            if ids.iter().any(|id| self.resolution.assigned.contains(id)) {
+                continue;
+            }
+            let names: Vec<String> = ids.iter().map(|&id| format!("`{}`", self.resolution.defs[id].name)).collect();
+            let label = match names.as_slice() {
+                [name] => format!("{} is never assigned", name),
+                [rest @ .., last] => format!("{} and {} are never assigned", rest.join(", "), last),
+                [] => unreachable!(),
+            };
+            let diagnostic = Diagnostic::warning("the condition of this loop never changes")
+                .with_label(condition, label)
+                .with_secondary(span, "this loop never ends once it starts")
+                .with_help("assign to a variable of the condition in the loop, or leave it with `break`");
+            self.lints.push(Emission::new(&lint::INVARIANT_LOOP_CONDITION, diagnostic));
+        }
+    }
+
+    fn report_unused(&mut self) {
+        for def in &self.resolution.defs {
+            if def.used || def.name.starts_with('_') {
+                continue;
+            }
+            let (lint, diagnostic) = match def.kind {
+                DefKind::Variable | DefKind::Param => (
+                    &lint::UNUSED_VARIABLES,
+                    Diagnostic::warning(format!("unused variable `{}`", def.name))
+                        .with_label(def.span, "never read")
+                        .with_suggestion(
+                            "if this is intentional, prefix it with an underscore",
+                            def.span,
+                            format!("_{}", def.name),
+                            Applicability::MachineApplicable,
+                        ),
+                ),
+                //public functions are there for other programs to call
+                DefKind::Function if def.name != "main" && !def.public => (
+                    &lint::UNUSED_FUNCTIONS,
+                    Diagnostic::warning(format!("function `{}` is never called", def.name))
+                        .with_label(def.span, "never called"),
+                ),
+                _ => continue,
+            };
+            self.lints.push(Emission::new(lint, diagnostic));
+        }
+    }
+}
+
+//whether a `return` or `?` in this node leaves the function it is in
+fn returns(node: &Node) -> bool {
+    match node.node_type {
+        NodeType::Return | NodeType::Try => true,
+        NodeType::Lambda => false,
+        _ => node.children.iter().any(returns),
+    }
+}
+
+//how many single character insertions, removals and substitutions turn one
+//string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
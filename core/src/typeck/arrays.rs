@@ -0,0 +1,204 @@
+use super::generics::ITERATOR;
+use super::strings::CODE_POINT;
+use super::{literal_value, TypeChecker};
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::{Node, NodeType};
+
+//indexes and lengths are 64 bit
+const LENGTH: CraneType = CraneType::Long { signed: false };
+
+impl TypeChecker<'_> {
+    //`[u16; 4]`, or the slice `[u16]` without a length
+    pub(super) fn array_type(&mut self, node: &Node) -> Option<CraneType> {
+        let element = Box::new(self.resolve_type(&node.children[0])?);
+        match node.children.get(1) {
+            Some(length) => Some(CraneType::Array { element, size: self.length(length)? }),
+            None => Some(CraneType::Slice { element }),
+        }
+    }
+
+    //the length of an array type or of `[0; 4]`, a number or a constant
+    //expression worked out when compiling
+    fn length(&mut self, node: &Node) -> Option<u32> {
+        if node.is(NodeType::Number) {
+            let length = node.value().parse().ok();
+            if length.is_none() {
+                let diagnostic = Diagnostic::error(E0326, format!("array length `{}` is too large", node.value()))
+                    .with_label(node.span, format!("lengths go up to {}", u32::MAX));
+                self.diagnostics.push(diagnostic);
+            }
+            return length;
+        }
+        let mut length = node.clone();
+        let found = self.check_expr(&mut length, Some(&LENGTH))?;
+        if !self.coerce(&mut length, &LENGTH, &found) {
+            self.mismatch(length.span, &LENGTH, &found);
+            return None;
+        }
+        let value = self.evaluate(&length)?.bits();
+        let length = u32::try_from(value).ok();
+        if length.is_none() {
+            let diagnostic = Diagnostic::error(E0326, format!("array length {} is too large", value))
+                .with_label(node.span, format!("lengths go up to {}", u32::MAX));
+            self.diagnostics.push(diagnostic);
+        }
+        length
+    }
+
+    //`[1, 2, 3]` or `[0; 4]`, the elements have the element type the array is
+    //expected to have, else that of the first one
+    pub(super) fn check_array(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let mut element = match expected {
+            Some(CraneType::Array { element, .. } | CraneType::Slice { element }) => Some((**element).clone()),
+            _ => None,
+        };
+        let repeat = node.value() == "repeat";
+        let size = match repeat {
+            true => {
+                node.children[1].ty = Some(LENGTH);
+                self.length(&node.children[1])
+            }
+            false => Some(node.children.len() as u32),
+        };
+        let values = if repeat { &mut node.children[..1] } else { &mut node.children[..] };
+        let mut ok = true;
+        for value in values {
+            let found = self.check_expr(value, element.as_ref());
+            match (&element, found) {
+                (Some(expected), Some(found)) => {
+                    if !self.coerce(value, expected, &found) {
+                        let span = value.span;
+                        self.mismatch(span, expected, &found);
+                        ok = false;
+                    }
+                }
+                (None, Some(found)) => element = Some(found),
+                (_, None) => ok = false,
+            }
+        }
+        if element.is_none() && ok {
+            let diagnostic = Diagnostic::error(E0324, "cannot infer the type of an empty array")
+                .with_label(node.span, "no elements to take the type from")
+                .with_help("give it a type, `let name: [i32; 0] = []`");
+            self.diagnostics.push(diagnostic);
+        }
+        let element = Box::new(element.filter(|_| ok)?);
+        Some(CraneType::Array { element, size: size? })
+    }
+
+    //`a[i]` is an element, `a[1..3]` a slice of the elements. A string is
+    //only sliced, by bytes, `text[0..4]`
+    pub(super) fn check_index(&mut self, node: &mut Node) -> Option<CraneType> {
+        let [target, index] = &mut node.children[..] else { unreachable!() };
+        let element = match self.check_expr(target, None) {
+            Some(CraneType::Array { element, size }) => Some((element, Some(size))),
+            Some(CraneType::Slice { element }) => Some((element, None)),
+            Some(CraneType::Str) if !index.is(NodeType::Range) => {
+                let diagnostic = Diagnostic::error(E0322, "cannot index into a `str` with an integer")
+                    .with_label(target.span, "a string is indexed by ranges of bytes")
+                    .with_help("take the bytes from `i` up to `i + 1`, `text[i..i + 1]`, or go through the characters with `for c in text`");
+                self.diagnostics.push(diagnostic);
+                None
+            }
+            Some(CraneType::Str) => Some((Box::new(CraneType::Str), None)),
+            Some(ty) => {
+                let diagnostic = Diagnostic::error(E0322, format!("cannot index into a value of type `{}`", ty))
+                    .with_label(target.span, format!("`{}` is not an array or a slice", ty));
+                self.diagnostics.push(diagnostic);
+                None
+            }
+            None => None,
+        };
+        let size = element.as_ref().and_then(|(_, size)| *size);
+        if !index.is(NodeType::Range) {
+            self.check_position(index, size, target.span, false);
+            return element.map(|(element, _)| *element);
+        }
+        let ends = index.value().to_string();
+        for (position, bound) in index.children.iter_mut().enumerate() {
+            //the end of a range can be the length itself
+            let end = position == 1 || ends == "..b";
+            self.check_position(bound, size, target.span, end);
+        }
+        element.map(|(element, _)| match *element {
+            CraneType::Str => CraneType::Str,
+            _ => CraneType::Slice { element },
+        })
+    }
+
+    //an index, or an end of a range, into an array of `size` elements
+    fn check_position(&mut self, node: &mut Node, size: Option<u32>, target: Span, end: bool) {
+        let Some(found) = self.check_expr(node, Some(&LENGTH)) else { return };
+        if !self.integer(&found) {
+            let span = node.span;
+            self.mismatch(span, &LENGTH, &found);
+            return;
+        }
+        let (Some(size), Some(index)) = (size, literal_value(node)) else { return };
+        if index > size as i128 || (index == size as i128 && !end) {
+            let diagnostic = Diagnostic::error(E0323, format!("index out of bounds: the length is {} but the index is {}", size, index))
+                .with_label(node.span, "past the end")
+                .with_secondary(target, format!("this has {} element{}", size, if size == 1 { "" } else { "s" }));
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    //`for x in values`, `x` is each element in turn, each integer of a range
+    //or each value of an iterator. The characters of a string are gone
+    //through as a slice of their code points
+    pub(super) fn check_for(&mut self, node: &mut Node) {
+        let values = &mut node.children[1];
+        let element = match values.is(NodeType::Range) {
+            true => self.range_element(values),
+            false => match self.check_expr(values, None) {
+                Some(ty) if self.trait_args(ITERATOR, &ty).is_some() => self.iterator_element(node, &ty),
+                Some(ty) => self.elements_of(&mut node.children[1], ty),
+                None => None,
+            },
+        };
+        let binding = &mut node.children[0];
+        if let Some(id) = self.resolution.declared_by(binding) {
+            self.def_types[id] = element.clone();
+        }
+        binding.ty = element;
+        self.check_loop_body(&mut node.children[2], "for");
+    }
+
+    fn elements_of(&mut self, values: &mut Node, ty: CraneType) -> Option<CraneType> {
+        match ty {
+            CraneType::Array { element, .. } | CraneType::Slice { element } => Some(*element),
+            CraneType::Str => {
+                values.coercion = Some(CraneType::Slice { element: Box::new(CODE_POINT) });
+                Some(CODE_POINT)
+            }
+            ty => {
+                let mut diagnostic = Diagnostic::error(E0325, format!("`{}` cannot be iterated over", ty))
+                    .with_label(values.span, format!("`{}` is not an array, a slice, a string, a range or an iterator", ty));
+                if self.integer(&ty) {
+                    diagnostic = diagnostic.with_help("go over the integers up to it with a range, `0..n`");
+                } else if matches!(ty, CraneType::Struct { .. } | CraneType::Enum { .. }) {
+                    diagnostic = diagnostic.with_help(format!("implement `Iterator` for it, `impl Iterator<T> for {} {{ ... }}`", ty));
+                }
+                self.diagnostics.push(diagnostic);
+                None
+            }
+        }
+    }
+
+    //`values.len()`, the number of elements of an array or a slice. The
+    //arguments come after the first `skip` children
+    pub(super) fn check_len(&mut self, node: &mut Node, skip: usize) -> Option<CraneType> {
+        let given = node.arguments().count() - skip;
+        if given != 0 {
+            let diagnostic = Diagnostic::error(E0303, format!("`len` takes 0 arguments but {} {} given", given, if given == 1 { "was" } else { "were" }))
+                .with_label(node.span, "expected 0 arguments");
+            self.diagnostics.push(diagnostic);
+        }
+        for arg in node.arguments_mut().skip(skip) {
+            self.check_expr(arg, None);
+        }
+        Some(LENGTH)
+    }
+}
@@ -0,0 +1,69 @@
+use super::{literal_value, TypeChecker};
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::Node;
+
+impl TypeChecker<'_> {
+    //`value as u8`, an integer is truncated or extended to the width of the
+    //type, a float rounded towards zero and saturated, a bool is 0 or 1 and
+    //an enum without fields is its discriminant. `value as f64` rounds an
+    //integer or another float to the nearest
+    pub(super) fn check_cast(&mut self, node: &mut Node) -> Option<CraneType> {
+        let ty = node.children[1].clone();
+        let target = self.resolve_type(&ty);
+        //a literal is one of the type it is cast to when it fits, `-1 as u64`
+        //and `300 as u8` keep their value and are converted like a variable
+        let value = &mut node.children[0];
+        let hint = match (literal_value(value), &target) {
+            (Some(n), Some(to)) if self.integer(to) => match to.range() {
+                Some((min, max)) if n < min || n > max => Some(CraneType::Long { signed: n < 0 }),
+                _ => Some(to.clone()),
+            },
+            _ => None,
+        };
+        let found = self.check_expr(value, hint.as_ref());
+        let (found, target) = (found?, target?);
+        let castable = found.casts_to(&target)
+            || (self.integer(&found) || found.is_float() || found == CraneType::Bool || self.fieldless(&found)) && self.integer(&target)
+            || self.integer(&found) && target.is_float();
+        if !castable {
+            self.invalid_cast(&node.children[0], &found, &target);
+            return None;
+        }
+        Some(target)
+    }
+
+    //an enum whose variants have no fields, which are told apart by their
+    //discriminant alone
+    fn fieldless(&self, ty: &CraneType) -> bool {
+        let CraneType::Enum { name, .. } = ty else { return false };
+        self.enums.get(name).is_some_and(|def| def.variants.iter().all(|v| v.fields.is_empty()))
+    }
+
+    fn invalid_cast(&mut self, value: &Node, found: &CraneType, target: &CraneType) {
+        let mut diagnostic = Diagnostic::error(E0340, format!("cannot cast `{}` to `{}`", found, target))
+            .with_label(value.span, format!("this is `{}`", found));
+        diagnostic = match (found, target) {
+            (from, CraneType::Bool) if self.integer(from) || from.is_float() => diagnostic.with_help("compare it with zero instead, `value != 0`"),
+            (CraneType::Enum { .. }, _) => diagnostic.with_note("only an enum whose variants have no fields can be cast to an integer"),
+            _ => diagnostic.with_note("`as` converts integers and floats, `bool` to an integer, and pointers to each other and to `u64`"),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    //an integer used where a type that cannot hold all of its values is
+    //expected, which takes a cast to say what happens to the others
+    pub(super) fn narrowing(&mut self, span: Span, expected: &CraneType, found: &CraneType) -> bool {
+        if !expected.is_integer() || !found.is_integer() {
+            return false;
+        }
+        let (min, max) = found.range().unwrap();
+        let diagnostic = Diagnostic::error(E0341, format!("`{}` is not converted to `{}` without a cast", found, expected))
+            .with_label(span, format!("expected `{}`, found `{}`", expected, found))
+            .with_note(format!("`{}` holds {} to {}, not all of which fit in `{}`", found, min, max, expected))
+            .with_help(format!("convert it with `as {}`, which keeps the low bits of the value", expected));
+        self.diagnostics.push(diagnostic);
+        true
+    }
+}
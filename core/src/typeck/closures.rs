@@ -0,0 +1,149 @@
+use super::{Returns, TypeChecker};
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::Diagnostic;
+use crate::parser::{Node, NodeType};
+
+impl TypeChecker<'_> {
+    //`def(i32, bool) -> u8`, the return type is the last child
+    pub(super) fn function_type(&mut self, node: &Node) -> Option<CraneType> {
+        let (ret, params) = node.children.split_last()?;
+        let mut types = Vec::new();
+        for param in params {
+            types.push(self.resolve_type(param)?);
+        }
+        let ret = Box::new(self.resolve_type(ret)?);
+        Some(CraneType::Function { params: types, ret })
+    }
+
+    //a function named as a value, `apply(double, 2)`. Only a function that
+    //is compiled once has an index to hold
+    pub(super) fn function_value(&mut self, node: &Node, id: usize) -> Option<CraneType> {
+        let signature = self.functions.get(&id)?.clone();
+        let native = self.natives.contains(&id);
+        if native || !signature.type_params.is_empty() {
+            let what = if native { "a native function" } else { "a generic function" };
+            let mut diagnostic = Diagnostic::error(E0320, format!("`{}` cannot be used as a value", node.value()))
+                .with_label(node.span, format!("`{}` is {}", node.value(), what))
+                .with_secondary(signature.span, "defined here");
+            diagnostic = match native {
+                true => diagnostic.with_help(format!("wrap it in a function expression, `def (...) {{ return {}(...) }}`", node.value())),
+                false => diagnostic.with_note("it is compiled separately for every list of type arguments it is called with"),
+            };
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        //the return type may only be known once the body has been checked
+        self.check_function(id);
+        let signature = &self.functions[&id];
+        let params = signature.params.iter().cloned().collect::<Option<Vec<_>>>()?;
+        let ret = Box::new(signature.ret.clone()?);
+        Some(CraneType::Function { params, ret })
+    }
+
+    //`f(1)` where the variable `f` holds a function
+    pub(super) fn check_indirect_call(&mut self, node: &mut Node, id: usize) -> Option<CraneType> {
+        let (params, ret) = match self.def_types[id].clone() {
+            Some(CraneType::Function { params, ret } | CraneType::Closure { params, ret }) => (params, *ret),
+            found => {
+                if let Some(ty) = found {
+                    let def = &self.resolution.defs[id];
+                    let diagnostic = Diagnostic::error(E0202, format!("`{}` is not a function", node.value()))
+                        .with_label(node.span, format!("`{}` is `{}`", node.value(), ty))
+                        .with_secondary(def.span, format!("`{}` is declared here", def.name));
+                    self.diagnostics.push(diagnostic);
+                }
+                for arg in node.arguments_mut() {
+                    self.check_expr(arg, None);
+                }
+                return None;
+            }
+        };
+        let given = node.arguments().count();
+        if given != params.len() {
+            let count = |n: usize| format!("{} argument{}", n, if n == 1 { "" } else { "s" });
+            let diagnostic = Diagnostic::error(
+                E0303,
+                format!("`{}` takes {} but {} {} given", node.value(), count(params.len()), given, if given == 1 { "was" } else { "were" }),
+            )
+            .with_label(node.span, format!("expected {}", count(params.len())))
+            .with_note(format!("`{}` is `{}`", node.value(), self.def_types[id].as_ref().unwrap()));
+            self.diagnostics.push(diagnostic);
+        }
+        for (index, arg) in node.arguments_mut().enumerate() {
+            let expected = params.get(index);
+            let found = self.check_expr(arg, expected);
+            if let (Some(expected), Some(found)) = (expected, &found) {
+                if !self.coerce(arg, expected, found) {
+                    let span = arg.span;
+                    self.mismatch(span, expected, found);
+                }
+            }
+        }
+        Some(ret)
+    }
+
+    //`def (x: i32) -> i32 { ... }`, a parameter without a type takes it from
+    //the function the expression is expected to be. It is a closure when it
+    //captures variables of the functions it is in
+    pub(super) fn check_lambda(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let hint = match expected {
+            Some(CraneType::Function { params, .. } | CraneType::Closure { params, .. }) => Some(params.clone()),
+            _ => None,
+        };
+        let mut params = Vec::new();
+        for (index, param) in node.children.iter_mut().filter(|c| c.is(NodeType::Param)).enumerate() {
+            let ty = match param.child(NodeType::Type).cloned() {
+                Some(ty) => self.resolve_type(&ty),
+                None => {
+                    let inferred = hint.as_ref().and_then(|params| params.get(index)).cloned();
+                    if inferred.is_none() {
+                        let diagnostic = Diagnostic::error(E0321, format!("cannot infer the type of `{}`", param.value()))
+                            .with_label(param.span, "no type given")
+                            .with_help(format!("give it a type, `{}: type`", param.value()));
+                        self.diagnostics.push(diagnostic);
+                    }
+                    inferred
+                }
+            };
+            if let Some(id) = self.resolution.declared_by(param) {
+                self.def_types[id] = ty.clone();
+            }
+            param.ty = ty.clone();
+            params.push(ty);
+        }
+        let declared = match node.return_type().cloned() {
+            Some(ty) => self.resolve_type(&ty).map(|resolved| (resolved, ty.span)),
+            None => None,
+        };
+        let annotated = node.return_type().is_some();
+        self.returns.push(Returns { declared: declared.clone(), first: None });
+        let loops = std::mem::take(&mut self.loops);
+        let body = node.children.last_mut().unwrap();
+        self.check_statement(body);
+        self.loops = loops;
+        let returns = self.returns.pop().unwrap();
+        let ret = match (declared, returns.first) {
+            (Some((ty, span)), _) => {
+                if ty != CraneType::Void && !body.diverges() {
+                    let diagnostic = Diagnostic::error(E0304, "the function does not return a value on every path")
+                        .with_label(body.span, "this body can reach its end")
+                        .with_secondary(span, format!("it has to return `{}`", ty));
+                    self.diagnostics.push(diagnostic);
+                }
+                Some(ty)
+            }
+            //a return type that could not be resolved has been reported
+            (None, _) if annotated => None,
+            (None, None) => Some(CraneType::Void),
+            (None, Some((ty, _))) => ty,
+        };
+        let params = params.into_iter().collect::<Option<Vec<_>>>()?;
+        let ret = Box::new(ret?);
+        if self.resolution.captures.contains_key(&node.span) {
+            Some(CraneType::Closure { params, ret })
+        } else {
+            Some(CraneType::Function { params, ret })
+        }
+    }
+}
@@ -0,0 +1,564 @@
+use super::{Progress, TypeChecker};
+use crate::compiler::types::CraneType;
+use crate::compiler::vm::from_float;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::{Node, NodeType};
+use crate::query;
+use crate::resolver::DefKind;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+//the type of the discriminants of enums
+pub const DISCRIMINANT: CraneType = CraneType::Short { signed: false };
+
+//how many values evaluating one constant may work out, and how deep the
+//calls of `const def` functions may go
+const STEPS: usize = 1_000_000;
+const DEPTH: usize = 256;
+
+//a value worked out when compiling, integers of every width fit in an i128
+//and an f32 is kept as the f64 of the same value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Float { value: f64, double: bool },
+    Bool(bool),
+}
+
+impl Value {
+    //the value as the VM holds it
+    pub fn bits(self) -> u64 {
+        match self {
+            Value::Int(n) => n as u64,
+            Value::Float { value, double } => from_float(value, double),
+            Value::Bool(b) => b as u64,
+        }
+    }
+    //a float of type `ty`, rounded to an f32 unless it is an f64
+    fn float(value: f64, ty: &CraneType) -> Value {
+        let double = *ty == CraneType::Double;
+        let value = if double { value } else { value as f32 as f64 };
+        Value::Float { value, double }
+    }
+}
+
+//why running a `const def` stopped before its end: a `return` with its
+//value, or an error that has been reported
+enum Stop {
+    Return(Value),
+    Failed,
+}
+
+//one evaluation: the variables of the `const def` being run, and the
+//expression that started it, which errors in a call point back to
+struct Evaluation {
+    locals: HashMap<usize, Value>,
+    steps: usize,
+    depth: usize,
+    root: Span,
+}
+
+impl TypeChecker<'_> {
+    //the constants and statics of the module, evaluated on their first use
+    pub(super) fn declare_constants(&mut self) {
+        for (index, node) in self.items.iter().enumerate() {
+            let node = node.as_ref().unwrap();
+            if !node.is(NodeType::Const) && !node.is(NodeType::Static) {
+                continue;
+            }
+            if let Some(id) = self.resolution.declared_by(node) {
+                self.constant_items.insert(id, (index, Progress::Waiting));
+            }
+        }
+    }
+
+    //those nothing has used so far, in the order of the file
+    pub(super) fn check_constants(&mut self) {
+        let mut waiting: Vec<(usize, usize)> = self
+            .constant_items
+            .iter()
+            .filter(|(_, (_, progress))| *progress == Progress::Waiting)
+            .map(|(&id, &(index, _))| (index, id))
+            .collect();
+        waiting.sort();
+        for (_, id) in waiting {
+            self.constant(id);
+        }
+    }
+
+    //the value of a constant or a static, checked and evaluated once. One
+    //whose value is being worked out depends on itself
+    pub(super) fn constant(&mut self, id: usize) -> Option<Value> {
+        if let Some(&value) = self.constants.get(&id) {
+            return Some(value);
+        }
+        let &(index, progress) = self.constant_items.get(&id)?;
+        match progress {
+            Progress::Waiting => {}
+            Progress::Checking => {
+                let def = &self.resolution.defs[id];
+                let diagnostic = Diagnostic::error(E0336, format!("the value of `{}` depends on itself", def.name))
+                    .with_label(def.span, format!("working out `{}` needs `{}`", def.name, def.name));
+                self.diagnostics.push(diagnostic);
+                return None;
+            }
+            Progress::Done => return None,
+        }
+        let mut node = self.items[index].take()?;
+        self.constant_items.insert(id, (index, Progress::Checking));
+        let annotation = match node.child(NodeType::Type) {
+            Some(ty) => {
+                let ty = ty.clone();
+                self.resolve_type(&ty)
+            }
+            None => None,
+        };
+        let value = node.children.last_mut().unwrap();
+        let found = self.check_expr(value, annotation.as_ref());
+        if let (Some(expected), Some(found)) = (&annotation, &found) {
+            if !self.coerce(value, expected, found) {
+                let span = value.span;
+                self.mismatch(span, expected, found);
+            }
+        }
+        let ty = annotation.or(found);
+        let result = match &ty {
+            Some(ty) if ty.is_numeric() || *ty == CraneType::Bool => self.evaluate(node.children.last().unwrap()),
+            Some(ty) => {
+                let what = if node.is(NodeType::Const) { "constant" } else { "static" };
+                let span = node.children.last().unwrap().span;
+                let diagnostic = Diagnostic::error(E0337, format!("a {} cannot have the type `{}`", what, ty))
+                    .with_label(span, format!("this is `{}`", ty))
+                    .with_note("constants and statics are integers, floats, `char` or `bool`");
+                self.diagnostics.push(diagnostic);
+                None
+            }
+            None => None,
+        };
+        self.def_types[id] = ty.clone();
+        node.ty = ty;
+        if let Some(value) = result {
+            self.constants.insert(id, value);
+        }
+        self.items[index] = Some(node);
+        self.constant_items.insert(id, (index, Progress::Done));
+        result
+    }
+
+    //the value of an expression that has to be known when compiling, which
+    //has been checked
+    pub(super) fn evaluate(&mut self, node: &Node) -> Option<Value> {
+        let mut evaluation = Evaluation {
+            locals: HashMap::new(),
+            steps: 0,
+            depth: 0,
+            root: node.span,
+        };
+        self.eval(node, &mut evaluation).ok()
+    }
+
+    //a node without a type has an error that has been reported
+    fn eval(&mut self, node: &Node, evaluation: &mut Evaluation) -> Result<Value, Stop> {
+        evaluation.steps += 1;
+        if evaluation.steps > STEPS {
+            let diagnostic = Diagnostic::error(E0334, "this takes too long to evaluate when compiling")
+                .with_label(evaluation.root, format!("still running after {} steps", STEPS));
+            self.diagnostics.push(diagnostic);
+            return Err(Stop::Failed);
+        }
+        //a global read before its type is known has none yet
+        if node.is(NodeType::Identifier) {
+            return self.eval_name(node, evaluation);
+        }
+        let Some(ty) = node.ty.clone() else {
+            return Err(Stop::Failed);
+        };
+        match node.node_type {
+            NodeType::Number | NodeType::Float if ty.is_float() => Ok(Value::float(node.value().parse().unwrap_or(0.0), &ty)),
+            NodeType::Number => Ok(Value::Int(node.value().parse().unwrap_or(0))),
+            NodeType::Bool => Ok(Value::Bool(node.value() == "true")),
+            NodeType::Character => Ok(Value::Int(node.value().bytes().next().unwrap_or(0) as i128)),
+            NodeType::UnaryExpression if node.value() == "Sub" => match self.eval(&node.children[0], evaluation)? {
+                Value::Int(n) => self.fit(node, &ty, n.checked_neg(), evaluation),
+                Value::Float { value, double } => Ok(Value::Float { value: -value, double }),
+                Value::Bool(_) => Err(Stop::Failed),
+            },
+            NodeType::UnaryExpression if node.value() == "Not" => match self.eval(&node.children[0], evaluation)? {
+                Value::Bool(b) => Ok(Value::Bool(!b)),
+                Value::Int(n) if ty.is_signed() => Ok(Value::Int(!n)),
+                Value::Int(n) => Ok(Value::Int(ty.range().map_or(0, |(_, max)| max) ^ n)),
+                Value::Float { .. } => Err(Stop::Failed),
+            },
+            NodeType::Cast => {
+                let value = self.eval(&node.children[0], evaluation)?;
+                cast(value, &ty).ok_or_else(|| self.not_constant(node, "this cannot be converted when compiling", evaluation))
+            }
+            NodeType::Expression if node.target.is_none() => self.eval_binary(node, &ty, evaluation),
+            NodeType::FunctionCall if node.target.is_none() && !self.resolution.is_field_path(node) => {
+                self.eval_call(node, evaluation)
+            }
+            _ => Err(self.not_constant(node, "this cannot be worked out when compiling", evaluation)),
+        }
+    }
+
+    //a constant, or a variable of the `const def` being run
+    fn eval_name(&mut self, node: &Node, evaluation: &mut Evaluation) -> Result<Value, Stop> {
+        let Some(id) = self.resolution.id_of(node) else {
+            return Err(Stop::Failed);
+        };
+        if let Some(&value) = evaluation.locals.get(&id).filter(|_| !self.resolution.is_field_path(node)) {
+            return Ok(value);
+        }
+        let def = &self.resolution.defs[id];
+        let label = match def.kind {
+            DefKind::Const => return self.constant(id).ok_or(Stop::Failed),
+            DefKind::Static => format!("`{}` is a static, which can change while the program runs", def.name),
+            DefKind::Function => format!("`{}` is a function", def.name),
+            _ => format!("`{}` is a variable, only known when the program runs", def.name),
+        };
+        Err(self.not_constant(node, label, evaluation))
+    }
+
+    fn eval_binary(&mut self, node: &Node, ty: &CraneType, evaluation: &mut Evaluation) -> Result<Value, Stop> {
+        let operator = node.value();
+        let left = self.eval(&node.children[0], evaluation)?;
+        //`&&` and `||` stop at their first operand when it decides
+        match (operator, left) {
+            ("AndAnd", Value::Bool(false)) => return Ok(left),
+            ("OrOr", Value::Bool(true)) => return Ok(left),
+            ("AndAnd" | "OrOr", _) => return self.eval(&node.children[1], evaluation),
+            _ => {}
+        }
+        let right = self.eval(&node.children[1], evaluation)?;
+        self.apply(node, operator, ty, left, right, evaluation)
+    }
+
+    //`left operator right` for a value of type `ty`, which overflows when
+    //the type cannot hold it
+    fn apply(
+        &mut self,
+        node: &Node,
+        operator: &str,
+        ty: &CraneType,
+        left: Value,
+        right: Value,
+        evaluation: &mut Evaluation,
+    ) -> Result<Value, Stop> {
+        let (a, b) = match (left, right) {
+            (Value::Int(a), Value::Int(b)) => (a, b),
+            //IEEE 754 arithmetic, dividing by zero is an infinity or NaN
+            (Value::Float { value: a, .. }, Value::Float { value: b, .. }) => {
+                return Ok(match operator {
+                    "EqEq" => Value::Bool(a == b),
+                    "NotEq" => Value::Bool(a != b),
+                    "Less" => Value::Bool(a < b),
+                    "LessEq" => Value::Bool(a <= b),
+                    "Greater" => Value::Bool(a > b),
+                    "GreaterEq" => Value::Bool(a >= b),
+                    "Add" => Value::float(a + b, ty),
+                    "Sub" => Value::float(a - b, ty),
+                    "Mul" => Value::float(a * b, ty),
+                    "Div" => Value::float(a / b, ty),
+                    "Mod" => Value::float(a % b, ty),
+                    _ => Value::float(a.powf(b), ty),
+                });
+            }
+            (Value::Bool(a), Value::Bool(b)) => {
+                return match operator {
+                    "EqEq" => Ok(Value::Bool(a == b)),
+                    "NotEq" => Ok(Value::Bool(a != b)),
+                    "And" => Ok(Value::Bool(a & b)),
+                    "Or" => Ok(Value::Bool(a | b)),
+                    _ => Ok(Value::Bool(a ^ b)),
+                };
+            }
+            _ => return Err(Stop::Failed),
+        };
+        let value = match operator {
+            "EqEq" => return Ok(Value::Bool(a == b)),
+            "NotEq" => return Ok(Value::Bool(a != b)),
+            "Less" => return Ok(Value::Bool(a < b)),
+            "LessEq" => return Ok(Value::Bool(a <= b)),
+            "Greater" => return Ok(Value::Bool(a > b)),
+            "GreaterEq" => return Ok(Value::Bool(a >= b)),
+            "Div" | "Mod" if b == 0 => {
+                let diagnostic = Diagnostic::error(E0335, "division by zero when evaluating a constant")
+                    .with_label(node.children[1].span, "this is 0");
+                return Err(self.stop(diagnostic, evaluation));
+            }
+            "Add" => a.checked_add(b),
+            "Sub" => a.checked_sub(b),
+            "Mul" => a.checked_mul(b),
+            "Div" => a.checked_div(b),
+            "Mod" => a.checked_rem(b),
+            "Pow" => power(a, b),
+            "And" => Some(a & b),
+            "Or" => Some(a | b),
+            _ => Some(a ^ b),
+        };
+        self.fit(node, ty, value, evaluation)
+    }
+
+    //a result has to fit the width of its type, a wrapped value would not be
+    //what the program means
+    fn fit(&mut self, node: &Node, ty: &CraneType, value: Option<i128>, evaluation: &mut Evaluation) -> Result<Value, Stop> {
+        let Some((min, max)) = ty.range() else {
+            return Err(Stop::Failed);
+        };
+        match value {
+            Some(n) if n >= min && n <= max => Ok(Value::Int(n)),
+            _ => {
+                let label = match value {
+                    Some(n) => format!("this is {} but `{}` holds {} to {}", n, ty, min, max),
+                    None => format!("`{}` holds {} to {}", ty, min, max),
+                };
+                let diagnostic = Diagnostic::error(E0335, format!("overflow evaluating a constant of type `{}`", ty))
+                    .with_label(node.span, label);
+                Err(self.stop(diagnostic, evaluation))
+            }
+        }
+    }
+
+    //run a `const def` with the values of its arguments
+    fn eval_call(&mut self, node: &Node, evaluation: &mut Evaluation) -> Result<Value, Stop> {
+        let Some(id) = self.resolution.id_of(node) else {
+            return Err(Stop::Failed);
+        };
+        let def = &self.resolution.defs[id];
+        if def.kind != DefKind::Function {
+            let label = format!("`{}` is a variable", def.name);
+            return Err(self.not_constant(node, label, evaluation));
+        }
+        //a constant in a type is worked out before the functions are declared
+        if !self.functions.contains_key(&id) {
+            self.declare_functions();
+        }
+        if !self.const_functions.contains(&id) {
+            let def = &self.resolution.defs[id];
+            let diagnostic = Diagnostic::error(E0334, format!("cannot call `{}` when compiling", def.name))
+                .with_label(node.span, format!("`{}` is not a `const def`", def.name))
+                .with_secondary(def.span, "defined here")
+                .with_help("mark it `const def` if it only works with numbers, `char` and `bool`");
+            return Err(self.stop(diagnostic, evaluation));
+        }
+        if self.functions.get(&id).is_some_and(|f| !f.type_params.is_empty()) {
+            return Err(self.not_constant(node, "a generic function cannot be called when compiling", evaluation));
+        }
+        let mut values = Vec::new();
+        for argument in node.arguments() {
+            values.push(self.eval(argument, evaluation)?);
+        }
+        self.check_function(id);
+        let Some(function) = self.const_bodies.get(&id).cloned() else {
+            let label = format!("`{}` needs this value to be checked", self.resolution.defs[id].name);
+            let diagnostic = Diagnostic::error(E0336, "a `const def` is called while it is being checked")
+                .with_label(node.span, label);
+            return Err(self.stop(diagnostic, evaluation));
+        };
+        if evaluation.depth == DEPTH {
+            let diagnostic = Diagnostic::error(E0334, "this takes too long to evaluate when compiling")
+                .with_label(evaluation.root, format!("calls go more than {} deep", DEPTH));
+            self.diagnostics.push(diagnostic);
+            return Err(Stop::Failed);
+        }
+        let params = function.params().map(|param| self.resolution.declared_by(param));
+        let locals = params.zip(values).filter_map(|(id, value)| Some((id?, value))).collect();
+        let outer = std::mem::replace(&mut evaluation.locals, locals);
+        evaluation.depth += 1;
+        let result = self.run(function.body().unwrap(), evaluation);
+        evaluation.depth -= 1;
+        evaluation.locals = outer;
+        match result {
+            Err(Stop::Return(value)) => Ok(value),
+            Err(Stop::Failed) => Err(Stop::Failed),
+            Ok(()) => {
+                let name = &self.resolution.defs[id].name;
+                let label = format!("`{}` ends without returning a value", name);
+                Err(self.not_constant(node, label, evaluation))
+            }
+        }
+    }
+
+    //a statement of a `const def`
+    fn run(&mut self, node: &Node, evaluation: &mut Evaluation) -> Result<(), Stop> {
+        match node.node_type {
+            NodeType::Block => {
+                for child in &node.children {
+                    self.run(child, evaluation)?;
+                }
+            }
+            NodeType::Assignment => {
+                let value = self.eval(node.last().unwrap(), evaluation)?;
+                if let Some(id) = self.resolution.declared_by(node) {
+                    evaluation.locals.insert(id, value);
+                }
+            }
+            NodeType::Reassignment => {
+                let target = &node.children[0];
+                let id = self.resolution.id_of(target).filter(|_| !self.resolution.is_field_path(target));
+                let Some(&current) = id.and_then(|id| evaluation.locals.get(&id)) else {
+                    let label = "a `const def` can only change its own variables";
+                    return Err(self.not_constant(target, label, evaluation));
+                };
+                let mut value = self.eval(&node.children[1], evaluation)?;
+                if let Some(operator) = node.value().strip_suffix("Eq").filter(|op| !op.is_empty()) {
+                    let Some(ty) = target.ty.clone() else {
+                        return Err(Stop::Failed);
+                    };
+                    value = self.apply(node, operator, &ty, current, value, evaluation)?;
+                }
+                evaluation.locals.insert(id.unwrap(), value);
+            }
+            NodeType::Conditional => match self.eval(&node.children[0], evaluation)? {
+                Value::Bool(true) => self.run(&node.children[1], evaluation)?,
+                _ => {
+                    if let Some(otherwise) = node.children.get(2) {
+                        self.run(otherwise, evaluation)?;
+                    }
+                }
+            },
+            NodeType::Return => {
+                let Some(value) = node.children.first() else {
+                    return Err(self.not_constant(node, "this returns no value", evaluation));
+                };
+                let value = self.eval(value, evaluation)?;
+                return Err(Stop::Return(value));
+            }
+            NodeType::Loop | NodeType::Break | NodeType::Continue | NodeType::Destructure | NodeType::Keyword => {
+                return Err(self.not_constant(node, "this cannot be run when compiling", evaluation));
+            }
+            _ => {
+                self.eval(node, evaluation)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn not_constant(&mut self, node: &Node, label: impl Into<String>, evaluation: &Evaluation) -> Stop {
+        let diagnostic = Diagnostic::error(E0334, "this cannot be evaluated when compiling").with_label(node.span, label);
+        self.stop(diagnostic, evaluation)
+    }
+
+    //report why an evaluation stopped, from the expression it started at
+    //when that is in a `const def` it calls
+    fn stop(&mut self, diagnostic: Diagnostic, evaluation: &Evaluation) -> Stop {
+        let diagnostic = match evaluation.depth {
+            0 => diagnostic,
+            _ => diagnostic.with_secondary(evaluation.root, "while evaluating this"),
+        };
+        self.diagnostics.push(diagnostic);
+        Stop::Failed
+    }
+
+    //the tag of a variant, one more than the variant before it unless it is
+    //written. `None` when it is wrong, which has been reported
+    pub(super) fn discriminant(&mut self, variant: &Node, previous: Option<u64>) -> Option<u64> {
+        let Some(written) = variant.child(NodeType::Discriminant) else {
+            let Some(previous) = previous else { return Some(0) };
+            if previous < u32::MAX as u64 {
+                return Some(previous + 1);
+            }
+            let diagnostic = Diagnostic::error(E0335, format!("the discriminant of `{}` does not fit in `u32`", variant.value()))
+                .with_label(variant.span, format!("this would be {}", previous + 1))
+                .with_help("give it a discriminant with `= value`");
+            self.diagnostics.push(diagnostic);
+            return None;
+        };
+        let mut value = written.children[0].clone();
+        let found = self.check_expr(&mut value, Some(&DISCRIMINANT))?;
+        if !self.coerce(&mut value, &DISCRIMINANT, &found) {
+            self.mismatch(value.span, &DISCRIMINANT, &found);
+            return None;
+        }
+        self.evaluate(&value).map(Value::bits)
+    }
+
+    //a constant has one value for the whole program
+    pub(super) fn check_not_constant(&mut self, target: &Node) {
+        let Some(id) = self.resolution.id_of(target).filter(|_| target.is(NodeType::Identifier)) else {
+            return;
+        };
+        let def = &self.resolution.defs[id];
+        if def.kind == DefKind::Const {
+            let diagnostic = Diagnostic::error(E0338, format!("cannot assign to the constant `{}`", def.name))
+                .with_label(target.span, "this is a constant")
+                .with_secondary(def.span, "defined here")
+                .with_help("make it a `static` to change it while the program runs");
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    //a checked `const def` is kept to be run, once it is known to only call
+    //other `const def` functions and to not read statics or globals
+    pub(super) fn check_const_function(&mut self, node: &Node, id: usize) {
+        let extent = query::extent(node);
+        let mut uses = Vec::new();
+        outside_uses(self, node, extent, &mut uses);
+        let mut ok = true;
+        for (span, used) in uses {
+            let def = &self.resolution.defs[used];
+            let diagnostic = match def.kind {
+                DefKind::Function if !self.const_functions.contains(&used) => {
+                    Diagnostic::error(E0334, format!("cannot call `{}` in a `const def`", def.name))
+                        .with_label(span, format!("`{}` is not a `const def`", def.name))
+                        .with_secondary(def.span, "defined here")
+                }
+                DefKind::Static | DefKind::Variable => Diagnostic::error(E0334, format!("cannot read `{}` in a `const def`", def.name))
+                    .with_label(span, format!("`{}` can change while the program runs", def.name))
+                    .with_help("make it a `const`, or pass it as a parameter"),
+                _ => continue,
+            };
+            self.diagnostics.push(diagnostic);
+            ok = false;
+        }
+        if ok {
+            self.const_bodies.insert(id, Rc::new(node.clone()));
+        }
+    }
+}
+
+//the defs outside of a function its body uses, with where
+fn outside_uses(checker: &TypeChecker, node: &Node, extent: Span, uses: &mut Vec<(Span, usize)>) {
+    if let Some(id) = checker.resolution.id_of(node) {
+        let span = checker.resolution.defs[id].span;
+        let inside = span.file == extent.file && span.start >= extent.start && span.end <= extent.end;
+        if !inside {
+            uses.push((node.span, id));
+        }
+    }
+    for child in &node.children {
+        outside_uses(checker, child, extent, uses);
+    }
+}
+
+//`value as ty` the way the VM converts it, `None` for what is not a number
+fn cast(value: Value, ty: &CraneType) -> Option<Value> {
+    let (min, max) = ty.range().unwrap_or_default();
+    Some(match value {
+        //straight to an f32, going through an f64 could round twice
+        Value::Int(n) if *ty == CraneType::Float => Value::Float { value: n as f32 as f64, double: false },
+        Value::Int(n) if ty.is_float() => Value::float(n as f64, ty),
+        //the low bits, sign extended for a signed type
+        Value::Int(n) => {
+            let width = 1i128 << ty.bits()?;
+            let low = n.rem_euclid(width);
+            Value::Int(if low > max { low - width } else { low })
+        }
+        Value::Bool(b) if ty.is_integer() => Value::Int(b as i128),
+        Value::Bool(_) => value,
+        Value::Float { value, .. } if ty.is_float() => Value::float(value, ty),
+        //towards zero and saturated, NaN is 0
+        Value::Float { value, .. } => Value::Int((value as i128).clamp(min, max)),
+    })
+}
+
+//`base ^ exp`, a negative exponent has no integer result
+fn power(base: i128, exp: i128) -> Option<i128> {
+    match base {
+        _ if exp < 0 => None,
+        0 | 1 => Some(if exp == 0 { 1 } else { base }),
+        -1 => Some(if exp % 2 == 0 { 1 } else { -1 }),
+        _ => base.checked_pow(u32::try_from(exp).ok()?),
+    }
+}
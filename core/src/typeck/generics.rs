@@ -0,0 +1,201 @@
+use super::Signature;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::Span;
+use crate::parser::Node;
+use std::collections::HashMap;
+use std::fmt;
+
+//what a type parameter can be constrained to, what a generic body may do
+//with a value of the parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    //arithmetic, bitwise operators and ordering
+    Integer,
+    Signed,
+    Unsigned,
+    //a trait by qualified name with its type arguments, `shapes.Shape` or
+    //`prelude.Iterator<T>`
+    Trait(String, Vec<CraneType>),
+}
+
+//the traits of the prelude the operators are overloaded through
+pub const EQ: &str = "std.prelude.Eq";
+pub const ADD: &str = "std.prelude.Add";
+pub const SUB: &str = "std.prelude.Sub";
+pub const MUL: &str = "std.prelude.Mul";
+//the trait `for` goes through the values of a struct or enum with
+pub const ITERATOR: &str = "std.prelude.Iterator";
+
+impl Constraint {
+    pub const BUILT_IN: [Constraint; 3] = [Constraint::Integer, Constraint::Signed, Constraint::Unsigned];
+
+    pub fn from_name(name: &str) -> Option<Constraint> {
+        Constraint::BUILT_IN.into_iter().find(|c| c.name() == name)
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            Constraint::Integer => "Integer",
+            Constraint::Signed => "Signed",
+            Constraint::Unsigned => "Unsigned",
+            Constraint::Trait(name, _) => name.rsplit('.').next().unwrap(),
+        }
+    }
+    //the bound with what the type parameters in its type arguments stand for
+    pub fn substitute(&self, types: &HashMap<String, CraneType>) -> Constraint {
+        match self {
+            Constraint::Trait(name, args) => Constraint::Trait(name.clone(), args.iter().map(|arg| arg.substitute(types)).collect()),
+            constraint => constraint.clone(),
+        }
+    }
+    //whether a type that is not a type parameter satisfies it without an
+    //impl, the primitive types implement the operator traits
+    pub fn holds(&self, ty: &CraneType) -> bool {
+        match self {
+            Constraint::Integer => ty.is_integer(),
+            Constraint::Signed => ty.is_integer() && ty.is_signed(),
+            Constraint::Unsigned => ty.is_integer() && !ty.is_signed(),
+            Constraint::Trait(name, _) if name == EQ => {
+                ty.is_numeric() || matches!(ty, CraneType::Bool | CraneType::Str | CraneType::Pointer { .. })
+            }
+            Constraint::Trait(name, _) if name == ADD && *ty == CraneType::Str => true,
+            Constraint::Trait(name, _) => ty.is_numeric() && [ADD, SUB, MUL].contains(&name.as_str()),
+        }
+    }
+    //whether a type parameter constrained with this satisfies `other` too
+    pub fn implies(&self, other: &Constraint) -> bool {
+        match (self, other) {
+            (Constraint::Trait(..), _) => self == other,
+            (_, Constraint::Integer) => true,
+            (_, Constraint::Trait(name, _)) => [EQ, ADD, SUB, MUL].contains(&name.as_str()),
+            _ => self == other,
+        }
+    }
+}
+
+//a constraint as it is written, `Integer` or `Iterator<i64>`
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::Trait(_, args) if !args.is_empty() => {
+                let args: Vec<String> = args.iter().map(CraneType::to_string).collect();
+                write!(f, "{}<{}>", self.name(), args.join(", "))
+            }
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+//the trait an operator is overloaded through and the method it calls
+pub fn operator_trait(operator: &str) -> Option<(&'static str, &'static str)> {
+    match operator {
+        "Add" => Some((ADD, "add")),
+        "Sub" => Some((SUB, "sub")),
+        "Mul" => Some((MUL, "mul")),
+        "EqEq" | "NotEq" => Some((EQ, "eq")),
+        _ => None,
+    }
+}
+
+//a type parameter of a generic function or struct
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub constraints: Vec<Constraint>,
+    pub span: Span,
+}
+
+//a generic function compiled for one list of type arguments
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub id: usize,
+    pub args: Vec<CraneType>,
+    //the function and its arguments, `max<i64>`
+    pub name: String,
+    pub signature: Signature,
+    //the function with the types of this instantiation, once checked
+    pub node: Option<Node>,
+    //the call it was first instantiated for, and the instantiation that
+    //call is in
+    pub span: Span,
+    pub parent: Option<usize>,
+}
+
+//bind the type parameters in `pattern` so it becomes `actual`, false when it
+//cannot or a parameter is already bound to something else
+pub fn unify(pattern: &CraneType, actual: &CraneType, types: &mut HashMap<String, CraneType>) -> bool {
+    match (pattern, actual) {
+        (CraneType::Param { name }, actual) => match types.get(name) {
+            Some(bound) => bound == actual,
+            None => {
+                types.insert(name.clone(), actual.clone());
+                true
+            }
+        },
+        (CraneType::Pointer { pointee }, CraneType::Pointer { pointee: actual }) => unify(pointee, actual, types),
+        (CraneType::Array { element, size }, CraneType::Array { element: actual, size: actual_size }) => {
+            size == actual_size && unify(element, actual, types)
+        }
+        (CraneType::Slice { element }, CraneType::Slice { element: actual }) => unify(element, actual, types),
+        (CraneType::Tuple { elements }, CraneType::Tuple { elements: actual }) => {
+            elements.len() == actual.len() && elements.iter().zip(actual).all(|(element, actual)| unify(element, actual, types))
+        }
+        (CraneType::Struct { name, args }, CraneType::Struct { name: actual_name, args: actual })
+        | (CraneType::Enum { name, args }, CraneType::Enum { name: actual_name, args: actual }) => {
+            name == actual_name
+                && args.len() == actual.len()
+                && args.iter().zip(actual).all(|(arg, actual)| unify(arg, actual, types))
+        }
+        (CraneType::Function { params, ret }, CraneType::Function { params: actual, ret: actual_ret })
+        | (CraneType::Closure { params, ret }, CraneType::Closure { params: actual, ret: actual_ret }) => {
+            params.len() == actual.len()
+                && params.iter().zip(actual).all(|(param, actual)| unify(param, actual, types))
+                && unify(ret, actual_ret, types)
+        }
+        (pattern, actual) => pattern == actual,
+    }
+}
+
+//whether `ty` has a type parameter that is not bound yet
+pub fn unresolved(ty: &CraneType, types: &HashMap<String, CraneType>) -> bool {
+    match ty {
+        CraneType::Param { name } => !types.contains_key(name),
+        CraneType::Pointer { pointee } => unresolved(pointee, types),
+        CraneType::Array { element, .. } | CraneType::Slice { element } => unresolved(element, types),
+        CraneType::Struct { args: elements, .. } | CraneType::Enum { args: elements, .. } | CraneType::Tuple { elements } => elements.iter().any(|arg| unresolved(arg, types)),
+        CraneType::Function { params, ret } | CraneType::Closure { params, ret } => {
+            params.iter().any(|param| unresolved(param, types)) || unresolved(ret, types)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str) -> CraneType {
+        CraneType::Param { name: name.to_string() }
+    }
+
+    fn pair(arg: CraneType) -> CraneType {
+        CraneType::Struct {
+            name: "main.Pair".to_string(),
+            args: vec![arg],
+        }
+    }
+
+    #[test]
+    fn parameters_are_bound_once() {
+        let i64 = CraneType::Long { signed: true };
+        let mut types = HashMap::new();
+        assert!(unify(&pair(param("T")), &pair(i64.clone()), &mut types));
+        assert!(!unresolved(&param("T"), &types));
+        assert!(unify(&param("T"), &i64, &mut types));
+        assert!(!unify(&param("T"), &CraneType::Bool, &mut types));
+        assert_eq!(pair(param("T")).substitute(&types), pair(i64.clone()));
+        let eq = Constraint::Trait(EQ.to_string(), Vec::new());
+        assert!(Constraint::Signed.implies(&eq));
+        assert!(!eq.implies(&Constraint::Integer));
+        assert!(eq.holds(&CraneType::Bool) && !eq.holds(&pair(i64)));
+    }
+}
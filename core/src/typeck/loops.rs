@@ -0,0 +1,193 @@
+use super::generics::ITERATOR;
+use super::{is_literal, TypeChecker, OPTION};
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::Node;
+
+//a loop around the code being checked. Only the `break`s of `loop` give a
+//value, which has the type it is expected to have, else that of the first
+//one; a `break` without one gives `void`
+pub(super) struct Loop {
+    keyword: &'static str,
+    expected: Option<CraneType>,
+    found: Option<(CraneType, Span)>,
+}
+
+impl TypeChecker<'_> {
+    //`while`, `for` or `loop` as a statement
+    pub(super) fn check_loop(&mut self, node: &mut Node) {
+        match node.value() {
+            "for" => self.check_for(node),
+            "loop" => {
+                self.check_expr(node, None);
+            }
+            "while" => {
+                self.check_condition(&mut node.children[0]);
+                self.check_loop_body(&mut node.children[1], "while");
+            }
+            _ => {
+                let [init, condition, step, body] = &mut node.children[..] else { unreachable!() };
+                self.check_statement(init);
+                self.check_condition(condition);
+                self.check_loop_body(body, "for");
+                self.check_statement(step);
+            }
+        }
+    }
+
+    pub(super) fn check_loop_body(&mut self, body: &mut Node, keyword: &'static str) {
+        self.loops.push(Loop { keyword, expected: None, found: None });
+        self.check_statement(body);
+        self.loops.pop();
+    }
+
+    //`loop { ... }` is the value of its `break`s. One without any never ends
+    //and can stand for whatever value is expected
+    pub(super) fn check_loop_value(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let expected = expected.cloned();
+        self.loops.push(Loop { keyword: "loop", expected: expected.clone(), found: None });
+        self.check_statement(&mut node.children[0]);
+        let found = self.loops.pop().unwrap().found;
+        match found {
+            Some((ty, _)) => Some(ty),
+            None => expected.or(Some(CraneType::Void)),
+        }
+    }
+
+    pub(super) fn check_break(&mut self, node: &mut Node) {
+        let Some(current) = self.loops.last() else {
+            for value in node.children.iter_mut() {
+                self.check_expr(value, None);
+            }
+            self.outside_loop(node, "break");
+            return;
+        };
+        let keyword = current.keyword;
+        let wanted = current.found.as_ref().map(|(ty, _)| ty.clone()).or(current.expected.clone());
+        let first = current.found.as_ref().map(|(_, span)| *span);
+        let Some(value) = node.children.first_mut() else {
+            match wanted {
+                Some(ty) if keyword == "loop" && ty != CraneType::Void => {
+                    self.break_mismatch(node.span, &ty, &CraneType::Void, first);
+                }
+                _ => self.record_break(CraneType::Void, node.span),
+            }
+            return;
+        };
+        let found = self.check_expr(value, wanted.as_ref().filter(|_| keyword == "loop"));
+        if keyword != "loop" {
+            let diagnostic = Diagnostic::error(E0343, format!("`break` with a value in a `{}` loop", keyword))
+                .with_label(value.span, format!("a `{}` loop has no value", keyword))
+                .with_help("only `loop` gives the value of its `break`, leave the value out here");
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        let Some(found) = found else { return };
+        match wanted {
+            Some(ty) if !self.coerce(value, &ty, &found) => {
+                let span = value.span;
+                self.break_mismatch(span, &ty, &found, first);
+            }
+            Some(ty) => self.record_break(ty, value.span),
+            None => self.record_break(found, value.span),
+        }
+    }
+
+    pub(super) fn check_continue(&mut self, node: &Node) {
+        if self.loops.is_empty() {
+            self.outside_loop(node, "continue");
+        }
+    }
+
+    //a `break` whose value does not agree with the first `break` of the
+    //loop, or with the type the loop is expected to have
+    fn break_mismatch(&mut self, span: Span, expected: &CraneType, found: &CraneType, first: Option<Span>) {
+        let mut diagnostic = Diagnostic::error(E0300, "mismatched types")
+            .with_label(span, format!("expected `{}`, found `{}`", expected, found));
+        if let Some(first) = first {
+            diagnostic = diagnostic.with_secondary(first, format!("the loop gives `{}` here", expected));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn record_break(&mut self, ty: CraneType, span: Span) {
+        let current = self.loops.last_mut().unwrap();
+        if current.found.is_none() {
+            current.found = Some((ty, span));
+        }
+    }
+
+    fn outside_loop(&mut self, node: &Node, keyword: &str) {
+        let diagnostic = Diagnostic::error(E0342, format!("`{}` outside of a loop", keyword))
+            .with_label(node.span, format!("there is no loop to {} here", keyword))
+            .with_note("the body of a function expression is not in the loops around it");
+        self.diagnostics.push(diagnostic);
+    }
+
+    //`for i in a..b`, every integer from `a` up to but not including `b`, or
+    //every one from `a` on when there is no end. A literal end takes the type
+    //of the other one, and the narrower of two integers is widened
+    pub(super) fn range_element(&mut self, range: &mut Node) -> Option<CraneType> {
+        if range.children.len() < 2 && range.value() != "a.." {
+            let diagnostic = Diagnostic::error(E0325, "a range without a start cannot be iterated over")
+                .with_label(range.span, "where would this start")
+                .with_help("start it at 0, `0..b`");
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        let (start, end) = range.children.split_at_mut(1);
+        let (start, end) = (&mut start[0], end.first_mut());
+        let ty = match end {
+            Some(end) if is_literal(start) && !is_literal(end) => {
+                let e = self.check_expr(end, None);
+                let s = self.check_expr(start, e.as_ref());
+                self.join_ends(start, s?, e?)?
+            }
+            Some(end) => {
+                let s = self.check_expr(start, None);
+                let e = self.check_expr(end, s.as_ref());
+                self.join_ends(end, s?, e?)?
+            }
+            None => self.check_expr(start, None)?,
+        };
+        if !self.integer(&ty) {
+            let diagnostic = Diagnostic::error(E0325, format!("a range of `{}` cannot be iterated over", ty))
+                .with_label(range.span, "`for` goes over ranges of integers");
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        range.ty = Some(ty.clone());
+        Some(ty)
+    }
+
+    fn join_ends(&mut self, other: &Node, start: CraneType, end: CraneType) -> Option<CraneType> {
+        let joined = start.join(&end);
+        if joined.is_none() && start != end {
+            self.mismatch(other.span, &start, &end);
+            return None;
+        }
+        Some(joined.unwrap_or(start))
+    }
+
+    //`for x in values` where `values` implements `Iterator<T>`, `x` is each
+    //value its `next` gives until it gives `None`. In a generic body the
+    //instantiation finds the `next` to call
+    pub(super) fn iterator_element(&mut self, node: &mut Node, ty: &CraneType) -> Option<CraneType> {
+        let element = self.trait_args(ITERATOR, ty)?.into_iter().next()?;
+        if ty.is_generic() {
+            return Some(element);
+        }
+        self.ensure_layout(&CraneType::Enum { name: OPTION.to_string(), args: vec![element.clone()] }, node.span);
+        let (id, type_args) = self.impl_method(ITERATOR, ty, "next")?;
+        if let Some(args) = &type_args {
+            if !self.instantiate(id, args, node.span) {
+                return Some(element);
+            }
+        }
+        self.check_function(id);
+        node.target = Some(id);
+        node.type_args = type_args;
+        Some(element)
+    }
+}
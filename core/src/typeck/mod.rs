@@ -0,0 +1,2336 @@
+mod arrays;
+mod casts;
+mod closures;
+mod consts;
+mod generics;
+mod loops;
+mod options;
+mod patterns;
+mod pointers;
+mod strings;
+mod traits;
+mod tuples;
+use crate::compiler::types::{CraneType, EnumLayout, Layouts, StructLayout};
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lint::{self, Emission};
+use crate::parser::{Node, NodeType, Tree};
+use crate::query::{self, Cache, Checked, Fingerprint};
+use crate::resolver::{DefKind, Resolution};
+pub use consts::Value;
+pub use generics::{Constraint, Instance, TypeParam};
+pub use options::OPTION;
+use generics::{operator_trait, unify, unresolved};
+use loops::Loop;
+use patterns::{Matrix, Pat};
+pub use traits::{ImplDef, TraitDef};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+//integer literals without any other hint get this type
+pub const DEFAULT_INTEGER: CraneType = CraneType::Short { signed: true };
+pub const DEFAULT_FLOAT: CraneType = CraneType::Double;
+
+//how many instantiations deep a chain of generic calls can go, deeper is
+//taken to be a generic function instantiating itself without end
+const INSTANTIATION_LIMIT: usize = 32;
+//how many types the type arguments of one instantiation can be made of
+const TYPE_WEIGHT_LIMIT: usize = 256;
+
+//the parameter and return types of a function, `None` where a type is not
+//written and could not be inferred
+#[derive(Debug, Clone)]
+pub struct Signature {
+    //empty unless the function is generic
+    pub type_params: Vec<TypeParam>,
+    pub params: Vec<Option<CraneType>>,
+    pub ret: Option<CraneType>,
+    //where the return type is written, `None` when it is inferred
+    pub ret_span: Option<Span>,
+    pub span: Span,
+}
+
+//a variant of an enum, `names` are the field names of `Rect { w: i32 }`.
+//The discriminant is the tag of its values
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Option<CraneType>>,
+    pub names: Option<Vec<String>>,
+    pub discriminant: u64,
+}
+
+//an enum of the program, by qualified name in the enum table. The field
+//types of a generic enum have its type parameters in them
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub type_params: Vec<TypeParam>,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+impl EnumDef {
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants.iter().position(|v| v.name == name)
+    }
+    //what the type parameters stand for in the instantiation with `args`
+    pub fn substitution(&self, args: &[CraneType]) -> HashMap<String, CraneType> {
+        self.type_params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()).collect()
+    }
+    //the field types of a variant in the instantiation with `args`
+    pub fn fields(&self, index: usize, args: &[CraneType]) -> Vec<Option<CraneType>> {
+        let types = self.substitution(args);
+        self.variants[index].fields.iter().map(|ty| ty.as_ref().map(|ty| ty.substitute(&types))).collect()
+    }
+}
+
+//a struct of the program, by qualified name in the struct table. The field
+//types of a generic struct have its type parameters in them
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub type_params: Vec<TypeParam>,
+    pub fields: Vec<(String, Option<CraneType>)>,
+    pub span: Span,
+}
+
+impl StructDef {
+    //what the type parameters stand for in the instantiation with `args`
+    pub fn substitution(&self, args: &[CraneType]) -> HashMap<String, CraneType> {
+        self.type_params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()).collect()
+    }
+    //the index and type of a field in the instantiation with `args`
+    pub fn field(&self, name: &str, args: &[CraneType]) -> Option<(usize, Option<CraneType>)> {
+        let index = self.fields.iter().position(|(n, _)| n == name)?;
+        let types = self.substitution(args);
+        Some((index, self.fields[index].1.as_ref().map(|ty| ty.substitute(&types))))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    Waiting,
+    Checking,
+    Done,
+}
+
+//what the `return`s of the function being checked have to agree with
+struct Returns {
+    declared: Option<(CraneType, Span)>,
+    //the type and span of the first `return`, when the type is inferred
+    first: Option<(Option<CraneType>, Span)>,
+}
+
+//walks the tree after name resolution, giving every expression node a type
+//in `Node::ty`; `None` means the type is not known and silences any error
+//depending on it
+pub struct TypeChecker<'a> {
+    resolution: &'a Resolution,
+    //the type of every def, by def id
+    def_types: Vec<Option<CraneType>>,
+    //the function table, by def id
+    pub functions: HashMap<usize, Signature>,
+    //the enum and struct tables by qualified name, and the layout of every
+    //enum and struct instantiation
+    pub enums: HashMap<String, EnumDef>,
+    pub structs: HashMap<String, StructDef>,
+    pub layouts: Layouts,
+    //the trait table by qualified name, and the impl table by trait and type
+    pub traits: HashMap<String, TraitDef>,
+    pub impls: HashMap<(String, CraneType), ImplDef>,
+    //the functions of `impl Square { ... }` by type and name
+    inherent: HashMap<CraneType, HashMap<String, usize>>,
+    //the qualified name of every enum, struct and trait, by def id
+    enum_names: HashMap<usize, String>,
+    struct_names: HashMap<usize, String>,
+    trait_names: HashMap<usize, String>,
+    //the trait of every trait method and the type of every impl method, by
+    //def id, and the methods whose first parameter is `self`
+    trait_methods: HashMap<usize, String>,
+    impl_methods: HashMap<usize, CraneType>,
+    takes_self: HashSet<usize>,
+    //the type parameters of the generic impl of a method, which the method
+    //is generic over
+    impl_params: HashMap<usize, Vec<TypeParam>>,
+    //the functions implemented by the VM, which have no index to call
+    natives: HashSet<usize>,
+    //set while the enums and structs of a module are declared, before their
+    //layouts can be worked out
+    declaring: bool,
+    //the constraints on the type parameters of the generic item being
+    //checked, and what they stand for while an instantiation is checked
+    constraints: HashMap<String, Vec<Constraint>>,
+    substitution: HashMap<String, CraneType>,
+    //every instantiation of a generic function, the untouched tree of each
+    //generic function to check them with, and those waiting to be checked
+    pub instances: Vec<Instance>,
+    instantiated: HashMap<(usize, Vec<CraneType>), usize>,
+    generic_items: HashMap<usize, Node>,
+    pending: Vec<usize>,
+    //the instantiation being checked
+    instance: Option<usize>,
+    //generic functions with errors in their body, which are not instantiated
+    broken: HashSet<usize>,
+    //types too large to lay out, reported once
+    oversized: HashSet<CraneType>,
+    //the top level items, taken out of the tree while they are checked so a
+    //function whose return type is inferred can be checked on its first call
+    items: Vec<Option<Node>>,
+    function_items: HashMap<usize, (usize, Progress)>,
+    //the value of every constant and static, worked out on its first use,
+    //and the checked body of every `const def` that can be run
+    pub constants: HashMap<usize, Value>,
+    constant_items: HashMap<usize, (usize, Progress)>,
+    const_functions: HashSet<usize>,
+    const_bodies: HashMap<usize, Rc<Node>>,
+    returns: Vec<Returns>,
+    //whether anything in each `unsafe` block around the code being checked
+    //needs it
+    unsafe_blocks: Vec<bool>,
+    //the loops around the code being checked, innermost last
+    loops: Vec<Loop>,
+    //what checking each function body reported, added to `diagnostics` in
+    //the order of the functions in the file whatever order they are checked in
+    bodies: HashMap<usize, (Vec<Diagnostic>, Vec<Emission>)>,
+    //results of earlier builds, when compiling incrementally
+    pub cache: Option<&'a mut Cache>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub lints: Vec<Emission>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(resolution: &'a Resolution) -> Self {
+        Self {
+            resolution,
+            def_types: vec![None; resolution.defs.len()],
+            functions: HashMap::new(),
+            enums: HashMap::new(),
+            structs: HashMap::new(),
+            layouts: Layouts::default(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
+            inherent: HashMap::new(),
+            enum_names: HashMap::new(),
+            struct_names: HashMap::new(),
+            trait_names: HashMap::new(),
+            trait_methods: HashMap::new(),
+            impl_methods: HashMap::new(),
+            takes_self: HashSet::new(),
+            impl_params: HashMap::new(),
+            natives: HashSet::new(),
+            declaring: false,
+            constraints: HashMap::new(),
+            substitution: HashMap::new(),
+            instances: Vec::new(),
+            instantiated: HashMap::new(),
+            generic_items: HashMap::new(),
+            pending: Vec::new(),
+            instance: None,
+            broken: HashSet::new(),
+            oversized: HashSet::new(),
+            items: Vec::new(),
+            function_items: HashMap::new(),
+            constants: HashMap::new(),
+            constant_items: HashMap::new(),
+            const_functions: HashSet::new(),
+            const_bodies: HashMap::new(),
+            returns: Vec::new(),
+            unsafe_blocks: Vec::new(),
+            loops: Vec::new(),
+            bodies: HashMap::new(),
+            cache: None,
+            diagnostics: Vec::new(),
+            lints: Vec::new(),
+        }
+    }
+
+    //check the tree of the module `module`, the modules it imports have been
+    //checked already. The generic functions it instantiates are checked last.
+    //The methods of traits and impls are checked like the functions of the
+    //module, taken out of their trait or impl until they are done
+    pub fn check(&mut self, module: &str, tree: &mut Tree) {
+        self.items = std::mem::take(&mut tree.nodes).into_iter().map(Some).collect();
+        self.declare_constants();
+        self.declare_trait_names(module);
+        self.declare_types(module);
+        let impls = self.declare_traits();
+        let mut methods = Vec::new();
+        for owner in 0..self.items.len() {
+            let node = self.items[owner].as_mut().unwrap();
+            if node.is(NodeType::Trait) || node.is(NodeType::Impl) {
+                for (index, child) in node.children.iter_mut().enumerate().filter(|(_, c)| c.is(NodeType::Function)) {
+                    let placeholder = Node::new(NodeType::Err, None, child.line, child.span);
+                    methods.push((owner, index, std::mem::replace(child, placeholder)));
+                }
+            }
+        }
+        let first_method = self.items.len();
+        let owners: Vec<(usize, usize)> = methods.iter().map(|&(owner, index, _)| (owner, index)).collect();
+        self.items.extend(methods.into_iter().map(|(_, _, method)| Some(method)));
+        self.declare_functions();
+        self.declare_impls(impls);
+        self.check_constants();
+        //the same order as name resolution, globals before function bodies
+        let (functions, statements): (Vec<usize>, Vec<usize>) = (0..self.items.len())
+            .partition(|&i| self.items[i].as_ref().unwrap().is(NodeType::Function));
+        for index in statements {
+            let mut node = self.items[index].take().unwrap();
+            self.check_statement(&mut node);
+            self.items[index] = Some(node);
+        }
+        for index in functions {
+            let id = self.resolution.declared_by(self.items[index].as_ref().unwrap());
+            match id {
+                Some(id) => self.check_function(id),
+                None => {
+                    //a duplicate definition, it still gets its body checked
+                    let mut node = self.items[index].take().unwrap();
+                    self.check_statement(&mut node);
+                    self.items[index] = Some(node);
+                }
+            }
+        }
+        for node in self.items.iter().flatten() {
+            let body = self.resolution.declared_by(node).and_then(|id| self.bodies.remove(&id));
+            if let Some((mut diagnostics, mut lints)) = body {
+                self.diagnostics.append(&mut diagnostics);
+                self.lints.append(&mut lints);
+            }
+        }
+        while let Some(index) = self.pending.pop() {
+            self.check_instance(index);
+        }
+        let methods = self.items.split_off(first_method);
+        for ((owner, index), method) in owners.into_iter().zip(methods) {
+            self.items[owner].as_mut().unwrap().children[index] = method.unwrap();
+        }
+        tree.nodes = std::mem::take(&mut self.items).into_iter().map(Option::unwrap).collect();
+    }
+
+    //put the enums and structs of a module in their tables, all names and
+    //type parameters first so a field can hold a type declared further down
+    fn declare_types(&mut self, module: &str) {
+        let types: Vec<(usize, usize)> = (0..self.items.len())
+            .filter_map(|index| {
+                let node = self.items[index].as_ref().unwrap();
+                let id = self.resolution.declared_by(node)?;
+                (node.is(NodeType::Enum) || node.is(NodeType::Struct)).then_some((index, id))
+            })
+            .collect();
+        self.declaring = true;
+        for &(index, id) in &types {
+            let node = self.items[index].take().unwrap();
+            let name = format!("{}.{}", module, node.value());
+            let type_params = self.type_params(&node);
+            if node.is(NodeType::Enum) {
+                let def = EnumDef {
+                    type_params,
+                    variants: Vec::new(),
+                    span: node.span,
+                };
+                self.enums.insert(name.clone(), def);
+                self.enum_names.insert(id, name);
+            } else {
+                let def = StructDef {
+                    type_params,
+                    fields: Vec::new(),
+                    span: node.span,
+                };
+                self.structs.insert(name.clone(), def);
+                self.struct_names.insert(id, name);
+            }
+            self.items[index] = Some(node);
+        }
+        for &(index, id) in &types {
+            let node = self.items[index].take().unwrap();
+            if node.is(NodeType::Struct) {
+                self.declare_struct(&node, id);
+            } else {
+                self.declare_enum(&node, id);
+            }
+            self.items[index] = Some(node);
+        }
+        self.declaring = false;
+        for (index, id) in types {
+            let ty = match self.items[index].as_ref().unwrap().node_type {
+                //a generic enum or struct is laid out for each instantiation
+                NodeType::Enum if !self.enums[&self.enum_names[&id]].type_params.is_empty() => continue,
+                NodeType::Enum => CraneType::Enum { name: self.enum_names[&id].clone(), args: Vec::new() },
+                _ if !self.structs[&self.struct_names[&id]].type_params.is_empty() => continue,
+                _ => CraneType::Struct { name: self.struct_names[&id].clone(), args: Vec::new() },
+            };
+            let span = self.items[index].as_ref().unwrap().span;
+            self.layout_of(&ty, &mut Vec::new(), span);
+        }
+    }
+
+    //the variants of an enum, whose type parameters have been declared
+    fn declare_enum(&mut self, node: &Node, id: usize) {
+        let name = self.enum_names[&id].clone();
+        let constraints = constraints(&self.enums[&name].type_params);
+        let outer = std::mem::replace(&mut self.constraints, constraints);
+        let mut variants: Vec<Variant> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        //the discriminants that could be worked out, the variants counting on
+        //from one that could not are not known either
+        let mut known: Vec<Option<u64>> = Vec::new();
+        for variant in node.children.iter().filter(|c| c.is(NodeType::Variant)) {
+            let discriminant = match known.last() {
+                Some(None) if variant.child(NodeType::Discriminant).is_none() => None,
+                previous => self.discriminant(variant, previous.copied().flatten()),
+            };
+            if let Some(discriminant) = discriminant {
+                if let Some(used) = known.iter().position(|&d| d == Some(discriminant)) {
+                    let diagnostic = Diagnostic::error(E0339, format!("the discriminant {} is used twice", discriminant))
+                        .with_label(variant.span, format!("`{}` is {}", variant.value(), discriminant))
+                        .with_secondary(spans[used], format!("`{}` is {} too", variants[used].name, discriminant));
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+            let mut fields = Vec::new();
+            let mut names = Vec::new();
+            for field in variant.children.iter().filter(|c| !c.is(NodeType::Discriminant)) {
+                let ty = if field.is(NodeType::Field) {
+                    names.push(field.value().to_string());
+                    &field.children[0]
+                } else {
+                    field
+                };
+                fields.push(self.resolve_type(ty));
+            }
+            let named = variant.children.first().is_some_and(|c| c.is(NodeType::Field));
+            variants.push(Variant {
+                name: variant.value().to_string(),
+                fields,
+                names: named.then_some(names),
+                discriminant: discriminant.unwrap_or(0),
+            });
+            spans.push(variant.span);
+            known.push(discriminant);
+        }
+        self.constraints = outer;
+        self.enums.get_mut(&name).unwrap().variants = variants;
+    }
+
+    //the fields of a struct, whose type parameters have been declared
+    fn declare_struct(&mut self, node: &Node, id: usize) {
+        let name = self.struct_names[&id].clone();
+        let constraints = constraints(&self.structs[&name].type_params);
+        let outer = std::mem::replace(&mut self.constraints, constraints);
+        let fields = node
+            .children
+            .iter()
+            .filter(|c| c.is(NodeType::Field))
+            .map(|field| (field.value().to_string(), self.resolve_type(&field.children[0])))
+            .collect();
+        self.constraints = outer;
+        self.structs.get_mut(&name).unwrap().fields = fields;
+    }
+
+    //the type parameters of a generic function, struct, enum, trait or impl,
+    //bound by built-in constraints and traits
+    fn type_params(&mut self, node: &Node) -> Vec<TypeParam> {
+        let mut params = Vec::new();
+        for param in node.type_params() {
+            let mut constraints = Vec::new();
+            for name in &param.children {
+                let id = self.resolution.id_of(name);
+                let args = name.children.iter().map(|arg| self.resolve_type(arg)).collect::<Option<Vec<CraneType>>>();
+                let found = Constraint::from_name(name.value()).or_else(|| {
+                    let bound = id.and_then(|id| self.trait_names.get(&id))?;
+                    Some(Constraint::Trait(bound.clone(), args?))
+                });
+                match found {
+                    Some(constraint) => constraints.push(constraint),
+                    //a name that is not a trait has been reported
+                    None if id.is_some() || name.value().contains('.') => {}
+                    None => {
+                        let known: Vec<String> = Constraint::BUILT_IN.iter().map(|c| format!("`{}`", c.name())).collect();
+                        let diagnostic = Diagnostic::error(E0311, format!("unknown constraint `{}`", name.value()))
+                            .with_label(name.span, "not a constraint or a trait")
+                            .with_note(format!("the built-in constraints are {}, a bound can also be a trait", known.join(", ")));
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+            params.push(TypeParam {
+                name: param.value().to_string(),
+                constraints,
+                span: param.span,
+            });
+        }
+        params
+    }
+
+    //the size and alignment of a type, working out the layout of the enums
+    //and structs in it. A type that holds itself would be infinitely large,
+    //and one larger than a u32 is reported at `span`, where it is used
+    fn layout_of(&mut self, ty: &CraneType, visiting: &mut Vec<CraneType>, span: Span) -> Option<(u32, u32)> {
+        if self.oversized.contains(ty) {
+            return None;
+        }
+        let (def_span, kind) = match ty {
+            CraneType::Enum { name, .. } => {
+                if let Some(layout) = self.layouts.get(ty) {
+                    return Some((layout.size, layout.align));
+                }
+                if ty.is_generic() {
+                    return None;
+                }
+                (self.enums.get(name)?.span, "enum")
+            }
+            CraneType::Struct { name, .. } => {
+                if let Some(layout) = self.layouts.get_struct(ty) {
+                    return Some((layout.size, layout.align));
+                }
+                if ty.is_generic() {
+                    return None;
+                }
+                (self.structs.get(name)?.span, "struct")
+            }
+            CraneType::Array { element, size } => {
+                let (element_size, align) = self.layout_of(element, visiting, span)?;
+                return match element_size.checked_mul(*size) {
+                    Some(bytes) => Some((bytes, align)),
+                    None => self.too_large(ty, span),
+                };
+            }
+            CraneType::Tuple { elements } => {
+                let mut fields = Vec::new();
+                for element in elements {
+                    fields.push(self.layout_of(element, visiting, span)?);
+                }
+                let Some(layout) = StructLayout::new(&fields) else {
+                    return self.too_large(ty, span);
+                };
+                return Some((layout.size, layout.align));
+            }
+            ty => return Some((ty.size(), ty.align())),
+        };
+        if visiting.contains(ty) {
+            let diagnostic = Diagnostic::error(E0308, format!("recursive {} `{}` has infinite size", kind, ty))
+                .with_label(def_span, "holds itself without any indirection")
+                .with_help(format!("hold it through a pointer, `*{}`", ty));
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        visiting.push(ty.clone());
+        //values of an unknown type are kept as 64 bit integers. A type that
+        //holds one too large to lay out is too, which was already reported
+        let mut holds_oversized = false;
+        let mut field = |checker: &mut Self, ty: Option<CraneType>| match ty {
+            Some(ty) => checker.layout_of(&ty, visiting, span).unwrap_or_else(|| {
+                holds_oversized |= checker.oversized.contains(&ty);
+                (0, 1)
+            }),
+            None => (8, 8),
+        };
+        let layout = match ty {
+            CraneType::Enum { name, args } => {
+                let def = self.enums[name].clone();
+                let variants: Vec<Vec<(u32, u32)>> = (0..def.variants.len())
+                    .map(|index| def.fields(index, args).into_iter().map(|ty| field(self, ty)).collect())
+                    .collect();
+                let largest = def.variants.iter().map(|v| v.discriminant).max().unwrap_or(0);
+                if holds_oversized {
+                    visiting.pop();
+                    self.oversized.insert(ty.clone());
+                    return None;
+                }
+                let Some(layout) = EnumLayout::new(&variants, largest) else {
+                    visiting.pop();
+                    return self.too_large(ty, span);
+                };
+                self.layouts.insert(ty, layout.clone());
+                (layout.size, layout.align)
+            }
+            _ => {
+                let CraneType::Struct { name, args } = ty else { unreachable!() };
+                let def = self.structs[name].clone();
+                let types = def.substitution(args);
+                let fields: Vec<(u32, u32)> = def
+                    .fields
+                    .into_iter()
+                    .map(|(_, ty)| field(self, ty.map(|ty| ty.substitute(&types))))
+                    .collect();
+                if holds_oversized {
+                    visiting.pop();
+                    self.oversized.insert(ty.clone());
+                    return None;
+                }
+                let Some(layout) = StructLayout::new(&fields) else {
+                    visiting.pop();
+                    return self.too_large(ty, span);
+                };
+                self.layouts.insert_struct(ty, layout.clone());
+                (layout.size, layout.align)
+            }
+        };
+        visiting.pop();
+        Some(layout)
+    }
+
+    fn is_oversized(&self, ty: &CraneType) -> bool {
+        self.oversized.contains(ty)
+            || match ty {
+                CraneType::Tuple { elements } => elements.iter().any(|e| self.is_oversized(e)),
+                CraneType::Array { element, .. } => self.is_oversized(element),
+                _ => false,
+            }
+    }
+
+    fn too_large(&mut self, ty: &CraneType, span: Span) -> Option<(u32, u32)> {
+        self.oversized.insert(ty.clone());
+        let diagnostic = Diagnostic::error(E0344, format!("`{}` is too large", ty))
+            .with_label(span, format!("a value of this type would take more than {} bytes", u32::MAX));
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    //lay out the structs a type holds, once it is known what it is
+    fn ensure_layout(&mut self, ty: &CraneType, span: Span) {
+        if let CraneType::Slice { element } | CraneType::Pointer { pointee: element } = ty {
+            return self.ensure_layout(element, span);
+        }
+        if let CraneType::Tuple { elements } = ty {
+            for element in elements {
+                self.ensure_layout(element, span);
+            }
+            if !ty.is_generic() {
+                self.layout_of(ty, &mut Vec::new(), span);
+            }
+            return;
+        }
+        if matches!(ty, CraneType::Enum { .. } | CraneType::Struct { .. } | CraneType::Array { .. }) && !ty.is_generic() {
+            self.layout_of(ty, &mut Vec::new(), span);
+        }
+    }
+
+    //the functions of the module not declared yet, a constant needed by a
+    //type can call one before the methods are taken out of their traits
+    fn declare_functions(&mut self) {
+        for index in 0..self.items.len() {
+            let Some(node) = self.items[index].take() else { continue };
+            let declared = self.resolution.declared_by(&node).is_some_and(|id| self.functions.contains_key(&id));
+            if node.is(NodeType::Function) && !declared {
+                self.declare_function(&node, index);
+            }
+            self.items[index] = Some(node);
+        }
+    }
+
+    //put the signature of a function in the function table. A method of a
+    //trait is generic over `Self`, in a method of an impl `Self` is the type
+    //of the impl. `self` has the type `Self`
+    fn declare_function(&mut self, node: &Node, index: usize) {
+        let Some(id) = self.resolution.declared_by(node) else {
+            return;
+        };
+        let mut type_params = self.type_params(node);
+        if let Some(name) = self.trait_methods.get(&id) {
+            let def = &self.traits[name];
+            let args = def.type_params.iter().map(|p| CraneType::Param { name: p.name.clone() }).collect();
+            let constraints = vec![Constraint::Trait(name.clone(), args)];
+            let param = TypeParam { name: "Self".to_string(), constraints, span: node.span };
+            type_params.splice(0..0, [param].into_iter().chain(def.type_params.iter().cloned()));
+        }
+        if let Some(params) = self.impl_params.get(&id) {
+            type_params.splice(0..0, params.iter().cloned());
+        }
+        let outer = std::mem::replace(&mut self.constraints, constraints(&type_params));
+        let types = self.self_type(id);
+        let outer_types = std::mem::replace(&mut self.substitution, types);
+        let method = self.trait_methods.contains_key(&id) || self.impl_methods.contains_key(&id);
+        if method && node.params().next().is_some_and(|p| p.value() == "self") {
+            self.takes_self.insert(id);
+        }
+        if node.is_native() {
+            self.natives.insert(id);
+        }
+        if node.is_const() {
+            self.const_functions.insert(id);
+        }
+        //only a native function takes any value, passed with its type
+        let native = node.is_native();
+        let params = node
+            .params()
+            .map(|param| match param.child(NodeType::Type) {
+                Some(ty) => self.resolve_type(ty),
+                None if method && param.value() == "self" => {
+                    Some(CraneType::Param { name: "Self".to_string() }.substitute(&self.substitution))
+                }
+                None if native => None,
+                None => {
+                    let diagnostic = Diagnostic::error(E0345, format!("parameter `{}` has no type", param.value()))
+                        .with_label(param.span, "expected `: type` after the name")
+                        .with_help(format!("give it a type, `{}: i32`, or make the function generic, `{}: T`", param.value(), param.value()));
+                    self.diagnostics.push(diagnostic);
+                    None
+                }
+            })
+            .collect();
+        let (ret, ret_span) = match node.return_type() {
+            Some(ty) => (self.resolve_type(ty), Some(ty.span)),
+            None => (None, None),
+        };
+        self.constraints = outer;
+        self.substitution = outer_types;
+        if !type_params.is_empty() {
+            self.generic_items.insert(id, node.clone());
+        }
+        let signature = Signature {
+            type_params,
+            params,
+            ret,
+            ret_span,
+            span: node.span,
+        };
+        self.functions.insert(id, signature);
+        self.function_items.insert(id, (index, Progress::Waiting));
+    }
+
+    //what `Self` stands for in a method of an impl
+    fn self_type(&self, id: usize) -> HashMap<String, CraneType> {
+        let ty = self.impl_methods.get(&id).cloned();
+        ty.map(|ty| ("Self".to_string(), ty)).into_iter().collect()
+    }
+
+    //check the body of a function once, a function that is still being
+    //checked is being called recursively and keeps an unknown return type.
+    //The functions it calls are checked first, when compiling incrementally
+    //what they turned out to be is part of its fingerprint. The body of a
+    //generic function is checked with its type parameters standing for any
+    //type that satisfies their constraints
+    fn check_function(&mut self, id: usize) {
+        let Some(&(index, Progress::Waiting)) = self.function_items.get(&id) else {
+            return;
+        };
+        self.function_items.insert(id, (index, Progress::Checking));
+        let types = self.self_type(id);
+        let outer = std::mem::replace(&mut self.substitution, types);
+        self.check_body(id, index);
+        self.substitution = outer;
+        let node = self.items[index].take().unwrap();
+        if node.is_const() {
+            let outer = std::mem::take(&mut self.diagnostics);
+            self.check_const_function(&node, id);
+            let diagnostics = std::mem::replace(&mut self.diagnostics, outer);
+            if let Some((body, _)) = self.bodies.get_mut(&id) {
+                body.extend(diagnostics);
+            }
+        }
+        self.items[index] = Some(node);
+    }
+
+    fn check_body(&mut self, id: usize, index: usize) {
+        let mut node = self.items[index].take().unwrap();
+        let mut callees = Vec::new();
+        self.callees(&node, &mut callees);
+        for &callee in &callees {
+            self.check_function(callee);
+        }
+        let signature = self.functions[&id].clone();
+        let generic = !signature.type_params.is_empty();
+        //what a call to a generic function instantiates is not cached
+        let calls_generic = callees.iter().any(|c| !self.functions[c].type_params.is_empty());
+        let mut params = signature.params.iter();
+        for param in node.children.iter_mut().filter(|c| c.is(NodeType::Param)) {
+            let ty = params.next().cloned().flatten();
+            if let Some(param_id) = self.resolution.declared_by(param) {
+                self.def_types[param_id] = ty.clone();
+            }
+            param.ty = ty;
+        }
+        let declared = signature.ret.clone().zip(signature.ret_span);
+        //a native function only has its signature
+        if !node.children.last().is_some_and(|c| c.is(NodeType::Block)) {
+            node.ty = Some(signature.ret.unwrap_or(CraneType::Void));
+            self.functions.get_mut(&id).unwrap().ret = node.ty.clone();
+            self.items[index] = Some(node);
+            self.function_items.insert(id, (index, Progress::Done));
+            return;
+        }
+        //nor a method of an impl, whose `Self` is not in its fingerprint
+        let method = self.impl_methods.contains_key(&id);
+        let key = (self.cache.is_some() && !generic && !calls_generic && !method).then(|| self.fingerprint(&node));
+        if let Some(key) = key {
+            let cache = self.cache.as_mut().unwrap();
+            cache.keys.insert(node.span, key);
+            if let Some(checked) = cache.checked(key, query::extent(&node)) {
+                if restore(&mut node, &mut checked.types.into_iter()) {
+                    if signature.ret_span.is_none() {
+                        self.functions.get_mut(&id).unwrap().ret = checked.ret;
+                    }
+                    self.bodies.insert(id, (checked.diagnostics, checked.lints));
+                    self.items[index] = Some(node);
+                    self.function_items.insert(id, (index, Progress::Done));
+                    return;
+                }
+            }
+        }
+        let outer = (std::mem::take(&mut self.diagnostics), std::mem::take(&mut self.lints));
+        //a function checked on its first call is not in the caller's `unsafe` block
+        let unsafe_blocks = std::mem::take(&mut self.unsafe_blocks);
+        let loops = std::mem::take(&mut self.loops);
+        let constraints = std::mem::replace(&mut self.constraints, constraints(&signature.type_params));
+        self.returns.push(Returns { declared, first: None });
+        let body = node.children.last_mut().unwrap();
+        self.check_statement(body);
+        let returns = self.returns.pop().unwrap();
+        self.unsafe_blocks = unsafe_blocks;
+        self.loops = loops;
+        let ret = match (&returns.declared, returns.first) {
+            (Some((ty, span)), _) => {
+                if *ty != CraneType::Void && !body.diverges() {
+                    self.diagnostics.push(
+                        Diagnostic::error(E0304, format!("`{}` does not return a value on every path", node.value()))
+                            .with_label(node.span, "this function can reach its end")
+                            .with_secondary(*span, format!("it has to return `{}`", ty)),
+                    );
+                }
+                Some(ty.clone())
+            }
+            //a missing return type on a function with only bare returns is void
+            (None, None) => Some(CraneType::Void),
+            (None, Some((ty, _))) => ty,
+        };
+        if signature.ret_span.is_none() {
+            self.functions.get_mut(&id).unwrap().ret = ret.clone();
+        }
+        node.ty = ret;
+        self.constraints = constraints;
+        let diagnostics = std::mem::replace(&mut self.diagnostics, outer.0);
+        let lints = std::mem::replace(&mut self.lints, outer.1);
+        if generic && diagnostics.iter().any(Diagnostic::is_error) {
+            self.broken.insert(id);
+        }
+        //nor a body that calls methods or makes trait objects, what they
+        //dispatch to is not in its types
+        let key = match (key, self.cache.as_mut()) {
+            (Some(_), Some(cache)) if dispatches(&node) => {
+                cache.keys.remove(&node.span);
+                None
+            }
+            _ => key,
+        };
+        if let (Some(key), Some(cache)) = (key, self.cache.as_mut()) {
+            let mut types = Vec::new();
+            collect_types(&node, &mut types);
+            let checked = Checked {
+                ret: node.ty.clone(),
+                types,
+                diagnostics: diagnostics.clone(),
+                lints: lints.clone(),
+            };
+            cache.store_checked(key, query::extent(&node), &checked);
+        }
+        self.bodies.insert(id, (diagnostics, lints));
+        self.items[index] = Some(node);
+        self.function_items.insert(id, (index, Progress::Done));
+    }
+
+    //the functions called in a function body, `Square::new(1)` calls a
+    //function of the type
+    fn callees(&self, node: &Node, callees: &mut Vec<usize>) {
+        if node.is(NodeType::FunctionCall) || node.is(NodeType::Construct) {
+            if let Some(id) = self.resolution.id_of(node).filter(|&id| self.functions.contains_key(&id)) {
+                callees.push(id);
+            }
+        }
+        for child in &node.children {
+            self.callees(child, callees);
+        }
+    }
+
+    //the tree of a function and what it knows about every def outside of
+    //it: the signature of a function, the type of a global
+    fn fingerprint(&self, node: &Node) -> u64 {
+        let index = &self.cache.as_ref().unwrap().index;
+        let item = query::extent(node);
+        let mut hash = Fingerprint::new();
+        hash.write(index.module(item.file));
+        let mut stack = vec![node];
+        while let Some(next) = stack.pop() {
+            hash.write(&format!(
+                "{:?} {} {} {}",
+                next.node_type,
+                next.value(),
+                next.span.start - item.start,
+                next.span.end - item.start
+            ));
+            stack.extend(next.children.iter().rev());
+            let Some(id) = self.resolution.id_of(next) else { continue };
+            let def = &self.resolution.defs[id];
+            if def.span.file == item.file && def.span.start >= item.start && def.span.end <= item.end {
+                continue;
+            }
+            hash.write(index.name(def.span).unwrap_or(&def.name));
+            let types = match (def.kind, self.functions.get(&id)) {
+                (DefKind::Function, Some(signature)) => signature.params.iter().chain([&signature.ret]).collect(),
+                (DefKind::Enum, _) => {
+                    if let Some(name) = self.enum_names.get(&id) {
+                        hash.write(&self.describe(&CraneType::Enum { name: name.clone(), args: Vec::new() }));
+                    }
+                    continue;
+                }
+                (DefKind::Struct, _) => {
+                    if let Some(name) = self.struct_names.get(&id) {
+                        let ty = CraneType::Struct { name: name.clone(), args: Vec::new() };
+                        hash.write(&self.describe(&ty));
+                    }
+                    continue;
+                }
+                //the methods of a trait and the types implementing it
+                (DefKind::Trait, _) => {
+                    let Some(name) = self.trait_names.get(&id) else { continue };
+                    let Some(def) = self.traits.get(name) else { continue };
+                    let methods: Vec<(&str, Option<&Signature>)> =
+                        def.methods.iter().map(|m| (m.name.as_str(), self.functions.get(&m.id))).collect();
+                    let mut types: Vec<String> =
+                        self.impls.keys().filter(|(t, _)| t == name).map(|(_, ty)| self.describe(ty)).collect();
+                    types.sort();
+                    hash.write(&format!("{:?} {:?}", methods, types));
+                    continue;
+                }
+                //what a constant is, and what calling a `const def` works out
+                (DefKind::Const, _) => {
+                    hash.write(&format!("{:?} {:?}", self.def_types[id], self.constants.get(&id)));
+                    continue;
+                }
+                _ => vec![&self.def_types[id]],
+            };
+            if let Some(body) = self.const_bodies.get(&id) {
+                hash.write(&format!("{:?}", body));
+            }
+            for ty in types {
+                hash.write(&ty.as_ref().map_or("?".to_string(), |ty| self.describe(ty)));
+            }
+        }
+        hash.finish()
+    }
+
+    //a type with the variants, fields and layout of the enums and structs in
+    //it, which is what code using a value of the type depends on
+    fn describe(&self, ty: &CraneType) -> String {
+        match ty {
+            CraneType::Enum { name, .. } => {
+                format!("{} {:?} {:?} {:?}", ty, self.enums.get(name), self.layouts.get(ty), self.impls_of(ty))
+            }
+            CraneType::Struct { name, .. } => {
+                let layout = self.layouts.get_struct(ty);
+                format!("{} {:?} {:?} {:?}", ty, self.structs.get(name), layout, self.impls_of(ty))
+            }
+            CraneType::Pointer { pointee } => format!("*{}", self.describe(pointee)),
+            CraneType::Array { element, size } => format!("[{}; {}]", self.describe(element), size),
+            CraneType::Slice { element } => format!("[{}]", self.describe(element)),
+            CraneType::Tuple { elements } => {
+                let elements: Vec<String> = elements.iter().map(|element| self.describe(element)).collect();
+                format!("({})", elements.join(", "))
+            }
+            ty => ty.to_string(),
+        }
+    }
+
+    fn mismatch(&mut self, span: Span, expected: &CraneType, found: &CraneType) {
+        if self.narrowing(span, expected, found) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::error(E0300, "mismatched types")
+            .with_label(span, format!("expected `{}`, found `{}`", expected, found));
+        if expected.is_numeric() && found.is_numeric() {
+            diagnostic = diagnostic.with_help(format!("convert it with `as {}`", expected));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    //the type named by a type node
+    pub fn resolve_type(&mut self, node: &Node) -> Option<CraneType> {
+        if node.is(NodeType::Err) {
+            return None;
+        }
+        if node.value() == "*" {
+            let pointee = self.resolve_type(&node.children[0])?;
+            return Some(CraneType::Pointer {
+                pointee: Box::new(pointee),
+            });
+        }
+        if node.value() == "def" {
+            return self.function_type(node);
+        }
+        if node.value() == "[]" {
+            return self.array_type(node);
+        }
+        if node.value() == "()" {
+            return self.tuple_type(node);
+        }
+        if node.value() == "dyn" && node.children[0].value() == "def" {
+            let CraneType::Function { params, ret } = self.function_type(&node.children[0])? else { unreachable!() };
+            return Some(CraneType::Closure { params, ret });
+        }
+        if node.value() == "dyn" {
+            return self.trait_object(node);
+        }
+        let id = self.resolution.id_of(node);
+        let primitive = CraneType::from_name(node.value());
+        let param = id.filter(|&id| self.resolution.defs[id].kind == DefKind::TypeParam);
+        let known = match (primitive, param) {
+            (Some(ty), _) => Some(ty),
+            (None, Some(id)) => {
+                let name = self.resolution.defs[id].name.clone();
+                Some(CraneType::Param { name }.substitute(&self.substitution))
+            }
+            (None, None) => None,
+        };
+        if let Some(ty) = known {
+            if !node.children.is_empty() {
+                self.wrong_type_args(node.span, &ty.to_string(), node.children.len(), &[], None);
+            }
+            return Some(ty);
+        }
+        let enumeration = id.and_then(|id| self.enum_names.get(&id)).cloned();
+        let structure = id.and_then(|id| self.struct_names.get(&id)).cloned();
+        if let Some(name) = enumeration.clone().or(structure) {
+            let mut args = Vec::new();
+            for arg in &node.children {
+                args.push(self.resolve_type(arg)?);
+            }
+            let (params, span) = match enumeration {
+                Some(_) => (self.enums[&name].type_params.clone(), self.enums[&name].span),
+                None => (self.structs[&name].type_params.clone(), self.structs[&name].span),
+            };
+            if args.len() != params.len() {
+                self.wrong_type_args(node.span, node.value(), args.len(), &params, Some(span));
+                return None;
+            }
+            self.check_constraints(&params, &args, node.span, node.value());
+            let ty = match enumeration {
+                Some(_) => CraneType::Enum { name, args },
+                None => CraneType::Struct { name, args },
+            };
+            if !self.declaring {
+                self.ensure_layout(&ty, node.span);
+            }
+            return Some(ty);
+        }
+        //a path to another module that could not be found has been reported
+        if id.is_none() && node.value().contains('.') {
+            return None;
+        }
+        let mut diagnostic = Diagnostic::error(E0301, format!("unknown type `{}`", node.value()))
+            .with_label(node.span, "not a known type");
+        if let Some(def) = id.map(|id| &self.resolution.defs[id]) {
+            diagnostic = diagnostic.with_secondary(def.span, format!("`{}` is defined here, it is not a type", def.name));
+        }
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    fn check_statement(&mut self, node: &mut Node) {
+        match node.node_type {
+            NodeType::Function => {
+                if let Some(body) = node.children.last_mut().filter(|c| c.is(NodeType::Block)) {
+                    self.check_statement(body);
+                }
+            }
+            NodeType::Block if node.value.is_some() => self.check_unsafe(node),
+            NodeType::Block => {
+                for child in node.children.iter_mut() {
+                    self.check_statement(child);
+                }
+            }
+            NodeType::Assignment => {
+                let annotation = match node.child(NodeType::Type) {
+                    Some(ty) => {
+                        let ty = ty.clone();
+                        self.resolve_type(&ty)
+                    }
+                    None => None,
+                };
+                let value = node.children.last_mut().unwrap();
+                let found = self.check_expr(value, annotation.as_ref());
+                if let (Some(expected), Some(found)) = (&annotation, &found) {
+                    if !self.coerce(value, expected, found) {
+                        let span = value.span;
+                        self.mismatch(span, expected, found);
+                    }
+                }
+                let ty = annotation.or(found);
+                if let Some(id) = self.resolution.declared_by(node) {
+                    self.def_types[id] = ty.clone();
+                }
+                node.ty = ty;
+            }
+            NodeType::Destructure => self.check_destructure(node),
+            NodeType::Reassignment => {
+                self.check_not_constant(&node.children[0]);
+                let expected = match node.children[0].node_type {
+                    NodeType::Index | NodeType::Member | NodeType::UnaryExpression => self.check_expr(&mut node.children[0], None),
+                    _ => self.check_name(&node.children[0]),
+                };
+                node.children[0].ty = expected.clone();
+                let operator = node.value().to_string();
+                let value = &mut node.children[1];
+                let found = self.check_expr(value, expected.as_ref());
+                let span = value.span;
+                if let (Some(expected), Some(found)) = (&expected, &found) {
+                    //`p += n` moves the pointer `p`
+                    let moved = matches!(expected, CraneType::Pointer { .. }) && matches!(operator.as_str(), "AddEq" | "SubEq");
+                    //`s += "text"` joins strings, floats have the arithmetic operators
+                    let operable = operator == "Eq"
+                        || self.integer(expected)
+                        || match expected {
+                            CraneType::Str => operator == "AddEq",
+                            ty => ty.is_float() && matches!(operator.as_str(), "AddEq" | "SubEq" | "MulEq" | "DivEq" | "ModEq" | "PowEq"),
+                        };
+                    if moved && self.integer(found) {
+                        self.require_unsafe(node.span, "pointer arithmetic");
+                    } else if !self.coerce(value, expected, found) {
+                        self.mismatch(span, expected, found);
+                    } else if !operable {
+                        let diagnostic = Diagnostic::error(E0300, format!("`{}` needs integer operands", operator))
+                            .with_label(span, format!("found `{}`", found));
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+            NodeType::Conditional => {
+                self.check_condition(&mut node.children[0]);
+                for child in node.children[1..].iter_mut() {
+                    self.check_statement(child);
+                }
+            }
+            NodeType::Loop => self.check_loop(node),
+            NodeType::Break => self.check_break(node),
+            NodeType::Continue => self.check_continue(node),
+            NodeType::Return => self.check_return(node),
+            NodeType::Attribute
+            | NodeType::Import
+            | NodeType::Keyword
+            | NodeType::Enum
+            | NodeType::Struct
+            | NodeType::Trait
+            | NodeType::Impl
+            | NodeType::Const
+            | NodeType::Static
+            | NodeType::Err => {}
+            _ => {
+                self.check_expr(node, None);
+            }
+        }
+    }
+
+    //the condition of an `if` or a loop
+    fn check_condition(&mut self, condition: &mut Node) {
+        if let Some(found) = self.check_expr(condition, Some(&CraneType::Bool)) {
+            if found != CraneType::Bool {
+                let span = condition.span;
+                self.mismatch(span, &CraneType::Bool, &found);
+            }
+        }
+    }
+
+    //the type of an expression, `expected` lets integer literals pick a width,
+    //or be a float
+    pub fn check_expr(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let ty = match node.node_type {
+            NodeType::Number => {
+                let ty = match expected {
+                    Some(t) if self.integer(t) || t.is_float() => t.clone(),
+                    _ => DEFAULT_INTEGER,
+                };
+                self.check_literal_range(node, &ty, false);
+                Some(ty)
+            }
+            NodeType::Float => {
+                let ty = match expected {
+                    Some(t) if t.is_float() => t.clone(),
+                    _ => DEFAULT_FLOAT,
+                };
+                self.check_float_range(node, &ty);
+                Some(ty)
+            }
+            NodeType::Bool => Some(CraneType::Bool),
+            NodeType::Character => Some(CraneType::Char),
+            NodeType::String => Some(CraneType::Str),
+            NodeType::Format => self.check_format(node),
+            NodeType::Identifier => self.check_name(node),
+            NodeType::FunctionCall => self.check_call(node, expected),
+            NodeType::UnaryExpression => self.check_unary(node, expected),
+            NodeType::Expression => self.check_binary(node, expected),
+            NodeType::Construct if self.resolution.id_of(node).is_some_and(|id| self.functions.contains_key(&id)) => {
+                self.check_call(node, expected)
+            }
+            NodeType::Construct => self.check_construct(node, expected),
+            NodeType::Match => self.check_match(node, expected),
+            NodeType::Loop => self.check_loop_value(node, expected),
+            NodeType::StructLiteral => self.check_struct_literal(node, expected),
+            NodeType::Lambda => self.check_lambda(node, expected),
+            NodeType::Null => self.check_null(expected),
+            NodeType::ArrayLiteral => self.check_array(node, expected),
+            NodeType::Tuple => self.check_tuple(node, expected),
+            NodeType::Try => self.check_try(node),
+            NodeType::Cast => self.check_cast(node),
+            NodeType::Index => self.check_index(node),
+            NodeType::Member => self.check_member(node),
+            NodeType::MethodCall => self.check_method_call(node),
+            _ => None,
+        };
+        if let Some(ty) = &ty {
+            self.ensure_layout(ty, node.span);
+        }
+        node.ty = ty.clone();
+        ty
+    }
+
+    //the type of a variable, or of its fields for `p.a.b`
+    fn check_name(&mut self, node: &Node) -> Option<CraneType> {
+        let id = self.resolution.id_of(node)?;
+        match self.resolution.defs[id].kind {
+            DefKind::Function => return self.function_value(node, id),
+            DefKind::Const | DefKind::Static if self.def_types[id].is_none() => {
+                self.constant(id);
+            }
+            _ => {}
+        }
+        let mut ty = self.def_types[id].clone()?;
+        if !self.resolution.is_field_path(node) {
+            return Some(ty);
+        }
+        let mut path = node.value().split('.');
+        let mut walked = path.next().unwrap().to_string();
+        for field in path {
+            ty = self.field_type(node.span, &format!("`{}`", walked), &ty, field)?;
+            walked = format!("{}.{}", walked, field);
+        }
+        Some(ty)
+    }
+
+    //`divmod(a, b).0` or `c[1].x`, a field of a value that is not a name
+    fn check_member(&mut self, node: &mut Node) -> Option<CraneType> {
+        let ty = self.check_expr(&mut node.children[0], None)?;
+        let field = node.value().to_string();
+        self.field_type(node.span, "this value", &ty, &field)
+    }
+
+    //the type of a field of a struct or of a value of a tuple, `what` is
+    //the value it is taken from
+    fn field_type(&mut self, span: Span, what: &str, ty: &CraneType, field: &str) -> Option<CraneType> {
+        if let CraneType::Tuple { elements } = ty {
+            return self.tuple_element(span, what, elements, field);
+        }
+        let def = match ty {
+            CraneType::Struct { name, .. } => self.structs.get(name),
+            _ => None,
+        };
+        let found = match (ty, def) {
+            (CraneType::Struct { args, .. }, Some(def)) => def.field(field, args),
+            _ => None,
+        };
+        let Some((_, field_ty)) = found else {
+            let mut diagnostic = Diagnostic::error(E0309, format!("no field `{}` on type `{}`", field, ty))
+                .with_label(span, format!("{} is `{}`", what, ty));
+            if let Some(def) = def.filter(|def| !def.fields.is_empty()) {
+                let fields: Vec<String> = def.fields.iter().map(|(n, _)| format!("`{}`", n)).collect();
+                diagnostic = diagnostic.with_note(format!("its fields are {}", fields.join(", ")));
+            }
+            self.diagnostics.push(diagnostic);
+            return None;
+        };
+        field_ty
+    }
+
+    //`Pair { first: 1, second: 2 }`, the type arguments of a generic struct
+    //are inferred from the values and the type the struct is expected to be
+    fn check_struct_literal(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        //a name that is not a struct has been reported
+        let found = self.resolution.id_of(node).and_then(|id| self.struct_names.get(&id)).cloned();
+        let Some((name, def)) = found.map(|name| (name.clone(), self.structs[&name].clone())) else {
+            for field in node.children.iter_mut() {
+                self.check_expr(&mut field.children[0], None);
+            }
+            return None;
+        };
+        let path = node.value().to_string();
+        let mut types = match expected {
+            Some(CraneType::Struct { name: wanted, args }) if *wanted == name => def.substitution(args),
+            _ => HashMap::new(),
+        };
+        let mut given = Vec::new();
+        let mut ok = true;
+        for field in node.children.iter_mut() {
+            let position = def.fields.iter().position(|(n, _)| n == field.value());
+            if position.is_none() {
+                let fields: Vec<String> = def.fields.iter().map(|(n, _)| format!("`{}`", n)).collect();
+                let mut diagnostic = Diagnostic::error(E0309, format!("`{}` has no field `{}`", path, field.value()))
+                    .with_label(field.span, "unknown field");
+                if !fields.is_empty() {
+                    diagnostic = diagnostic.with_note(format!("its fields are {}", fields.join(", ")));
+                }
+                self.diagnostics.push(diagnostic);
+                ok = false;
+            }
+            let declared = position.and_then(|i| def.fields[i].1.clone());
+            let hint = declared.as_ref().filter(|ty| !unresolved(ty, &types)).map(|ty| ty.substitute(&types));
+            let value = &mut field.children[0];
+            let found = self.check_expr(value, hint.as_ref());
+            if let (Some(declared), Some(found)) = (&declared, &found) {
+                if !unify(declared, found, &mut types) && !self.coerce(value, &declared.substitute(&types), found) {
+                    let span = value.span;
+                    self.mismatch(span, &declared.substitute(&types), found);
+                    ok = false;
+                }
+            }
+            given.extend(position);
+        }
+        let missing: Vec<String> = (0..def.fields.len())
+            .filter(|index| !given.contains(index))
+            .map(|index| format!("`{}`", def.fields[index].0))
+            .collect();
+        if !missing.is_empty() {
+            let fields = if missing.len() == 1 { "field" } else { "fields" };
+            let diagnostic = Diagnostic::error(E0309, format!("missing {} {} in `{}`", fields, missing.join(", "), path))
+                .with_label(node.span, "every field needs a value");
+            self.diagnostics.push(diagnostic);
+            ok = false;
+        }
+        let help = format!("give the variable a type, `let name: {}<...> = ...`", path);
+        let args = self.infer(&def.type_params, &types, node.span, &path, &help)?;
+        if ok {
+            self.check_constraints(&def.type_params, &args, node.span, &path);
+        }
+        Some(CraneType::Struct { name, args })
+    }
+
+    //the type arguments in the order of the parameters, reporting the first
+    //one that could not be inferred
+    fn infer(
+        &mut self,
+        params: &[TypeParam],
+        types: &HashMap<String, CraneType>,
+        span: Span,
+        item: &str,
+        help: &str,
+    ) -> Option<Vec<CraneType>> {
+        if let Some(param) = params.iter().find(|p| !types.contains_key(&p.name)) {
+            let diagnostic = Diagnostic::error(E0312, format!("cannot infer the type of `{}` for `{}`", param.name, item))
+                .with_label(span, format!("the type of `{}` is not known here", param.name))
+                .with_secondary(param.span, format!("`{}` is declared here", param.name))
+                .with_help(help.to_string());
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        Some(params.iter().map(|p| types[&p.name].clone()).collect())
+    }
+
+    fn wrong_type_args(&mut self, span: Span, item: &str, given: usize, params: &[TypeParam], def: Option<Span>) {
+        let count = |n: usize| format!("{} type argument{}", n, if n == 1 { "" } else { "s" });
+        let mut diagnostic = if params.is_empty() {
+            Diagnostic::error(E0313, format!("`{}` is not generic", item))
+                .with_label(span, format!("{} given", count(given)))
+        } else {
+            Diagnostic::error(E0313, format!("`{}` takes {} but {} {} given", item, count(params.len()), given, if given == 1 { "was" } else { "were" }))
+                .with_label(span, format!("expected {}", count(params.len())))
+        };
+        if let Some(def) = def {
+            diagnostic = diagnostic.with_secondary(def, "defined here");
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    //whether a type satisfies a constraint, a type parameter only satisfies
+    //those it is declared with and an object the trait it is an object of
+    fn satisfies(&self, ty: &CraneType, constraint: &Constraint) -> bool {
+        match (ty, constraint) {
+            (CraneType::Param { name }, _) => self
+                .constraints
+                .get(name)
+                .is_some_and(|have| have.iter().any(|c| c.implies(constraint))),
+            (CraneType::Dyn { name }, Constraint::Trait(wanted, args)) => name == wanted && args.is_empty(),
+            (ty, Constraint::Trait(name, args)) => {
+                constraint.holds(ty) || self.trait_args(name, ty).is_some_and(|found| found == *args)
+            }
+            (ty, constraint) => constraint.holds(ty),
+        }
+    }
+
+    //arithmetic and ordering work on integers and on type parameters that
+    //are constrained to be integers
+    fn integer(&self, ty: &CraneType) -> bool {
+        ty.is_integer() || self.satisfies(ty, &Constraint::Integer)
+    }
+
+    fn check_constraints(&mut self, params: &[TypeParam], args: &[CraneType], span: Span, item: &str) -> bool {
+        let mut ok = true;
+        let types: HashMap<String, CraneType> = params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()).collect();
+        for (param, arg) in params.iter().zip(args) {
+            for constraint in &param.constraints {
+                let constraint = &constraint.substitute(&types);
+                if self.satisfies(arg, constraint) {
+                    continue;
+                }
+                let label = match arg {
+                    CraneType::Param { .. } => format!("`{}` is not constrained to be `{}`", arg, constraint),
+                    _ => format!("`{}` is `{}` here", param.name, arg),
+                };
+                let mut diagnostic = Diagnostic::error(E0310, format!("`{}` does not satisfy `{}`", arg, constraint))
+                    .with_label(span, label)
+                    .with_secondary(param.span, format!("`{}` of `{}` has to be `{}`", param.name, item, constraint));
+                match (arg, constraint) {
+                    (CraneType::Param { name }, _) => {
+                        diagnostic = diagnostic.with_help(format!("constrain it too, `{}: {}`", name, constraint));
+                    }
+                    (CraneType::Struct { .. } | CraneType::Enum { .. }, Constraint::Trait(..)) => {
+                        diagnostic = diagnostic.with_help(format!("implement it, `impl {} for {} {{ ... }}`", constraint, arg));
+                    }
+                    _ => {}
+                }
+                self.diagnostics.push(diagnostic);
+                ok = false;
+            }
+        }
+        ok
+    }
+
+    fn check_return(&mut self, node: &mut Node) {
+        let span = node.span;
+        let declared = self.returns.last().and_then(|r| r.declared.clone());
+        let found = match node.children.first_mut() {
+            Some(value) => {
+                let expected = declared.as_ref().map(|(ty, _)| ty);
+                let mut found = self.check_expr(value, expected);
+                if let (Some(expected), Some(ty)) = (expected, &found) {
+                    if self.coerce(value, expected, ty) {
+                        found = Some(expected.clone());
+                    }
+                }
+                (found, value.span)
+            }
+            None => (Some(CraneType::Void), span),
+        };
+        let Some(first) = self.returns.last().map(|r| r.first.clone()) else {
+            return;
+        };
+        match (declared, first) {
+            (Some((expected, ret_span)), _) => {
+                if let (Some(ty), value_span) = &found {
+                    if *ty != expected && !self.narrowing(*value_span, &expected, ty) {
+                        let diagnostic = Diagnostic::error(E0300, "mismatched types")
+                            .with_label(*value_span, format!("expected `{}`, found `{}`", expected, ty))
+                            .with_secondary(ret_span, format!("expected `{}` because of the return type", expected));
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+            (None, None) => self.returns.last_mut().unwrap().first = Some(found),
+            (None, Some((Some(first), first_span))) => {
+                if let (Some(ty), value_span) = &found {
+                    if *ty != first && !self.narrowing(*value_span, &first, ty) {
+                        let diagnostic = Diagnostic::error(E0300, "mismatched types")
+                            .with_label(*value_span, format!("expected `{}`, found `{}`", first, ty))
+                            .with_secondary(first_span, format!("the first `return` gives `{}`", first))
+                            .with_help("write the return type after the parameters, `-> type`");
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+            }
+            //the first return has an unknown type, later ones may know it
+            (None, Some((None, _))) => self.returns.last_mut().unwrap().first = Some(found),
+        }
+    }
+
+    fn check_call(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        if self.resolution.is_field_path(node) {
+            return self.check_method_call(node);
+        }
+        let id = self.resolution.id_of(node);
+        let variable = id.filter(|&id| matches!(self.resolution.defs[id].kind, DefKind::Variable | DefKind::Param));
+        if let Some(id) = variable {
+            return self.check_indirect_call(node, id);
+        }
+        //a constant in a type is checked before the functions are declared
+        let function = id.filter(|&id| self.resolution.defs[id].kind == DefKind::Function);
+        if function.is_some_and(|id| !self.functions.contains_key(&id)) {
+            self.declare_functions();
+        }
+        let Some(signature) = id.and_then(|id| self.functions.get(&id)).cloned() else {
+            //a call to something that is not a function has been reported
+            for arg in node.arguments_mut() {
+                self.check_expr(arg, None);
+            }
+            return None;
+        };
+        let given = node.arguments().count();
+        if given != signature.params.len() {
+            let diagnostic = Diagnostic::error(
+                E0303,
+                format!(
+                    "`{}` takes {} argument{} but {} {} given",
+                    node.value(),
+                    signature.params.len(),
+                    if signature.params.len() == 1 { "" } else { "s" },
+                    given,
+                    if given == 1 { "was" } else { "were" }
+                ),
+            )
+            .with_label(node.span, format!("expected {} argument{}", signature.params.len(), if signature.params.len() == 1 { "" } else { "s" }))
+            .with_secondary(signature.span, "defined here");
+            self.diagnostics.push(diagnostic);
+        }
+        let id = id.unwrap();
+        if !signature.type_params.is_empty() || node.child(NodeType::Type).is_some() {
+            return self.check_generic_call(node, id, &signature, expected);
+        }
+        for (index, arg) in node.arguments_mut().enumerate() {
+            let expected = signature.params.get(index).cloned().flatten();
+            let found = self.check_expr(arg, expected.as_ref());
+            if let (Some(expected), Some(found)) = (&expected, &found) {
+                if !self.coerce(arg, expected, found) {
+                    let span = arg.span;
+                    self.mismatch(span, expected, found);
+                }
+            }
+            //an untyped parameter, of `print` and the like, shows its value
+            if let (Some(None), Some(found)) = (signature.params.get(index), &found) {
+                if !strings::shown(found) {
+                    let diagnostic = Diagnostic::error(E0330, format!("`{}` cannot be shown as text", found))
+                        .with_label(arg.span, format!("this is `{}`", found))
+                        .with_note("integers, floats, `bool`, `char`, `str` and pointers can be printed");
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        //the return type may only be known once the body has been checked
+        self.check_function(id);
+        self.functions[&id].ret.clone()
+    }
+
+    //infer the type arguments of a call to a generic function from the
+    //arguments, literals last, and from the type the result is expected to
+    //have. A call with concrete type arguments instantiates the function
+    fn check_generic_call(
+        &mut self,
+        node: &mut Node,
+        id: usize,
+        signature: &Signature,
+        expected: Option<&CraneType>,
+    ) -> Option<CraneType> {
+        let name = node.value().to_string();
+        let mut types = HashMap::new();
+        let explicit: Vec<Node> = node.children.iter().filter(|c| c.is(NodeType::Type)).cloned().collect();
+        if !explicit.is_empty() && explicit.len() != signature.type_params.len() {
+            let span = node.span;
+            self.wrong_type_args(span, &name, explicit.len(), &signature.type_params, Some(signature.span));
+            for arg in node.arguments_mut() {
+                self.check_expr(arg, None);
+            }
+            return None;
+        }
+        for (param, ty) in signature.type_params.iter().zip(&explicit) {
+            if let Some(ty) = self.resolve_type(ty) {
+                types.insert(param.name.clone(), ty);
+            }
+        }
+        let mut ok = true;
+        let mut args: Vec<(usize, &mut Node)> = node.arguments_mut().enumerate().collect();
+        args.sort_by_key(|(_, arg)| is_literal(arg));
+        for (index, arg) in args {
+            let param = signature.params.get(index).cloned().flatten();
+            let hint = param.as_ref().filter(|ty| !unresolved(ty, &types)).map(|ty| ty.substitute(&types));
+            let found = self.check_expr(arg, hint.as_ref());
+            if let (Some(param), Some(found)) = (&param, &found) {
+                //an array is passed as a slice of its elements
+                if let (CraneType::Slice { element }, CraneType::Array { element: found, .. }) = (param, found) {
+                    unify(element, found, &mut types);
+                }
+                if !unify(param, found, &mut types) && !self.coerce(arg, &param.substitute(&types), found) {
+                    let span = arg.span;
+                    self.mismatch(span, &param.substitute(&types), found);
+                    ok = false;
+                }
+            }
+        }
+        self.bind_bounds(&signature.type_params, &mut types);
+        self.check_function(id);
+        let ret = self.functions[&id].ret.clone();
+        if let (Some(ret), Some(expected)) = (&ret, expected) {
+            if unresolved(ret, &types) {
+                unify(ret, expected, &mut types);
+            }
+        }
+        let names: Vec<&str> = signature.type_params.iter().map(|p| p.name.as_str()).collect();
+        let help = format!("give the type arguments, `{}::<{}>(...)`", name, names.join(", "));
+        let args = self.infer(&signature.type_params, &types, node.span, &name, &help)?;
+        ok &= self.check_constraints(&signature.type_params, &args, node.span, &name);
+        //inside a generic body the arguments can be type parameters, the
+        //call is instantiated when the body is
+        if ok && !args.iter().any(CraneType::is_generic) && self.instantiate(id, &args, node.span) {
+            node.type_args = Some(args);
+        }
+        ret.map(|ret| ret.substitute(&types))
+    }
+
+    //the instantiation of a generic function with `args`, checked once the
+    //functions of the module have been. False when it cannot be instantiated
+    fn instantiate(&mut self, id: usize, args: &[CraneType], span: Span) -> bool {
+        if self.broken.contains(&id) {
+            return false;
+        }
+        let key = (id, args.to_vec());
+        if self.instantiated.contains_key(&key) {
+            return true;
+        }
+        //type arguments that double at each step would take exponential time
+        //long before the chain gets too deep
+        let weight: usize = args.iter().map(CraneType::weight).sum();
+        if weight > TYPE_WEIGHT_LIMIT {
+            let diagnostic = Diagnostic::error(E0314, format!("instantiating `{}` never ends", self.resolution.defs[id].name))
+                .with_label(span, format!("its type arguments are made of more than {} types here", TYPE_WEIGHT_LIMIT))
+                .with_note("each instantiation calls one with larger type arguments");
+            self.diagnostics.push(diagnostic);
+            self.broken.insert(id);
+            return false;
+        }
+        let generic = self.functions[&id].clone();
+        let shown: Vec<String> = args.iter().map(CraneType::to_string).collect();
+        let name = format!("{}<{}>", self.resolution.defs[id].name, shown.join(", "));
+        let depth = std::iter::successors(self.instance, |&i| self.instances[i].parent).count();
+        if depth >= INSTANTIATION_LIMIT {
+            let diagnostic = Diagnostic::error(E0314, format!("instantiating `{}` never ends", self.resolution.defs[id].name))
+                .with_label(span, format!("instantiated {} times over here", depth))
+                .with_note("each instantiation calls one with different type arguments");
+            self.diagnostics.push(diagnostic);
+            self.broken.insert(id);
+            return false;
+        }
+        let types: HashMap<String, CraneType> = generic.type_params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()).collect();
+        let signature = Signature {
+            type_params: Vec::new(),
+            params: generic.params.iter().map(|ty| ty.as_ref().map(|ty| ty.substitute(&types))).collect(),
+            ret: generic.ret.as_ref().map(|ty| ty.substitute(&types)),
+            ret_span: generic.ret_span,
+            span: generic.span,
+        };
+        //a chain of instantiations with ever larger types ends when one is
+        //too large to lay out
+        let mut oversized = false;
+        for ty in signature.params.iter().chain([&signature.ret]).flatten() {
+            self.ensure_layout(ty, span);
+            oversized |= self.is_oversized(ty);
+        }
+        if oversized {
+            self.broken.insert(id);
+            return false;
+        }
+        self.instances.push(Instance {
+            id,
+            args: args.to_vec(),
+            name,
+            signature,
+            node: None,
+            span,
+            parent: self.instance,
+        });
+        let index = self.instances.len() - 1;
+        self.instantiated.insert(key, index);
+        self.pending.push(index);
+        true
+    }
+
+    //check a copy of a generic function with the types of an instantiation,
+    //which finds what only goes wrong with those types, like a literal out of
+    //their range. Its lints were already given for the generic body
+    fn check_instance(&mut self, index: usize) {
+        let instance = &self.instances[index];
+        let (id, signature) = (instance.id, instance.signature.clone());
+        let generic = &self.functions[&id].type_params;
+        let mut types: HashMap<String, CraneType> = generic.iter().map(|p| p.name.clone()).zip(instance.args.iter().cloned()).collect();
+        //`Self` in a method of a generic impl
+        if let Some(ty) = self.impl_methods.get(&id) {
+            types.insert("Self".to_string(), ty.substitute(&types));
+        }
+        let constraints = constraints(generic);
+        let mut node = self.generic_items[&id].clone();
+        let outer_types = std::mem::replace(&mut self.substitution, types);
+        let outer_constraints = std::mem::replace(&mut self.constraints, constraints);
+        let outer_instance = self.instance.replace(index);
+        let mut params = signature.params.iter();
+        for param in node.children.iter_mut().filter(|c| c.is(NodeType::Param)) {
+            let ty = params.next().cloned().flatten();
+            if let Some(param_id) = self.resolution.declared_by(param) {
+                self.def_types[param_id] = ty.clone();
+            }
+            param.ty = ty;
+        }
+        let outer = (std::mem::take(&mut self.diagnostics), std::mem::take(&mut self.lints));
+        let unsafe_blocks = std::mem::take(&mut self.unsafe_blocks);
+        let loops = std::mem::take(&mut self.loops);
+        let declared = signature.ret.clone().zip(signature.ret_span);
+        self.returns.push(Returns { declared, first: None });
+        if let Some(body) = node.children.last_mut().filter(|c| c.is(NodeType::Block)) {
+            self.check_statement(body);
+        }
+        self.returns.pop();
+        self.unsafe_blocks = unsafe_blocks;
+        self.loops = loops;
+        node.ty = signature.ret;
+        let diagnostics = std::mem::replace(&mut self.diagnostics, outer.0);
+        self.lints = outer.1;
+        for diagnostic in diagnostics {
+            let diagnostic = self.in_instance(diagnostic, Some(index));
+            self.diagnostics.push(diagnostic);
+        }
+        self.substitution = outer_types;
+        self.constraints = outer_constraints;
+        self.instance = outer_instance;
+        self.instances[index].node = Some(node);
+    }
+
+    //point an error found in an instantiation at the calls that led to it,
+    //the innermost ones and the one outside of any generic function
+    fn in_instance(&self, mut diagnostic: Diagnostic, index: Option<usize>) -> Diagnostic {
+        const SHOWN: usize = 2;
+        let chain: Vec<&Instance> = std::iter::successors(index, |&i| self.instances[i].parent)
+            .map(|i| &self.instances[i])
+            .collect();
+        let skipped = chain.len().saturating_sub(SHOWN + 1);
+        for (position, instance) in chain.iter().enumerate() {
+            if position < SHOWN || position == chain.len() - 1 {
+                diagnostic = diagnostic.with_secondary(instance.span, format!("in `{}`, instantiated here", instance.name));
+            }
+        }
+        if skipped > 0 {
+            diagnostic = diagnostic.with_note(format!("and {} more instantiations in between", skipped));
+        }
+        diagnostic
+    }
+
+    fn check_unary(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let operator = node.value().to_string();
+        match operator.as_str() {
+            "And" => return self.check_address_of(node),
+            "Mul" => return self.check_deref(node),
+            _ => {}
+        }
+        let operand = &mut node.children[0];
+        if operator == "Sub" && operand.is(NodeType::Number) {
+            //a negative literal, checked against the range as a whole
+            let ty = match expected {
+                Some(t) if self.integer(t) || t.is_float() => t.clone(),
+                _ => DEFAULT_INTEGER,
+            };
+            operand.ty = Some(ty.clone());
+            self.check_literal_range(operand, &ty, true);
+            return Some(ty);
+        }
+        let ty = self.check_expr(operand, expected)?;
+        let ok = match operator.as_str() {
+            "Sub" => self.integer(&ty) || ty.is_float(),
+            _ => self.integer(&ty) || ty == CraneType::Bool,
+        };
+        if !ok {
+            let span = node.span;
+            let diagnostic = Diagnostic::error(E0300, format!("cannot apply `{}` to `{}`", symbol(&operator), ty))
+                .with_label(span, format!("this is `{}`", ty));
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        Some(ty)
+    }
+
+    fn check_binary(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let operator = node.value().to_string();
+        let span = node.span;
+        if operator == "AndAnd" || operator == "OrOr" {
+            for operand in node.children.iter_mut() {
+                if let Some(found) = self.check_expr(operand, Some(&CraneType::Bool)) {
+                    if found != CraneType::Bool {
+                        let span = operand.span;
+                        self.mismatch(span, &CraneType::Bool, &found);
+                    }
+                }
+            }
+            return Some(CraneType::Bool);
+        }
+        let comparison = is_comparison(&operator);
+        //a literal operand takes the type of the other side
+        let hint = if comparison { None } else { expected };
+        let (left, right) = node.children.split_at_mut(1);
+        let (left, right) = (&mut left[0], &mut right[0]);
+        let (l, r) = if comparison && is_literal(right) && !is_literal(left) {
+            //a literal out of range makes the comparison useless rather than wrong
+            let l = self.check_expr(left, None);
+            (l.clone(), l.map(|ty| type_literal(right, ty)))
+        } else if comparison && is_literal(left) && !is_literal(right) {
+            let r = self.check_expr(right, None);
+            (r.clone().map(|ty| type_literal(left, ty)), r)
+        } else if (is_literal(left) || is_float_literal(left) || left.is(NodeType::Null)) && !is_literal(right) {
+            let r = self.check_expr(right, hint);
+            let l = self.check_expr(left, r.as_ref().or(hint));
+            (l, r)
+        } else {
+            let l = self.check_expr(left, hint);
+            let r = self.check_expr(right, l.as_ref().or(hint));
+            (l, r)
+        };
+        let (l, r) = (l?, r?);
+        if let CraneType::Pointer { .. } = l {
+            if let Some(ty) = self.pointer_arithmetic(&operator, span, &l, &r) {
+                return Some(ty);
+            }
+        }
+        //the narrower of two integers is widened to the other
+        let l = match l.join(&r) {
+            Some(joined) => joined,
+            None if l != r => {
+                self.mismatch(right.span, &l, &r);
+                return None;
+            }
+            None => l,
+        };
+        //a struct or enum has the operators of the prelude traits it implements
+        let overloaded = operator_trait(&operator);
+        if let (Some((name, method)), CraneType::Enum { .. } | CraneType::Struct { .. }) = (overloaded, &l) {
+            if let Some(ty) = self.check_operator(node, name, method, &l) {
+                return Some(ty);
+            }
+        }
+        let equality = matches!(operator.as_str(), "EqEq" | "NotEq");
+        let bound = |checker: &Self| overloaded.is_some_and(|(name, _)| checker.satisfies(&l, &Constraint::Trait(name.to_string(), Vec::new())));
+        let ok = match (operator.as_str(), &l) {
+            (_, CraneType::Param { .. }) if equality => bound(self),
+            (_, CraneType::Enum { .. } | CraneType::Struct { .. } | CraneType::Dyn { .. }) if equality => false,
+            _ if equality => true,
+            (_, CraneType::Str) => comparison || operator == "Add",
+            ("And" | "Or", _) => self.integer(&l) || l == CraneType::Bool,
+            (_, CraneType::Param { .. }) => self.integer(&l) || bound(self),
+            (_, CraneType::Pointer { .. }) => comparison,
+            (_, ty) if ty.is_float() => comparison || matches!(operator.as_str(), "Add" | "Sub" | "Mul" | "Div" | "Mod" | "Pow"),
+            _ => self.integer(&l),
+        };
+        if !ok {
+            let mut diagnostic = Diagnostic::error(E0300, format!("cannot apply `{}` to `{}`", symbol(&operator), l))
+                .with_label(span, format!("both sides are `{}`", l));
+            let shown = overloaded.map(|(name, _)| name.rsplit('.').next().unwrap());
+            match (&l, shown) {
+                (CraneType::Enum { .. } | CraneType::Struct { .. }, Some(shown)) => {
+                    diagnostic = diagnostic.with_help(format!("implement `{}` for `{}` to give it `{}`", shown, l, symbol(&operator)));
+                }
+                (CraneType::Enum { .. }, None) => {
+                    diagnostic = diagnostic.with_help("use `match` to tell the variants of an enum apart");
+                }
+                (CraneType::Param { name }, _) => {
+                    let constraint = if equality { "Eq" } else { "Integer" };
+                    diagnostic = diagnostic.with_help(format!("constrain it, `{}: {}`", name, constraint));
+                }
+                _ => {}
+            }
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        if comparison {
+            self.useless_comparison(node, &l);
+            return Some(CraneType::Bool);
+        }
+        Some(l)
+    }
+
+    //the enum and variant a construction or a variant pattern names
+    fn variant_of(&mut self, node: &Node) -> Option<(String, Variant)> {
+        //a path that is not an enum has been reported
+        let id = self.resolution.id_of(node)?;
+        let Some(name) = self.enum_names.get(&id).cloned() else {
+            //`Shape::new(1)` used as a pattern
+            if self.functions.contains_key(&id) {
+                let diagnostic = Diagnostic::error(E0306, format!("`{}` is a function, not a variant", node.value()))
+                    .with_label(node.span, "expected a variant")
+                    .with_secondary(self.functions[&id].span, "defined here");
+                self.diagnostics.push(diagnostic);
+            }
+            return None;
+        };
+        let def = &self.enums[&name];
+        let wanted = node.value().rsplit("::").next().unwrap();
+        if let Some(index) = def.variant(wanted) {
+            return Some((name, def.variants[index].clone()));
+        }
+        let ty = self.generic_enum(&name);
+        let variants: Vec<String> = def.variants.iter().map(|v| format!("`{}`", v.name)).collect();
+        let mut diagnostic = Diagnostic::error(E0306, format!("no variant `{}` in enum `{}`", wanted, ty))
+            .with_label(node.span, "variant not found")
+            .with_secondary(def.span, format!("`{}` is defined here", ty));
+        if !variants.is_empty() {
+            diagnostic = diagnostic.with_note(format!("`{}` has the variants {}", ty, variants.join(", ")));
+        }
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    //which field of the variant every child of a construction or pattern
+    //gives, by child index. `None` when they don't fit the variant, which
+    //has been reported
+    fn match_fields(&mut self, node: &Node, variant: &Variant) -> Option<Vec<(usize, usize)>> {
+        let path = node.value();
+        let named = |c: &Node| c.is(NodeType::Field) || c.is(NodeType::Wildcard) && c.value() == "..";
+        let count = |n: usize| format!("{} field{}", n, if n == 1 { "" } else { "s" });
+        let Some(names) = &variant.names else {
+            let named = node.children.iter().any(named);
+            if named || node.children.len() != variant.fields.len() {
+                let given = if named { "named fields".to_string() } else { count(node.children.len()) };
+                let diagnostic = Diagnostic::error(E0307, format!("`{}` has {} but {} were given", path, count(variant.fields.len()), given))
+                    .with_label(node.span, format!("expected {}", count(variant.fields.len())));
+                self.diagnostics.push(diagnostic);
+                return None;
+            }
+            return Some((0..variant.fields.len()).map(|i| (i, i)).collect());
+        };
+        if !node.children.iter().all(named) {
+            let fields: Vec<String> = names.iter().map(|n| format!("{}: _", n)).collect();
+            let diagnostic = Diagnostic::error(E0307, format!("`{}` has named fields", path))
+                .with_label(node.span, "expected the fields by name")
+                .with_help(format!("write `{} {{ {} }}`", path, fields.join(", ")));
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        let mut given = Vec::new();
+        let mut ok = true;
+        for (child, field) in node.children.iter().enumerate().filter(|(_, c)| c.is(NodeType::Field)) {
+            match names.iter().position(|n| n == field.value()) {
+                Some(index) => given.push((child, index)),
+                None => {
+                    let listed: Vec<String> = names.iter().map(|n| format!("`{}`", n)).collect();
+                    let diagnostic = Diagnostic::error(E0307, format!("`{}` has no field `{}`", path, field.value()))
+                        .with_label(field.span, "unknown field")
+                        .with_note(format!("its fields are {}", listed.join(", ")));
+                    self.diagnostics.push(diagnostic);
+                    ok = false;
+                }
+            }
+        }
+        let rest = node.children.iter().any(|c| c.is(NodeType::Wildcard));
+        let missing: Vec<String> = names
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !given.iter().any(|(_, i)| i == index))
+            .map(|(_, n)| format!("`{}`", n))
+            .collect();
+        if !missing.is_empty() && !rest {
+            let diagnostic = Diagnostic::error(E0307, format!("missing {} {} in `{}`", if missing.len() == 1 { "field" } else { "fields" }, missing.join(", "), path))
+                .with_label(node.span, "every field needs a value");
+            self.diagnostics.push(diagnostic);
+            ok = false;
+        }
+        ok.then_some(given)
+    }
+
+    //an enum with its type parameters as the arguments, `Option<T>`, for messages
+    fn generic_enum(&self, name: &str) -> CraneType {
+        let args = self.enums[name].type_params.iter().map(|p| CraneType::Param { name: p.name.clone() }).collect();
+        CraneType::Enum { name: name.to_string(), args }
+    }
+
+    //`Shape::Circle(1)` or `Shape::Rect { w: 1, h: 2 }`, the type arguments
+    //of a generic enum are inferred from the values and the type the variant
+    //is expected to be
+    fn check_construct(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let found = self.variant_of(node);
+        let fields = match &found {
+            Some((_, variant)) => self.match_fields(node, variant),
+            None => None,
+        };
+        let Some(fields) = fields else {
+            for child in node.children.iter_mut() {
+                let value = if child.is(NodeType::Field) { &mut child.children[0] } else { child };
+                self.check_expr(value, None);
+            }
+            let generic = |(name, _): &(String, Variant)| !self.enums[name].type_params.is_empty();
+            return found.filter(|found| !generic(found)).map(|(name, _)| CraneType::Enum { name, args: Vec::new() });
+        };
+        let (name, variant) = found.unwrap();
+        let params = self.enums[&name].type_params.clone();
+        let mut types = match expected {
+            Some(CraneType::Enum { name: wanted, args }) if *wanted == name => self.enums[&name].substitution(args),
+            _ => HashMap::new(),
+        };
+        let mut ok = true;
+        for (child, field) in fields {
+            let child = &mut node.children[child];
+            let value = if child.is(NodeType::Field) { &mut child.children[0] } else { child };
+            let declared = variant.fields[field].clone();
+            let hint = declared.as_ref().filter(|ty| !unresolved(ty, &types)).map(|ty| ty.substitute(&types));
+            let found = self.check_expr(value, hint.as_ref());
+            if let (Some(declared), Some(found)) = (&declared, &found) {
+                if !unify(declared, found, &mut types) && !self.coerce(value, &declared.substitute(&types), found) {
+                    let span = value.span;
+                    self.mismatch(span, &declared.substitute(&types), found);
+                    ok = false;
+                }
+            }
+        }
+        let (path, _) = node.value().split_once("::").unwrap();
+        let help = format!("give the variable a type, `let name: {}<...> = ...`", path);
+        let args = self.infer(&params, &types, node.span, node.value(), &help)?;
+        if ok {
+            self.check_constraints(&params, &args, node.span, path);
+        }
+        Some(CraneType::Enum { name, args })
+    }
+
+    //the arms agree on a type, block arms give nothing unless they never
+    //finish. Every value has to be matched by an arm without a guard
+    fn check_match(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let span = node.span;
+        let (scrutinee, arms) = node.children.split_first_mut().unwrap();
+        let ty = self.check_expr(scrutinee, None);
+        let mut first: Option<(Option<CraneType>, Span)> = None;
+        let mut rows: Vec<Vec<Pat>> = Vec::new();
+        let mut unreachable = Vec::new();
+        for arm in arms.iter_mut() {
+            let pattern = self.check_pattern(&mut arm.children[0], ty.as_ref());
+            let guarded = arm.child(NodeType::Guard).is_some();
+            if let Some(guard) = arm.children.iter_mut().find(|c| c.is(NodeType::Guard)) {
+                let condition = &mut guard.children[0];
+                if let Some(found) = self.check_expr(condition, Some(&CraneType::Bool)) {
+                    if found != CraneType::Bool {
+                        let span = condition.span;
+                        self.mismatch(span, &CraneType::Bool, &found);
+                    }
+                }
+            }
+            if ty.is_some() {
+                let matrix = Matrix::new(&self.enums, &self.structs);
+                if !matrix.useful(&rows, std::slice::from_ref(&pattern), std::slice::from_ref(&ty)) {
+                    unreachable.push(arm.children[0].span);
+                }
+                if !guarded {
+                    rows.push(vec![pattern]);
+                }
+            }
+            let body = arm.children.last_mut().unwrap();
+            let found = if body.is(NodeType::Block) {
+                self.check_statement(body);
+                if body.diverges() {
+                    continue;
+                }
+                Some(CraneType::Void)
+            } else {
+                let hint = first.as_ref().and_then(|(ty, _)| ty.clone()).or(expected.cloned());
+                self.check_expr(body, hint.as_ref())
+            };
+            match &first {
+                None => first = Some((found, body.span)),
+                Some((Some(first_ty), first_span)) => {
+                    if let Some(found) = found.filter(|found| found != first_ty) {
+                        let diagnostic = Diagnostic::error(E0300, "`match` arms have different types")
+                            .with_label(body.span, format!("expected `{}`, found `{}`", first_ty, found))
+                            .with_secondary(*first_span, format!("the first arm gives `{}`", first_ty));
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+                Some((None, _)) => {}
+            }
+        }
+        for pattern in unreachable {
+            let diagnostic = Diagnostic::warning("unreachable pattern")
+                .with_label(pattern, "the arms above match every value this one does");
+            self.lints.push(Emission::new(&lint::UNREACHABLE_PATTERNS, diagnostic));
+        }
+        if let Some(ty) = &ty {
+            let matrix = Matrix::new(&self.enums, &self.structs);
+            if let Some(missing) = matrix.missing(&rows, &[Some(ty.clone())]) {
+                let shown = matrix.show(&missing[0], Some(ty));
+                //a type whose values cannot all be named, like an integer, is
+                //only covered by a `_` arm
+                let diagnostic = if shown == "_" {
+                    Diagnostic::error(E0305, format!("non-exhaustive match: not every `{}` is covered", ty))
+                        .with_label(span, "some values match no arm")
+                        .with_help("add a `_` arm for the values the other arms leave out")
+                } else {
+                    Diagnostic::error(E0305, format!("non-exhaustive match: `{}` not covered", shown))
+                        .with_label(span, format!("pattern `{}` not covered", shown))
+                        .with_help(format!("add an arm for `{}`, or a `_` arm for every other value", shown))
+                };
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        match first {
+            Some((ty, _)) => ty,
+            //every arm returns
+            None => Some(CraneType::Void),
+        }
+    }
+
+    //give a pattern and its parts the type of what they match
+    fn check_pattern(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Pat {
+        node.ty = expected.cloned();
+        let found = match node.node_type {
+            NodeType::Binding => {
+                if let Some(id) = self.resolution.declared_by(node) {
+                    self.def_types[id] = expected.cloned();
+                }
+                return Pat::Any;
+            }
+            NodeType::Construct => return self.check_variant_pattern(node, expected),
+            NodeType::StructLiteral => return self.check_struct_pattern(node, expected),
+            NodeType::Number | NodeType::UnaryExpression => match expected {
+                Some(ty) if self.integer(ty) => {
+                    let negative = node.is(NodeType::UnaryExpression);
+                    let number = if negative { &mut node.children[0] } else { &mut *node };
+                    number.ty = Some(ty.clone());
+                    self.check_literal_range(number, ty, negative);
+                    return literal_value(node).map_or(Pat::Any, Pat::Literal);
+                }
+                _ => DEFAULT_INTEGER,
+            },
+            NodeType::Character if expected == Some(&CraneType::Char) => {
+                return Pat::Literal(node.value().bytes().next().unwrap_or(0) as i128);
+            }
+            NodeType::Bool if expected == Some(&CraneType::Bool) => {
+                return Pat::Literal((node.value() == "true") as i128);
+            }
+            NodeType::Character => CraneType::Char,
+            NodeType::Bool => CraneType::Bool,
+            _ => return Pat::Any,
+        };
+        if let Some(expected) = expected {
+            let span = node.span;
+            self.mismatch(span, expected, &found);
+        }
+        Pat::Any
+    }
+
+    fn check_variant_pattern(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Pat {
+        let found = self.variant_of(node);
+        let fields = match &found {
+            Some((name, variant)) => match expected {
+                Some(CraneType::Enum { name: wanted, .. }) if wanted == name => self.match_fields(node, variant),
+                Some(expected) => {
+                    let (span, ty) = (node.span, self.generic_enum(name));
+                    self.mismatch(span, expected, &ty);
+                    None
+                }
+                None => self.match_fields(node, variant),
+            },
+            None => None,
+        };
+        let Some(fields) = fields else {
+            for child in node.children.iter_mut() {
+                let pattern = if child.is(NodeType::Field) { &mut child.children[0] } else { child };
+                self.check_pattern(pattern, None);
+            }
+            return Pat::Any;
+        };
+        let (name, variant) = found.unwrap();
+        //the fields of a generic enum are only known through the value matched
+        let ty = match expected {
+            Some(ty) => Some(ty.clone()),
+            None if self.enums[&name].type_params.is_empty() => Some(CraneType::Enum { name: name.clone(), args: Vec::new() }),
+            None => None,
+        };
+        let index = self.enums[&name].variant(&variant.name).unwrap();
+        let types = match &ty {
+            Some(CraneType::Enum { args, .. }) => self.enums[&name].fields(index, args),
+            _ => vec![None; variant.fields.len()],
+        };
+        node.ty = ty;
+        let mut patterns = vec![Pat::Any; variant.fields.len()];
+        for (child, field) in fields {
+            let child = &mut node.children[child];
+            let pattern = if child.is(NodeType::Field) { &mut child.children[0] } else { child };
+            patterns[field] = self.check_pattern(pattern, types[field].as_ref());
+        }
+        Pat::Variant(index, patterns)
+    }
+
+    //`P { x: 0, y }`, the fields are checked as those of a variant with names
+    fn check_struct_pattern(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Pat {
+        let found = self.resolution.id_of(node).and_then(|id| self.struct_names.get(&id)).cloned();
+        let ty = match (&found, expected) {
+            (Some(name), Some(ty @ CraneType::Struct { name: wanted, .. })) if wanted == name => Some(ty.clone()),
+            (Some(name), Some(expected)) => {
+                let args = self.structs[name].type_params.iter().map(|p| CraneType::Param { name: p.name.clone() }).collect();
+                let span = node.span;
+                self.mismatch(span, expected, &CraneType::Struct { name: name.clone(), args });
+                None
+            }
+            (Some(name), None) if self.structs[name].type_params.is_empty() => Some(CraneType::Struct { name: name.clone(), args: Vec::new() }),
+            _ => None,
+        };
+        let fields = match (&found, &ty) {
+            (Some(name), Some(CraneType::Struct { args, .. })) => {
+                let def = &self.structs[name];
+                let names: Vec<String> = def.fields.iter().map(|(n, _)| n.clone()).collect();
+                let fields = names.iter().map(|n| def.field(n, args).unwrap().1).collect();
+                let variant = Variant {
+                    name: name.clone(),
+                    fields,
+                    names: Some(names),
+                    discriminant: 0,
+                };
+                self.match_fields(node, &variant).map(|given| (given, variant.fields))
+            }
+            _ => None,
+        };
+        let Some((given, types)) = fields else {
+            for child in node.children.iter_mut().filter(|c| c.is(NodeType::Field)) {
+                self.check_pattern(&mut child.children[0], None);
+            }
+            return Pat::Any;
+        };
+        node.ty = ty;
+        let mut patterns = vec![Pat::Any; types.len()];
+        for (child, field) in given {
+            patterns[field] = self.check_pattern(&mut node.children[child].children[0], types[field].as_ref());
+        }
+        Pat::Variant(0, patterns)
+    }
+
+    fn check_literal_range(&mut self, node: &Node, ty: &CraneType, negative: bool) {
+        let Some((min, max)) = ty.range() else { return };
+        let value = node.value().parse::<i128>().ok().map(|v| if negative { -v } else { v });
+        if value.is_none_or(|v| v < min || v > max) {
+            let diagnostic = Diagnostic::error(E0302, format!("literal out of range for `{}`", ty))
+                .with_label(node.span, format!("`{}` holds {} to {}", ty, min, max));
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    //a float literal too large for the type, which would be infinity
+    fn check_float_range(&mut self, node: &Node, ty: &CraneType) {
+        let value = node.value().parse::<f64>().unwrap_or(f64::INFINITY);
+        let (finite, largest) = match ty {
+            CraneType::Float => ((value as f32).is_finite(), format!("{:e}", f32::MAX)),
+            _ => (value.is_finite(), format!("{:e}", f64::MAX)),
+        };
+        if !finite {
+            let diagnostic = Diagnostic::error(E0302, format!("literal out of range for `{}`", ty))
+                .with_label(node.span, format!("`{}` holds values up to {}", ty, largest));
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    //comparing against a literal outside, or at the edge of, the range of the
+    //other operand gives the same answer whatever the operand holds
+    fn useless_comparison(&mut self, node: &Node, ty: &CraneType) {
+        let Some((min, max)) = ty.range() else { return };
+        let (left, right) = (&node.children[0], &node.children[1]);
+        let (operator, literal) = match (literal_value(left), literal_value(right)) {
+            (None, Some(value)) => (node.value().to_string(), value),
+            //`5 > x` is `x < 5`
+            (Some(value), None) => (flip(node.value()).to_string(), value),
+            _ => return,
+        };
+        let always = match operator.as_str() {
+            "EqEq" if literal < min || literal > max => false,
+            "NotEq" if literal < min || literal > max => true,
+            "Less" if literal <= min => false,
+            "Less" if literal > max => true,
+            "LessEq" if literal < min => false,
+            "LessEq" if literal >= max => true,
+            "Greater" if literal >= max => false,
+            "Greater" if literal < min => true,
+            "GreaterEq" if literal > max => false,
+            "GreaterEq" if literal <= min => true,
+            _ => return,
+        };
+        let diagnostic = Diagnostic::warning(format!("comparison is always {}", always))
+            .with_label(node.span, format!("always `{}`", always))
+            .with_note(format!("`{}` holds values from {} to {}", ty, min, max));
+        self.lints.push(Emission::new(&lint::USELESS_COMPARISONS, diagnostic));
+    }
+}
+
+//the constraints of every type parameter by name
+fn constraints(params: &[TypeParam]) -> HashMap<String, Vec<Constraint>> {
+    params.iter().map(|p| (p.name.clone(), p.constraints.clone())).collect()
+}
+
+//whether a method call, an overloaded operator or a trait object is in a body
+fn dispatches(node: &Node) -> bool {
+    let function = matches!(node.ty, Some(CraneType::Function { .. } | CraneType::Closure { .. }));
+    node.target.is_some() || node.coercion.is_some() || function || node.children.iter().any(dispatches)
+}
+
+//the type of every node in pre-order
+fn collect_types(node: &Node, types: &mut Vec<Option<CraneType>>) {
+    types.push(node.ty.clone());
+    for child in &node.children {
+        collect_types(child, types);
+    }
+}
+
+//give the nodes the types `collect_types` found, false when the tree does
+//not have the same shape
+fn restore(node: &mut Node, types: &mut impl Iterator<Item = Option<CraneType>>) -> bool {
+    let Some(ty) = types.next() else { return false };
+    node.ty = ty;
+    node.children.iter_mut().all(|child| restore(child, types))
+}
+
+fn is_comparison(operator: &str) -> bool {
+    matches!(operator, "EqEq" | "NotEq" | "Less" | "LessEq" | "Greater" | "GreaterEq")
+}
+
+fn type_literal(node: &mut Node, ty: CraneType) -> CraneType {
+    for child in node.children.iter_mut() {
+        type_literal(child, ty.clone());
+    }
+    node.ty = Some(ty.clone());
+    ty
+}
+
+fn is_literal(node: &Node) -> bool {
+    literal_value(node).is_some()
+}
+
+//`2.5` or `-2.5`
+fn is_float_literal(node: &Node) -> bool {
+    match node.node_type {
+        NodeType::Float => true,
+        NodeType::UnaryExpression if node.value() == "Sub" => is_float_literal(&node.children[0]),
+        _ => false,
+    }
+}
+
+fn literal_value(node: &Node) -> Option<i128> {
+    match node.node_type {
+        NodeType::Number => node.value().parse().ok(),
+        NodeType::UnaryExpression if node.value() == "Sub" => {
+            literal_value(&node.children[0]).map(|v| -v)
+        }
+        _ => None,
+    }
+}
+
+//the operator to use when the operands swap sides
+fn flip(operator: &str) -> &str {
+    match operator {
+        "Less" => "Greater",
+        "LessEq" => "GreaterEq",
+        "Greater" => "Less",
+        "GreaterEq" => "LessEq",
+        op => op,
+    }
+}
+
+//how an operator is written in source, for messages
+pub fn symbol(operator: &str) -> &str {
+    match operator {
+        "Add" => "+",
+        "Sub" => "-",
+        "Mul" => "*",
+        "Div" => "/",
+        "Mod" => "%",
+        "Pow" => "^",
+        "And" => "&",
+        "Or" => "|",
+        "AndAnd" => "&&",
+        "OrOr" => "||",
+        "Not" => "!",
+        "EqEq" => "==",
+        "NotEq" => "!=",
+        "Less" => "<",
+        "LessEq" => "<=",
+        "Greater" => ">",
+        "GreaterEq" => ">=",
+        op => op,
+    }
+}
@@ -0,0 +1,50 @@
+use super::TypeChecker;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::Diagnostic;
+use crate::parser::Node;
+
+pub const OPTION: &str = "std.prelude.Option";
+const RESULT: &str = "std.prelude.Result";
+
+impl TypeChecker<'_> {
+    //`value?`, the value of `Some` or `Ok`, a `None` or `Err` is returned
+    //from the function as it is
+    pub(super) fn check_try(&mut self, node: &mut Node) -> Option<CraneType> {
+        let value = &mut node.children[0];
+        let found = self.check_expr(value, None)?;
+        let (enum_name, args) = match &found {
+            CraneType::Enum { name, args } if name == OPTION || name == RESULT => (name.clone(), args.clone()),
+            _ => {
+                let diagnostic = Diagnostic::error(E0332, format!("`?` cannot be used on a value of type `{}`", found))
+                    .with_label(value.span, format!("this is `{}`", found))
+                    .with_note("`?` takes the value out of an `Option` or a `Result`");
+                self.diagnostics.push(diagnostic);
+                return None;
+            }
+        };
+        let span = node.span;
+        let declared = self.returns.last().map(|r| r.declared.clone());
+        let fits = match &declared {
+            Some(Some((CraneType::Enum { name, args: returned }, _))) if *name == enum_name => {
+                name == OPTION || returned.get(1) == args.get(1)
+            }
+            _ => false,
+        };
+        if !fits {
+            let returns = if enum_name == OPTION { "`None`".to_string() } else { format!("`Err({})`", args[1]) };
+            let diagnostic = Diagnostic::error(E0333, format!("`?` returns {} from a function that cannot return it", returns));
+            let diagnostic = match declared {
+                Some(Some((ty, ret_span))) => diagnostic
+                    .with_label(span, format!("this can return {}", returns))
+                    .with_secondary(ret_span, format!("the function returns `{}`", ty)),
+                Some(None) => diagnostic
+                    .with_label(span, format!("this can return {}", returns))
+                    .with_help("write the return type after the parameters, `-> type`"),
+                None => diagnostic.with_label(span, "`?` is outside of a function"),
+            };
+            self.diagnostics.push(diagnostic);
+        }
+        args.into_iter().next()
+    }
+}
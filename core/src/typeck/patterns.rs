@@ -0,0 +1,264 @@
+use super::{EnumDef, StructDef};
+use crate::compiler::types::CraneType;
+use std::collections::HashMap;
+
+//a pattern reduced to what decides which values it matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pat {
+    //`_` or a name
+    Any,
+    //a bool, char or integer literal, bools are 0 and 1
+    Literal(i128),
+    //the variant with this index, and a pattern for each of its fields. A
+    //struct is the only variant of its type, 0
+    Variant(usize, Vec<Pat>),
+}
+
+//which arms can match and what no arm matches, the usefulness algorithm of
+//Maranget's "Warnings for pattern matching". A row is the patterns of one arm
+//for each column of values still to be looked at, `types` has the type of
+//every column, `None` when it is not known
+pub struct Matrix<'a> {
+    enums: &'a HashMap<String, EnumDef>,
+    structs: &'a HashMap<String, StructDef>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ctor {
+    Variant(usize),
+    Literal(i128),
+}
+
+type Types = [Option<CraneType>];
+
+impl<'a> Matrix<'a> {
+    pub fn new(enums: &'a HashMap<String, EnumDef>, structs: &'a HashMap<String, StructDef>) -> Self {
+        Self { enums, structs }
+    }
+
+    //whether `row` matches a value that none of `rows` does
+    pub fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], types: &Types) -> bool {
+        let Some(head) = row.first() else {
+            return rows.is_empty();
+        };
+        let ctors = match head {
+            Pat::Variant(index, _) => vec![Ctor::Variant(*index)],
+            Pat::Literal(value) => vec![Ctor::Literal(*value)],
+            Pat::Any => match self.complete(rows, &types[0]) {
+                Some(ctors) => ctors,
+                //a value none of the rows names can only be matched by `_`
+                None => return self.useful(&default(rows), &row[1..], &types[1..]),
+            },
+        };
+        ctors.into_iter().any(|ctor| {
+            let fields = self.fields(&types[0], ctor, head);
+            let row = specialize_row(row, ctor, fields.len()).unwrap();
+            self.useful(&specialize(rows, ctor, fields.len()), &row, &[fields, types[1..].to_vec()].concat())
+        })
+    }
+
+    //a value for every column that none of `rows` matches
+    pub fn missing(&self, rows: &[Vec<Pat>], types: &Types) -> Option<Vec<Pat>> {
+        let Some(ty) = types.first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        if let Some(ctors) = self.complete(rows, ty) {
+            return ctors.into_iter().find_map(|ctor| {
+                let fields = self.fields(ty, ctor, &Pat::Any);
+                let arity = fields.len();
+                let mut values = self.missing(&specialize(rows, ctor, arity), &[fields, types[1..].to_vec()].concat())?;
+                let rest = values.split_off(arity);
+                let head = match ctor {
+                    Ctor::Variant(index) => Pat::Variant(index, values),
+                    Ctor::Literal(value) => Pat::Literal(value),
+                };
+                Some([vec![head], rest].concat())
+            });
+        }
+        let mut values = self.missing(&default(rows), &types[1..])?;
+        values.insert(0, self.unnamed(rows, ty));
+        Some(values)
+    }
+
+    //every constructor of the type when the first column names them all
+    fn complete(&self, rows: &[Vec<Pat>], ty: &Option<CraneType>) -> Option<Vec<Ctor>> {
+        let all: Vec<Ctor> = match ty.as_ref()? {
+            CraneType::Enum { name, .. } => (0..self.enums.get(name)?.variants.len()).map(Ctor::Variant).collect(),
+            CraneType::Struct { name, .. } if self.structs.contains_key(name) => vec![Ctor::Variant(0)],
+            CraneType::Bool => vec![Ctor::Literal(0), Ctor::Literal(1)],
+            _ => return None,
+        };
+        let named = heads(rows);
+        all.iter().all(|ctor| named.contains(ctor)).then_some(all)
+    }
+
+    //a value the first column does not name, `_` when it names nothing
+    fn unnamed(&self, rows: &[Vec<Pat>], ty: &Option<CraneType>) -> Pat {
+        let named = heads(rows);
+        if named.is_empty() {
+            return Pat::Any;
+        }
+        match ty {
+            Some(CraneType::Enum { name, .. }) => {
+                let variants = self.enums.get(name).map_or(&[][..], |e| &e.variants[..]);
+                variants
+                    .iter()
+                    .enumerate()
+                    .find(|(index, _)| !named.contains(&Ctor::Variant(*index)))
+                    .map_or(Pat::Any, |(index, variant)| Pat::Variant(index, vec![Pat::Any; variant.fields.len()]))
+            }
+            Some(CraneType::Bool) => Pat::Literal(named.contains(&Ctor::Literal(0)) as i128),
+            _ => Pat::Any,
+        }
+    }
+
+    //the types of the fields of a constructor, an unknown enum takes the
+    //number of fields from the pattern
+    fn fields(&self, ty: &Option<CraneType>, ctor: Ctor, pattern: &Pat) -> Vec<Option<CraneType>> {
+        let Ctor::Variant(index) = ctor else {
+            return Vec::new();
+        };
+        let fields = match ty {
+            Some(CraneType::Enum { name, args }) => self.enums.get(name).filter(|e| index < e.variants.len()).map(|e| e.fields(index, args)),
+            Some(CraneType::Struct { name, args }) => self.structs.get(name).map(|def| {
+                let types = def.substitution(args);
+                def.fields.iter().map(|(_, ty)| ty.as_ref().map(|ty| ty.substitute(&types))).collect()
+            }),
+            _ => None,
+        };
+        match (fields, pattern) {
+            (Some(fields), _) => fields,
+            (None, Pat::Variant(_, fields)) => vec![None; fields.len()],
+            (None, _) => Vec::new(),
+        }
+    }
+
+    //a value written as a pattern for messages, `Shape::Circle(_)`
+    pub fn show(&self, pat: &Pat, ty: Option<&CraneType>) -> String {
+        match (pat, ty) {
+            (Pat::Any, _) => "_".to_string(),
+            (Pat::Literal(value), Some(CraneType::Bool)) => (*value == 1).to_string(),
+            (Pat::Literal(value), Some(CraneType::Char)) => format!("'{}'", (*value as u8 as char).escape_default()),
+            (Pat::Literal(value), _) => value.to_string(),
+            (Pat::Variant(index, fields), Some(CraneType::Enum { name, args })) => {
+                let Some((def, variant)) = self.enums.get(name).and_then(|e| Some((e, e.variants.get(*index)?))) else {
+                    return "_".to_string();
+                };
+                let path = format!("{}::{}", name.rsplit('.').next().unwrap(), variant.name);
+                let shown: Vec<String> = fields
+                    .iter()
+                    .zip(&def.fields(*index, args))
+                    .map(|(field, ty)| self.show(field, ty.as_ref()))
+                    .collect();
+                match &variant.names {
+                    _ if fields.is_empty() => path,
+                    Some(_) if fields.iter().all(|f| *f == Pat::Any) => format!("{} {{ .. }}", path),
+                    Some(names) => {
+                        let named: Vec<String> = names.iter().zip(shown).map(|(n, s)| format!("{}: {}", n, s)).collect();
+                        format!("{} {{ {} }}", path, named.join(", "))
+                    }
+                    None => format!("{}({})", path, shown.join(", ")),
+                }
+            }
+            (Pat::Variant(_, fields), Some(CraneType::Struct { name, args })) => {
+                let Some(def) = self.structs.get(name) else {
+                    return "_".to_string();
+                };
+                let path = name.rsplit('.').next().unwrap();
+                if fields.iter().all(|f| *f == Pat::Any) {
+                    return format!("{} {{ .. }}", path);
+                }
+                let types = def.substitution(args);
+                let named: Vec<String> = fields
+                    .iter()
+                    .zip(&def.fields)
+                    .map(|(field, (n, ty))| format!("{}: {}", n, self.show(field, ty.as_ref().map(|ty| ty.substitute(&types)).as_ref())))
+                    .collect();
+                format!("{} {{ {} }}", path, named.join(", "))
+            }
+            (Pat::Variant(..), _) => "_".to_string(),
+        }
+    }
+}
+
+//the constructors the first column names
+fn heads(rows: &[Vec<Pat>]) -> Vec<Ctor> {
+    let mut ctors = Vec::new();
+    for row in rows {
+        let ctor = match row[0] {
+            Pat::Variant(index, _) => Ctor::Variant(index),
+            Pat::Literal(value) => Ctor::Literal(value),
+            Pat::Any => continue,
+        };
+        if !ctors.contains(&ctor) {
+            ctors.push(ctor);
+        }
+    }
+    ctors
+}
+
+//the rows for values built with `ctor`, with its fields as new columns
+fn specialize(rows: &[Vec<Pat>], ctor: Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter().filter_map(|row| specialize_row(row, ctor, arity)).collect()
+}
+
+fn specialize_row(row: &[Pat], ctor: Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let fields = match (&row[0], ctor) {
+        (Pat::Any, _) => vec![Pat::Any; arity],
+        (Pat::Variant(index, fields), Ctor::Variant(wanted)) if *index == wanted => fields.clone(),
+        (Pat::Literal(value), Ctor::Literal(wanted)) if *value == wanted => Vec::new(),
+        _ => return None,
+    };
+    Some([fields, row[1..].to_vec()].concat())
+}
+
+//the rows for values built with a constructor no row names
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| row[0] == Pat::Any)
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Span;
+    use crate::typeck::Variant;
+
+    fn variant(name: &str, fields: Vec<Option<CraneType>>) -> Variant {
+        Variant {
+            name: name.to_string(),
+            fields,
+            names: None,
+            discriminant: 0,
+        }
+    }
+
+    //enum Shape { Circle(bool), Empty }
+    fn shapes() -> HashMap<String, EnumDef> {
+        let shape = EnumDef {
+            type_params: Vec::new(),
+            variants: vec![variant("Circle", vec![Some(CraneType::Bool)]), variant("Empty", Vec::new())],
+            span: Span::default(),
+        };
+        HashMap::from([("main.Shape".to_string(), shape)])
+    }
+
+    #[test]
+    fn missing_values_are_found_inside_variants() {
+        let enums = shapes();
+        let structs = HashMap::new();
+        let matrix = Matrix::new(&enums, &structs);
+        let ty = Some(CraneType::Enum { name: "main.Shape".to_string(), args: Vec::new() });
+        let rows = vec![
+            vec![Pat::Variant(0, vec![Pat::Literal(1)])],
+            vec![Pat::Variant(1, Vec::new())],
+        ];
+        let missing = matrix.missing(&rows, std::slice::from_ref(&ty)).unwrap();
+        assert_eq!(matrix.show(&missing[0], ty.as_ref()), "Shape::Circle(false)");
+        let all = [rows, vec![vec![Pat::Variant(0, vec![Pat::Any])]]].concat();
+        assert_eq!(matrix.missing(&all, std::slice::from_ref(&ty)), None);
+        assert!(!matrix.useful(&all, &[Pat::Any], &[ty]));
+    }
+}
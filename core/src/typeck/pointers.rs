@@ -0,0 +1,113 @@
+use super::TypeChecker;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lint::{self, Emission};
+use crate::parser::{Node, NodeType};
+use crate::resolver::DefKind;
+
+impl TypeChecker<'_> {
+    //`unsafe { ... }`, a block nothing in which needs it is reported
+    pub(super) fn check_unsafe(&mut self, node: &mut Node) {
+        self.unsafe_blocks.push(false);
+        for child in node.children.iter_mut() {
+            self.check_statement(child);
+        }
+        if !self.unsafe_blocks.pop().unwrap() {
+            let keyword = Span { end: node.span.start + "unsafe".len(), ..node.span };
+            let diagnostic = Diagnostic::warning("unnecessary `unsafe` block")
+                .with_label(keyword, "nothing in this block dereferences or moves a pointer")
+                .with_help("remove `unsafe`");
+            self.lints.push(Emission::new(&lint::UNUSED_UNSAFE, diagnostic));
+        }
+    }
+
+    //dereferencing and moving pointers is only allowed in an `unsafe` block,
+    //the innermost one around it is then needed
+    pub(super) fn require_unsafe(&mut self, span: Span, what: &str) {
+        if let Some(used) = self.unsafe_blocks.last_mut() {
+            *used = true;
+            return;
+        }
+        let diagnostic = Diagnostic::error(E0327, format!("{} needs an `unsafe` block", what))
+            .with_label(span, "nothing checks where the pointer points")
+            .with_help("wrap it in `unsafe { ... }`");
+        self.diagnostics.push(diagnostic);
+    }
+
+    //a variable, a field of one, `c[1].x`, an element or what a pointer
+    //points to
+    fn is_place(&self, node: &Node) -> bool {
+        match node.node_type {
+            NodeType::Identifier => self
+                .resolution
+                .id_of(node)
+                .is_some_and(|id| matches!(self.resolution.defs[id].kind, DefKind::Variable | DefKind::Param | DefKind::Static)),
+            NodeType::Index => !node.children[1].is(NodeType::Range),
+            NodeType::Member => self.is_place(&node.children[0]),
+            NodeType::UnaryExpression => node.value() == "Mul",
+            _ => false,
+        }
+    }
+
+    //`&x`, the address of a variable, a field, an element or of what a
+    //pointer points to
+    pub(super) fn check_address_of(&mut self, node: &mut Node) -> Option<CraneType> {
+        let operand = &mut node.children[0];
+        let place = self.is_place(operand);
+        let ty = self.check_expr(operand, None)?;
+        if !place {
+            let diagnostic = Diagnostic::error(E0328, "cannot take the address of a temporary value")
+                .with_label(operand.span, "this value is not stored anywhere")
+                .with_help("store it in a variable first, `let value = ...`");
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        Some(CraneType::Pointer { pointee: Box::new(ty) })
+    }
+
+    //`*p`, the value a pointer points to
+    pub(super) fn check_deref(&mut self, node: &mut Node) -> Option<CraneType> {
+        let span = node.span;
+        let operand = &mut node.children[0];
+        let ty = self.check_expr(operand, None)?;
+        let pointee = match ty {
+            CraneType::Pointer { pointee } if *pointee != CraneType::Void => *pointee,
+            ty => {
+                let label = match ty {
+                    CraneType::Pointer { .. } => "a `*void` does not say what it points to".to_string(),
+                    _ => format!("`{}` is not a pointer", ty),
+                };
+                let diagnostic = Diagnostic::error(E0329, format!("cannot dereference a value of type `{}`", ty))
+                    .with_label(operand.span, label);
+                self.diagnostics.push(diagnostic);
+                //the error is enough, the block around it is not reported too
+                if let Some(used) = self.unsafe_blocks.last_mut() {
+                    *used = true;
+                }
+                return None;
+            }
+        };
+        self.require_unsafe(span, "dereferencing a pointer");
+        Some(pointee)
+    }
+
+    //`p + n` and `p - n` move a pointer by `n` values of the type it points
+    //to, `p - q` is how many values apart two pointers are
+    pub(super) fn pointer_arithmetic(&mut self, operator: &str, span: Span, l: &CraneType, r: &CraneType) -> Option<CraneType> {
+        let distance = operator == "Sub" && l == r;
+        if !matches!(operator, "Add" | "Sub") || !(distance || self.integer(r)) {
+            return None;
+        }
+        self.require_unsafe(span, "pointer arithmetic");
+        Some(if distance { CraneType::Long { signed: true } } else { l.clone() })
+    }
+
+    //`null` is a pointer to whatever it is expected to point to
+    pub(super) fn check_null(&mut self, expected: Option<&CraneType>) -> Option<CraneType> {
+        match expected {
+            Some(ty @ CraneType::Pointer { .. }) => Some(ty.clone()),
+            _ => Some(CraneType::Pointer { pointee: Box::new(CraneType::Void) }),
+        }
+    }
+}
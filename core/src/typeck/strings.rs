@@ -0,0 +1,34 @@
+use super::TypeChecker;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::Diagnostic;
+use crate::parser::Node;
+
+//`for c in text` goes through the characters of a string as code points
+pub(super) const CODE_POINT: CraneType = CraneType::Short { signed: false };
+
+impl TypeChecker<'_> {
+    //"x = {x}", every hole has to be a value that can be shown as text
+    pub(super) fn check_format(&mut self, node: &mut Node) -> Option<CraneType> {
+        let mut ok = true;
+        for part in node.children.iter_mut() {
+            match self.check_expr(part, None) {
+                Some(ty) if shown(&ty) => {}
+                Some(ty) => {
+                    let diagnostic = Diagnostic::error(E0330, format!("`{}` cannot be shown in a string", ty))
+                        .with_label(part.span, format!("this is `{}`", ty))
+                        .with_note("integers, floats, `bool`, `char`, `str` and pointers can be put in a hole");
+                    self.diagnostics.push(diagnostic);
+                    ok = false;
+                }
+                None => ok = false,
+            }
+        }
+        ok.then_some(CraneType::Str)
+    }
+}
+
+//the types `print` and the holes of a string know how to show
+pub(super) fn shown(ty: &CraneType) -> bool {
+    ty.is_numeric() || matches!(ty, CraneType::Bool | CraneType::Str | CraneType::Pointer { .. })
+}
@@ -0,0 +1,615 @@
+use super::generics::{unify, unresolved};
+use super::{constraints, Constraint, TypeChecker, TypeParam};
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::{Node, NodeType};
+use std::collections::HashMap;
+
+//what a slot of a vtable calls: a function, with the type arguments when it
+//is the default of the trait instantiated for the type
+pub type Slot = (usize, Option<Vec<CraneType>>);
+
+//a method of a trait, its index in the trait is its slot in a vtable
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub name: String,
+    pub id: usize,
+    //whether it has a body an impl can leave out
+    pub default: bool,
+}
+
+//a trait of the program, by qualified name in the trait table. Its methods
+//are generic functions over `Self`, the type implementing the trait, and
+//the type parameters of the trait
+#[derive(Debug, Clone)]
+pub struct TraitDef {
+    pub type_params: Vec<TypeParam>,
+    pub methods: Vec<TraitMethod>,
+    //the method that keeps the trait from being made into an object, and why
+    pub not_object: Option<(Span, String)>,
+    //the vtable of every type turned into an object of the trait
+    pub vtables: Vec<(CraneType, Vec<Slot>)>,
+    pub span: Span,
+}
+
+impl TraitDef {
+    pub fn method(&self, name: &str) -> Option<&TraitMethod> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+}
+
+//an implementation of a trait for a type, by trait and type in the impl
+//table, with the def id of every method it implements. The type of a
+//generic impl has its type parameters in it, as do the type arguments of
+//the trait
+#[derive(Debug, Clone)]
+pub struct ImplDef {
+    pub type_params: Vec<TypeParam>,
+    pub args: Vec<CraneType>,
+    pub methods: HashMap<String, usize>,
+    pub span: Span,
+}
+
+//an impl waiting for the signatures of its methods to be compared with the
+//trait
+pub struct PendingImpl {
+    //`impl Square { ... }`, which gives the type functions of its own
+    inherent: bool,
+    trait_name: Option<String>,
+    ty: Option<CraneType>,
+    type_params: Vec<TypeParam>,
+    //the type arguments of the trait, `None` where one is not known
+    args: Vec<Option<CraneType>>,
+    span: Span,
+    type_span: Span,
+    methods: Vec<(String, usize, Span)>,
+}
+
+//a method found for a call: the trait, the function and the type arguments
+//when it is the default of the trait instantiated for the type
+type Found = (String, usize, Option<Vec<CraneType>>);
+
+impl TypeChecker<'_> {
+    //the qualified name of every trait of a module, before the type
+    //parameters they can bound are declared
+    pub(super) fn declare_trait_names(&mut self, module: &str) {
+        for node in self.items.iter().flatten().filter(|n| n.is(NodeType::Trait)) {
+            if let Some(id) = self.resolution.declared_by(node) {
+                self.trait_names.insert(id, format!("{}.{}", module, node.value()));
+            }
+        }
+    }
+
+    //the methods of every trait of a module, and the type of every impl
+    pub(super) fn declare_traits(&mut self) -> Vec<PendingImpl> {
+        let mut impls = Vec::new();
+        for index in 0..self.items.len() {
+            let node = self.items[index].take().unwrap();
+            match node.node_type {
+                NodeType::Trait => self.declare_trait(&node),
+                NodeType::Impl => impls.push(self.declare_impl(&node)),
+                _ => {}
+            }
+            self.items[index] = Some(node);
+        }
+        impls
+    }
+
+    fn declare_trait(&mut self, node: &Node) {
+        let found = self.resolution.declared_by(node).and_then(|id| self.trait_names.get(&id));
+        let Some(name) = found.cloned() else { return };
+        let type_params = self.type_params(node);
+        let mut methods = Vec::new();
+        let mut not_object = None;
+        if !type_params.is_empty() {
+            not_object = Some((node.span, format!("`{}` has type parameters", node.value())));
+        }
+        for method in node.methods() {
+            let Some(id) = self.resolution.declared_by(method) else { continue };
+            self.trait_methods.insert(id, name.clone());
+            methods.push(TraitMethod {
+                name: method.value().to_string(),
+                id,
+                default: method.body().is_some(),
+            });
+            not_object = not_object.or_else(|| object_safety(method));
+        }
+        let def = TraitDef {
+            type_params,
+            methods,
+            not_object,
+            vtables: Vec::new(),
+            span: node.span,
+        };
+        self.traits.insert(name, def);
+    }
+
+    fn declare_impl(&mut self, node: &Node) -> PendingImpl {
+        let trait_name = self.resolution.id_of(node).and_then(|id| self.trait_names.get(&id)).cloned();
+        let type_params = self.type_params(node);
+        let outer = std::mem::replace(&mut self.constraints, constraints(&type_params));
+        let ty = self.resolve_type(&node.children[0]);
+        let args = node.children[1..].iter().filter(|c| c.is(NodeType::Type)).map(|arg| self.resolve_type(arg)).collect();
+        self.constraints = outer;
+        let mut methods = Vec::new();
+        for method in node.methods() {
+            let Some(id) = self.resolution.declared_by(method) else { continue };
+            if let Some(ty) = &ty {
+                self.impl_methods.insert(id, ty.clone());
+            }
+            if !type_params.is_empty() {
+                self.impl_params.insert(id, type_params.clone());
+            }
+            methods.push((method.value().to_string(), id, method.span));
+        }
+        PendingImpl {
+            inherent: node.value.is_none(),
+            trait_name,
+            ty,
+            type_params,
+            args,
+            span: node.span,
+            type_span: node.children[0].span,
+            methods,
+        }
+    }
+
+    //compare every impl of a module with its trait once the signatures of
+    //the methods are known, and put it in the impl table
+    pub(super) fn declare_impls(&mut self, impls: Vec<PendingImpl>) {
+        for pending in impls {
+            if pending.inherent {
+                self.declare_inherent(pending);
+                continue;
+            }
+            let (Some(name), Some(ty)) = (pending.trait_name, pending.ty) else { continue };
+            let shown = name.rsplit('.').next().unwrap().to_string();
+            if !matches!(ty, CraneType::Struct { .. } | CraneType::Enum { .. }) {
+                let diagnostic = Diagnostic::error(E0319, format!("`{}` cannot implement `{}`", ty, shown))
+                    .with_label(pending.type_span, "only structs and enums can implement traits");
+                self.diagnostics.push(diagnostic);
+                continue;
+            }
+            let key = (name.clone(), ty.clone());
+            if let Some(previous) = self.impls.get(&key) {
+                let diagnostic = Diagnostic::error(E0317, format!("conflicting implementations of `{}` for `{}`", shown, ty))
+                    .with_label(pending.span, "implemented again here")
+                    .with_secondary(previous.span, "first implemented here");
+                self.diagnostics.push(diagnostic);
+                continue;
+            }
+            let def = self.traits[&name].clone();
+            let Some(args) = pending.args.into_iter().collect::<Option<Vec<CraneType>>>() else { continue };
+            if args.len() != def.type_params.len() {
+                self.wrong_type_args(pending.span, &shown, args.len(), &def.type_params, Some(def.span));
+                continue;
+            }
+            let mut types = HashMap::from([("Self".to_string(), ty.clone())]);
+            types.extend(def.type_params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()));
+            for (method, id, span) in &pending.methods {
+                let Some(declared) = def.method(method) else {
+                    let methods: Vec<String> = def.methods.iter().map(|m| format!("`{}`", m.name)).collect();
+                    let mut diagnostic = Diagnostic::error(E0316, format!("`{}` is not a method of `{}`", method, shown))
+                        .with_label(*span, format!("not in `{}`", shown))
+                        .with_secondary(def.span, format!("`{}` is defined here", shown));
+                    if !methods.is_empty() {
+                        diagnostic = diagnostic.with_note(format!("its methods are {}", methods.join(", ")));
+                    }
+                    self.diagnostics.push(diagnostic);
+                    continue;
+                };
+                let expected = self.method_type(declared.id, &types);
+                let found = self.method_type(*id, &HashMap::new());
+                let Some((expected, found)) = expected.zip(found).filter(|(e, f)| e != f) else { continue };
+                let diagnostic = Diagnostic::error(E0316, format!("`{}` does not match its declaration in `{}`", method, shown))
+                    .with_label(*span, format!("expected `{}`, found `{}`", expected, found))
+                    .with_secondary(self.functions[&declared.id].span, format!("declared in `{}` here", shown));
+                self.diagnostics.push(diagnostic);
+            }
+            let missing: Vec<String> = def
+                .methods
+                .iter()
+                .filter(|m| !m.default && !pending.methods.iter().any(|(name, _, _)| *name == m.name))
+                .map(|m| format!("`{}`", m.name))
+                .collect();
+            if !missing.is_empty() {
+                let methods = if missing.len() == 1 { "method" } else { "methods" };
+                let diagnostic = Diagnostic::error(E0316, format!("missing {} {} in the implementation of `{}` for `{}`", methods, missing.join(", "), shown, ty))
+                    .with_label(pending.span, "every method without a default has to be implemented")
+                    .with_secondary(def.span, format!("`{}` is defined here", shown));
+                self.diagnostics.push(diagnostic);
+            }
+            //an impl that does not match is still entered, its uses are fine
+            let methods = pending.methods.into_iter().map(|(name, id, _)| (name, id)).collect();
+            let def = ImplDef {
+                type_params: pending.type_params,
+                args,
+                methods,
+                span: pending.span,
+            };
+            self.impls.insert(key, def);
+        }
+    }
+
+    //the functions of `impl Square { ... }`, the resolver has reported those
+    //defined twice
+    fn declare_inherent(&mut self, pending: PendingImpl) {
+        let Some(ty) = pending.ty else { return };
+        if !matches!(ty, CraneType::Struct { .. } | CraneType::Enum { .. }) {
+            let diagnostic = Diagnostic::error(E0319, format!("`{}` cannot have functions of its own", ty))
+                .with_label(pending.type_span, "only structs and enums can have an `impl`");
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        let functions = self.inherent.entry(ty).or_default();
+        for (name, id, _) in pending.methods {
+            functions.entry(name).or_insert(id);
+        }
+    }
+
+    //the parameter and return types of a method as written, `(Square) -> i32`,
+    //a missing return type is void. `None` when a type is not known
+    fn method_type(&self, id: usize, types: &HashMap<String, CraneType>) -> Option<String> {
+        let signature = &self.functions[&id];
+        let mut params = Vec::new();
+        for param in &signature.params {
+            params.push(param.as_ref()?.substitute(types).to_string());
+        }
+        let ret = match signature.ret_span {
+            Some(_) => signature.ret.as_ref()?.substitute(types),
+            None => CraneType::Void,
+        };
+        Some(format!("({}) -> {}", params.join(", "), ret))
+    }
+
+    //`dyn Shape`, a trait whose methods can all be called through a vtable
+    pub(super) fn trait_object(&mut self, node: &Node) -> Option<CraneType> {
+        //a name that is not a trait has been reported
+        let id = self.resolution.id_of(&node.children[0])?;
+        let name = self.trait_names.get(&id)?.clone();
+        if let Some((span, reason)) = &self.traits.get(&name)?.not_object {
+            let shown = node.children[0].value();
+            let diagnostic = Diagnostic::error(E0318, format!("`{}` cannot be made into an object", shown))
+                .with_label(node.span, format!("`dyn {}` is not allowed", shown))
+                .with_secondary(*span, reason.clone())
+                .with_note("every method of a trait object is called through `self` without knowing its type");
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+        Some(CraneType::Dyn { name })
+    }
+
+    //the methods called `name` a value of type `ty` has: those of the type
+    //itself, else those of the traits its type implements, of the traits a
+    //type parameter is bound by, or of the trait of an object
+    fn methods_named(&self, ty: &CraneType, name: &str) -> Vec<Found> {
+        let mut found = Vec::new();
+        match ty {
+            CraneType::Param { name: param } => {
+                for constraint in self.constraints.get(param).into_iter().flatten() {
+                    let Constraint::Trait(bound, args) = constraint else { continue };
+                    if let Some(method) = self.traits.get(bound).and_then(|def| def.method(name)) {
+                        //`Self` and the type parameters of a generic trait
+                        let args = (!args.is_empty()).then(|| [ty.clone()].into_iter().chain(args.iter().cloned()).collect());
+                        found.push((bound.clone(), method.id, args));
+                    }
+                }
+            }
+            CraneType::Dyn { name: object } => {
+                if let Some(method) = self.traits.get(object).and_then(|def| def.method(name)) {
+                    found.push((object.clone(), method.id, None));
+                }
+            }
+            ty => {
+                if let Some((id, args)) = self.inherent_method(ty, name) {
+                    return vec![(ty.to_string(), id, args)];
+                }
+                let mut traits: Vec<&String> = self.impls.keys().map(|(implemented, _)| implemented).collect();
+                traits.sort();
+                traits.dedup();
+                for implemented in traits {
+                    if let Some((id, args)) = self.impl_method(implemented, ty, name) {
+                        found.push((implemented.clone(), id, args));
+                    }
+                }
+            }
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found
+    }
+
+    //a function of the type itself, with the type arguments of its impl
+    //when that is generic, `impl<T> Stack<T> { ... }`
+    fn inherent_method(&self, ty: &CraneType, name: &str) -> Option<(usize, Option<Vec<CraneType>>)> {
+        if let Some(&id) = self.inherent.get(ty).and_then(|functions| functions.get(name)) {
+            return Some((id, None));
+        }
+        for (implementer, functions) in &self.inherent {
+            let Some(&id) = functions.get(name) else { continue };
+            let Some(params) = self.impl_params.get(&id) else { continue };
+            if let Some(types) = self.matches(params, implementer, ty) {
+                return Some((id, Some(params.iter().map(|p| types[&p.name].clone()).collect())));
+            }
+        }
+        None
+    }
+
+    //the impl of a trait for a type, with what the type parameters of a
+    //generic impl stand for
+    pub(super) fn impl_for(&self, name: &str, ty: &CraneType) -> Option<(&ImplDef, HashMap<String, CraneType>)> {
+        if let Some(def) = self.impls.get(&(name.to_string(), ty.clone())) {
+            return Some((def, HashMap::new()));
+        }
+        self.impls
+            .iter()
+            .filter(|((implemented, _), def)| implemented == name && !def.type_params.is_empty())
+            .find_map(|((_, implementer), def)| Some((def, self.matches(&def.type_params, implementer, ty)?)))
+    }
+
+    //what the type parameters of a generic impl stand for when `ty` is its
+    //type, `None` when it is not or their bounds do not hold
+    fn matches(&self, params: &[TypeParam], implementer: &CraneType, ty: &CraneType) -> Option<HashMap<String, CraneType>> {
+        let mut types = HashMap::new();
+        if !unify(implementer, ty, &mut types) {
+            return None;
+        }
+        self.bind_bounds(params, &mut types);
+        for param in params {
+            let arg = types.get(&param.name)?;
+            if !param.constraints.iter().all(|c| self.satisfies(arg, &c.substitute(&types))) {
+                return None;
+            }
+        }
+        Some(types)
+    }
+
+    //bind the type parameters only found in the type arguments of a bound,
+    //`T` of `I: Iterator<T>` is what the impl of `Iterator` for `I` says
+    pub(super) fn bind_bounds(&self, params: &[TypeParam], types: &mut HashMap<String, CraneType>) {
+        loop {
+            let known = types.len();
+            for param in params {
+                let Some(arg) = types.get(&param.name).cloned() else { continue };
+                for constraint in &param.constraints {
+                    let Constraint::Trait(name, args) = constraint else { continue };
+                    if !args.iter().any(|arg| unresolved(arg, types)) {
+                        continue;
+                    }
+                    for (arg, found) in args.iter().zip(self.trait_args(name, &arg).unwrap_or_default()) {
+                        unify(arg, &found, types);
+                    }
+                }
+            }
+            if types.len() == known {
+                return;
+            }
+        }
+    }
+
+    //the type arguments of a trait a type implements, `[i64]` for a type
+    //implementing `Iterator<i64>`, `None` when it does not implement it
+    pub(super) fn trait_args(&self, name: &str, ty: &CraneType) -> Option<Vec<CraneType>> {
+        match ty {
+            CraneType::Param { name: param } => self.constraints.get(param)?.iter().find_map(|constraint| match constraint {
+                Constraint::Trait(bound, args) if bound == name => Some(args.clone()),
+                _ => None,
+            }),
+            CraneType::Dyn { name: object } => (object == name).then(Vec::new),
+            ty => {
+                let (def, types) = self.impl_for(name, ty)?;
+                Some(def.args.iter().map(|arg| arg.substitute(&types)).collect())
+            }
+        }
+    }
+
+    //the function a method of a trait is for a type, with its type arguments
+    //when it is the default of the trait or a method of a generic impl
+    pub(super) fn impl_method(&self, name: &str, ty: &CraneType, method: &str) -> Option<(usize, Option<Vec<CraneType>>)> {
+        let (def, types) = self.impl_for(name, ty)?;
+        if let Some(&id) = def.methods.get(method) {
+            let args = (!def.type_params.is_empty()).then(|| def.type_params.iter().map(|p| types[&p.name].clone()).collect());
+            return Some((id, args));
+        }
+        let default = self.traits[name].method(method).filter(|m| m.default)?;
+        let args = [ty.clone()].into_iter().chain(def.args.iter().map(|arg| arg.substitute(&types))).collect();
+        Some((default.id, Some(args)))
+    }
+
+    //`p.area()`, `p` is passed as `self`. A method of a type parameter or a
+    //trait object is the method of the trait, which the instantiation or the
+    //vtable replaces. The receiver of `V::new().get()` is the first child
+    pub(super) fn check_method_call(&mut self, node: &mut Node) -> Option<CraneType> {
+        let (path, name, found) = if node.is(NodeType::MethodCall) {
+            let name = node.value().to_string();
+            let found = self.check_expr(&mut node.children[0], None);
+            ("this value".to_string(), name, found)
+        } else {
+            let (path, name) = node.value().rsplit_once('.').unwrap();
+            let (path, name) = (path.to_string(), name.to_string());
+            let receiver = Node::new(NodeType::Identifier, Some(path.clone()), node.line, node.span);
+            (format!("`{}`", path), name, self.check_name(&receiver))
+        };
+        let skip = node.is(NodeType::MethodCall) as usize;
+        let found = found.map(|ty| (self.methods_named(&ty, &name), ty));
+        if name == "len" && matches!(found, Some((_, CraneType::Array { .. } | CraneType::Slice { .. } | CraneType::Str))) {
+            return self.check_len(node, skip);
+        }
+        let (trait_name, id, type_args, ty) = match found {
+            Some((found, ty)) if found.len() == 1 => {
+                let (trait_name, id, type_args) = found.into_iter().next().unwrap();
+                (trait_name, id, type_args, ty)
+            }
+            found => {
+                if let Some((found, ty)) = found {
+                    let diagnostic = if found.is_empty() {
+                        let mut diagnostic = Diagnostic::error(E0315, format!("no method `{}` on `{}`", name, ty))
+                            .with_label(node.span, format!("{} is `{}`", path, ty));
+                        if let CraneType::Param { name: param } = &ty {
+                            diagnostic = diagnostic.with_help(format!("bound it by a trait with the method, `{}: Trait`", param));
+                        }
+                        diagnostic
+                    } else {
+                        let traits: Vec<String> = found.iter().map(|(t, _, _)| format!("`{}`", t.rsplit('.').next().unwrap())).collect();
+                        Diagnostic::error(E0315, format!("`{}` is a method of more than one trait of `{}`", name, ty))
+                            .with_label(node.span, "cannot tell which one is called")
+                            .with_note(format!("it is a method of {}", traits.join(" and ")))
+                    };
+                    self.diagnostics.push(diagnostic);
+                }
+                for arg in node.arguments_mut().skip(skip) {
+                    self.check_expr(arg, None);
+                }
+                return None;
+            }
+        };
+        let inherent = !self.traits.contains_key(&trait_name);
+        let signature = self.functions[&id].clone();
+        let mut types = HashMap::from([("Self".to_string(), ty)]);
+        if let Some(args) = &type_args {
+            types.extend(signature.type_params.iter().map(|p| p.name.clone()).zip(args.iter().cloned()));
+        }
+        let params: Vec<Option<CraneType>> = signature.params.iter().map(|p| p.as_ref().map(|p| p.substitute(&types))).collect();
+        let given = node.arguments().count() - skip;
+        if !self.takes_self.contains(&id) || given + 1 != params.len() {
+            let count = |n: usize| format!("{} argument{}", n, if n == 1 { "" } else { "s" });
+            let diagnostic = if self.takes_self.contains(&id) {
+                Diagnostic::error(E0303, format!("`{}` takes {} but {} {} given", name, count(params.len() - 1), given, if given == 1 { "was" } else { "were" }))
+                    .with_label(node.span, format!("expected {}", count(params.len() - 1)))
+            } else if inherent {
+                Diagnostic::error(E0315, format!("`{}` of `{}` does not take `self`", name, trait_name))
+                    .with_label(node.span, "it cannot be called on a value")
+                    .with_help(format!("call it through the type, `{}::{}(...)`", trait_name, name))
+            } else {
+                Diagnostic::error(E0315, format!("`{}` of `{}` does not take `self`", name, trait_name.rsplit('.').next().unwrap()))
+                    .with_label(node.span, "it cannot be called on a value")
+            };
+            self.diagnostics.push(diagnostic.with_secondary(signature.span, "defined here"));
+        }
+        for (index, arg) in node.arguments_mut().skip(skip).enumerate() {
+            let expected = params.get(index + 1).cloned().flatten();
+            let found = self.check_expr(arg, expected.as_ref());
+            if let (Some(expected), Some(found)) = (&expected, &found) {
+                if !self.coerce(arg, expected, found) {
+                    let span = arg.span;
+                    self.mismatch(span, expected, found);
+                }
+            }
+        }
+        self.check_function(id);
+        let ret = self.functions[&id].ret.as_ref().map(|ret| ret.substitute(&types));
+        //inside a generic body the instantiation finds the method
+        let type_args = type_args.filter(|args| !args.iter().any(CraneType::is_generic));
+        if let Some(args) = &type_args {
+            if !self.instantiate(id, args, node.span) {
+                return ret;
+            }
+        }
+        node.target = Some(id);
+        node.type_args = type_args;
+        ret
+    }
+
+    //an operator on a struct or enum calls the method of the prelude trait
+    //it implements, `None` when it does not
+    pub(super) fn check_operator(&mut self, node: &mut Node, trait_name: &str, method: &str, ty: &CraneType) -> Option<CraneType> {
+        let (id, type_args) = self.impl_method(trait_name, ty, method)?;
+        let type_args = type_args.filter(|args| !args.iter().any(CraneType::is_generic));
+        if let Some(args) = &type_args {
+            if !self.instantiate(id, args, node.span) {
+                return None;
+            }
+        }
+        self.check_function(id);
+        node.target = Some(id);
+        node.type_args = type_args;
+        let types = HashMap::from([("Self".to_string(), ty.clone())]);
+        self.functions[&id].ret.as_ref().map(|ret| ret.substitute(&types))
+    }
+
+    //whether a value of type `found` can be used where `expected` is, which
+    //is when they are the same, or the value can be made into the expected
+    //trait object, closure or slice. The value is then marked to be turned into one
+    pub(super) fn coerce(&mut self, value: &mut Node, expected: &CraneType, found: &CraneType) -> bool {
+        //an integer already holds its value extended to 64 bits, so it is used
+        //as a wider one as it is
+        if expected == found || found.widens_to(expected) {
+            return true;
+        }
+        //an array is viewed through a slice of all of its elements
+        if let (CraneType::Slice { element }, CraneType::Array { element: found, .. }) = (expected, found) {
+            if element != found {
+                return false;
+            }
+            value.coercion = Some(expected.clone());
+            return true;
+        }
+        //a function is called through a closure with no environment
+        if let (CraneType::Closure { params, ret }, CraneType::Function { params: found, ret: found_ret }) = (expected, found) {
+            if params != found || ret != found_ret {
+                return false;
+            }
+            value.coercion = Some(expected.clone());
+            return true;
+        }
+        let CraneType::Dyn { name } = expected else { return false };
+        let constraint = Constraint::Trait(name.clone(), Vec::new());
+        if matches!(found, CraneType::Dyn { .. }) || !self.satisfies(found, &constraint) {
+            return false;
+        }
+        if !found.is_generic() {
+            self.vtable(found, name, value.span);
+        }
+        value.coercion = Some(expected.clone());
+        true
+    }
+
+    //what each slot of the vtable of a type for a trait calls
+    fn vtable(&mut self, ty: &CraneType, name: &str, span: Span) {
+        let def = self.traits[name].clone();
+        if def.vtables.iter().any(|(t, _)| t == ty) {
+            return;
+        }
+        let mut slots = Vec::new();
+        for method in &def.methods {
+            let (id, args) = self.impl_method(name, ty, &method.name).unwrap_or((method.id, Some(vec![ty.clone()])));
+            if let Some(args) = &args {
+                self.instantiate(id, args, span);
+            }
+            slots.push((id, args));
+        }
+        self.traits.get_mut(name).unwrap().vtables.push((ty.clone(), slots));
+    }
+
+    //the traits a type implements, which code using the type depends on
+    pub(super) fn impls_of(&self, ty: &CraneType) -> Vec<&str> {
+        let mut traits: Vec<&str> = self
+            .impls
+            .iter()
+            .filter(|((_, implementer), def)| self.matches(&def.type_params, implementer, ty).is_some())
+            .map(|((name, _), _)| name.as_str())
+            .collect();
+        traits.sort();
+        traits.dedup();
+        traits
+    }
+}
+
+//why a method keeps its trait from being made into an object, when it does
+fn object_safety(method: &Node) -> Option<(Span, String)> {
+    let name = method.value();
+    if method.is_generic() {
+        return Some((method.span, format!("`{}` has type parameters", name)));
+    }
+    let mut params = method.params();
+    if params.next().is_none_or(|p| p.value() != "self") {
+        return Some((method.span, format!("`{}` does not take `self`", name)));
+    }
+    let mut types = params.filter_map(|p| p.child(NodeType::Type)).chain(method.return_type());
+    let ty = types.find(|ty| mentions_self(ty))?;
+    Some((ty.span, format!("`{}` uses `Self` other than as `self`", name)))
+}
+
+fn mentions_self(ty: &Node) -> bool {
+    ty.value() == "Self" || ty.children.iter().any(mentions_self)
+}
@@ -0,0 +1,111 @@
+use super::TypeChecker;
+use crate::compiler::types::CraneType;
+use crate::diagnostic::codes::*;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::{Node, NodeType};
+
+impl TypeChecker<'_> {
+    //`(u16, bool)`, `()` has no values and is `void`
+    pub(super) fn tuple_type(&mut self, node: &Node) -> Option<CraneType> {
+        if node.children.is_empty() {
+            return Some(CraneType::Void);
+        }
+        let mut elements = Vec::new();
+        for element in &node.children {
+            elements.push(self.resolve_type(element)?);
+        }
+        Some(CraneType::Tuple { elements })
+    }
+
+    //`(1, true)`, each value has the type the tuple is expected to have there
+    pub(super) fn check_tuple(&mut self, node: &mut Node, expected: Option<&CraneType>) -> Option<CraneType> {
+        let hints = match expected {
+            Some(CraneType::Tuple { elements }) if elements.len() == node.children.len() => Some(elements.clone()),
+            _ => None,
+        };
+        let mut elements = Vec::new();
+        for (index, value) in node.children.iter_mut().enumerate() {
+            let hint = hints.as_ref().map(|hints| &hints[index]);
+            let found = self.check_expr(value, hint);
+            if let (Some(hint), Some(found)) = (hint, &found) {
+                if self.coerce(value, hint, found) {
+                    elements.push(Some(hint.clone()));
+                    continue;
+                }
+            }
+            elements.push(found);
+        }
+        let elements = elements.into_iter().collect::<Option<Vec<_>>>()?;
+        Some(CraneType::Tuple { elements })
+    }
+
+    //`let (a, (b, _)) = value`
+    pub(super) fn check_destructure(&mut self, node: &mut Node) {
+        let annotation = match node.child(NodeType::Type) {
+            Some(ty) => {
+                let ty = ty.clone();
+                self.resolve_type(&ty)
+            }
+            None => None,
+        };
+        let value = node.children.last_mut().unwrap();
+        let found = self.check_expr(value, annotation.as_ref());
+        if let (Some(expected), Some(found)) = (&annotation, &found) {
+            if !self.coerce(value, expected, found) {
+                let span = value.span;
+                self.mismatch(span, expected, found);
+            }
+        }
+        let ty = annotation.or(found);
+        node.ty = ty.clone();
+        self.bind_tuple(&mut node.children[0], ty);
+    }
+
+    //the type of every name of the pattern, none when the value does not
+    //have the shape of the pattern
+    fn bind_tuple(&mut self, pattern: &mut Node, ty: Option<CraneType>) {
+        let tuple = pattern.is(NodeType::Tuple);
+        let ty = match (tuple, ty) {
+            (true, Some(CraneType::Tuple { elements })) if elements.len() == pattern.children.len() => {
+                for (child, element) in pattern.children.iter_mut().zip(elements.clone()) {
+                    self.bind_tuple(child, Some(element));
+                }
+                Some(CraneType::Tuple { elements })
+            }
+            (true, found) => {
+                if let Some(found) = found {
+                    let count = |n: usize| format!("{} value{}", n, if n == 1 { "" } else { "s" });
+                    let label = match &found {
+                        CraneType::Tuple { elements } => format!("this takes apart {} but `{}` has {}", count(pattern.children.len()), found, elements.len()),
+                        _ => format!("`{}` is not a tuple", found),
+                    };
+                    let diagnostic = Diagnostic::error(E0331, format!("the pattern does not match a value of type `{}`", found))
+                        .with_label(pattern.span, label);
+                    self.diagnostics.push(diagnostic);
+                }
+                for child in pattern.children.iter_mut() {
+                    self.bind_tuple(child, None);
+                }
+                None
+            }
+            (false, ty) => ty,
+        };
+        if let Some(id) = self.resolution.declared_by(pattern).filter(|_| pattern.is(NodeType::Binding)) {
+            self.def_types[id] = ty.clone();
+        }
+        pattern.ty = ty;
+    }
+
+    //`t.0`, the value of a tuple at that position
+    pub(super) fn tuple_element(&mut self, span: Span, what: &str, elements: &[CraneType], field: &str) -> Option<CraneType> {
+        let element = field.parse::<usize>().ok().and_then(|index| elements.get(index));
+        if element.is_none() {
+            let ty = CraneType::Tuple { elements: elements.to_vec() };
+            let diagnostic = Diagnostic::error(E0309, format!("no field `{}` on type `{}`", field, ty))
+                .with_label(span, format!("{} is `{}`", what, ty))
+                .with_note(format!("its values are `.0` to `.{}`", elements.len() - 1));
+            self.diagnostics.push(diagnostic);
+        }
+        element.cloned()
+    }
+}
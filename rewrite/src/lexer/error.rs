@@ -51,15 +51,27 @@ pub(super) enum ErrorType {
     ExtraToken,
 }
 
-pub fn underline(code: String, section: String) -> String {
+//underline the characters of `code` covered by the column range, tabs are kept
+//so the carets line up with the snippet however wide the terminal draws them
+pub fn underline(code: &str, columns: std::ops::Range<usize>) -> String {
     let mut underline = String::new();
-    for c in code.chars() {
-        if section.contains(c) {
+    let end = columns.end.max(columns.start + 1);
+    for (i, c) in code.chars().enumerate().take(end) {
+        if i >= columns.start {
             underline.push('^');
+        } else if c == '\t' {
+            underline.push('\t');
         } else {
             underline.push(' ');
         }
     }
+    //spans pointing past the end of the line (a missing token) get one caret
+    while underline.chars().count() < columns.start {
+        underline.push(' ');
+    }
+    if underline.chars().count() == columns.start {
+        underline.push('^');
+    }
     underline
 }
 
@@ -74,28 +86,31 @@ macro_rules! color {
     };
 }
 
-//error macro to take error, line number, error type, snippet and error precedence, and return a formatted error message
+//error macro to take error, line number, error type, snippet and the column range of the
+//offending code inside the snippet, and print a formatted error message
 #[macro_export]
 macro_rules! error {
-    ($error:expr, $line:expr, $error_type:expr, $snippet:expr, $code:expr) => {{
+    ($error:expr, $line:expr, $error_type:expr, $snippet:expr, $columns:expr) => {{
         use $crate::error::*;
+        let columns: std::ops::Range<usize> = $columns;
         //underline the error snippet
         let underline = $crate::color!(
             ColorGreen,
-            $crate::error::underline($snippet.to_string(), $code.to_string())
+            $crate::error::underline(&$snippet.to_string(), columns.clone())
         );
         //colorize the error snippet
         let snippet = $crate::color!(ColorWhite, $snippet);
         //colorize the error type
         let error_type = $crate::color!(ColorRed, format!("{:?}", $error_type));
-        //colorize the line number
-        let line = $crate::color!(ColorWhite, $line.to_string());
+        //colorize the line and column, both 1 based
+        let line = $crate::color!(ColorWhite, ($line + 1).to_string());
+        let column = $crate::color!(ColorWhite, (columns.start + 1).to_string());
         //colorize the error message
         let error = $crate::color!(ColorRed, $error.to_string());
         //return the formatted error message
         let x = format!(
-            "{}: {} at line {}\n{}\n{}",
-            error_type, error, line, snippet, underline,
+            "{}: {} at line {}, column {}\n{}\n{}",
+            error_type, error, line, column, snippet, underline,
         );
         println!("{}", x);
         std::process::exit(0);
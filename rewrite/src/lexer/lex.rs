@@ -89,6 +89,8 @@ struct Context<'a> {
     input: &'a str,
     pos: usize,
     line: usize,
+    //position of the first character of the current line
+    line_start: usize,
     lines: Vec<&'a str>,
 }
 
@@ -105,6 +107,7 @@ impl<'a> Context<'a> {
             input,
             pos,
             line: 0,
+            line_start: 0,
             lines: input.lines().collect(),
         }
     }
@@ -118,10 +121,16 @@ impl<'a> Context<'a> {
     fn push(&mut self, token: Token) {
         self.tokens.push(token);
     }
+    fn column(&self) -> usize {
+        self.pos - self.line_start
+    }
+    fn current_line(&self) -> &'a str {
+        self.lines.get(self.line).copied().unwrap_or("")
+    }
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(s: &'a str) -> Lexer {
+    pub fn new(s: &'a str) -> Lexer<'a> {
         Lexer {
             input: s,
             pos: 0,
@@ -146,13 +155,13 @@ impl<'a> Lexer<'a> {
                 if c == '\n' {
                     self.context.line += 1;
                     self.advance();
+                    self.context.line_start = self.context.pos;
                 } else if c == ' ' {
                     self.advance();
                     continue;
                 }
             }
-            let lex: &dyn Lex =
-                Lexer::decide(self.context.input[self.context.pos..].chars().next());
+            let lex: &dyn Lex = Lexer::decide(&self.context);
             let f = lex.lex(&mut self.context);
             // Add logic to break the loop when done
             match f {
@@ -165,18 +174,18 @@ impl<'a> Lexer<'a> {
         tokens
     }
 
-    pub fn decide<'d>(c: Option<char>) -> &'d dyn Lex {
-        match c {
+    pub fn decide<'d>(ctx: &Context) -> &'d dyn Lex {
+        match ctx.peek() {
             Some(c) => match c {
                 '0'..='9' => &Number,
                 'a'..='z' | 'A'..='Z' => &Identifier,
                 '+' | '-' | '*' | '/' => &Operator,
                 _ => error!(
                     format!("Unexpected Token '{}'", c),
-                    0,
+                    ctx.line,
                     ErrorType::UnexpectedToken,
-                    "",
-                    c
+                    ctx.current_line(),
+                    ctx.column()..ctx.column() + 1
                 ),
             },
             None => &Eof,
@@ -188,13 +197,16 @@ impl Lex for Number {
     fn lex(&self, ctx: &mut Context) -> Option<Token> {
         let mut num = String::new();
         let start = ctx.pos;
-        while ctx.advance().map_or(false, |c| c.is_numeric() || c == '.') {}
+        while ctx.advance().is_some_and(|c| c.is_numeric() || c == '.') {}
         if ctx.pos == start {
             return None;
         }
+        let val = ctx.input[start..ctx.pos].to_string();
+        // A number with a decimal point is a float
+        let tp = if val.contains('.') { TokenType::Float } else { TokenType::Int };
         Some(Token {
-            tp: TokenType::Int,
-            val: ctx.input[start..ctx.pos].to_string(),
+            tp,
+            val,
             line: ctx.line,
         })
     }
@@ -206,7 +218,7 @@ impl Lex for Identifier {
         let start = ctx.pos;
         while ctx
             .advance()
-            .map_or(false, |c| c.is_alphanumeric() || c == '_')
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
         {}
         if ctx.pos == start {
             return None;
@@ -235,8 +247,8 @@ impl Lex for Operator {
                 format!("Unexpected character '{}'", ctx.peek().unwrap_or('\0')),
                 ctx.line,
                 ErrorType::UnexpectedCharacter,
-                ctx.lines[ctx.line],
-                ctx.peek().unwrap()
+                ctx.current_line(),
+                ctx.column()..ctx.column() + 1
             ),
         }
     }